@@ -1,6 +1,13 @@
-// OLE Reader
-mod ole;
+// OLE Reader, which is all that is built without the "msg" feature
+pub mod ole;
+pub use ole::{sanitize_filename, Application, FileNamePolicy};
 
 // Outlook Email Message File Parser
+#[cfg(feature = "msg")]
 mod parser;
+#[cfg(feature = "msg")]
 pub use parser::*;
+
+// Synthetic .msg files for tests
+#[cfg(all(any(test, feature = "testing"), feature = "msg"))]
+pub mod testing;
@@ -11,5 +11,64 @@ pub(crate) const END_OF_CHAIN_SECID: [u8; 4] = [0xFE, 0xFF, 0xFF, 0xFF];
 pub(crate) const FREE_SECID: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
 pub(crate) const END_OF_CHAIN_SECID_U32: u32 = 0xFFFFFFFEu32;
 pub(crate) const FREE_SECID_U32: u32 = 0xFFFFFFFFu32;
+pub(crate) const SAT_SECID_U32: u32 = 0xFFFFFFFDu32;
+pub(crate) const MSAT_SECID_U32: u32 = 0xFFFFFFFCu32;
 
 pub(crate) const DIRECTORY_ENTRY_SIZE: usize = 128;
+
+/// Files up to this size are read into memory by `Reader::from_file`,
+/// larger ones on demand.
+pub(crate) const MAX_IN_MEMORY_SIZE: u64 = 1 << 30;
+
+/// Major version of the files whose stream sizes are 64-bit, those of
+/// version 3 files being 32-bit with a high part to ignore.
+pub(crate) const MAJOR_VERSION_4: u16 = 4;
+
+/// Application which wrote a compound file, as told by the CLSID of its
+/// root storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Application {
+  /// Outlook message (.msg) or template (.oft)
+  OutlookMessage,
+  /// Word 97-2003 document
+  Word,
+  /// Word 6.0/95 document
+  Word6,
+  /// Excel 97-2003 workbook
+  Excel,
+  /// Excel 5.0/95 workbook
+  Excel5,
+  /// PowerPoint 97-2003 presentation
+  PowerPoint,
+  /// Visio drawing
+  Visio,
+  /// Publisher document
+  Publisher,
+  /// Windows Installer package (.msi)
+  WindowsInstaller,
+  /// Equation Editor 3.0 object
+  Equation,
+}
+
+impl Application {
+  /// Returns the application which wrote the compound file held by
+  /// `slice`, if it is a valid compound file of a known application.
+  pub fn detect(slice: &[u8]) -> Option<Application> {
+    super::Reader::new(slice).ok()?.application()
+  }
+}
+
+/// CLSIDs of the root storage of known applications, formatted as by
+/// `Entry::clsid`.
+pub(crate) const APPLICATION_CLSIDS: [(&str, Application); 10] = [
+  ("00020D0B-0000-0000-C000-000000000046", Application::OutlookMessage),
+  ("00020906-0000-0000-C000-000000000046", Application::Word),
+  ("00020900-0000-0000-C000-000000000046", Application::Word6),
+  ("00020820-0000-0000-C000-000000000046", Application::Excel),
+  ("00020810-0000-0000-C000-000000000046", Application::Excel5),
+  ("64818D10-4F9B-11CF-86EA-00AA00B929E8", Application::PowerPoint),
+  ("00021A14-0000-0000-C000-000000000046", Application::Visio),
+  ("00021201-0000-0000-00C0-000000000046", Application::Publisher),
+  ("000C1084-0000-0000-C000-000000000046", Application::WindowsInstaller),
+  ("0002CE02-0000-0000-C000-000000000046", Application::Equation),
+];
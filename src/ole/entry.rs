@@ -1,8 +1,9 @@
 use std;
 use crate::ole::util::FromSlice;
 
-#[derive(Debug)]
-pub(crate) enum NodeColour {
+/// Colour of an entry in the red-black tree of its storage.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum NodeColour {
   Red,
   Black
 }
@@ -134,6 +135,14 @@ pub struct Entry {
   /// Size of the entry.
   size: usize,
 
+  /// Whether the chain is in the SSAT, i.e. the entry is held in the short
+  /// stream container.
+  short: bool,
+
+  /// Whether the chain was found in the other allocation table than the
+  /// size of the entry calls for.
+  misplaced: bool,
+
   /// Array of the children's DirIDs
   children_nodes: std::vec::Vec<u32>,
 
@@ -143,8 +152,17 @@ pub struct Entry {
 
 impl Entry {
 
-  fn from_slice(sector: &[u8], dir_id: u32)
+  /// Reads the entry at `dir_id` from its bytes, in a file of
+  /// `major_version`.
+  pub(crate) fn from_slice(sector: &[u8], dir_id: u32, major_version: u16)
       -> Result<Entry, super::error::Error> {
+    let size = if major_version >= super::constants::MAJOR_VERSION_4 {
+      std::convert::TryFrom::try_from(u64::from_slice(&sector[120 .. 128]))
+        .map_err(|_| super::error::Error::BadSizeValue(
+          "Stream size does not fit in memory"))?
+    } else {
+      usize::from_slice(&sector[120 .. 124])
+    };
     let entry = Entry {
       id: dir_id,
       name: Entry::build_name(&sector[0 .. 64]),
@@ -158,7 +176,9 @@ impl Entry {
       creation_time: u64::from_slice(&sector[100 .. 108]),
       last_modification_time: u64::from_slice(&sector[108 .. 116]),
       sec_id_chain: vec![u32::from_slice(&sector[116 .. 120])],
-      size: usize::from_slice(&sector[120 .. 124]),
+      size,
+      short: false,
+      misplaced: false,
       children_nodes: std::vec::Vec::new(),
       parent_node: None
     };
@@ -174,7 +194,7 @@ impl Entry {
     let mut i = 0usize;
     while i < 64 && array[i] != 0 {
       name.push(array[i] as char);
-      i = i + 2;
+      i += 2;
     }
 
     name
@@ -211,6 +231,11 @@ impl Entry {
     self.size
   }
 
+  /// Returns `true` if the entry holds no data
+  pub fn is_empty(&self) -> bool {
+    self.size == 0
+  }
+
   /// Returns the DirID of the left child node
   pub fn left_child_node(&self) -> u32 {
     self.left_child_node
@@ -230,6 +255,45 @@ impl Entry {
   pub fn children_nodes(&self) -> &std::vec::Vec<u32> {
     &self.children_nodes
   }
+
+  /// Returns the colour of the entry in the red-black tree
+  pub fn color(&self) -> NodeColour {
+    self.color
+  }
+
+  /// Returns the DirID of the root of the children tree, for storages
+  pub fn child_node(&self) -> u32 {
+    self.root_node
+  }
+
+  /// Returns the state bits of the entry (user-defined flags)
+  pub fn state_bits(&self) -> u32 {
+    u32::from_slice(&self.flags)
+  }
+
+  /// Returns the SecIDs of the sectors holding the entry, in order.
+  ///
+  /// Streams smaller than the minimum standard stream size are held in
+  /// short sectors, whose SecIDs index the short stream container.
+  pub fn sector_chain(&self) -> &[u32] {
+    &self.sec_id_chain
+  }
+
+  /// Returns `true` if the stream is held in short sectors, in the short
+  /// stream container, rather than in sectors of its own.
+  pub fn is_short(&self) -> bool {
+    self.short
+  }
+
+  /// Returns the CLSID of the entry as a GUID string, e.g.
+  /// `00020D0B-0000-0000-C000-000000000046` (all zeros if unset)
+  pub fn clsid(&self) -> std::string::String {
+    let id = &self.identifier;
+    format!("{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+      u32::from_slice(&id[0 .. 4]), u16::from_le_bytes([id[4], id[5]]),
+      u16::from_le_bytes([id[6], id[7]]), id[8], id[9], id[10], id[11], id[12],
+      id[13], id[14], id[15])
+  }
 }
 
 impl std::fmt::Display for Entry {
@@ -268,8 +332,9 @@ pub struct EntrySlice<'s> {
   /// Chunk size, i.e. size of the sector.
   max_chunk_size: usize,
 
-  /// List of slices.
-  chunks: std::vec::Vec<&'s [u8]>,
+  /// List of slices, borrowed from the file in memory or read from the
+  /// source.
+  chunks: std::vec::Vec<std::borrow::Cow<'s, [u8]>>,
 
   /// How many bytes which have been already read.
   read: usize,
@@ -284,7 +349,7 @@ pub struct EntrySlice<'s> {
 impl<'s> EntrySlice<'s> {
   fn new(max_chunk_size: usize, size: usize) -> EntrySlice<'s> {
     EntrySlice {
-      max_chunk_size: max_chunk_size,
+      max_chunk_size,
       chunks: std::vec::Vec::new(),
       read: 0usize,
       total_size: size,
@@ -292,7 +357,7 @@ impl<'s> EntrySlice<'s> {
     }
   }
 
-  fn add_chunk(&mut self, chunk: &'s [u8]) {
+  fn add_chunk(&mut self, chunk: std::borrow::Cow<'s, [u8]>) {
     self.real_size += chunk.len();
     self.chunks.push(chunk);
   }
@@ -302,6 +367,11 @@ impl<'s> EntrySlice<'s> {
     self.total_size
   }
 
+  /// Returns `true` if the slice has nothing to read.
+  pub fn is_empty(&self) -> bool {
+    self.total_size == 0
+  }
+
   /// Returns the real length of all chunks
   pub fn real_len(&self) -> usize {
     self.real_size
@@ -345,25 +415,415 @@ impl<'s> std::io::Read for EntrySlice<'s> {
 
 
 
+/// Owned stream of an entry, see `Reader::get_entry_stream`.
+///
+/// It reads and seeks within the bytes of the entry only.
+pub struct EntryStream {
+
+  /// Body of the file, shared with the reader, read on demand when the
+  /// reader seeks in its source.
+  body: super::ole::Body,
+
+  /// Chunk size, i.e. size of the sector.
+  chunk_size: usize,
+
+  /// Ranges of the chunks in the body.
+  chunks: std::vec::Vec<std::ops::Range<usize>>,
+
+  /// Position in the stream.
+  position: u64,
+
+  /// Total size of the stream.
+  total_size: usize,
+
+  /// Real size of all chunks
+  real_size: usize
+}
+
+impl EntryStream {
+  /// Returns the length of the stream, therefore the length of the entry.
+  pub fn len(&self) -> usize {
+    self.total_size
+  }
+
+  /// Returns `true` if the stream has nothing to read.
+  pub fn is_empty(&self) -> bool {
+    self.total_size == 0
+  }
+
+  /// Returns the real length of all chunks
+  pub fn real_len(&self) -> usize {
+    self.real_size
+  }
+}
+
+impl std::io::Read for EntryStream {
+
+  fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+    let mut read = 0;
+    while read < buf.len() && (self.position as usize) < self.total_size {
+      let offset = self.position as usize;
+      let chunk = match self.chunks.get(offset / self.chunk_size) {
+        Some(chunk) => chunk.clone(),
+        None => break,
+      };
+      let local_offset = offset % self.chunk_size;
+      if local_offset >= chunk.len() {
+        break;
+      }
+      let n = std::cmp::min(chunk.len() - local_offset, buf.len() - read);
+      let start = chunk.start + local_offset;
+      let bytes = self.body.read(start .. start + n)?;
+      buf[read .. read + n].copy_from_slice(&bytes);
+      read += n;
+      self.position += n as u64;
+    }
+    Ok(read)
+  }
+}
+
+impl std::io::Seek for EntryStream {
+
+  fn seek(&mut self, pos: std::io::SeekFrom) -> Result<u64, std::io::Error> {
+    let position = match pos {
+      std::io::SeekFrom::Start(offset) => Some(offset),
+      std::io::SeekFrom::End(offset) =>
+        (self.total_size as u64).checked_add_signed(offset),
+      std::io::SeekFrom::Current(offset) =>
+        self.position.checked_add_signed(offset),
+    };
+    match position {
+      Some(position) => {
+        self.position = position;
+        Ok(position)
+      },
+      None => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput,
+        "invalid seek to a negative or overflowing position"))
+    }
+  }
+}
+
 impl<'ole> super::ole::Reader<'ole> {
 
 
   /// Returns the slice for the entry.
+  ///
+  /// Entries with a size of zero yield an empty slice, so that a stream
+  /// which is present but empty can be told apart from a read failure.
   pub fn get_entry_slice(&self, entry: &Entry) ->
-    Result<EntrySlice, super::error::Error> {
+    Result<EntrySlice<'_>, super::error::Error> {
+
+    let (chunk_size, chunks) = self.get_entry_chunks(entry)?;
+    let body = self.body.as_ref().unwrap();
+    if body.bytes().is_some() {
+      let mut entry_slice = EntrySlice::new(chunk_size, entry.size);
+      for chunk in chunks {
+        entry_slice.add_chunk(body.read(chunk).map_err(super::error::Error::IOError)?);
+      }
+      return Ok(entry_slice);
+    }
+    // Read from the source as a single chunk, rather than a chunk per
+    // sector, short sectors being as small as 64 bytes. Adjacent sectors
+    // are read at once.
+    let mut ranges: std::vec::Vec<std::ops::Range<usize>> = vec![];
+    for chunk in chunks {
+      match ranges.last_mut() {
+        Some(last) if last.end == chunk.start => last.end = chunk.end,
+        _ => ranges.push(chunk),
+      }
+    }
+    let mut content = std::vec::Vec::with_capacity(entry.size);
+    for range in ranges {
+      content.extend_from_slice(&body.read(range).map_err(super::error::Error::IOError)?);
+    }
+    let mut entry_slice = EntrySlice::new(content.len().max(1), entry.size);
+    if !content.is_empty() {
+      entry_slice.add_chunk(std::borrow::Cow::Owned(content));
+    }
+    Ok(entry_slice)
+  }
 
-    let entry_slice: EntrySlice;
-    let size = entry.size;
-    if size == 0 {
-      Err(super::error::Error::EmptyEntry)
+  /// Returns an owned stream for the entry.
+  ///
+  /// Unlike `EntrySlice`, the stream does not borrow the reader: it shares
+  /// the body of the file, so that it can be returned from functions or
+  /// moved to other threads.
+  ///
+  /// # Examples
+  ///
+  /// ```ignore
+  /// use ole;
+  /// use std::io::Read;
+  /// let parser = ole::Reader::from_path("assets/Thumbs.db").unwrap();
+  /// let entry = parser.iterate().next().unwrap();
+  /// let mut stream = parser.get_entry_stream(entry).unwrap();
+  /// std::thread::spawn(move || {
+  ///   let mut buf = vec![];
+  ///   stream.read_to_end(&mut buf).unwrap();
+  /// });
+  /// ```
+  pub fn get_entry_stream(&self, entry: &Entry) ->
+    Result<EntryStream, super::error::Error> {
+    let (chunk_size, chunks) = self.get_entry_chunks(entry)?;
+    Ok(EntryStream {
+      body: self.body.clone().unwrap(),
+      chunk_size,
+      real_size: chunks.iter().map(|chunk| chunk.len()).sum(),
+      chunks,
+      position: 0,
+      total_size: entry.size
+    })
+  }
+
+  /// Returns the content of the entry whose id, its index in the
+  /// directory, is `id`.
+  ///
+  /// Unlike `get_entry_slice`, the bytes are owned, so that no reference
+  /// to the entry is kept. A truncated stream yields the bytes found.
+  pub fn get_entry_slice_by_id(&self, id: u32)
+    -> Result<std::vec::Vec<u8>, super::error::Error> {
+    let entry = self.entries.as_ref()
+      .and_then(|entries| entries.get(id as usize))
+      .ok_or(super::error::Error::EntryNotFound)?;
+    self.read_entry(entry)
+  }
+
+  /// Returns the content of the stream at `path`, names being separated
+  /// by `/` as returned by `path`.
+  ///
+  /// Names are compared regardless of case, as in the directory.
+  ///
+  /// # Examples
+  ///
+  /// ```ignore
+  /// use ole;
+  /// let parser = ole::Reader::from_path("data/sample.ppt").unwrap();
+  /// let summary = parser.get_stream("\u{5}SummaryInformation").unwrap();
+  /// ```
+  pub fn get_stream(&self, path: &str)
+    -> Result<std::vec::Vec<u8>, super::error::Error> {
+    let path = path.trim_matches('/').to_uppercase();
+    let entry = self.iterate()
+      .filter(|entry| entry.entry_type == EntryType::UserStream)
+      .find(|entry| self.path(entry).to_uppercase() == path)
+      .ok_or(super::error::Error::EntryNotFound)?;
+    self.read_entry(entry)
+  }
+
+  /// Reads the content of the entry, as much as can be read.
+  fn read_entry(&self, entry: &Entry)
+    -> Result<std::vec::Vec<u8>, super::error::Error> {
+    use std::io::Read;
+
+    let mut slice = self.get_entry_slice(entry)?;
+    let mut buf = std::vec::Vec::with_capacity(slice.len());
+    slice.read_to_end(&mut buf).map_err(super::error::Error::IOError)?;
+    Ok(buf)
+  }
+
+  /// Returns the ranges of the file holding the content of the entry, in
+  /// order, as absolute byte offsets.
+  ///
+  /// Ranges stop at the size of the entry, so that the last one can be
+  /// shorter than a sector. Adjacent sectors are not merged.
+  pub fn entry_byte_ranges(&self, entry: &Entry)
+    -> Result<std::vec::Vec<std::ops::Range<usize>>, super::error::Error> {
+    let (_, chunks) = self.get_entry_chunks(entry)?;
+    // Sectors start after the header sector
+    let offset = *self.sec_size.as_ref().unwrap();
+    Ok(chunks.into_iter()
+      .map(|chunk| chunk.start + offset .. chunk.end + offset)
+      .collect())
+  }
+
+  /// Returns the streams whose chain of sectors holds less data than
+  /// their declared size.
+  ///
+  /// Storages written in transacted mode are only consistent once the
+  /// transaction is committed: a crash during the save can leave directory
+  /// entries pointing to sectors which were never written, so the content
+  /// of these streams is truncated.
+  pub fn incomplete_entries(&self) -> std::vec::Vec<&Entry> {
+    self.iterate()
+      .filter(|entry| entry.entry_type == EntryType::UserStream)
+      .filter(|entry| {
+        let sector_size = if entry.short {
+          *self.short_sec_size.as_ref().unwrap()
+        } else {
+          *self.sec_size.as_ref().unwrap()
+        };
+        entry.sec_id_chain.len().saturating_mul(sector_size) < entry.size
+      })
+      .collect()
+  }
+
+  /// Returns the streams whose chain was found in the other allocation
+  /// table than their size calls for, the SSAT for a stream of at least
+  /// the minimum standard stream size or the reverse.
+  pub fn misplaced_entries(&self) -> std::vec::Vec<&Entry> {
+    self.iterate().filter(|entry| entry.misplaced).collect()
+  }
+
+  /// Returns `true` if the file looks like a partially committed save.
+  pub fn is_partially_committed(&self) -> bool {
+    !self.incomplete_entries().is_empty()
+  }
+
+  /// Returns `true` if the file ends before its last directory sector
+  /// does, as left by a truncated copy.
+  ///
+  /// The entries found in the part which was written are read, the
+  /// others are lost along with the streams and storages below them.
+  pub fn is_directory_truncated(&self) -> bool {
+    self.directory_truncated
+  }
+
+  /// Returns the directory red-black trees in the Graphviz DOT format.
+  ///
+  /// Each entry is a node filled with its colour. Edges go to the left
+  /// and right siblings, and from storages to the root of their children
+  /// tree. Edges to missing entries are kept, so that the dangling
+  /// references written by some generators show up.
+  pub fn tree_dot(&self) -> std::string::String {
+    let entries = self.entries.as_ref().unwrap();
+    let mut dot = std::string::String::from("digraph directory {\n");
+    for entry in entries {
+      if entry.entry_type == EntryType::Empty {
+        continue;
+      }
+      let colour = match entry.color {
+        NodeColour::Red => "red",
+        NodeColour::Black => "black",
+      };
+      dot.push_str(&format!(
+        "  {} [label=\"#{} {}\", style=filled, fillcolor={}, fontcolor=white];\n",
+        entry.id, entry.id, entry.name.escape_default(), colour));
+      let edges = [
+        ("L", entry.left_child_node),
+        ("R", entry.right_child_node),
+        ("child", entry.root_node),
+      ];
+      for (label, id) in edges.iter() {
+        if *id != super::constants::FREE_SECID_U32 {
+          dot.push_str(&format!("  {} -> {} [label=\"{}\"];\n",
+            entry.id, id, label));
+        }
+      }
+    }
+    dot.push_str("}\n");
+    dot
+  }
+
+  /// Returns the path of the entry from the root storage, its name
+  /// included, with names separated by `/`.
+  ///
+  /// The root storage itself has an empty path.
+  pub fn path(&self, entry: &Entry) -> std::string::String {
+    self.path_names(entry).join("/")
+  }
+
+  /// Returns the directory as a JSON array, one object per entry with its
+  /// id, name, path, type, colour, size, timestamps (as FILETIME), CLSID,
+  /// state bits and the ids of its parent, children and siblings.
+  ///
+  /// # Examples
+  ///
+  /// ```ignore
+  /// use ole;
+  /// let parser = ole::Reader::from_path("assets/Thumbs.db").unwrap();
+  /// println!("{}", parser.entries_json());
+  /// ```
+  ///
+  /// Needs `serde_json`, which the `msg` feature enables.
+  #[cfg(feature = "serde_json")]
+  pub fn entries_json(&self) -> std::string::String {
+    let free = |id: u32| if id == super::constants::FREE_SECID_U32 {
+      None
     } else {
-      if &size < self.minimum_standard_stream_size.as_ref().unwrap() {
-        entry_slice = self.get_short_stream_slices(&entry.sec_id_chain, size)?;
-      } else {
-        entry_slice = self.get_stream_slices(&entry.sec_id_chain, size)?;
+      Some(id)
+    };
+    let entries: std::vec::Vec<serde_json::Value> = self.iterate()
+      .map(|entry| serde_json::json!({
+        "id": entry.id,
+        "name": entry.name,
+        "path": self.path(entry),
+        "type": format!("{:?}", entry.entry_type),
+        "color": format!("{:?}", entry.color),
+        "size": entry.size,
+        "creation_time": entry.creation_time,
+        "last_modification_time": entry.last_modification_time,
+        "clsid": entry.clsid(),
+        "state_bits": entry.state_bits(),
+        "parent": entry.parent_node,
+        "children": entry.children_nodes,
+        "left": free(entry.left_child_node),
+        "right": free(entry.right_child_node),
+        "child": free(entry.root_node),
+      }))
+      .collect();
+    serde_json::Value::Array(entries).to_string()
+  }
+
+  /// Names of the storages leading to the entry from the root storage,
+  /// followed by the name of the entry.
+  fn path_names<'a>(&'a self, entry: &'a Entry) -> std::vec::Vec<&'a str> {
+    let entries = self.entries.as_ref().unwrap();
+    let mut names = std::vec::Vec::<&str>::new();
+    let mut current = Some(entry);
+    // A corrupted tree may loop, so walk at most every entry once.
+    for _ in 0 .. entries.len() {
+      match current {
+        Some(e) if e.entry_type != EntryType::RootStorage => {
+          names.push(e.name());
+          current = e.parent_node.and_then(|id| entries.get(id as usize));
+        },
+        _ => break,
       }
-      Ok(entry_slice)
     }
+    names.reverse();
+    names
+  }
+
+  /// Writes every stream to a file under `dir`, in directories named
+  /// after the storages holding it, and returns the number of streams
+  /// written.
+  ///
+  /// Names are made safe for the file system the way 7-Zip does it:
+  /// control characters such as the one starting `\u{5}SummaryInformation`
+  /// are written as their code in brackets, `[5]SummaryInformation`,
+  /// and path separators are replaced by `_`. See `sanitize_filename`.
+  ///
+  /// # Examples
+  ///
+  /// ```ignore
+  /// use ole;
+  /// let parser = ole::Reader::from_path("assets/Thumbs.db").unwrap();
+  /// parser.extract_all("/tmp/thumbs").unwrap();
+  /// ```
+  pub fn extract_all<P: AsRef<std::path::Path>>(&self, dir: P)
+      -> Result<usize, super::error::Error> {
+    let mut count = 0;
+    for entry in self.iterate() {
+      let mut path = dir.as_ref().to_path_buf();
+      for name in self.path_names(entry) {
+        path.push(super::sanitize_filename(name, super::FileNamePolicy::Bracketed));
+      }
+      match entry.entry_type {
+        EntryType::UserStorage => {
+          std::fs::create_dir_all(&path).map_err(super::error::Error::IOError)?;
+        },
+        EntryType::UserStream => {
+          if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(super::error::Error::IOError)?;
+          }
+          let buf = self.read_entry(entry)?;
+          std::fs::write(&path, &buf).map_err(super::error::Error::IOError)?;
+          count += 1;
+        },
+        _ => {}
+      }
+    }
+    Ok(count)
   }
 
   pub(crate) fn build_directory_entries(&mut self)
@@ -373,109 +833,205 @@ impl<'ole> super::ole::Reader<'ole> {
     let mut entries = std::vec::Vec::<Entry>::with_capacity(
       self.dsat.as_ref().unwrap().len() * n_entry_by_sector);
 
+    let major_version = self.version_number.unwrap_or_default();
     let mut k = 0usize;
+    let mut truncated = false;
     for i in 0 .. self.dsat.as_ref().unwrap().len() {
       let sector_index = self.dsat.as_ref().unwrap()[i];
-      let sector = self.read_sector(sector_index as usize)?;
+      // Files cut within their directory keep the entries written in
+      // full, the missing ones being read as Empty entries so that the
+      // following DirIDs still match.
+      let sector = match self.read_sector(sector_index as usize) {
+        Ok(sector) => sector,
+        Err(_) => {
+          truncated = true;
+          std::borrow::Cow::Owned(self.read_padded_sector(sector_index as usize))
+        }
+      };
       for l in 0 .. n_entry_by_sector {
         let entry = Entry::from_slice(&sector[l
           * super::constants::DIRECTORY_ENTRY_SIZE .. (l + 1)
-          * super::constants::DIRECTORY_ENTRY_SIZE], k as u32)?;
+          * super::constants::DIRECTORY_ENTRY_SIZE], k as u32, major_version)?;
         entries.push(entry);
-        k = k + 1;
+        k += 1;
       }
     }
     let stream_size = *self.minimum_standard_stream_size.as_ref().unwrap();
-    for i in 0 .. entries.len() {
-      let entry = &mut entries[i];
+    for (i, entry) in entries.iter_mut().enumerate() {
       match entry.entry_type {
         EntryType::UserStream => {
           let start_index = entry.sec_id_chain.pop().unwrap();
-          if entry.size < stream_size {
-            entry.sec_id_chain = self.build_chain_from_ssat(start_index);
-          } else {
-            entry.sec_id_chain = self.build_chain_from_sat(start_index);
+          // Nothing to read, whatever the start SecID says
+          if entry.size == 0 {
+            continue;
           }
+          let short = entry.size < stream_size;
+          let (chain, misplaced) = self.build_stream_chain(start_index,
+            entry.size, short)?;
+          entry.short = short != misplaced;
+          entry.misplaced = misplaced;
+          entry.sec_id_chain = chain;
         },
         EntryType::RootStorage => {
           self.root_entry = Some(i as u32);
           let start_index = entry.sec_id_chain.pop().unwrap();
-          entry.sec_id_chain = self.build_chain_from_sat(start_index);
+          entry.sec_id_chain = self.build_chain_from_sat(start_index)?;
         },
         _ => {}
       }
     }
     self.entries = Some(entries);
+    self.directory_truncated = truncated;
     self.build_entry_tree(0, None);
     Ok(())
   }
 
-  fn get_short_stream_slices(&self, chain: &std::vec::Vec<u32>, size: usize)
-  -> Result<EntrySlice, super::error::Error> {
+  /// Returns the chain of a stream of `size` bytes, from the SSAT if
+  /// `short`, else from the SAT, and whether it was found in the other
+  /// table instead.
+  ///
+  /// Some writers save a stream in the short stream container while its
+  /// size calls for standard sectors, or the reverse. When the expected
+  /// chain cannot hold the stream, the chain is looked up in the other
+  /// table, and kept if it holds the stream without a spare sector.
+  fn build_stream_chain(&self, start: u32, size: usize, short: bool)
+    -> Result<(std::vec::Vec<u32>, bool), super::error::Error> {
+    let build = |short: bool| if short {
+      self.build_chain_from_ssat(start)
+    } else {
+      self.build_chain_from_sat(start)
+    };
+    let sector_size = |short: bool| if short {
+      *self.short_sec_size.as_ref().unwrap()
+    } else {
+      *self.sec_size.as_ref().unwrap()
+    };
+    let expected = build(short);
+    if let Ok(chain) = &expected {
+      if chain.len().saturating_mul(sector_size(short)) >= size {
+        return expected.map(|chain| (chain, false));
+      }
+    }
+    let other_size = sector_size(!short);
+    match build(!short) {
+      Ok(chain) if chain.len().saturating_mul(other_size) >= size
+        && chain.len().saturating_mul(other_size) - size < other_size =>
+        Ok((chain, true)),
+      _ => expected.map(|chain| (chain, false))
+    }
+  }
+
+  /// Returns the ranges of the body holding the chunks of the stream.
+  fn get_short_stream_chunks(&self, chain: &[u32], size: usize)
+  -> Result<std::vec::Vec<std::ops::Range<usize>>, super::error::Error> {
     let ssector_size = *self.short_sec_size.as_ref().unwrap();
-    let mut entry_slice = EntrySlice::new(ssector_size, size);
+    let mut chunks = std::vec::Vec::with_capacity(chain.len());
+    let root_entry = self.root_entry
+      .ok_or(super::error::Error::BadRootStorageSize)?;
     let short_stream_chain =
-    &self.entries.as_ref().unwrap()[0].sec_id_chain.clone();
+      &self.entries.as_ref().unwrap()[root_entry as usize].sec_id_chain;
     let n_per_sector = *self.sec_size.as_ref().unwrap() /
       ssector_size;
+    if n_per_sector == 0 {
+      return Err(super::error::Error::BadSizeValue(
+        "Short sector size is greater than sector size"));
+    }
     let mut total_read = 0;
     for ssector_id in chain {
-      let sector_index = short_stream_chain[*ssector_id as usize / n_per_sector];
-      let sector = self.read_sector(sector_index as usize)?;
+      // The short sector has to lie inside the short stream container,
+      // i.e. the stream held by the root entry.
+      let sector_index = *short_stream_chain
+        .get(*ssector_id as usize / n_per_sector)
+        .ok_or(super::error::Error::ShortSectorOutOfRange(*ssector_id))?;
+      let sector = self.sector_range(sector_index as usize)?;
       let ssector_index = *ssector_id as usize % n_per_sector;
-      let start = ssector_index as usize * ssector_size;
+      let start = sector.start + ssector_index * ssector_size;
       let end = start + std::cmp::min(ssector_size, size - total_read);
-      entry_slice.add_chunk(&sector[start .. end]);
+      chunks.push(start .. end);
       total_read += end - start;
     }
-    Ok(entry_slice)
+    Ok(chunks)
   }
 
-  fn get_stream_slices(&self, chain: &std::vec::Vec<u32>, size: usize)
-  -> Result<EntrySlice, super::error::Error> {
+  /// Returns the ranges of the body holding the chunks of the stream.
+  fn get_stream_chunks(&self, chain: &[u32], size: usize)
+  -> Result<std::vec::Vec<std::ops::Range<usize>>, super::error::Error> {
     let sector_size = *self.sec_size.as_ref().unwrap();
-    let mut entry_slice = EntrySlice::new(sector_size, size);
+    let mut chunks = std::vec::Vec::with_capacity(chain.len());
     let mut total_read = 0;
     for sector_id in chain {
-      let sector = self.read_sector(*sector_id as usize)?;
-      let start = 0usize;
-      let end = std::cmp::min(sector_size, size - total_read);
-      entry_slice.add_chunk(&sector[start .. end]);
-      total_read += end - start;
+      let sector = self.sector_range(*sector_id as usize)?;
+      let end = sector.start + std::cmp::min(sector_size, size - total_read);
+      total_read += end - sector.start;
+      chunks.push(sector.start .. end);
     }
-    Ok(entry_slice)
+    Ok(chunks)
   }
 
+  /// Returns the size of the chunks of the entry, and their ranges in the
+  /// body.
+  fn get_entry_chunks(&self, entry: &Entry)
+  -> Result<(usize, std::vec::Vec<std::ops::Range<usize>>), super::error::Error> {
+    let size = entry.size;
+    if size == 0 {
+      Ok((*self.sec_size.as_ref().unwrap(), vec![]))
+    } else if entry.short {
+      Ok((*self.short_sec_size.as_ref().unwrap(),
+        self.get_short_stream_chunks(&entry.sec_id_chain, size)?))
+    } else {
+      Ok((*self.sec_size.as_ref().unwrap(),
+        self.get_stream_chunks(&entry.sec_id_chain, size)?))
+    }
+  }
+
+  /// Registers the parent and children of the entries reachable from
+  /// the root, walking the red-black trees with a stack of its own.
+  ///
+  /// Each entry is visited once, so that siblings or children pointing
+  /// back to an entry already seen, as in crafted files, end the walk
+  /// there rather than looping.
   fn build_entry_tree(&mut self, id: u32, parent_id: Option<u32>) {
+    let entries = self.entries.as_mut().unwrap();
+    let n = entries.len() as u32;
+    let mut visited = vec![false; entries.len()];
+    let mut stack = vec![(id, parent_id)];
+    while let Some((id, parent_id)) = stack.pop() {
+      // Children of storages may be entries which were lost
+      // along with the end of the directory
+      if id >= n || visited[id as usize] {
+        continue;
+      }
+      visited[id as usize] = true;
 
-    if id != super::constants::FREE_SECID_U32 {
+      let node_type = entries[id as usize]._type();
 
-      // Register the parent id for the current node
-      self.entries.as_mut().unwrap()[id as usize].parent_node = parent_id;
+      // Some exporters leave Empty entries in the middle of a sibling
+      // tree. They keep their id but are not registered, and only their
+      // siblings are followed.
+      if node_type != EntryType::Empty {
+        // Register the parent id for the current node
+        entries[id as usize].parent_node = parent_id;
 
-      // Register as child
-      if parent_id.is_some() {
-        self.entries.as_mut().unwrap()[parent_id.unwrap() as usize]
-          .children_nodes.push(id);
+        // Register as child
+        if let Some(parent_id) = parent_id {
+          entries[parent_id as usize].children_nodes.push(id);
+        }
       }
 
-      let node_type = self.entries.as_ref().unwrap()[id as usize]._type();
-
-      if node_type == EntryType::RootStorage || node_type ==
-        EntryType::UserStorage {
-          let child = self.entries.as_mut().unwrap()[id as usize].root_node;
-          self.build_entry_tree(child, Some(id));
+      // The root storage is never a sibling, an id of 0 is
+      // what the zeroed pointers of an Empty entry read as.
+      // Pushed in reverse, so that the child is walked first.
+      let right_child = entries[id as usize].right_child_node();
+      if right_child != 0 {
+        stack.push((right_child, parent_id));
       }
-      let left_child = self.entries.as_mut().unwrap()[id as usize]
-          .left_child_node();
-      let right_child = self.entries.as_mut().unwrap()[id as usize]
-          .right_child_node();
-      let n = self.entries.as_ref().unwrap().len() as u32;
-      if left_child < n {
-        self.build_entry_tree(left_child, parent_id);
+      let left_child = entries[id as usize].left_child_node();
+      if left_child != 0 {
+        stack.push((left_child, parent_id));
       }
-      if right_child < n {
-        self.build_entry_tree(right_child, parent_id);
+      if node_type == EntryType::RootStorage || node_type ==
+        EntryType::UserStorage {
+          stack.push((entries[id as usize].root_node, Some(id)));
       }
     }
   }
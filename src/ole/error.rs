@@ -1,7 +1,25 @@
-use std;
-
 /// Errors related to the process of parsing.
+///
+/// Each variant has a stable numeric code, see `Error::code`:
+///
+/// | Code | Variant                            |
+/// |------|------------------------------------|
+/// | 101  | `BadFileSize`                      |
+/// | 102  | `IOError`                          |
+/// | 103  | `NotImplementedYet`                |
+/// | 104  | `InvalidOLEFile`                   |
+/// | 105  | `BadSizeValue`                     |
+/// | 106  | `EmptyMasterSectorAllocationTable` |
+/// | 107  | `NotSectorUsedBySAT`               |
+/// | 108  | `NodeTypeUnknown`                  |
+/// | 109  | `BadRootStorageSize`               |
+/// | 110  | `EmptyEntry`                       |
+/// | 111  | `EntryNotFound`                    |
+/// | 112  | `ShortSectorOutOfRange`            |
+/// | 113  | `MalformedChain`                   |
 #[derive(Debug)]
+#[non_exhaustive]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
   /// This happens when filesize is null, or to big to fit into an usize.
   BadFileSize,
@@ -32,21 +50,59 @@ pub enum Error {
 
   /// User query an empty entry
   EmptyEntry,
+
+  /// No entry has the queried id or path.
+  EntryNotFound,
+
+  /// A short sector lies outside of the short stream container.
+  ShortSectorOutOfRange(u32),
+
+  /// A chain of SecIDs points outside of its allocation table, or loops.
+  MalformedChain,
 }
 
 impl std::fmt::Display for Error {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match *self {
       Error::BadFileSize => write!(f, "Filesize is null or too big."),
-      Error::IOError(ref e) => write!(f, "{}", e.to_string()),
+      Error::IOError(ref e) => write!(f, "{}", e),
       Error::NotImplementedYet => write!(f, "Method not implemented yet"),
       Error::InvalidOLEFile => write!(f, "Invalid OLE File"),
-      Error::BadSizeValue(ref e) => write!(f, "{}", e.to_string()),
+      Error::BadSizeValue(e) => write!(f, "{}", e),
       Error::EmptyMasterSectorAllocationTable => write!(f, "MSAT is empty"),
       Error::NotSectorUsedBySAT => write!(f, "Sector is not a sector used by the SAT."),
       Error::NodeTypeUnknown => write!(f, "Unknown node type"),
       Error::BadRootStorageSize => write!(f, "Bad RootStorage size"),
-      Error::EmptyEntry => write!(f, "Empty entry")
+      Error::EmptyEntry => write!(f, "Empty entry"),
+      Error::EntryNotFound => write!(f, "Entry not found"),
+      Error::ShortSectorOutOfRange(id) => write!(f,
+        "Short sector {} is outside of the short stream container", id),
+      Error::MalformedChain => write!(f, "Malformed sector chain")
+    }
+  }
+}
+
+impl Error {
+  /// Returns the stable numeric code of the error, from 100 to 199,
+  /// so that it can be reported without matching messages.
+  ///
+  /// Codes are never reused: a removed variant leaves its code unused,
+  /// and new variants take new codes.
+  pub fn code(&self) -> u32 {
+    match *self {
+      Error::BadFileSize => 101,
+      Error::IOError(_) => 102,
+      Error::NotImplementedYet => 103,
+      Error::InvalidOLEFile => 104,
+      Error::BadSizeValue(_) => 105,
+      Error::EmptyMasterSectorAllocationTable => 106,
+      Error::NotSectorUsedBySAT => 107,
+      Error::NodeTypeUnknown => 108,
+      Error::BadRootStorageSize => 109,
+      Error::EmptyEntry => 110,
+      Error::EntryNotFound => 111,
+      Error::ShortSectorOutOfRange(_) => 112,
+      Error::MalformedChain => 113,
     }
   }
 }
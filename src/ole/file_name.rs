@@ -0,0 +1,83 @@
+/// Names Windows gives to devices, whatever their extension.
+const RESERVED_NAMES: [&str; 22] = [
+  "CON", "PRN", "AUX", "NUL",
+  "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+  "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// How `sanitize_filename` writes the characters which are not valid
+/// in file names.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileNamePolicy {
+
+  /// Control characters are written as their code in brackets, such as
+  /// `[5]`, and the other characters as `_`, the way 7-Zip does it.
+  #[default]
+  Bracketed,
+
+  /// Characters are replaced by the given one, or by `_` when it is not
+  /// valid in file names either, such as `/` or `.`.
+  Replace(char),
+
+  /// Characters are left out.
+  Remove
+}
+
+/// Returns `name` as a file name which is valid on common file systems,
+/// Windows included.
+///
+/// Path separators and the characters Windows does not allow are written
+/// according to `policy`, as are trailing dots and spaces. Device names such
+/// as `CON` or `com1.txt`, and names left empty, get a leading `_`.
+///
+/// # Examples
+///
+/// ```
+/// use msg_parser::{sanitize_filename, FileNamePolicy};
+/// assert_eq!(sanitize_filename("a/b?.txt", FileNamePolicy::Bracketed), "a_b_.txt");
+/// assert_eq!(sanitize_filename("\u{5}Summary", FileNamePolicy::Bracketed),
+///   "[5]Summary");
+/// assert_eq!(sanitize_filename("Q3: plan.", FileNamePolicy::Replace('-')),
+///   "Q3- plan-");
+/// assert_eq!(sanitize_filename("CON.txt", FileNamePolicy::Remove), "_CON.txt");
+/// ```
+pub fn sanitize_filename(name: &str, policy: FileNamePolicy)
+    -> std::string::String {
+  let replace = |safe: &mut std::string::String, c: char| match policy {
+    FileNamePolicy::Bracketed if c < ' ' => safe.push_str(&format!("[{}]", c as u32)),
+    FileNamePolicy::Bracketed => safe.push('_'),
+    FileNamePolicy::Replace(replacement) if is_invalid(replacement)
+      || replacement == '.' || replacement == ' ' => safe.push('_'),
+    FileNamePolicy::Replace(replacement) => safe.push(replacement),
+    FileNamePolicy::Remove => {}
+  };
+
+  let kept = name.trim_end_matches(['.', ' ']);
+  let mut safe = std::string::String::new();
+  for c in kept.chars() {
+    if is_invalid(c) {
+      replace(&mut safe, c);
+    } else {
+      safe.push(c);
+    }
+  }
+  for c in name[kept.len()..].chars() {
+    replace(&mut safe, c);
+  }
+
+  let stem = safe.split('.').next().unwrap_or_default().trim_end();
+  let reserved = RESERVED_NAMES.iter()
+    .any(|reserved| reserved.eq_ignore_ascii_case(stem));
+  match safe.as_str() {
+    "" | "." | ".." => format!("_{}", safe),
+    _ if reserved => format!("_{}", safe),
+    _ => safe,
+  }
+}
+
+/// Whether `c` is a path separator or a character Windows does not allow
+/// in file names.
+fn is_invalid(c: char) -> bool {
+  matches!(c, '\0' ..= '\u{1f}' | '/' | '\\' | ':' | '*' | '?' | '"' | '<'
+    | '>' | '|')
+}
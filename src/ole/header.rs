@@ -1,4 +1,3 @@
-use std;
 use std::io::Read;
 use crate::ole::util::FromSlice;
 
@@ -14,7 +13,7 @@ impl<'ole> super::ole::Reader<'ole> {
     let result: Result<(), super::error::Error>;
 
     // Check file identifier
-    if &super::constants::IDENTIFIER != &header[0..8] {
+    if super::constants::IDENTIFIER != header[0..8] {
       result = Err(super::error::Error::InvalidOLEFile);
     } else {
 
@@ -28,10 +27,10 @@ impl<'ole> super::ole::Reader<'ole> {
       self.version_number = Some(rv_number as u16);
 
       // Check little-endianness; big endian not yet supported
-      if &header[28..30] == &super::constants::BIG_ENDIAN_IDENTIFIER {
+      if header[28..30] == super::constants::BIG_ENDIAN_IDENTIFIER {
         result = Err(super::error::Error::NotImplementedYet);
       } else if
-          &header[28..30] != &super::constants::LITTLE_ENDIAN_IDENTIFIER {
+          header[28..30] != super::constants::LITTLE_ENDIAN_IDENTIFIER {
         result = Err(super::error::Error::InvalidOLEFile);
       } else {
 
@@ -44,6 +43,10 @@ impl<'ole> super::ole::Reader<'ole> {
           result =
             Err(super::error::Error::BadSizeValue("Overflow on sector
             size"));
+        } else if 2usize.pow(k as u32) < super::constants::DIRECTORY_ENTRY_SIZE {
+          // a sector has to hold at least one directory entry
+          result = Err(super::error::Error::BadSizeValue(
+            "Sector size is too small"));
         } else {
           self.sec_size = Some(2usize.pow(k as u32));
 
@@ -58,17 +61,12 @@ impl<'ole> super::ole::Reader<'ole> {
           } else {
             self.short_sec_size = Some(2usize.pow(k as u32));
 
-            let sat: std::vec::Vec<u32>;
-
-
-            // Total number of sectors used for the sector allocation table
-            sat = std::vec::Vec::with_capacity(
-              (*self.sec_size.as_ref().unwrap() / 4)
-              *  usize::from_slice(&header[44..48]));
+            // The allocation tables grow as their sectors are read, rather
+            // than from the sizes of the header, which may be anything.
+            let sat: std::vec::Vec<u32> = std::vec::Vec::new();
 
             // SecID of the first sector of directory stream
-            let mut dsat: std::vec::Vec<u32> = std::vec::Vec::new();
-            dsat.push(u32::from_slice(&header[48..52]));
+            let dsat: std::vec::Vec<u32> = vec![u32::from_slice(&header[48..52])];
 
             // Minimum size of a standard stream (bytes)
             self.minimum_standard_stream_size =
@@ -80,25 +78,14 @@ impl<'ole> super::ole::Reader<'ole> {
                 < 4096usize {
               result = Err(super::error::Error::InvalidOLEFile);
             } else {
-              let mut ssat: std::vec::Vec<u32>;
-              let mut msat: std::vec::Vec<u32>;
-
-              // secID of the first sector of the SSAT & Total number
-              // of sectors used for the short-sector allocation table
-              ssat = std::vec::Vec::with_capacity(
-                usize::from_slice(&header[64..68])
-                * (*self.sec_size.as_ref().unwrap() / 4));
-              ssat.push(u32::from_slice(&header[60..64]));
-
-              // secID of first sector of the master sector allocation table
-              // & Total number of sectors used for
-              // the master sector allocation table
-              msat = vec![super::constants::FREE_SECID_U32; 109];
-              if &header[68..72] != &super::constants::END_OF_CHAIN_SECID {
-                msat.resize(109usize + usize::from_slice(&header[72..76])
-                  * (*self.sec_size.as_ref().unwrap() / 4),
-                  super::constants::FREE_SECID_U32);
-              }
+              // secID of the first sector of the SSAT
+              let ssat: std::vec::Vec<u32> =
+                vec![u32::from_slice(&header[60..64])];
+
+              // The MSAT starts in the header, further sectors of it are
+              // added by read_sec_ids
+              let msat: std::vec::Vec<u32> =
+                vec![super::constants::FREE_SECID_U32; 109];
               self.sat = Some(sat);
               self.msat = Some(msat);
               self.dsat = Some(dsat);
@@ -122,69 +109,106 @@ impl<'ole> super::ole::Reader<'ole> {
       -> Result<(), super::error::Error> {
 
     // First, we build the master sector allocation table from the header
-    let mut total_sec_id_read = self.read_sec_ids(&header[76 ..], 0);
+    let mut total_sec_id_read = self.read_sec_ids(&header[76 ..], 0)?;
+
+    // Sectors start after the header, which takes a whole sector of 4096
+    // bytes in version 4 files.
+    let sec_size = *self.sec_size.as_ref().unwrap();
+    let padding = sec_size.saturating_sub(super::constants::HEADER_SIZE);
+    match self.body.as_mut() {
+      Some(body) => body.set_offset(sec_size),
+      None => {
+        let mut skipped = vec![0u8; padding];
+        self.read(&mut skipped)?;
+      }
+    }
 
     // Check if additional sectors are used for building the msat
+    let mut buffer = vec![0u8; 0];
     if total_sec_id_read == 109 {
-      let sec_size = *self.sec_size.as_ref().unwrap();
       let mut sec_id = usize::from_slice(&header[68..72]);
-      let mut buffer = vec![0u8; 0];
       let mut steps_since_last_resize = 0;
+      let mut steps = 0usize;
 
 
       while sec_id != super::constants::END_OF_CHAIN_SECID_U32 as usize {
-        let relative_offset = sec_id * sec_size;
-
-        // check if we need to read more data
-        if buffer.len() < relative_offset + sec_size {
-          let old_len = buffer.len();
-          let new_len = relative_offset + sec_size;
-          buffer.resize(new_len, 0xFFu8);
-          self.read(&mut buffer[old_len..new_len])?;
-          steps_since_last_resize = 0;
-        }
+        // Sectors of a body already known are read from it, others from
+        // the source, up to the sector.
+        let sector = if let Some(body) = &self.body {
+          steps += 1;
+          if steps > body.len() / sec_size {
+            // There is a loop in the MSAT chain
+            return Err(super::error::Error::InvalidOLEFile);
+          }
+          self.read_sector(sec_id)?.into_owned()
+        } else {
+          let relative_offset = sec_id.checked_mul(sec_size)
+            .ok_or(super::error::Error::BadSizeValue(
+              "Overflow on MSAT sector offset"))?;
+          let sector_end = relative_offset.checked_add(sec_size)
+            .ok_or(super::error::Error::BadSizeValue(
+              "Overflow on MSAT sector offset"))?;
+
+          // check if we need to read more data
+          if buffer.len() < sector_end {
+            let old_len = buffer.len();
+            let new_len = sector_end;
+            buffer.resize(new_len, 0xFFu8);
+            self.read(&mut buffer[old_len..new_len])?;
+            steps_since_last_resize = 0;
+          }
 
-        total_sec_id_read += self.read_sec_ids(&buffer[relative_offset
-          .. relative_offset + sec_size - 4], total_sec_id_read);
-        sec_id = usize::from_slice(&buffer[relative_offset + sec_size - 4
-          .. relative_offset + sec_size]);
+          steps_since_last_resize += 1;
+          if steps_since_last_resize * sec_size > buffer.len() {
+            // There is a loop in the MSAT chain
+            return Err(super::error::Error::InvalidOLEFile);
+          }
+          buffer[relative_offset .. sector_end].to_vec()
+        };
 
-        steps_since_last_resize += 1;
-        if steps_since_last_resize * sec_size > buffer.len() {
-          // There is a loop in the MSAT chain
-          return Err(super::error::Error::InvalidOLEFile);
-        }
+        total_sec_id_read += self.read_sec_ids(&sector[.. sec_size - 4],
+          total_sec_id_read)?;
+        sec_id = usize::from_slice(&sector[sec_size - 4 ..]);
       }
-        // save the buffer for later usage
-        self.body = Some(buffer);
     }
     self.msat.as_mut().unwrap().resize(
       total_sec_id_read, super::constants::FREE_SECID_U32);
 
-    // Now, we read the all file
-    let mut buf: &mut std::vec::Vec<u8>;
-    if !self.body.is_some() {
-      self.body = Some(std::vec::Vec::new());
+    // Now, we read the all file, unless it is shared with the caller or
+    // read on demand. The buffer already holds the sectors read for the
+    // msat.
+    if self.body.is_none() {
+      self.buf_reader.as_mut().unwrap().read_to_end(&mut buffer)
+        .map_err(super::error::Error::IOError)?;
+      self.body = Some(super::ole::Body::from(buffer));
     }
-    buf = self.body.as_mut().unwrap();
-
-    self.buf_reader.as_mut().unwrap().read_to_end(&mut
-      buf).map_err(super::error::Error::IOError)?;
     Ok(())
   }
 
-  fn read_sec_ids(&mut self, buffer: &[u8], msat_offset: usize) -> usize {
+  /// Reads the SecIDs of `buffer` into the MSAT from `msat_offset`, up to
+  /// the first free SecID, and returns how many were read.
+  ///
+  /// The header may declare fewer MSAT sectors than the chain holds, so
+  /// the MSAT grows as needed.
+  fn read_sec_ids(&mut self, buffer: &[u8], msat_offset: usize)
+      -> Result<usize, super::error::Error> {
     let mut i = 0usize;
     let mut offset = 0usize;
     let max_sec_ids = buffer.len() / 4;
-    let msat = &mut self.msat.as_mut().unwrap()[msat_offset .. ];
-    while i < max_sec_ids && &buffer[offset .. offset + 4]
-      != &super::constants::FREE_SECID {
+    let msat = self.msat.as_mut().unwrap();
+    let needed = msat_offset.checked_add(max_sec_ids)
+      .ok_or(super::error::Error::BadSizeValue("Overflow on MSAT size"))?;
+    if msat.len() < needed {
+      msat.resize(needed, super::constants::FREE_SECID_U32);
+    }
+    let msat = &mut msat[msat_offset .. ];
+    while i < max_sec_ids && buffer[offset .. offset + 4]
+      != super::constants::FREE_SECID {
       msat[i] = u32::from_slice(&buffer[offset .. offset + 4]);
       offset += 4;
       i += 1;
     }
 
-    i
+    Ok(i)
   }
 }
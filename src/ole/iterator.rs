@@ -8,7 +8,7 @@ impl<'a> OLEIterator<'a> {
 
   pub(crate) fn new(ole: &'a super::ole::Reader) -> OLEIterator<'a> {
     OLEIterator {
-      ole: ole,
+      ole,
       curr: 0
     }
   }
@@ -6,10 +6,14 @@
 //! It includes a reader too, for iterating over entries and for extracting
 //! files inside the OLE storage.
 //!
+//! The reader does not depend on the .msg parser: building the crate with
+//! `default-features = false` leaves out the parser and its dependencies,
+//! for callers only reading compound files such as `Thumbs.db` or `.doc`.
+//!
 //! ## Example
 //!
 //! ```ignore
-//! use crate::ole::Reader;
+//! use msg_parser::ole::Reader;
 //! use std::io::{Read, Write};
 //!
 //! let mut file = std::fs::File::open("data/Thumbs.db").unwrap();
@@ -31,6 +35,7 @@
 //! extracted_file.write_all(&buffer[..]);
 //! ```
 
+#[allow(clippy::module_inception)]
 mod ole;
 pub use ole::Reader;
 
@@ -42,12 +47,22 @@ pub use error::Error;
 
 pub(crate) mod header;
 pub(crate) mod util;
+pub(crate) mod file_name;
+pub use file_name::{sanitize_filename, FileNamePolicy};
 pub(crate) mod sat;
 pub(crate) mod constants;
+pub use constants::Application;
 
 pub mod entry;
 pub use entry::Entry;
 pub use entry::EntrySlice;
+pub use entry::EntryStream;
 pub use entry::EntryType;
+pub use entry::NodeColour;
 
 pub(crate) mod sector;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod writer;
+#[cfg(any(test, feature = "testing"))]
+pub use writer::Writer;
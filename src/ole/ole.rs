@@ -1,5 +1,3 @@
-use std;
-
 /// An OLE file reader.
 ///
 /// The parsing method follows the same method described here:
@@ -18,7 +16,6 @@ use std;
 ///   println!("{}", entry);
 /// }
 /// ```
-
 pub struct Reader<'ole> {
 
   /// Buffer for reading from the source.
@@ -55,13 +52,116 @@ pub struct Reader<'ole> {
   pub(crate) msat: Option<std::vec::Vec<u32>>,
 
   /// Body of the file.
-  pub(crate) body: Option<std::vec::Vec<u8>>,
+  pub(crate) body: Option<Body>,
 
   /// Directory entries.
   pub(crate) entries: Option<std::vec::Vec<super::entry::Entry>>,
 
   /// DirID of the root entry.
-  pub(crate) root_entry: Option<u32>
+  pub(crate) root_entry: Option<u32>,
+
+  /// Whether the directory runs past the end of the file.
+  pub(crate) directory_truncated: bool,
+
+  /// Time spent reading the header and the directory entries.
+  pub(crate) header_time: std::time::Duration,
+
+  /// Time spent building the allocation tables.
+  pub(crate) sat_time: std::time::Duration
+}
+
+/// Bytes shared between readers, such as an `Arc<Vec<u8>>`.
+pub(crate) type SharedBytes = std::sync::Arc<dyn AsRef<[u8]> + Send + Sync>;
+
+/// Source read on demand, shared between a reader and its entry streams.
+pub(crate) type SharedSource = std::sync::Arc<std::sync::Mutex<Box<dyn ReadSeek + Send>>>;
+
+/// Sources which can be read at any offset, such as files.
+pub(crate) trait ReadSeek: std::io::Read + std::io::Seek {}
+
+impl<T: std::io::Read + std::io::Seek> ReadSeek for T {}
+
+/// Bytes of the file which follow its header, from the first sector on.
+///
+/// They are either read from the source, borrowed from the buffer given
+/// to `Reader::from_shared` without being copied, or read on demand from
+/// the source given to `Reader::from_seekable`.
+#[derive(Clone)]
+pub(crate) enum Body {
+  /// Bytes held in memory, from `offset`.
+  Memory { data: SharedBytes, offset: usize },
+
+  /// Bytes of a source of `len` bytes, from `offset`.
+  Seekable { source: SharedSource, offset: u64, len: u64 }
+}
+
+impl From<std::vec::Vec<u8>> for Body {
+  fn from(data: std::vec::Vec<u8>) -> Body {
+    Body::Memory { data: std::sync::Arc::new(data), offset: 0 }
+  }
+}
+
+impl Body {
+  /// Returns the number of bytes of the body.
+  pub(crate) fn len(&self) -> usize {
+    match self {
+      Body::Memory { .. } => self.bytes().map_or(0, |bytes| bytes.len()),
+      Body::Seekable { offset, len, .. } =>
+        std::convert::TryFrom::try_from(len.saturating_sub(*offset)).unwrap_or(usize::MAX)
+    }
+  }
+
+  /// Returns the bytes held in memory, none for a seekable source.
+  pub(crate) fn bytes(&self) -> Option<&[u8]> {
+    match self {
+      Body::Memory { data, offset } =>
+        Some((**data).as_ref().get(*offset ..).unwrap_or_default()),
+      Body::Seekable { .. } => None
+    }
+  }
+
+  /// Moves the start of the body to `offset` bytes into the file, where
+  /// the first sector starts.
+  pub(crate) fn set_offset(&mut self, value: usize) {
+    match self {
+      Body::Memory { offset, .. } => *offset = value,
+      Body::Seekable { offset, .. } => *offset = value as u64
+    }
+  }
+
+  /// Returns the bytes of `range`, borrowed from memory or read from the
+  /// source.
+  pub(crate) fn read(&self, range: std::ops::Range<usize>)
+      -> std::io::Result<std::borrow::Cow<'_, [u8]>> {
+    use std::io::{Read, Seek};
+    let out_of_range = || std::io::Error::new(std::io::ErrorKind::UnexpectedEof,
+      "range past the end of the file");
+    match self {
+      Body::Memory { .. } => self.bytes()
+        .and_then(|bytes| bytes.get(range))
+        .map(std::borrow::Cow::Borrowed)
+        .ok_or_else(out_of_range),
+      Body::Seekable { source, offset, .. } => {
+        let start = offset.checked_add(range.start as u64)
+          .ok_or_else(out_of_range)?;
+        let mut buf = vec![0u8; range.len()];
+        let mut source = source.lock()
+          .map_err(|_| std::io::Error::other("source poisoned by a panic"))?;
+        source.seek(std::io::SeekFrom::Start(start))?;
+        source.read_exact(&mut buf)?;
+        Ok(std::borrow::Cow::Owned(buf))
+      }
+    }
+  }
+}
+
+/// Source of `Reader::from_shared`, read from the start of the data.
+struct SharedCursor(SharedBytes);
+
+impl AsRef<[u8]> for SharedCursor {
+  fn as_ref(&self) -> &[u8] {
+    (*self.0).as_ref()
+  }
 }
 
 impl<'ole> Reader<'ole> {
@@ -75,11 +175,103 @@ impl<'ole> Reader<'ole> {
   /// let mut my_resume = std::fs::File::open("assets/Thumbs.db").unwrap();
   /// let mut parser = ole::Reader::new(my_resume).unwrap();
   /// ```
-  pub fn new<T: 'ole>(readable: T)
+  pub fn new<T>(readable: T)
         -> std::result::Result<Reader<'ole>, super::error::Error>
-    where T: std::io::Read {
+    where T: std::io::Read + 'ole {
+    Reader::with_body(Box::new(readable), None)
+  }
+
+  /// Constructs a new `Reader` from a source whose first bytes were
+  /// already read, e.g. to route the file by its header.
+  ///
+  /// `peeked_header` holds the bytes read from `readable` so far, usually
+  /// the 512 bytes of the header, and the rest of the file is read from
+  /// `readable`: sources which cannot seek, such as network streams, are
+  /// neither reopened nor read twice. A header which is not the one of
+  /// an OLE file fails with `Error::InvalidOLEFile` before `readable` is
+  /// read.
+  ///
+  /// # Examples
+  ///
+  /// ```ignore
+  /// use ole;
+  /// use std::io::Read;
+  /// let mut file = std::fs::File::open("assets/Thumbs.db").unwrap();
+  /// let mut header = [0u8; 512];
+  /// file.read_exact(&mut header).unwrap();
+  /// let parser = ole::Reader::try_new_sniffed(file, &header).unwrap();
+  /// ```
+  pub fn try_new_sniffed<T>(readable: T, peeked_header: &[u8])
+        -> std::result::Result<Reader<'ole>, super::error::Error>
+    where T: std::io::Read + 'ole {
+    let identifier = &super::constants::IDENTIFIER;
+    let len = peeked_header.len().min(identifier.len());
+    if peeked_header[.. len] != identifier[.. len] {
+      return Err(super::error::Error::InvalidOLEFile);
+    }
+    let peeked = std::io::Cursor::new(peeked_header.to_vec());
+    Reader::new(std::io::Read::chain(peeked, readable))
+  }
+
+  /// Constructs a new `Reader` over bytes shared with other threads.
+  ///
+  /// Streams are read from `data` itself rather than from a copy, so that
+  /// several readers can parse the same buffer concurrently.
+  ///
+  /// # Examples
+  ///
+  /// ```ignore
+  /// use ole;
+  /// let data = std::sync::Arc::new(std::fs::read("assets/Thumbs.db").unwrap());
+  /// let parser = ole::Reader::from_shared(data.clone()).unwrap();
+  /// ```
+  pub fn from_shared<T>(data: std::sync::Arc<T>)
+        -> std::result::Result<Reader<'ole>, super::error::Error>
+    where T: AsRef<[u8]> + Send + Sync + 'static {
+    let data: SharedBytes = data;
+    // The header is read from the start of the data
+    let source = std::io::Cursor::new(SharedCursor(data.clone()));
+    Reader::with_body(Box::new(source), Some(Body::Memory { data, offset: 0 }))
+  }
+
+  /// Constructs a new `Reader` over a source which can seek, such as a
+  /// file.
+  ///
+  /// Only the header, the allocation tables and the directory are read
+  /// when constructing the reader. Streams are read from the source as
+  /// they are asked for, so that files larger than the memory, such as
+  /// multi-GB version 4 files, can be read. Entry streams share the
+  /// source, which is why it has to be `Send`.
+  ///
+  /// # Examples
+  ///
+  /// ```ignore
+  /// use ole;
+  /// let file = std::fs::File::open("assets/Thumbs.db").unwrap();
+  /// let parser = ole::Reader::from_seekable(file).unwrap();
+  /// ```
+  pub fn from_seekable<T>(mut source: T)
+        -> std::result::Result<Reader<'ole>, super::error::Error>
+    where T: std::io::Read + std::io::Seek + Send + 'static {
+    let len = source.seek(std::io::SeekFrom::End(0))
+      .and_then(|len| source.seek(std::io::SeekFrom::Start(0)).map(|_| len))
+      .map_err(super::error::Error::IOError)?;
+    let mut header = vec![0u8; super::constants::HEADER_SIZE];
+    std::io::Read::read_exact(&mut source, &mut header)
+      .map_err(super::error::Error::IOError)?;
+    let source: Box<dyn ReadSeek + Send> = Box::new(source);
+    let body = Body::Seekable {
+      source: std::sync::Arc::new(std::sync::Mutex::new(source)),
+      offset: 0,
+      len
+    };
+    Reader::with_body(Box::new(std::io::Cursor::new(header)), Some(body))
+  }
+
+  fn with_body(readable: Box<dyn std::io::Read + 'ole>, body: Option<Body>)
+        -> std::result::Result<Reader<'ole>, super::error::Error> {
     let mut t = Reader {
-      buf_reader: Some(std::io::BufReader::new(Box::new(readable))),
+      buf_reader: Some(std::io::BufReader::new(readable)),
       uid: vec![0u8; super::constants::UID_SIZE],
       revision_number: None,
       version_number: None,
@@ -90,18 +282,25 @@ impl<'ole> Reader<'ole> {
       minimum_standard_stream_size: None,
       ssat: None,
       msat: None,
-      body: None,
+      body,
       entries: None,
-      root_entry: None
+      root_entry: None,
+      directory_truncated: false,
+      header_time: std::time::Duration::default(),
+      sat_time: std::time::Duration::default()
     };
+    let start = std::time::Instant::now();
     t.parse_header()?;
+    let header_time = start.elapsed();
     t.build_sat()?;
+    t.sat_time = start.elapsed() - header_time;
     t.build_directory_entries()?;
+    t.header_time = start.elapsed() - t.sat_time;
     Ok(t)
   }
 
 
-  /// Constructs a new `Reader` from a file.
+  /// Constructs a new `Reader` from a file, see `Reader::from_file`.
   ///
   /// # Examples
   ///
@@ -109,9 +308,34 @@ impl<'ole> Reader<'ole> {
   /// use ole;
   /// let mut parser = ole::Reader::from_path("assets/Thumbs.db").unwrap();
   /// ```
-  pub fn from_path(path: &str) -> Result<Reader, super::error::Error> {
+  pub fn from_path(path: &str) -> Result<Reader<'_>, super::error::Error> {
     let f = std::fs::File::open(path).map_err(super::error::Error::IOError)?;
-    Reader::new(f)
+    Reader::from_file(f)
+  }
+
+  /// Constructs a new `Reader` from a file.
+  ///
+  /// Files up to 1 GiB are read into memory at once, which is faster than
+  /// seeking for each sector. Larger ones, such as multi-GB version 4
+  /// files, are read on demand, see `Reader::from_seekable`.
+  ///
+  /// # Examples
+  ///
+  /// ```ignore
+  /// use ole;
+  /// let file = std::fs::File::open("assets/Thumbs.db").unwrap();
+  /// let parser = ole::Reader::from_file(file).unwrap();
+  /// ```
+  pub fn from_file(mut file: std::fs::File)
+        -> std::result::Result<Reader<'ole>, super::error::Error> {
+    let len = file.metadata().map_err(super::error::Error::IOError)?.len();
+    if len > super::constants::MAX_IN_MEMORY_SIZE {
+      return Reader::from_seekable(file);
+    }
+    let mut data = std::vec::Vec::with_capacity(len as usize);
+    std::io::Read::read_to_end(&mut file, &mut data)
+      .map_err(super::error::Error::IOError)?;
+    Reader::from_shared(std::sync::Arc::new(data))
   }
 
 
@@ -127,10 +351,62 @@ impl<'ole> Reader<'ole> {
   ///   println!("Entry {}", entry.name());
   /// }
   /// ```
-  pub fn iterate(&self) -> super::iterator::OLEIterator {
+  pub fn iterate(&self) -> super::iterator::OLEIterator<'_> {
     super::iterator::OLEIterator::new(self)
   }
 
+  /// Returns an iterator for the streams whose parent is the storage
+  /// `parent_id`.
+  ///
+  /// # Examples
+  ///
+  /// ```ignore
+  /// use ole;
+  /// let mut parser = ole::Reader::from_path("assets/Thumbs.db").unwrap();
+  ///
+  /// for entry in parser.streams_of(0) {
+  ///   println!("Stream {}", entry.name());
+  /// }
+  /// ```
+  pub fn streams_of(&self, parent_id: u32)
+      -> impl Iterator<Item = &super::entry::Entry> + '_ {
+    self.iterate().filter(move |entry| {
+      entry._type() == super::entry::EntryType::UserStream
+        && entry.parent_node() == Some(parent_id)
+    })
+  }
+
+  /// Returns the application which wrote the file, from the CLSID of its
+  /// root storage, if it is a known one.
+  ///
+  /// # Examples
+  ///
+  /// ```ignore
+  /// use ole;
+  /// let parser = ole::Reader::from_path("data/sample.doc").unwrap();
+  /// assert_eq!(parser.application(), Some(ole::Application::Word));
+  /// ```
+  pub fn application(&self) -> Option<super::constants::Application> {
+    let root = self.entries.as_ref()?.get(self.root_entry? as usize)?;
+    let clsid = root.clsid();
+    super::constants::APPLICATION_CLSIDS.iter()
+      .find(|(known, _)| *known == clsid)
+      .map(|(_, application)| *application)
+  }
+
+  /// Returns the major version of the file format, 3 for sectors of 512
+  /// bytes or 4 for sectors of 4096 bytes.
+  pub fn major_version(&self) -> u16 {
+    self.version_number.unwrap_or_default()
+  }
+
+  /// Returns an iterator for the storages of the OLE file,
+  /// the root storage excepted.
+  pub fn storages(&self) -> impl Iterator<Item = &super::entry::Entry> + '_ {
+    self.iterate()
+      .filter(|entry| entry._type() == super::entry::EntryType::UserStorage)
+  }
+
   /// Read some bytes from the source.
   pub(crate) fn read(&mut self, buf: &mut [u8])
         -> Result<usize, super::error::Error> {
@@ -144,6 +420,7 @@ impl<'ole> Reader<'ole> {
 
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::op_ref, clippy::to_string_in_format_args)]
 mod tests {
   use std;
   use super::Reader;
@@ -221,6 +498,66 @@ mod tests {
     assert_eq!(ole.is_ok(), false);
   }
 
+  #[test]
+  fn sector_offset_overflow() {
+    let ole = Reader::from_path("data/Thumbs.db").unwrap();
+    assert!(ole.read_sector(usize::MAX).is_err());
+    assert!(ole.read_sector(usize::MAX / 512).is_err());
+  }
+
+  #[test]
+  fn entry_byte_ranges() {
+    use std::io::Read;
+    let data = std::fs::read("data/unicode.msg").unwrap();
+    let ole = Reader::new(&data[..]).unwrap();
+    let mut sizes = std::collections::HashSet::new();
+    for entry in ole.iterate().filter(|entry| !entry.is_empty()) {
+      if entry._type() != super::super::entry::EntryType::UserStream {
+        continue;
+      }
+      let ranges = ole.entry_byte_ranges(entry).unwrap();
+      assert_eq!(ranges.len(), entry.sector_chain().len());
+      let carved: std::vec::Vec<u8> = ranges.iter()
+        .flat_map(|range| data[range.clone()].iter().copied())
+        .collect();
+      let mut content = vec![];
+      ole.get_entry_slice(entry).unwrap().read_to_end(&mut content).unwrap();
+      assert_eq!(carved, content, "{}", entry.name());
+      sizes.insert(ranges[0].len());
+    }
+    // Both short and standard sectors
+    assert!(sizes.iter().any(|size| *size <= 64));
+    assert!(sizes.iter().any(|size| *size == 512));
+  }
+
+  #[test]
+  fn too_small_sec_size() {
+    let mut vec = std::fs::read("data/Thumbs.db").unwrap();
+    // 2 ^ 2 bytes per sector
+    vec[30 .. 32].copy_from_slice(&[0x02, 0x00]);
+    let ole = Reader::new(&vec[..]);
+    assert!(matches!(ole.err().unwrap(), Error::BadSizeValue(_)));
+  }
+
+  #[test]
+  fn huge_table_sizes() {
+    // Sizes of the SAT, the SSAT and the MSAT are not allocated up front
+    for range in [44 .. 48, 64 .. 68, 72 .. 76] {
+      let mut vec = std::fs::read("data/Thumbs.db").unwrap();
+      vec[range].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0x7F]);
+      assert!(Reader::new(&vec[..]).is_ok());
+    }
+  }
+
+  #[test]
+  fn from_file_in_memory() {
+    let file = std::fs::File::open("data/Thumbs.db").unwrap();
+    let ole = Reader::from_file(file).unwrap();
+    assert!(ole.body.as_ref().unwrap().bytes().is_some());
+    assert_eq!(ole.iterate().count(),
+      Reader::from_path("data/Thumbs.db").unwrap().iterate().count());
+  }
+
   #[test]
   fn several_values() {
     let ole = Reader::from_path("data/Thumbs.db").unwrap();
@@ -230,25 +567,793 @@ mod tests {
   }
 
   #[test]
-  fn print_things() {
-    use std::io::{Read, Write};
+  fn empty_entry_slice() {
+    use std::io::Read;
+    let ole = Reader::from_path("data/test_email.msg").unwrap();
+    let entry = ole.iterate().find(|x| x.name() == "__substg1.0_003D001F")
+      .unwrap();
+    assert_eq!(entry.len(), 0);
+    let mut slice = ole.get_entry_slice(entry).unwrap();
+    assert_eq!(slice.len(), 0);
+    let mut buf = vec![];
+    assert_eq!(slice.read_to_end(&mut buf).unwrap(), 0);
+  }
+
+  #[test]
+  fn short_sector_out_of_range() {
+    let mut vec = std::fs::read("data/test_email.msg").unwrap();
+    // Point the root entry, i.e. the short stream container, to an
+    // empty chain.
+    let sec_size = 512usize;
+    let dir_sector = u32::from_le_bytes([vec[48], vec[49], vec[50], vec[51]]);
+    let root_offset = (dir_sector as usize + 1) * sec_size;
+    vec[root_offset + 116 .. root_offset + 120]
+      .copy_from_slice(&super::super::constants::END_OF_CHAIN_SECID);
+
+    let ole = Reader::new(&vec[..]).unwrap();
+    let entry = ole.iterate().find(|x| x.name() == "__substg1.0_0037001F")
+      .unwrap();
+    let err = ole.get_entry_slice(entry).err().unwrap();
+    assert!(matches!(err, Error::ShortSectorOutOfRange(_)));
+  }
+
+  #[test]
+  fn malformed_chain() {
+    let vec = std::fs::read("data/test_email.msg").unwrap();
+    let sec_size = 512usize;
+    let dir_sector = u32::from_le_bytes([vec[48], vec[49], vec[50], vec[51]]);
+    let root_offset = (dir_sector as usize + 1) * sec_size;
+
+    // Start SecID far beyond the SAT
+    let mut corrupted = vec.clone();
+    corrupted[root_offset + 116 .. root_offset + 120]
+      .copy_from_slice(&[0x00, 0x00, 0xFF, 0x00]);
+    let err = Reader::new(&corrupted[..]).err().unwrap();
+    assert!(matches!(err, Error::MalformedChain));
+
+    // First directory sector pointing to itself in the SAT
+    let mut corrupted = vec;
+    let sat_sector = u32::from_le_bytes([corrupted[76], corrupted[77],
+      corrupted[78], corrupted[79]]);
+    let sat_offset = (sat_sector as usize + 1) * sec_size
+      + dir_sector as usize * 4;
+    corrupted[sat_offset .. sat_offset + 4]
+      .copy_from_slice(&dir_sector.to_le_bytes());
+    let err = Reader::new(&corrupted[..]).err().unwrap();
+    assert!(matches!(err, Error::MalformedChain));
+  }
+
+  #[test]
+  fn special_secids_in_chain() {
+    use super::super::constants::{MSAT_SECID_U32, SAT_SECID_U32};
+    let mut ole = Reader::from_path("data/Thumbs.db").unwrap();
+    ole.sat = Some(vec![1, SAT_SECID_U32, 3, MSAT_SECID_U32]);
+    assert_eq!(ole.build_chain_from_sat(0).unwrap(), vec![0, 1]);
+    assert_eq!(ole.build_chain_from_sat(2).unwrap(), vec![2, 3]);
+    assert!(ole.build_chain_from_sat(SAT_SECID_U32).unwrap().is_empty());
+  }
+
+  #[test]
+  fn special_secids_in_msat() {
+    let mut vec = std::fs::read("data/Thumbs.db").unwrap();
+    // Second MSAT slot of the header marked as a SAT sector
+    vec[80 .. 84].copy_from_slice(&[0xFD, 0xFF, 0xFF, 0xFF]);
+    let ole = Reader::new(&vec[..]).unwrap();
+    assert_eq!(ole.msat.as_ref().unwrap().len(), 2usize);
+    assert_eq!(ole.iterate().count(),
+      Reader::from_path("data/Thumbs.db").unwrap().iterate().count());
+  }
+
+  #[test]
+  fn msat_larger_than_declared() {
+    use super::super::constants::{END_OF_CHAIN_SECID, MSAT_SECID_U32};
+    let mut vec = std::fs::read("data/Thumbs.db").unwrap();
+    // All the header MSAT slots taken, followed by an MSAT sector
+    // although the header declares none.
+    for slot in vec[80 .. 512].chunks_exact_mut(4) {
+      slot.copy_from_slice(&MSAT_SECID_U32.to_le_bytes());
+    }
+    let msat_sector = (vec.len() - 512) / 512;
+    vec[68 .. 72].copy_from_slice(&(msat_sector as u32).to_le_bytes());
+    vec[72 .. 76].copy_from_slice(&[0u8; 4]);
+    let mut sector = std::vec::Vec::new();
+    for _ in 0 .. 127 {
+      sector.extend_from_slice(&MSAT_SECID_U32.to_le_bytes());
+    }
+    sector.extend_from_slice(&END_OF_CHAIN_SECID);
+    vec.extend_from_slice(&sector);
+
+    let ole = Reader::new(&vec[..]).unwrap();
+    assert_eq!(ole.msat.as_ref().unwrap().len(), 109 + 127);
+    assert_eq!(ole.iterate().count(),
+      Reader::from_path("data/Thumbs.db").unwrap().iterate().count());
+  }
+
+  #[test]
+  fn state_bits() {
+    let ole = Reader::from_path("data/test_email.msg").unwrap();
+    assert!(ole.iterate().all(|entry| entry.state_bits() == 0));
+  }
+
+  #[test]
+  fn partially_committed() {
+    let ole = Reader::from_path("data/test_email.msg").unwrap();
+    assert!(!ole.is_partially_committed());
+
+    // Declare a size larger than what the chain of the subject holds
+    let id = ole.iterate().find(|x| x.name() == "__substg1.0_0037001F")
+      .unwrap().id() as usize;
+    let dir_sector = ole.dsat.as_ref().unwrap()[id / 4] as usize;
+    let offset = (dir_sector + 1) * 512 + (id % 4) * 128;
+    let mut vec = std::fs::read("data/test_email.msg").unwrap();
+    vec[offset + 120 .. offset + 124].copy_from_slice(&[0x00, 0x08, 0, 0]);
+
+    let ole = Reader::new(&vec[..]).unwrap();
+    assert!(ole.is_partially_committed());
+    let incomplete = ole.incomplete_entries();
+    assert_eq!(incomplete.len(), 1);
+    assert_eq!(incomplete[0].name(), "__substg1.0_0037001F");
+  }
+
+  #[test]
+  fn truncated_directory() {
+    // Empty streams, so that the directory ends the file
+    let mut writer = super::super::Writer::new();
+    for name in ["a", "b", "c", "d", "e", "f"] {
+      writer.add_stream(name, &[]);
+    }
+    let bytes = writer.to_bytes();
+    let ole = Reader::new(&bytes[..]).unwrap();
+    assert!(!ole.is_directory_truncated());
+    assert_eq!(ole.dsat.as_ref().unwrap().len(), 2);
+
+    let names = |ole: &Reader| {
+      let mut names: std::vec::Vec<std::string::String> = ole.iterate()
+        .filter(|entry| !entry.name().is_empty())
+        .map(|entry| entry.name().to_string())
+        .collect();
+      names.sort();
+      names
+    };
+    // Cut at the boundary of the last sector, then within its second entry
+    let ole = Reader::new(&bytes[.. bytes.len() - 512]).unwrap();
+    assert!(ole.is_directory_truncated());
+    assert_eq!(names(&ole), ["Root Entry", "a", "b", "c"]);
+    let ole = Reader::new(&bytes[.. bytes.len() - 512 + 200]).unwrap();
+    assert!(ole.is_directory_truncated());
+    assert_eq!(names(&ole), ["Root Entry", "a", "b", "c", "d"]);
+  }
+
+  #[test]
+  fn misplaced_chains() {
+    use std::io::Read;
+    let data: std::vec::Vec<u8> = (0..4096u32).map(|i| i as u8).collect();
+    let mut writer = super::super::Writer::new();
+    writer.add_stream("short", &data[.. 4095])
+      .add_stream("standard", &data);
+    let mut bytes = writer.to_bytes();
+    let offsets: std::vec::Vec<usize> = {
+      let ole = Reader::new(&bytes[..]).unwrap();
+      assert!(ole.misplaced_entries().is_empty());
+      ["short", "standard"].iter().map(|name| {
+        let id = ole.iterate().find(|x| x.name() == *name).unwrap().id() as usize;
+        let dir_sector = ole.dsat.as_ref().unwrap()[id / 4] as usize;
+        (dir_sector + 1) * 512 + (id % 4) * 128 + 120
+      }).collect()
+    };
+    // Sizes calling for the other allocation table
+    bytes[offsets[0] .. offsets[0] + 4].copy_from_slice(&4096u32.to_le_bytes());
+    bytes[offsets[1] .. offsets[1] + 4].copy_from_slice(&4000u32.to_le_bytes());
+
+    let ole = Reader::new(&bytes[..]).unwrap();
+    let mut misplaced: std::vec::Vec<&str> = ole.misplaced_entries().iter()
+      .map(|entry| entry.name())
+      .collect();
+    misplaced.sort();
+    assert_eq!(misplaced, vec!["short", "standard"]);
+    assert!(ole.incomplete_entries().is_empty());
+    let read = |name: &str| {
+      let entry = ole.iterate().find(|x| x.name() == name).unwrap();
+      let mut buf = vec![];
+      ole.get_entry_slice(entry).unwrap().read_to_end(&mut buf).unwrap();
+      buf
+    };
+    // Up to its size, the last byte of short is padding
+    assert_eq!(read("short")[.. 4095], data[.. 4095]);
+    assert_eq!(read("standard"), data[.. 4000]);
+  }
+
+  #[test]
+  fn streams_of_and_storages() {
+    let ole = Reader::from_path("data/test_email.msg").unwrap();
+    let streams: std::vec::Vec<&super::super::Entry> = ole.streams_of(0).collect();
+    assert!(!streams.is_empty());
+    assert!(streams.iter().any(|x| x.name() == "__substg1.0_0037001F"));
+    assert!(streams.iter().all(|x| x.parent_node() == Some(0)
+      && x._type() == super::super::EntryType::UserStream));
+
+    let storages: std::vec::Vec<&super::super::Entry> = ole.storages().collect();
+    assert!(storages.iter().any(|x| x.name() == "__recip_version1.0_#00000000"));
+    assert!(storages.iter()
+      .all(|x| x._type() == super::super::EntryType::UserStorage));
+    assert_eq!(ole.streams_of(storages[0].id()).count(),
+      ole.iterate().filter(|x| x.parent_node() == Some(storages[0].id())
+        && x._type() == super::super::EntryType::UserStream).count());
+  }
+
+  #[test]
+  fn tree_dot() {
+    let ole = Reader::from_path("data/test_email.msg").unwrap();
+    let dot = ole.tree_dot();
+    assert!(dot.starts_with("digraph directory {\n"));
+    assert!(dot.ends_with("}\n"));
+
+    let root = ole.iterate().next().unwrap();
+    // Outlook writes a red root entry
+    assert_eq!(root.color(), super::super::NodeColour::Red);
+    assert!(dot.contains(&format!("  0 -> {} [label=\"child\"];\n",
+      root.child_node())));
+    assert!(dot.contains("  0 [label=\"#0 Root Entry\", style=filled, \
+      fillcolor=red, fontcolor=white];\n"));
+  }
+
+  #[test]
+  fn entry_path() {
+    let ole = Reader::from_path("data/test_email.msg").unwrap();
+    let root = ole.iterate().next().unwrap();
+    assert_eq!(ole.path(root), "");
+    let subject = ole.iterate()
+      .find(|x| x.name() == "__substg1.0_0037001F").unwrap();
+    assert_eq!(ole.path(subject), "__substg1.0_0037001F");
+    let recipient = ole.iterate()
+      .find(|x| x.name() == "__substg1.0_39FE001F").unwrap();
+    assert_eq!(ole.path(recipient),
+      "__recip_version1.0_#00000000/__substg1.0_39FE001F");
+  }
+
+  #[test]
+  fn from_shared() {
+    use std::io::Read;
+    let data = std::sync::Arc::new(std::fs::read("data/sample.ppt").unwrap());
+    let shared = Reader::from_shared(data.clone()).unwrap();
     let ole = Reader::from_path("data/sample.ppt").unwrap();
-    println!("STREAM SIZE: {}", *ole.minimum_standard_stream_size.as_ref().unwrap());
-    println!("MSAT: {:?}", ole.msat.as_ref().unwrap());
-    println!("SAT: {:?}", ole.sat.as_ref().unwrap());
-    println!("SSAT: {:?}", ole.ssat.as_ref().unwrap());
-    println!("DSAT: {:?}", ole.dsat.as_ref().unwrap());
-    for entry in ole.iterate() {
-      println!("{}", entry);
-      if let Ok(mut slice) = ole.get_entry_slice(entry) {
-        let mut buf = vec![0u8; slice.len()];
-        let read_size = slice.read(&mut buf).unwrap();
-        let mut file = std::fs::File::create(format!("data/streams/{}.bin", entry.name())).unwrap();
-        println!("Real len: {}", slice.real_len());
-        file.write_all(&buf).unwrap();
-        assert_eq!(read_size, slice.real_len());
-        assert_eq!(read_size, slice.len());
+    #[cfg(feature = "serde_json")]
+    assert_eq!(shared.entries_json(), ole.entries_json());
+    for (a, b) in shared.iterate().zip(ole.iterate()) {
+      let (mut a_buf, mut b_buf) = (vec![], vec![]);
+      shared.get_entry_slice(a).unwrap().read_to_end(&mut a_buf).unwrap();
+      ole.get_entry_slice(b).unwrap().read_to_end(&mut b_buf).unwrap();
+      assert_eq!(a_buf, b_buf);
+    }
+    // Sectors are read from the shared data
+    let body = shared.body.as_ref().unwrap().bytes().unwrap();
+    assert_eq!(body.as_ptr(), data[512 ..].as_ptr());
+
+    assert!(Reader::from_shared(std::sync::Arc::new(vec![0u8; 600])).is_err());
+  }
+
+  #[test]
+  fn entry_stream() {
+    use std::io::{Read, Seek, SeekFrom};
+    fn is_send<T: Send + 'static>(_: &T) {}
+
+    let ole = Reader::from_path("data/test_email.msg").unwrap();
+    // A short stream, and a stream of standard sectors
+    let entries = [
+      ole.iterate().find(|x| x.name() == "__substg1.0_0037001F").unwrap(),
+      ole.iterate().max_by_key(|x| if x._type()
+        == super::super::EntryType::UserStream { x.len() } else { 0 }).unwrap(),
+    ];
+    assert!(entries[1].len() >= 4096);
+    for entry in entries.iter() {
+      let mut expected = vec![];
+      ole.get_entry_slice(entry).unwrap().read_to_end(&mut expected).unwrap();
+
+      let mut stream = ole.get_entry_stream(entry).unwrap();
+      is_send(&stream);
+      assert_eq!(stream.len(), entry.len());
+      let stream = std::thread::spawn(move || {
+        let mut buf = vec![];
+        stream.read_to_end(&mut buf).unwrap();
+        (stream, buf)
+      });
+      let (mut stream, buf) = stream.join().unwrap();
+      assert_eq!(buf, expected);
+
+      // Seek back into the stream
+      let middle = expected.len() as u64 / 2 + 1;
+      assert_eq!(stream.seek(SeekFrom::End(-(middle as i64))).unwrap(),
+        expected.len() as u64 - middle);
+      let mut tail = vec![];
+      stream.read_to_end(&mut tail).unwrap();
+      assert_eq!(tail, &expected[expected.len() - middle as usize ..]);
+      stream.seek(SeekFrom::Start(3)).unwrap();
+      assert_eq!(stream.seek(SeekFrom::Current(2)).unwrap(), 5);
+      let mut byte = [0u8; 1];
+      stream.read_exact(&mut byte).unwrap();
+      assert_eq!(byte[0], expected[5]);
+      assert!(stream.seek(SeekFrom::Current(-10)).is_err());
+    }
+  }
+
+  #[test]
+  fn application() {
+    use super::super::Application;
+    for (path, expected) in [
+      ("data/test_email.msg", Some(Application::OutlookMessage)),
+      ("data/sample.doc", Some(Application::Word)),
+      ("data/sample.ppt", Some(Application::PowerPoint)),
+      ("data/Thumbs.db", None),
+    ] {
+      let ole = Reader::from_path(path).unwrap();
+      assert_eq!(ole.application(), expected);
+      let bytes = std::fs::read(path).unwrap();
+      assert_eq!(Application::detect(&bytes), expected);
+    }
+  }
+
+  #[test]
+  #[cfg(feature = "serde_json")]
+  fn entries_json() {
+    let ole = Reader::from_path("data/test_email.msg").unwrap();
+    let json: serde_json::Value = serde_json::from_str(&ole.entries_json())
+      .unwrap();
+    let entries = json.as_array().unwrap();
+    assert_eq!(entries.len(), ole.iterate().count());
+
+    let root = &entries[0];
+    assert_eq!(root["name"], "Root Entry");
+    assert_eq!(root["type"], "RootStorage");
+    assert_eq!(root["clsid"], "00020D0B-0000-0000-C000-000000000046");
+    assert!(root["parent"].is_null());
+    assert!(!root["children"].as_array().unwrap().is_empty());
+
+    let recipient = entries.iter()
+      .find(|x| x["path"] == "__recip_version1.0_#00000000/__substg1.0_39FE001F")
+      .unwrap();
+    assert_eq!(recipient["type"], "UserStream");
+    assert_eq!(recipient["clsid"], "00000000-0000-0000-0000-000000000000");
+    let parent = &entries[recipient["parent"].as_u64().unwrap() as usize];
+    assert_eq!(parent["name"], "__recip_version1.0_#00000000");
+    assert!(parent["children"].as_array().unwrap().contains(&recipient["id"]));
+  }
+
+  #[test]
+  fn extract_all() {
+    use std::io::Read;
+    let dir = std::env::temp_dir()
+      .join(format!("msg_parser_extract_all_{}", std::process::id()));
+    let ole = Reader::from_path("data/sample.ppt").unwrap();
+    let count = ole.extract_all(&dir).unwrap();
+    assert_eq!(count, ole.iterate()
+      .filter(|entry| entry._type() == super::super::EntryType::UserStream)
+      .count());
+    let mut slice = ole.iterate()
+      .find(|entry| entry.name() == "PowerPoint Document")
+      .map(|entry| ole.get_entry_slice(entry).unwrap())
+      .unwrap();
+    let mut buf = vec![];
+    slice.read_to_end(&mut buf).unwrap();
+    assert_eq!(std::fs::read(dir.join("PowerPoint Document")).unwrap(), buf);
+    assert!(dir.join("[5]SummaryInformation").is_file());
+
+    // Streams of nested storages
+    let ole = Reader::from_path("data/test_email.msg").unwrap();
+    ole.extract_all(&dir).unwrap();
+    assert!(dir.join("__attach_version1.0_#00000000")
+      .join("__properties_version1.0").is_file());
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn get_stream_by_id_and_path() {
+    use std::io::Read;
+    let ole = Reader::from_path("data/test_email.msg").unwrap();
+    let entry = ole.iterate()
+      .find(|entry| ole.path(entry)
+        == "__attach_version1.0_#00000000/__properties_version1.0")
+      .unwrap();
+    let mut buf = vec![];
+    ole.get_entry_slice(entry).unwrap().read_to_end(&mut buf).unwrap();
+    assert_eq!(ole.get_entry_slice_by_id(entry.id()).unwrap(), buf);
+    assert_eq!(ole.get_stream(&ole.path(entry)).unwrap(), buf);
+    assert_eq!(ole.get_stream("/__ATTACH_version1.0_#00000000/__PROPERTIES_version1.0")
+      .unwrap(), buf);
+
+    assert!(matches!(ole.get_entry_slice_by_id(u32::MAX),
+      Err(super::super::Error::EntryNotFound)));
+    assert!(matches!(ole.get_stream("__properties_version1.0/missing"),
+      Err(super::super::Error::EntryNotFound)));
+    // Storages are not streams
+    assert!(ole.get_stream("__attach_version1.0_#00000000").is_err());
+  }
+
+  #[test]
+  fn sanitize_filename() {
+    use super::super::{sanitize_filename, FileNamePolicy};
+    let bracketed = FileNamePolicy::Bracketed;
+    assert_eq!(sanitize_filename("\u{5}SummaryInformation", bracketed),
+      "[5]SummaryInformation");
+    assert_eq!(sanitize_filename("a<b>c:d\"e/f\\g|h?i*j", bracketed),
+      "a_b_c_d_e_f_g_h_i_j");
+    assert_eq!(sanitize_filename("__substg1.0_0037001F", bracketed),
+      "__substg1.0_0037001F");
+    assert_eq!(sanitize_filename("report. .", bracketed), "report___");
+    assert_eq!(sanitize_filename("report. .", FileNamePolicy::Remove), "report");
+    assert_eq!(sanitize_filename("a/b", FileNamePolicy::Replace('-')), "a-b");
+    // Replacements which are not valid either
+    assert_eq!(sanitize_filename("../x", FileNamePolicy::Replace('/')), ".._x");
+    assert_eq!(sanitize_filename("a:b", FileNamePolicy::Replace('\\')), "a_b");
+    assert_eq!(sanitize_filename("a?", FileNamePolicy::Replace(':')), "a_");
+    assert_eq!(sanitize_filename("..", FileNamePolicy::Replace('.')), "__");
+    assert_eq!(sanitize_filename("", bracketed), "_");
+    assert_eq!(sanitize_filename("..", FileNamePolicy::Remove), "_");
+    assert_eq!(sanitize_filename("Lpt1", bracketed), "_Lpt1");
+    assert_eq!(sanitize_filename("nul .tar.gz", bracketed), "_nul .tar.gz");
+    assert_eq!(sanitize_filename("CONSOLE.txt", bracketed), "CONSOLE.txt");
+  }
+
+  #[test]
+  fn empty_entries_in_sibling_tree() {
+    let mut writer = super::super::Writer::new();
+    for name in ["a", "b", "c", "d", "e"] {
+      writer.add_stream(&format!("storage/{}", name), name.as_bytes());
+    }
+    let mut bytes = writer.to_bytes();
+    // Directory entries as written by the exporters: "c", the root of
+    // the sibling tree, is Empty with its siblings kept, and "a" is
+    // Empty and zeroed.
+    let dir_start = 512 + 512 * u32::from_le_bytes(
+      [bytes[48], bytes[49], bytes[50], bytes[51]]) as usize;
+    let entry_offset = |bytes: &[u8], name: &str| (0..8)
+      .map(|id| dir_start + id * 128)
+      .find(|offset| bytes[*offset] == name.as_bytes()[0]
+        && bytes[offset + 64] == 4)
+      .unwrap();
+    let c = entry_offset(&bytes, "c");
+    bytes[c + 66] = 0;
+    let a = entry_offset(&bytes, "a");
+    for byte in bytes[a .. a + 128].iter_mut() {
+      *byte = 0;
+    }
+
+    let ole = Reader::new(&bytes[..]).unwrap();
+    let storage = ole.storages().find(|entry| entry.name() == "storage")
+      .unwrap();
+    let names: std::vec::Vec<&str> = storage.children_nodes().iter()
+      .map(|id| ole.iterate().nth(*id as usize).unwrap().name())
+      .collect();
+    assert_eq!(names.len(), 3);
+    for name in ["b", "d", "e"] {
+      assert!(names.contains(&name));
+    }
+    // Ids are kept
+    assert_eq!(ole.iterate().count(), 8);
+    assert!(ole.iterate()
+      .filter(|entry| entry._type() == super::super::EntryType::Empty)
+      .all(|entry| entry.parent_node().is_none()));
+    assert_eq!(ole.streams_of(storage.id()).count(), 3);
+  }
+
+  #[test]
+  fn empty_entries_fixture() {
+    // The root of the sibling tree of the message is Empty, and a
+    // stream of the named property map zeroed
+    let ole = Reader::from_path("data/empty_entries.msg").unwrap();
+    let root: std::vec::Vec<&str> = ole.streams_of(0)
+      .map(|entry| entry.name())
+      .collect();
+    assert!(root.contains(&"__substg1.0_0037001F"));
+    assert!(!root.contains(&"__substg1.0_1000001F"));
+    // Ids are kept
+    let body = ole.iterate().nth(7).unwrap();
+    assert_eq!(body.name(), "__substg1.0_1000001F");
+    assert_eq!(body._type(), super::super::EntryType::Empty);
+  }
+
+  #[test]
+  fn cyclic_sibling_tree() {
+    let mut writer = super::super::Writer::new();
+    for name in ["a", "b", "c"] {
+      writer.add_stream(&format!("storage/{}", name), name.as_bytes());
+    }
+    let mut bytes = writer.to_bytes();
+    let dir_start = 512 + 512 * u32::from_le_bytes(
+      [bytes[48], bytes[49], bytes[50], bytes[51]]) as usize;
+    // Siblings of each stream point back to the storage and the root
+    for id in 0 .. 5usize {
+      let offset = dir_start + id * 128;
+      if bytes[offset + 66] == 2 {
+        bytes[offset + 68 .. offset + 72].copy_from_slice(&1u32.to_le_bytes());
+        bytes[offset + 72 .. offset + 76].copy_from_slice(&(id as u32).to_le_bytes());
+      }
+    }
+
+    let ole = Reader::new(&bytes[..]).unwrap();
+    let storage = ole.storages().find(|entry| entry.name() == "storage")
+      .unwrap();
+    let mut names: std::vec::Vec<&str> = ole.streams_of(storage.id())
+      .map(|entry| entry.name())
+      .collect();
+    names.sort_unstable();
+    assert!(!names.is_empty());
+    assert_eq!(ole.iterate().filter(|entry| entry.parent_node().is_some()).count(),
+      1 + names.len());
+  }
+
+  #[test]
+  fn writer() {
+    use std::io::Read;
+    let large: std::vec::Vec<u8> = (0..10000u32).map(|i| i as u8).collect();
+    let mut writer = super::super::Writer::new();
+    writer.add_stream("small", b"hello")
+      .add_stream("storage/nested/large", &large)
+      .add_storage("empty")
+      .add_stream("storage/empty", &[]);
+    for i in 0..20 {
+      writer.add_stream(&format!("storage/stream{}", i), &[i as u8; 100]);
+    }
+    let bytes = writer.to_bytes();
+
+    let ole = Reader::new(&bytes[..]).unwrap();
+    let read = |path: &str| {
+      let entry = ole.iterate().find(|entry| ole.path(entry) == path).unwrap();
+      let mut buf = vec![];
+      ole.get_entry_slice(entry).unwrap().read_to_end(&mut buf).unwrap();
+      buf
+    };
+    assert_eq!(read("small"), b"hello");
+    assert_eq!(read("storage/nested/large"), large);
+    assert_eq!(read("storage/stream7"), vec![7u8; 100]);
+    assert!(read("storage/empty").is_empty());
+    let empty = ole.iterate().find(|entry| entry.name() == "empty"
+      && entry.parent_node() == ole.root_entry).unwrap();
+    assert_eq!(empty._type(), super::super::EntryType::UserStorage);
+    assert_eq!(ole.iterate()
+      .filter(|entry| entry._type() == super::super::EntryType::UserStream)
+      .count(), 23);
+    assert!(ole.incomplete_entries().is_empty());
+  }
+
+  #[test]
+  fn entry_size_by_version() {
+    use super::super::entry::Entry;
+    let mut sector = vec![0u8; super::super::constants::DIRECTORY_ENTRY_SIZE];
+    sector[66] = 2;
+    sector[120 .. 124].copy_from_slice(&0x10u32.to_le_bytes());
+    sector[124 .. 128].copy_from_slice(&1u32.to_le_bytes());
+    // The high part of the size of version 3 files is ignored
+    assert_eq!(Entry::from_slice(&sector, 1, 3).unwrap().len(), 0x10);
+    #[cfg(target_pointer_width = "64")]
+    assert_eq!(Entry::from_slice(&sector, 1, 4).unwrap().len(), 0x1_0000_0010);
+    #[cfg(target_pointer_width = "32")]
+    assert!(Entry::from_slice(&sector, 1, 4).is_err());
+  }
+
+  #[test]
+  fn sniffed_header() {
+    let bytes = std::fs::read("data/Thumbs.db").unwrap();
+    let names = |ole: &Reader| ole.iterate()
+      .map(|entry| ole.path(entry))
+      .collect::<std::vec::Vec<_>>();
+    let expected = names(&Reader::new(&bytes[..]).unwrap());
+    for peeked in [512, 100, 0] {
+      let ole = Reader::try_new_sniffed(&bytes[peeked ..], &bytes[.. peeked]).unwrap();
+      assert_eq!(names(&ole), expected);
+    }
+
+    // Rejected from the peeked bytes alone
+    let mut header = bytes[.. 512].to_vec();
+    header[0] = 0xD1;
+    let ole = Reader::try_new_sniffed(std::io::empty(), &header);
+    assert!(matches!(ole, Err(Error::InvalidOLEFile)));
+  }
+
+  /// Version 4 file holding a single stream of `stream_size` bytes, the
+  /// byte at offset `o` of the stream being `o % 251`. Its sectors are
+  /// written as they are read, so that files of several GB can be read
+  /// without being stored.
+  struct Version4File {
+    stream_size: u64,
+    /// Sectors of the stream, then the directory sector, the SAT sectors
+    /// and the MSAT sector if any.
+    data_sectors: u32,
+    sat_sectors: u32,
+    msat_sectors: u32,
+    position: u64
+  }
+
+  impl Version4File {
+    const SECTOR_SIZE: u64 = 4096;
+
+    fn new(stream_size: u64) -> Version4File {
+      let data_sectors = stream_size.div_ceil(Self::SECTOR_SIZE) as u32;
+      let mut sat_sectors = 1;
+      loop {
+        let msat_sectors = if sat_sectors > 109 { 1 } else { 0 };
+        let total = data_sectors + 1 + sat_sectors + msat_sectors;
+        if total.div_ceil(1024) <= sat_sectors {
+          return Version4File { stream_size, data_sectors, sat_sectors,
+            msat_sectors, position: 0 };
+        }
+        sat_sectors += 1;
+      }
+    }
+
+    fn len(&self) -> u64 {
+      (1 + (self.data_sectors + 1 + self.sat_sectors + self.msat_sectors)
+        as u64) * Self::SECTOR_SIZE
+    }
+
+    fn dir_sector(&self) -> u32 {
+      self.data_sectors
+    }
+
+    fn sat_sector(&self, k: u32) -> u32 {
+      self.data_sectors + 1 + k
+    }
+
+    fn msat_sector(&self) -> u32 {
+      self.data_sectors + 1 + self.sat_sectors
+    }
+
+    fn sec_ids(ids: impl Iterator<Item = u32>) -> std::vec::Vec<u8> {
+      ids.flat_map(u32::to_le_bytes).collect()
+    }
+
+    /// Bytes of the header sector, or of the sector numbered `index - 1`.
+    fn block(&self, index: u64) -> std::vec::Vec<u8> {
+      use super::super::constants::*;
+      let mut block = vec![0u8; Self::SECTOR_SIZE as usize];
+      if index == 0 {
+        block[.. 8].copy_from_slice(&IDENTIFIER);
+        block[24 .. 26].copy_from_slice(&0x3Eu16.to_le_bytes());
+        block[26 .. 28].copy_from_slice(&4u16.to_le_bytes());
+        block[28 .. 30].copy_from_slice(&LITTLE_ENDIAN_IDENTIFIER);
+        block[30 .. 32].copy_from_slice(&12u16.to_le_bytes());
+        block[32 .. 34].copy_from_slice(&6u16.to_le_bytes());
+        block[40 .. 44].copy_from_slice(&1u32.to_le_bytes());
+        block[44 .. 48].copy_from_slice(&self.sat_sectors.to_le_bytes());
+        block[48 .. 52].copy_from_slice(&self.dir_sector().to_le_bytes());
+        block[56 .. 60].copy_from_slice(&4096u32.to_le_bytes());
+        block[60 .. 64].copy_from_slice(&END_OF_CHAIN_SECID);
+        if self.msat_sectors > 0 {
+          block[68 .. 72].copy_from_slice(&self.msat_sector().to_le_bytes());
+        } else {
+          block[68 .. 72].copy_from_slice(&END_OF_CHAIN_SECID);
+        }
+        block[72 .. 76].copy_from_slice(&self.msat_sectors.to_le_bytes());
+        block[76 .. 512].copy_from_slice(&Self::sec_ids((0 .. 109)
+          .map(|k| if k < self.sat_sectors { self.sat_sector(k) }
+            else { FREE_SECID_U32 })));
+        return block;
       }
+      let sector = (index - 1) as u32;
+      if sector < self.data_sectors {
+        let start = sector as u64 * Self::SECTOR_SIZE;
+        for (i, byte) in block.iter_mut().enumerate() {
+          *byte = ((start + i as u64) % 251) as u8;
+        }
+      } else if sector == self.dir_sector() {
+        let utf16 = |name: &str| Self::sec_ids(name.encode_utf16()
+          .map(u32::from)).chunks(4).flat_map(|c| [c[0], c[1]])
+          .collect::<std::vec::Vec<u8>>();
+        for (i, name, kind, child, start, size) in [
+          (0, "Root Entry", 5u8, 1, END_OF_CHAIN_SECID_U32, 0),
+          (1, "Big", 2u8, FREE_SECID_U32, 0, self.stream_size),
+        ] {
+          let entry = &mut block[i * DIRECTORY_ENTRY_SIZE ..
+            (i + 1) * DIRECTORY_ENTRY_SIZE];
+          let name = utf16(name);
+          entry[.. name.len()].copy_from_slice(&name);
+          entry[64 .. 66].copy_from_slice(&(name.len() as u16 + 2).to_le_bytes());
+          entry[66] = kind;
+          entry[67] = 1;
+          entry[68 .. 76].copy_from_slice(&[0xFF; 8]);
+          entry[76 .. 80].copy_from_slice(&child.to_le_bytes());
+          entry[116 .. 120].copy_from_slice(&start.to_le_bytes());
+          entry[120 .. 128].copy_from_slice(&size.to_le_bytes());
+        }
+      } else if sector < self.msat_sector() {
+        let k = sector - self.sat_sector(0);
+        block.copy_from_slice(&Self::sec_ids((k * 1024 .. (k + 1) * 1024)
+          .map(|id| match id {
+            id if id + 1 < self.data_sectors => id + 1,
+            id if id <= self.dir_sector() => END_OF_CHAIN_SECID_U32,
+            id if id < self.msat_sector() => SAT_SECID_U32,
+            id if id < self.msat_sector() + self.msat_sectors => MSAT_SECID_U32,
+            _ => FREE_SECID_U32,
+          })));
+      } else {
+        block[.. 4092].copy_from_slice(&Self::sec_ids((109 .. 109 + 1023)
+          .map(|k| if k < self.sat_sectors { self.sat_sector(k) }
+            else { FREE_SECID_U32 })));
+        block[4092 ..].copy_from_slice(&END_OF_CHAIN_SECID);
+      }
+      block
+    }
+  }
+
+  impl std::io::Read for Version4File {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+      if self.position >= self.len() {
+        return Ok(0);
+      }
+      let block = self.block(self.position / Self::SECTOR_SIZE);
+      let offset = (self.position % Self::SECTOR_SIZE) as usize;
+      let n = buf.len().min(block.len() - offset);
+      buf[.. n].copy_from_slice(&block[offset .. offset + n]);
+      self.position += n as u64;
+      Ok(n)
+    }
+  }
+
+  impl std::io::Seek for Version4File {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+      self.position = match pos {
+        std::io::SeekFrom::Start(offset) => offset,
+        std::io::SeekFrom::End(offset) =>
+          self.len().checked_add_signed(offset).unwrap(),
+        std::io::SeekFrom::Current(offset) =>
+          self.position.checked_add_signed(offset).unwrap(),
+      };
+      Ok(self.position)
+    }
+  }
+
+  #[test]
+  fn version_4() {
+    use std::io::Read;
+    let size = 3 * 4096 + 100;
+    let expected: std::vec::Vec<u8> = (0 .. size).map(|o| (o % 251) as u8)
+      .collect();
+    let mut bytes = vec![];
+    Version4File::new(size).read_to_end(&mut bytes).unwrap();
+
+    let readers = [
+      Reader::new(&bytes[..]).unwrap(),
+      Reader::from_shared(std::sync::Arc::new(bytes.clone())).unwrap(),
+      Reader::from_seekable(std::io::Cursor::new(bytes.clone())).unwrap(),
+    ];
+    for ole in readers.iter() {
+      assert_eq!(ole.major_version(), 4);
+      let entry = ole.iterate().find(|x| x.name() == "Big").unwrap();
+      assert_eq!(entry.len() as u64, size);
+      let mut content = vec![];
+      ole.get_entry_slice(entry).unwrap().read_to_end(&mut content).unwrap();
+      assert_eq!(content, expected);
+      // Sectors start after the header sector of 4096 bytes
+      let carved: std::vec::Vec<u8> = ole.entry_byte_ranges(entry).unwrap()
+        .into_iter()
+        .flat_map(|range| bytes[range].to_vec())
+        .collect();
+      assert_eq!(carved, expected);
+    }
+  }
+
+  #[test]
+  #[cfg(target_pointer_width = "64")]
+  fn stream_larger_than_4_gib() {
+    use std::io::{Read, Seek, SeekFrom};
+    let size = u32::MAX as u64 + 4096 * 2;
+    let file = Version4File::new(size);
+    // More SAT sectors than the header holds
+    assert!(file.msat_sectors > 0);
+    let ole = Reader::from_seekable(file).unwrap();
+    let entry = ole.iterate().find(|x| x.name() == "Big").unwrap();
+    assert_eq!(entry.len() as u64, size);
+    assert!(ole.incomplete_entries().is_empty());
+    let ranges = ole.entry_byte_ranges(entry).unwrap();
+    assert!(ranges.last().unwrap().end as u64 > u32::MAX as u64);
+
+    let mut stream = ole.get_entry_stream(entry).unwrap();
+    for offset in [0, u32::MAX as u64 - 10, size - 16] {
+      stream.seek(SeekFrom::Start(offset)).unwrap();
+      let mut buf = [0u8; 16];
+      stream.read_exact(&mut buf).unwrap();
+      let expected: std::vec::Vec<u8> = (offset .. offset + 16)
+        .map(|o| (o % 251) as u8).collect();
+      assert_eq!(&buf[..], &expected[..]);
     }
   }
 }
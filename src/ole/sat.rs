@@ -1,4 +1,3 @@
-use std;
 use crate::ole::util::FromSlice;
 
 impl<'ole> super::ole::Reader<'ole> {
@@ -6,22 +5,27 @@ impl<'ole> super::ole::Reader<'ole> {
   pub(crate) fn build_sat(&mut self)
     -> Result<(), super::error::Error> {
     let sector_size = self.sec_size.unwrap();
-    let result: Result<(), super::error::Error>;
     let mut sec_ids = vec![super::constants::FREE_SECID_U32;
         sector_size / 4];
-    if self.msat.as_ref().unwrap().len() == 0 {
-      result = Err(super::error::Error::EmptyMasterSectorAllocationTable);
+    if self.msat.as_ref().unwrap().is_empty() {
+      Err(super::error::Error::EmptyMasterSectorAllocationTable)
     } else {
       for i in 0 .. self.msat.as_ref().unwrap().len() {
         let sector_index = self.msat.as_ref().unwrap()[i];
-        self.read_sat_sector(sector_index as usize, &mut sec_ids)?;
+        if sector_index >= super::constants::MSAT_SECID_U32 {
+          // Special SecIDs are not SAT sectors: keep the position of the
+          // following ones by filling with free SecIDs.
+          sec_ids.iter_mut()
+            .for_each(|x| *x = super::constants::FREE_SECID_U32);
+        } else {
+          self.read_sat_sector(sector_index as usize, &mut sec_ids)?;
+        }
         self.sat.as_mut().unwrap().extend_from_slice(&sec_ids);
       }
       self.build_ssat()?;
       self.build_dsat()?;
-      result = Ok(());
+      Ok(())
     }
-    result
   }
 
   pub(crate) fn read_sat_sector(&mut self, sector_index: usize,
@@ -34,32 +38,44 @@ impl<'ole> super::ole::Reader<'ole> {
     Ok(())
   }
 
-  pub(crate) fn build_chain_from_sat(&mut self, start: u32)
-        -> std::vec::Vec<u32> {
-    let mut chain = std::vec::Vec::new();
-    let mut sector_index = start;
-    let sat = self.sat.as_mut().unwrap();
-    while sector_index != super::constants::END_OF_CHAIN_SECID_U32 {
-      chain.push(sector_index);
-      sector_index = sat[sector_index as usize];
-    }
+  pub(crate) fn build_chain_from_sat(&self, start: u32)
+        -> Result<std::vec::Vec<u32>, super::error::Error> {
+    Self::build_chain(self.sat.as_ref().unwrap(), start,
+      &[super::constants::END_OF_CHAIN_SECID_U32,
+        super::constants::SAT_SECID_U32,
+        super::constants::MSAT_SECID_U32])
+  }
 
-    chain
+  pub(crate) fn build_chain_from_ssat(&self, start: u32)
+        -> Result<std::vec::Vec<u32>, super::error::Error> {
+    Self::build_chain(self.ssat.as_ref().unwrap(), start,
+      &[super::constants::END_OF_CHAIN_SECID_U32,
+        super::constants::FREE_SECID_U32,
+        super::constants::SAT_SECID_U32,
+        super::constants::MSAT_SECID_U32])
   }
 
-  pub(crate) fn build_chain_from_ssat(&mut self, start: u32)
-        -> std::vec::Vec<u32> {
+  /// Follows a chain of SecIDs in the given allocation table, until one of
+  /// the `terminators` is reached.
+  ///
+  /// A stream can never continue into a sector used by the SAT (-3) or the
+  /// MSAT (-4), so some generators use these markers to end chains.
+  ///
+  /// SecIDs outside of the table, as well as chains looping on themselves,
+  /// are reported as `MalformedChain`.
+  fn build_chain(table: &[u32], start: u32, terminators: &[u32])
+        -> Result<std::vec::Vec<u32>, super::error::Error> {
     let mut chain = std::vec::Vec::new();
     let mut sector_index = start;
-    let sat = self.ssat.as_mut().unwrap();
-    while sector_index != super::constants::END_OF_CHAIN_SECID_U32
-        && sector_index != super::constants::FREE_SECID_U32 {
+    while !terminators.contains(&sector_index) {
+      if sector_index as usize >= table.len() || chain.len() >= table.len() {
+        return Err(super::error::Error::MalformedChain);
+      }
       chain.push(sector_index);
-
-      sector_index = sat[sector_index as usize];
+      sector_index = table[sector_index as usize];
     }
 
-    chain
+    Ok(chain)
   }
 
   pub(crate) fn build_ssat(&mut self) -> Result<(), super::error::Error> {
@@ -67,7 +83,7 @@ impl<'ole> super::ole::Reader<'ole> {
         self.sec_size.as_ref().unwrap() / 4];
 
     let sector_index = self.ssat.as_mut().unwrap().remove(0);
-    let chain = self.build_chain_from_sat(sector_index);
+    let chain = self.build_chain_from_sat(sector_index)?;
 
     for sector_index in chain {
       self.read_sat_sector(sector_index as usize, &mut sec_ids)?;
@@ -79,7 +95,7 @@ impl<'ole> super::ole::Reader<'ole> {
   pub(crate) fn build_dsat(&mut self) -> Result<(), super::error::Error> {
 
     let sector_index = self.dsat.as_mut().unwrap().remove(0);
-    let chain = self.build_chain_from_sat(sector_index);
+    let chain = self.build_chain_from_sat(sector_index)?;
 
     for sector_index in chain {
       self.dsat.as_mut().unwrap().push(sector_index);
@@ -1,28 +1,46 @@
 impl<'ole> super::ole::Reader<'ole> {
+  /// Returns the sector, borrowed from the body in memory or read from the
+  /// source.
   pub(crate) fn read_sector(&self, sector_index: usize)
-    -> Result<&[u8], super::error::Error> {
-    let result: Result<&[u8], super::error::Error>;
+    -> Result<std::borrow::Cow<'_, [u8]>, super::error::Error> {
+    let range = self.sector_range(sector_index)?;
+    self.body.as_ref().unwrap().read(range)
+      .map_err(super::error::Error::IOError)
+  }
+
+  /// Returns the sector, padded with zeros where it lies past the end of
+  /// the file. A directory entry cut by the end of the file is left out.
+  pub(crate) fn read_padded_sector(&self, sector_index: usize)
+    -> std::vec::Vec<u8> {
     let sector_size = self.sec_size.unwrap();
-    let offset = sector_size * sector_index;
-    let max_size = offset + sector_size;
+    let body_size = self.body.as_ref().map_or(0, |body| body.len());
+    let offset = sector_size.saturating_mul(sector_index).min(body_size);
+    let available = (body_size - offset).min(sector_size);
+    let available = available - available
+      % super::constants::DIRECTORY_ENTRY_SIZE;
+    let mut sector = self.body.as_ref()
+      .and_then(|body| body.read(offset .. offset + available).ok())
+      .map_or_else(std::vec::Vec::new, |sector| sector.into_owned());
+    sector.resize(sector_size, 0);
+    sector
+  }
 
-    let body_size: usize;
-    if self.body.is_some() {
-      body_size = self.body.as_ref().unwrap().len();
-    } else {
-      body_size = 0;
-    }
+  /// Returns the range of the sector in the body of the file.
+  pub(crate) fn sector_range(&self, sector_index: usize)
+    -> Result<std::ops::Range<usize>, super::error::Error> {
+    let sector_size = self.sec_size.unwrap();
+    let offset = sector_size.checked_mul(sector_index)
+      .ok_or(super::error::Error::BadSizeValue("Overflow on sector offset"))?;
+    let max_size = offset.checked_add(sector_size)
+      .ok_or(super::error::Error::BadSizeValue("Overflow on sector offset"))?;
+
+    let body_size = self.body.as_ref().map_or(0, |body| body.len());
 
     // Check if the sector has already been read
-    let sector : &[u8];
     if body_size >= max_size {
-      let body = self.body.as_ref().unwrap();
-      sector = &body[offset .. offset + sector_size];
-      result = Ok(sector);
+      Ok(offset .. max_size)
     } else {
-      result = Err(super::error::Error::BadSizeValue("File is too short"));
+      Err(super::error::Error::BadSizeValue("File is too short"))
     }
-
-    result
   }
 }
@@ -0,0 +1,343 @@
+use super::constants;
+
+/// Size of the sectors written, as in version 3 files.
+const SECTOR_SIZE: usize = 512;
+
+/// Size of the sectors of the short stream.
+const SHORT_SECTOR_SIZE: usize = 64;
+
+/// Streams smaller than this are stored in the short stream.
+const MINIMUM_STANDARD_STREAM_SIZE: usize = 4096;
+
+/// Sector ids held by a sector of a sector allocation table.
+const SEC_IDS_PER_SECTOR: usize = SECTOR_SIZE / 4;
+
+/// Sector ids held by the header, the file having no MSAT sector.
+const HEADER_MSAT_SIZE: usize = 109;
+
+/// Longest name of an entry, in UTF-16 code units.
+const MAX_NAME_LEN: usize = 31;
+
+/// DirID of the absent siblings and children.
+const NO_STREAM: u32 = 0xFFFFFFFFu32;
+
+/// A storage or a stream added to the writer.
+struct Node {
+  name: std::string::String,
+  data: Option<std::vec::Vec<u8>>,
+  children: std::vec::Vec<usize>
+}
+
+/// An OLE file writer, mostly used to build test files.
+///
+/// Files are written with 512 bytes sectors, their streams smaller than
+/// 4096 bytes being stored in the short stream, as Outlook does.
+/// Timestamps and CLSIDs are left empty.
+///
+/// # Basic Example
+///
+/// ```ignore
+/// use crate::ole::{Reader, Writer};
+///
+/// let mut writer = Writer::new();
+/// writer.add_stream("storage/stream", b"data");
+/// let bytes = writer.to_bytes();
+/// let reader = Reader::new(&bytes[..]).unwrap();
+/// ```
+pub struct Writer {
+  nodes: std::vec::Vec<Node>
+}
+
+impl Default for Writer {
+  fn default() -> Writer {
+    Writer::new()
+  }
+}
+
+impl Writer {
+
+  /// Creates a writer holding the root storage only.
+  pub fn new() -> Writer {
+    Writer {
+      nodes: vec![Node {
+        name: "Root Entry".to_string(),
+        data: None,
+        children: vec![]
+      }]
+    }
+  }
+
+  /// Returns the index of the child named `name` of the storage `parent`,
+  /// adding it if missing.
+  fn child(&mut self, parent: usize, name: &str, data: Option<std::vec::Vec<u8>>)
+    -> usize {
+    assert!(!name.is_empty() && name.encode_utf16().count() <= MAX_NAME_LEN,
+      "invalid entry name {:?}", name);
+    let existing = self.nodes[parent].children.iter()
+      .copied()
+      .find(|&id| self.nodes[id].name.eq_ignore_ascii_case(name));
+    match existing {
+      Some(id) => {
+        if data.is_some() {
+          self.nodes[id].data = data;
+        }
+        id
+      },
+      None => {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+          name: name.to_string(),
+          data,
+          children: vec![]
+        });
+        self.nodes[parent].children.push(id);
+        id
+      }
+    }
+  }
+
+  /// Adds the storage at `path`, its names being separated by `/`,
+  /// along with its missing parents.
+  ///
+  /// # Panics
+  ///
+  /// Panics if a name is empty or longer than 31 characters.
+  pub fn add_storage(&mut self, path: &str) -> &mut Writer {
+    let mut parent = 0;
+    for name in path.split('/') {
+      parent = self.child(parent, name, None);
+    }
+    self
+  }
+
+  /// Adds the stream at `path` holding `data`, along with its missing
+  /// parent storages. An existing stream is replaced.
+  ///
+  /// # Panics
+  ///
+  /// Panics if a name is empty or longer than 31 characters.
+  pub fn add_stream(&mut self, path: &str, data: &[u8]) -> &mut Writer {
+    let (storage, name) = match path.rsplit_once('/') {
+      Some((storage, name)) => (Some(storage), name),
+      None => (None, path)
+    };
+    let mut parent = 0;
+    if let Some(storage) = storage {
+      for name in storage.split('/') {
+        parent = self.child(parent, name, None);
+      }
+    }
+    self.child(parent, name, Some(data.to_vec()));
+    self
+  }
+
+  /// Links the siblings `ids`, sorted as the specification requires,
+  /// into a balanced tree and returns its root.
+  fn link_siblings(&self, ids: &[usize], left: &mut [u32], right: &mut [u32])
+    -> u32 {
+    if ids.is_empty() {
+      return NO_STREAM;
+    }
+    let middle = ids.len() / 2;
+    left[ids[middle]] = self.link_siblings(&ids[..middle], left, right);
+    right[ids[middle]] = self.link_siblings(&ids[middle + 1..], left, right);
+    ids[middle] as u32
+  }
+
+  /// Sets the sector ids of a chain of `count` sectors from `start`.
+  fn chain(table: &mut [u32], start: usize, count: usize) {
+    let end = start + count;
+    for (id, next) in table[start..end].iter_mut().enumerate() {
+      let id = start + id;
+      *next = if id + 1 == end {
+        constants::END_OF_CHAIN_SECID_U32
+      } else {
+        (id + 1) as u32
+      };
+    }
+  }
+
+  fn sector_count(size: usize, sector_size: usize) -> usize {
+    size.div_ceil(sector_size)
+  }
+
+  /// Returns the bytes of the file.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the file would need more sector allocation table sectors
+  /// than the header holds, i.e. for files larger than about 6 MB.
+  pub fn to_bytes(&self) -> std::vec::Vec<u8> {
+    let count = self.nodes.len();
+
+    // Siblings are ordered by the length of their names, then by names
+    let mut left = vec![NO_STREAM; count];
+    let mut right = vec![NO_STREAM; count];
+    let mut child = vec![NO_STREAM; count];
+    for (id, node) in self.nodes.iter().enumerate() {
+      let mut children = node.children.clone();
+      children.sort_by_key(|&id| {
+        let name = &self.nodes[id].name;
+        (name.encode_utf16().count(), name.to_uppercase())
+      });
+      child[id] = self.link_siblings(&children, &mut left, &mut right);
+    }
+
+    // Short streams, then standard streams
+    let mut start = vec![constants::END_OF_CHAIN_SECID_U32; count];
+    let mut short_stream = std::vec::Vec::<u8>::new();
+    let mut ssat = std::vec::Vec::<u32>::new();
+    let mut standard = std::vec::Vec::<usize>::new();
+    for (id, node) in self.nodes.iter().enumerate() {
+      let data = match &node.data {
+        Some(data) if !data.is_empty() => data,
+        _ => continue
+      };
+      if data.len() >= MINIMUM_STANDARD_STREAM_SIZE {
+        standard.push(id);
+        continue;
+      }
+      let first = ssat.len();
+      let sectors = Writer::sector_count(data.len(), SHORT_SECTOR_SIZE);
+      start[id] = first as u32;
+      ssat.resize(first + sectors, constants::FREE_SECID_U32);
+      Writer::chain(&mut ssat, first, sectors);
+      short_stream.extend_from_slice(data);
+      short_stream.resize((first + sectors) * SHORT_SECTOR_SIZE, 0);
+    }
+
+    let dir_sectors = Writer::sector_count(
+      count * constants::DIRECTORY_ENTRY_SIZE, SECTOR_SIZE);
+    let ssat_sectors = Writer::sector_count(ssat.len() * 4, SECTOR_SIZE);
+    let short_stream_sectors =
+      Writer::sector_count(short_stream.len(), SECTOR_SIZE);
+    let standard_sectors: usize = standard.iter()
+      .map(|&id| Writer::sector_count(
+        self.nodes[id].data.as_ref().map_or(0, |data| data.len()),
+        SECTOR_SIZE))
+      .sum();
+    let other_sectors =
+      dir_sectors + ssat_sectors + short_stream_sectors + standard_sectors;
+    let mut sat_sectors = 1;
+    while sat_sectors * SEC_IDS_PER_SECTOR < sat_sectors + other_sectors {
+      sat_sectors += 1;
+    }
+    assert!(sat_sectors <= HEADER_MSAT_SIZE, "file too large");
+
+    // Sectors follow each other: SAT, directory, SSAT, short stream,
+    // then the standard streams.
+    let mut sat = vec![constants::FREE_SECID_U32;
+      sat_sectors * SEC_IDS_PER_SECTOR];
+    for id in sat.iter_mut().take(sat_sectors) {
+      *id = constants::SAT_SECID_U32;
+    }
+    let dir_start = sat_sectors;
+    Writer::chain(&mut sat, dir_start, dir_sectors);
+    let ssat_start = dir_start + dir_sectors;
+    Writer::chain(&mut sat, ssat_start, ssat_sectors);
+    let short_stream_start = ssat_start + ssat_sectors;
+    Writer::chain(&mut sat, short_stream_start, short_stream_sectors);
+    let mut next = short_stream_start + short_stream_sectors;
+    for &id in standard.iter() {
+      let size = self.nodes[id].data.as_ref().map_or(0, |data| data.len());
+      let sectors = Writer::sector_count(size, SECTOR_SIZE);
+      start[id] = next as u32;
+      Writer::chain(&mut sat, next, sectors);
+      next += sectors;
+    }
+    if short_stream_sectors > 0 {
+      start[0] = short_stream_start as u32;
+    }
+
+    let mut bytes = std::vec::Vec::<u8>::with_capacity(
+      constants::HEADER_SIZE + (sat_sectors + other_sectors) * SECTOR_SIZE);
+    let push_u32 = |bytes: &mut std::vec::Vec<u8>, value: u32| {
+      bytes.extend_from_slice(&value.to_le_bytes());
+    };
+
+    // Header
+    bytes.extend_from_slice(&constants::IDENTIFIER);
+    bytes.extend_from_slice(&[0u8; constants::UID_SIZE]);
+    bytes.extend_from_slice(&0x003Eu16.to_le_bytes());
+    bytes.extend_from_slice(&3u16.to_le_bytes());
+    bytes.extend_from_slice(&constants::LITTLE_ENDIAN_IDENTIFIER);
+    bytes.extend_from_slice(&9u16.to_le_bytes());
+    bytes.extend_from_slice(&6u16.to_le_bytes());
+    bytes.extend_from_slice(&[0u8; 10]);
+    push_u32(&mut bytes, sat_sectors as u32);
+    push_u32(&mut bytes, dir_start as u32);
+    push_u32(&mut bytes, 0);
+    push_u32(&mut bytes, MINIMUM_STANDARD_STREAM_SIZE as u32);
+    push_u32(&mut bytes, if ssat_sectors > 0 {
+      ssat_start as u32
+    } else {
+      constants::END_OF_CHAIN_SECID_U32
+    });
+    push_u32(&mut bytes, ssat_sectors as u32);
+    push_u32(&mut bytes, constants::END_OF_CHAIN_SECID_U32);
+    push_u32(&mut bytes, 0);
+    for i in 0..HEADER_MSAT_SIZE {
+      push_u32(&mut bytes, if i < sat_sectors {
+        i as u32
+      } else {
+        constants::FREE_SECID_U32
+      });
+    }
+
+    // SAT
+    for id in sat {
+      push_u32(&mut bytes, id);
+    }
+
+    // Directory, padded with empty entries
+    for id in 0..dir_sectors * SECTOR_SIZE / constants::DIRECTORY_ENTRY_SIZE {
+      let mut entry = [0u8; constants::DIRECTORY_ENTRY_SIZE];
+      entry[68..80].copy_from_slice(&[0xFF; 12]);
+      if let Some(node) = self.nodes.get(id) {
+        let name: std::vec::Vec<u8> = node.name.encode_utf16()
+          .flat_map(|c| c.to_le_bytes())
+          .collect();
+        entry[..name.len()].copy_from_slice(&name);
+        entry[64..66].copy_from_slice(&((name.len() + 2) as u16).to_le_bytes());
+        entry[66] = match (id, &node.data) {
+          (0, _) => 5,
+          (_, None) => 1,
+          (_, Some(_)) => 2
+        };
+        entry[67] = 1;
+        entry[68..72].copy_from_slice(&left[id].to_le_bytes());
+        entry[72..76].copy_from_slice(&right[id].to_le_bytes());
+        entry[76..80].copy_from_slice(&child[id].to_le_bytes());
+        let size = match &node.data {
+          Some(data) => data.len(),
+          None if id == 0 => short_stream.len(),
+          None => 0
+        };
+        let start = if node.data.is_none() && id != 0 { 0 } else { start[id] };
+        entry[116..120].copy_from_slice(&start.to_le_bytes());
+        entry[120..124].copy_from_slice(&(size as u32).to_le_bytes());
+      }
+      bytes.extend_from_slice(&entry);
+    }
+
+    // SSAT, short stream and standard streams, each padded to a sector
+    for id in ssat {
+      push_u32(&mut bytes, id);
+    }
+    let pad = |bytes: &mut std::vec::Vec<u8>| {
+      let len = bytes.len() - constants::HEADER_SIZE;
+      bytes.resize(constants::HEADER_SIZE
+        + Writer::sector_count(len, SECTOR_SIZE) * SECTOR_SIZE, 0);
+    };
+    pad(&mut bytes);
+    bytes.extend_from_slice(&short_stream);
+    pad(&mut bytes);
+    for &id in standard.iter() {
+      if let Some(data) = &self.nodes[id].data {
+        bytes.extend_from_slice(data);
+        pad(&mut bytes);
+      }
+    }
+    bytes
+  }
+}
@@ -0,0 +1,430 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{decode::DataType, kind::ItemKind, outlook::Person, storage::Storages};
+
+// Message class of cancelled meetings.
+// Refer to MS-OXOCAL 2.2.6.1
+const CANCELED_CLASS: &str = "IPM.Schedule.Meeting.Canceled";
+
+// "AppointmentStateFlags" bit set on cancelled meetings.
+// Refer to MS-OXOCAL 2.2.1.10
+const ASF_CANCELED: i64 = 0x0004;
+
+// "MeetingType" bit of informational updates.
+// Refer to MS-OXOCAL 2.2.6.5
+const MTG_INFO: i64 = 0x0002_0000;
+
+// Bits of "ChangeHighlight", by the field they flag as changed.
+// Refer to MS-OXOCAL 2.2.6.2
+const CHANGED_FIELDS: [(i64, &str); 10] = [
+    (0x0001, "start"),
+    (0x0002, "end"),
+    (0x0004, "recurrence"),
+    (0x0008, "location"),
+    (0x0010, "subject"),
+    (0x0020, "required_attendees"),
+    (0x0040, "optional_attendees"),
+    (0x0080, "body"),
+    (0x0200, "response"),
+    (0x0400, "allow_propose"),
+];
+
+// Layout of "GlobalObjectId" values: class id, instance date,
+// creation time, reserved bytes, then the size of the data
+// which follows. Refer to MS-OXOCAL 2.2.1.27
+const GLOBAL_ID_INSTANCE_DATE: std::ops::Range<usize> = 16..20;
+const GLOBAL_ID_DATA_OFFSET: usize = 40;
+
+// Data of ids converted from an iCalendar UID.
+const VCAL_UID_MARKER: &[u8] = b"vCal-Uid\x01\x00\x00\x00";
+
+// "RecipientFlags" bit of the organizer among the recipients.
+// Refer to MS-OXOCAL 2.2.4.10.1
+const RECIP_ORGANIZER: i64 = 0x0002;
+
+// BusyStatus is how the time of an appointment shows on the
+// calendar of its owner. Refer to MS-OXOCAL 2.2.1.2
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BusyStatus {
+    Free,
+    Tentative,
+    Busy,
+    OutOfOffice,
+    WorkingElsewhere,
+    Other(i32),
+}
+
+impl From<i32> for BusyStatus {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => BusyStatus::Free,
+            1 => BusyStatus::Tentative,
+            2 => BusyStatus::Busy,
+            3 => BusyStatus::OutOfOffice,
+            4 => BusyStatus::WorkingElsewhere,
+            value => BusyStatus::Other(value),
+        }
+    }
+}
+
+// ResponseStatus is the response of an attendee to a meeting,
+// as tracked on the organizer copy. Refer to MS-OXOCAL 2.2.1.11
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ResponseStatus {
+    None,
+    Organized,
+    Tentative,
+    Accepted,
+    Declined,
+    NotResponded,
+}
+
+impl ResponseStatus {
+    fn from_code(code: i64) -> Option<Self> {
+        match code {
+            0 => Some(ResponseStatus::None),
+            1 => Some(ResponseStatus::Organized),
+            2 => Some(ResponseStatus::Tentative),
+            3 => Some(ResponseStatus::Accepted),
+            4 => Some(ResponseStatus::Declined),
+            5 => Some(ResponseStatus::NotResponded),
+            _ => None,
+        }
+    }
+}
+
+// AttendeeResponse is the tracked response of a single attendee.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttendeeResponse {
+    pub attendee: Person,          // See Outlook.to
+    pub status: ResponseStatus,    // "RecipientTrackStatus"
+    pub responded_at: Option<u64>, // "RecipientTrackStatusTime", as FILETIME
+}
+
+// Appointment holds the calendar fields of appointments and meetings,
+// and the scheduling state of meeting requests, updates and
+// cancellations. Most of them are named properties.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Appointment {
+    #[serde(default)]
+    pub start: Option<DateTime<Utc>>,  // "AppointmentStartWhole", or "StartDate"
+    #[serde(default)]
+    pub end: Option<DateTime<Utc>>,    // "AppointmentEndWhole", or "EndDate"
+    #[serde(default)]
+    pub location: String,              // "Location", or "Where" of meeting requests
+    #[serde(default)]
+    pub organizer: Option<Person>,     // Recipient flagged as organizer, or else the sender
+    #[serde(default)]
+    pub all_day: bool,                 // "AppointmentSubType"
+    #[serde(default)]
+    pub busy_status: Option<BusyStatus>, // "BusyStatus"
+    pub sequence: Option<i32>,         // "AppointmentSequence"
+    pub is_cancelled: bool,            // Canceled message class, or "AppointmentStateFlags"
+    pub is_update: bool,               // Later sequence, or "MeetingType"
+    pub change_highlight: Option<i32>, // "ChangeHighlight", see Appointment::changed_fields
+    #[serde(default)]
+    pub attendees: Vec<AttendeeResponse>, // Recipients with a tracked response
+    #[serde(default)]
+    pub global_object_id: Option<String>, // "GlobalObjectId", hex encoded
+    #[serde(default)]
+    pub clean_global_object_id: Option<String>, // "CleanGlobalObjectId", hex encoded
+}
+
+impl Appointment {
+    pub(crate) fn create(storages: &Storages) -> Option<Self> {
+        let class = storages.get_val_from_root_or_default("MessageClass");
+        let kind = ItemKind::from_class(&class);
+        if !kind.is_calendar() {
+            return None;
+        }
+        let int = |key: &str| storages.root.get(key).and_then(|value| value.as_i64());
+        let time = |keys: [&str; 2]| {
+            keys.iter()
+                .find_map(|key| storages.root.get(*key).and_then(DataType::as_datetime))
+        };
+
+        let sequence = int("AppointmentSequence").map(|value| value as i32);
+        let is_cancelled = class.eq_ignore_ascii_case(CANCELED_CLASS)
+            || int("AppointmentStateFlags").is_some_and(|flags| flags & ASF_CANCELED != 0);
+        let is_update = !is_cancelled
+            && kind == ItemKind::Meeting
+            && (sequence.is_some_and(|sequence| sequence > 0)
+                || int("MeetingType").is_some_and(|kind| kind & MTG_INFO != 0));
+        Some(Self {
+            start: time(["AppointmentStartWhole", "StartDate"]),
+            end: time(["AppointmentEndWhole", "EndDate"]),
+            location: storages.get_first_from_root_or_default(&["Location", "Where"]),
+            organizer: Self::organizer(storages),
+            all_day: matches!(
+                storages.root.get("AppointmentSubType"),
+                Some(DataType::PtypBoolean(true))
+            ),
+            busy_status: int("BusyStatus").map(|value| BusyStatus::from(value as i32)),
+            sequence,
+            is_cancelled,
+            is_update,
+            change_highlight: int("ChangeHighlight").map(|value| value as i32),
+            attendees: Self::tracked_responses(storages),
+            global_object_id: Self::object_id(storages, "GlobalObjectId"),
+            clean_global_object_id: Self::object_id(storages, "CleanGlobalObjectId"),
+        })
+    }
+
+    fn object_id(storages: &Storages, key: &str) -> Option<String> {
+        match storages.root.get(key) {
+            Some(DataType::PtypBinary(bytes)) if !bytes.is_empty() => {
+                Some(hex::encode_upper(bytes))
+            }
+            _ => None,
+        }
+    }
+
+    // Identifier shared by a meeting request, its updates, its
+    // responses and its cancellation: the iCalendar UID the id was
+    // converted from, or the id itself without its instance date.
+    pub fn uid(&self) -> Option<String> {
+        let id = self
+            .clean_global_object_id
+            .as_ref()
+            .or(self.global_object_id.as_ref())?;
+        let mut bytes = hex::decode(id).ok()?;
+        let data = bytes.get(GLOBAL_ID_DATA_OFFSET..).unwrap_or_default();
+        if let Some(uid) = data.strip_prefix(VCAL_UID_MARKER) {
+            let uid = uid.split(|&c| c == 0).next().unwrap_or_default();
+            return Some(String::from_utf8_lossy(uid).into_owned());
+        }
+        if let Some(date) = bytes.get_mut(GLOBAL_ID_INSTANCE_DATE) {
+            date.fill(0);
+        }
+        Some(hex::encode_upper(bytes))
+    }
+
+    // The recipient flagged as organizer, or else the sender,
+    // none for appointments without attendees nor sender.
+    fn organizer(storages: &Storages) -> Option<Person> {
+        let recipient = storages.recipients.iter().position(|recipient| {
+            recipient
+                .get("RecipientFlags")
+                .and_then(DataType::as_i64)
+                .is_some_and(|flags| flags & RECIP_ORGANIZER != 0)
+        });
+        let person = match recipient {
+            Some(idx) => Person::create_from_recipient(storages, idx),
+            None => Person::create_from_props(&storages.root, "SenderName", "SenderSmtpAddress"),
+        };
+        (!person.name.is_empty() || !person.email.is_empty()).then_some(person)
+    }
+
+    // Only the organizer copy tracks the responses of attendees,
+    // on its recipients.
+    fn tracked_responses(storages: &Storages) -> Vec<AttendeeResponse> {
+        storages
+            .recipients
+            .iter()
+            .filter_map(|recipient| {
+                let status = recipient
+                    .get("RecipientTrackStatus")
+                    .and_then(|status| status.as_i64())
+                    .and_then(ResponseStatus::from_code)?;
+                let responded_at = match recipient.get("RecipientTrackStatusTime") {
+                    Some(DataType::PtypTime(time)) if *time > 0 => Some(*time),
+                    _ => None,
+                };
+                Some(AttendeeResponse {
+                    attendee: Person::create_from_props(
+                        recipient,
+                        "DisplayName",
+                        "SmtpAddress",
+                    ),
+                    status,
+                    responded_at,
+                })
+            })
+            .collect()
+    }
+
+    // Fields the organizer changed since the previous version
+    // of the meeting, as flagged by "ChangeHighlight".
+    pub fn changed_fields(&self) -> Vec<&'static str> {
+        let highlight = self.change_highlight.unwrap_or_default() as i64;
+        CHANGED_FIELDS
+            .iter()
+            .filter(|(bit, _)| highlight & bit != 0)
+            .map(|(_, field)| *field)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Appointment, BusyStatus, ResponseStatus};
+    use crate::parser::{decode::DataType, fixtures::storages, storage::Storages};
+
+    fn storages_with_flags(flags: i32) -> Storages {
+        storages(vec![
+            (
+                "MessageClass",
+                DataType::PtypString("IPM.Appointment".to_string()),
+            ),
+            ("AppointmentStateFlags", DataType::PtypInteger32(flags)),
+        ])
+    }
+
+    #[test]
+    fn test_not_a_meeting() {
+        let storages = storages(vec![(
+            "MessageClass",
+            DataType::PtypString("IPM.Note".to_string()),
+        )]);
+        assert!(Appointment::create(&storages).is_none());
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let storages = storages(vec![
+            (
+                "MessageClass",
+                DataType::PtypString("IPM.Schedule.Meeting.Canceled".to_string()),
+            ),
+            ("AppointmentSequence", DataType::PtypInteger32(3)),
+        ]);
+        let appointment = Appointment::create(&storages).unwrap();
+        assert_eq!(appointment.sequence, Some(3));
+        assert!(appointment.is_cancelled);
+        assert!(!appointment.is_update);
+
+        // Flagged on the appointment itself
+        let storages = storages_with_flags(0x5);
+        assert!(Appointment::create(&storages).unwrap().is_cancelled);
+    }
+
+    #[test]
+    fn test_update() {
+        let storages = storages(vec![
+            (
+                "MessageClass",
+                DataType::PtypString("IPM.Schedule.Meeting.Request".to_string()),
+            ),
+            ("AppointmentSequence", DataType::PtypInteger32(1)),
+            ("ChangeHighlight", DataType::PtypInteger32(0x9)),
+        ]);
+        let appointment = Appointment::create(&storages).unwrap();
+        assert!(appointment.is_update);
+        assert!(!appointment.is_cancelled);
+        assert_eq!(appointment.changed_fields(), vec!["start", "location"]);
+
+        let first = storages_with_flags(0x1);
+        let appointment = Appointment::create(&first).unwrap();
+        assert!(!appointment.is_update);
+        assert!(appointment.changed_fields().is_empty());
+    }
+
+    #[test]
+    fn test_global_object_id() {
+        // Outlook generated id, of an exception on 2024-05-17
+        let mut id = hex::decode("040000008200E00074C5B7101A82E008").unwrap();
+        id.extend([0x07, 0xE8, 0x05, 0x11]);
+        id.extend([0x10; 20]);
+        id.extend(16u32.to_le_bytes());
+        id.extend([0xAB; 16]);
+        let mut clean = id.clone();
+        clean[16..20].fill(0);
+
+        let mut storages = storages_with_flags(0);
+        storages.root.insert(
+            "GlobalObjectId".to_string(),
+            DataType::PtypBinary(id.clone()),
+        );
+        let appointment = Appointment::create(&storages).unwrap();
+        assert_eq!(appointment.global_object_id, Some(hex::encode_upper(&id)));
+        assert_eq!(appointment.clean_global_object_id, None);
+        assert_eq!(appointment.uid(), Some(hex::encode_upper(&clean)));
+
+        storages.root.insert(
+            "CleanGlobalObjectId".to_string(),
+            DataType::PtypBinary(clean.clone()),
+        );
+        let appointment = Appointment::create(&storages).unwrap();
+        assert_eq!(appointment.uid(), Some(hex::encode_upper(&clean)));
+
+        // Id converted from an iCalendar UID
+        let uid = b"vCal-Uid\x01\x00\x00\x00event-42@example.com\x00";
+        clean.truncate(36);
+        clean.extend((uid.len() as u32).to_le_bytes());
+        clean.extend(uid);
+        storages.root.insert(
+            "CleanGlobalObjectId".to_string(),
+            DataType::PtypBinary(clean),
+        );
+        let appointment = Appointment::create(&storages).unwrap();
+        assert_eq!(appointment.uid().unwrap(), "event-42@example.com");
+
+        assert!(Appointment::default().uid().is_none());
+    }
+
+    #[test]
+    fn test_tracked_responses() {
+        let mut storages = storages_with_flags(0x1);
+        assert_eq!(storages.recipients.len(), 2);
+        storages.recipients[0].insert(
+            "RecipientTrackStatus".to_string(),
+            DataType::PtypInteger32(3),
+        );
+        storages.recipients[0].insert(
+            "RecipientTrackStatusTime".to_string(),
+            DataType::PtypTime(130292343840000000),
+        );
+        storages.recipients[1].insert(
+            "RecipientTrackStatus".to_string(),
+            DataType::PtypInteger32(4),
+        );
+
+        let attendees = Appointment::create(&storages).unwrap().attendees;
+        assert_eq!(attendees.len(), 2);
+        assert_eq!(attendees[0].status, ResponseStatus::Accepted);
+        assert_eq!(attendees[0].responded_at, Some(130292343840000000));
+        assert_eq!(attendees[1].status, ResponseStatus::Declined);
+        assert_eq!(attendees[1].responded_at, None);
+        assert!(!attendees[1].attendee.name.is_empty());
+    }
+
+    #[test]
+    fn test_calendar_fields() {
+        let mut storages = storages(vec![
+            (
+                "MessageClass",
+                DataType::PtypString("IPM.Appointment".to_string()),
+            ),
+            // 2013-11-18 17:30 and 18:00 UTC
+            ("AppointmentStartWhole", DataType::PtypTime(130292694000000000)),
+            ("AppointmentEndWhole", DataType::PtypTime(130292712000000000)),
+            ("Location", DataType::PtypString("Room 4".to_string())),
+            ("AppointmentSubType", DataType::PtypBoolean(false)),
+            ("BusyStatus", DataType::PtypInteger32(3)),
+        ]);
+        let appointment = Appointment::create(&storages).unwrap();
+        let start = appointment.start.unwrap();
+        assert_eq!(start.to_rfc3339(), "2013-11-18T17:30:00+00:00");
+        assert_eq!((appointment.end.unwrap() - start).num_minutes(), 30);
+        assert_eq!(appointment.location, "Room 4");
+        assert!(!appointment.all_day);
+        assert_eq!(appointment.busy_status, Some(BusyStatus::OutOfOffice));
+        // The sender, without a recipient flagged as organizer
+        let sender = appointment.organizer.unwrap();
+        assert!(!sender.name.is_empty());
+
+        storages.recipients[1].insert("RecipientFlags".to_string(), DataType::PtypInteger32(0x3));
+        storages.root.remove("AppointmentStartWhole");
+        storages.root.insert("StartDate".to_string(), DataType::PtypTime(130292694000000000));
+        storages.root.insert("AppointmentSubType".to_string(), DataType::PtypBoolean(true));
+        storages.root.insert("BusyStatus".to_string(), DataType::PtypInteger32(7));
+        let appointment = Appointment::create(&storages).unwrap();
+        assert_eq!(appointment.start, Some(start));
+        assert!(appointment.all_day);
+        assert_eq!(appointment.busy_status, Some(BusyStatus::Other(7)));
+        let organizer = appointment.organizer.unwrap();
+        assert_eq!(organizer.storage_index, storages.recipient_ids.get(1).copied());
+        assert_ne!(organizer, sender);
+    }
+}
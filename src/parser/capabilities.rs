@@ -0,0 +1,70 @@
+use serde::Serialize;
+
+use super::decode::DECODED_TYPE_CODES;
+
+// Body formats read, by MIME type. RTF bodies are kept
+// compressed, see Outlook.rtf_compressed.
+const BODY_FORMATS: [&str; 3] = ["text/plain", "text/html", "application/rtf"];
+
+// Capabilities tells what the linked build of this crate can
+// parse, for applications that host it as a plugin.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Capabilities {
+    pub version: &'static str,                   // Version of the crate
+    pub property_types: &'static [&'static str], // Property type codes decoded, e.g. "0x001F"
+    pub body_formats: &'static [&'static str],   // See BODY_FORMATS
+    pub features: Vec<&'static str>,             // Cargo features enabled at compile time
+}
+
+impl Capabilities {
+    // Whether the property type code, e.g. "0x0040", is decoded.
+    pub fn decodes(&self, code: &str) -> bool {
+        self.property_types.contains(&code)
+    }
+
+    // Whether the Cargo feature was enabled at compile time.
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+// Capabilities of this build.
+pub fn capabilities() -> Capabilities {
+    let features = [
+        ("legacy", cfg!(feature = "legacy")),
+        ("markdown", cfg!(feature = "markdown")),
+        ("msg", cfg!(feature = "msg")),
+        ("testing", cfg!(feature = "testing")),
+    ];
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        property_types: &DECODED_TYPE_CODES,
+        body_formats: &BODY_FORMATS,
+        features: features
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(feature, _)| *feature)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::capabilities;
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = capabilities();
+        assert_eq!(capabilities.version, env!("CARGO_PKG_VERSION"));
+        assert!(capabilities.decodes("0x001F"));
+        assert!(capabilities.decodes("0x0040"));
+        // Known, but not decoded
+        assert!(!capabilities.decodes("0x0048"));
+        assert!(capabilities.body_formats.contains(&"text/plain"));
+        assert_eq!(capabilities.has_feature("legacy"), cfg!(feature = "legacy"));
+        assert_eq!(capabilities.has_feature("markdown"), cfg!(feature = "markdown"));
+        assert_eq!(capabilities.has_feature("msg"), cfg!(feature = "msg"));
+        assert_eq!(capabilities.has_feature("testing"), cfg!(feature = "testing"));
+        assert!(!capabilities.has_feature("unknown"));
+    }
+}
@@ -1,10 +1,18 @@
 use std::collections::HashMap;
 
+use super::named::{NamedPropertyMap, NAMED_PROPERTY_ID_START};
+
 // PropIdNameMap refers to mapping between property ID and
 // Full list is available in [MS-OXPROPS].
 #[derive(Debug)]
 pub struct PropIdNameMap {
     map: HashMap<String, String>,
+    // Reverse of map: canonical name to property tag
+    tags: HashMap<String, u32>,
+    // Canonical names by their upper case, see get_name
+    names: HashMap<String, String>,
+    // Names of the ids from 0x8000, specific to a file
+    named: NamedPropertyMap,
 }
 
 impl PropIdNameMap {
@@ -102,6 +110,7 @@ impl PropIdNameMap {
             ("0x0C1D", "SenderSearchKey"),
             ("0x0C1E", "SenderAddressType"),
             ("0x0C1F", "SenderEmailAddress"),
+            ("0x0C20", "NonDeliveryReportStatusCode"),
             ("0x0C21", "RemoteMessageTransferAgent"),
             ("0x0E01", "DeleteAfterSubmit"),
             ("0x0E02", "DisplayBcc"),
@@ -564,10 +573,74 @@ impl PropIdNameMap {
         .map(|(k, v)| (k.to_string(), v.to_string()))
         .collect();
 
-        Self { map }
+        let tags = map
+            .iter()
+            .filter_map(|(id, name)| {
+                let id = u32::from_str_radix(id.trim_start_matches("0x"), 16).ok()?;
+                Some((name.clone(), id << 16))
+            })
+            .collect();
+
+        let names = map
+            .values()
+            .map(|name| (name.to_ascii_uppercase(), name.clone()))
+            .collect();
+
+        Self {
+            map,
+            tags,
+            names,
+            named: NamedPropertyMap::default(),
+        }
+    }
+
+    // Resolves the ids from 0x8000 with the named properties of a file.
+    pub fn with_named(mut self, named: NamedPropertyMap) -> Self {
+        self.named = named;
+        self
     }
 
     pub fn get_canonical_name(&self, id: &str) -> Option<String> {
+        if !self.named.is_empty() {
+            let num = u32::from_str_radix(id.trim_start_matches("0x"), 16).ok()?;
+            if num >= NAMED_PROPERTY_ID_START {
+                return self.named.get(num)?.canonical_name();
+            }
+        }
         self.map.get(id).map(|v| v.to_string())
     }
+
+    // Returns the property tag of a canonical name,
+    // e.g. "SenderSmtpAddress" gives 0x5D010000.
+    // Property types are not part of the map, so the
+    // lower 16 bits are left to PtypUnspecified (0x0000).
+    pub fn get_tag(&self, name: &str) -> Option<u32> {
+        self.tags.get(name).copied()
+    }
+
+    // Returns the canonical name of a property named in
+    // any case, e.g. "subject" gives "Subject".
+    pub fn get_name(&self, name: &str) -> Option<&str> {
+        self.names.get(&name.to_ascii_uppercase()).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PropIdNameMap;
+
+    #[test]
+    fn test_get_tag() {
+        let prop_map = PropIdNameMap::init();
+        assert_eq!(prop_map.get_tag("SenderSmtpAddress"), Some(0x5D01_0000));
+        assert_eq!(prop_map.get_tag("Subject"), Some(0x0037_0000));
+        assert_eq!(prop_map.get_tag("NotAProperty"), None);
+        assert_eq!(prop_map.get_name("SENDERsmtpADDRESS"), Some("SenderSmtpAddress"));
+        assert_eq!(prop_map.get_name("NotAProperty"), None);
+
+        // Both directions agree
+        let tag = prop_map.get_tag("DisplayName").unwrap();
+        let id = format!("0x{:04X}", tag >> 16);
+        assert_eq!(prop_map.get_canonical_name(&id).unwrap(), "DisplayName");
+    }
 }
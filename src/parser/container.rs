@@ -0,0 +1,271 @@
+use std::{
+    borrow::Cow,
+    io::{Cursor, Read},
+};
+
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use zip::ZipArchive;
+
+use crate::ole::constants::IDENTIFIER;
+
+use super::{
+    eml::TransferEncoding,
+    error::Error,
+    headers::header_value,
+};
+
+const ZIP_SIGNATURE: [u8; 4] = [b'P', b'K', 0x03, 0x04];
+
+// Content-Type of the .msg files attached to MIME messages. Some
+// clients send them as application/octet-stream, named *.msg.
+const MSG_CONTENT_TYPE: &str = "application/vnd.ms-outlook";
+const OCTET_STREAM: &str = "application/octet-stream";
+
+// A container may itself be wrapped (e.g. a base64 encoded zip),
+// which is unwrapped up to this depth.
+const MAX_DEPTH: usize = 2;
+
+// Base64 payloads found in API responses are not always padded.
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+// Container refers to the formats a .msg file is commonly wrapped in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Container {
+    // A bare OLE file, nothing to unwrap.
+    Ole,
+    // A zip archive holding a single message.
+    Zip,
+    // A base64 encoded payload.
+    Base64,
+}
+
+impl Container {
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&IDENTIFIER) {
+            return Some(Container::Ole);
+        }
+        if bytes.starts_with(&ZIP_SIGNATURE) {
+            return Some(Container::Zip);
+        }
+        let is_base64 = !bytes.is_empty()
+            && bytes.iter().all(|&c| {
+                c.is_ascii_alphanumeric() || c == b'+' || c == b'/' || c == b'='
+                    || c.is_ascii_whitespace()
+            });
+        if is_base64 {
+            return Some(Container::Base64);
+        }
+        None
+    }
+}
+
+// Unwraps the containers around an OLE file.
+// Bytes which are not recognized are returned as is.
+// Files extracted from zip archives are read up to max_size bytes.
+pub fn unwrap(bytes: &[u8], max_size: usize) -> Result<Cow<'_, [u8]>, Error> {
+    let mut current = Cow::Borrowed(bytes);
+    for _ in 0..=MAX_DEPTH {
+        current = match Container::detect(&current) {
+            Some(Container::Zip) => Cow::Owned(extract_from_zip(&current, max_size)?),
+            Some(Container::Base64) => Cow::Owned(decode_base64(&current)?),
+            Some(Container::Ole) | None => return Ok(current),
+        };
+    }
+    Err(Error::ContainerError("too many nested containers".to_string()))
+}
+
+// Body of a MIME part holding a .msg file, such as an attachment
+// of an EML file, given with its headers. Refer to RFC 2045
+pub fn mime_part_body(part: &[u8]) -> Result<Cow<'_, [u8]>, Error> {
+    let (header_end, body_start) = [&b"\r\n\r\n"[..], b"\n\n"]
+        .iter()
+        .filter_map(|separator| {
+            part.windows(separator.len())
+                .position(|window| window == *separator)
+                .map(|end| (end, end + separator.len()))
+        })
+        .min()
+        .ok_or_else(|| Error::ContainerError("MIME part has no body".to_string()))?;
+    // Headers are read with CRLF line breaks
+    let headers = String::from_utf8_lossy(&part[..header_end])
+        .replace("\r\n", "\n")
+        .replace('\n', "\r\n");
+    let header = |name: &str| header_value(&headers, name).unwrap_or_default();
+
+    let content_type = header("Content-Type");
+    let mime_type = content_type.split(';').next().unwrap_or_default().trim();
+    let is_msg = mime_type.eq_ignore_ascii_case(MSG_CONTENT_TYPE)
+        || mime_type.eq_ignore_ascii_case(OCTET_STREAM)
+            && format!("{} {}", content_type, header("Content-Disposition"))
+                .to_ascii_lowercase()
+                .contains(".msg");
+    if !is_msg {
+        return Err(Error::ContainerError(format!(
+            "MIME part of type {:?} is not a message",
+            mime_type
+        )));
+    }
+
+    let body = &part[body_start..];
+    let encoding = header("Content-Transfer-Encoding");
+    match TransferEncoding::from_header(&encoding) {
+        Some(TransferEncoding::Base64) => Ok(Cow::Owned(decode_base64(body)?)),
+        Some(TransferEncoding::QuotedPrintable) => Err(Error::ContainerError(
+            "quoted-printable MIME parts are not supported".to_string(),
+        )),
+        // 7bit, 8bit and binary
+        _ => Ok(Cow::Borrowed(body)),
+    }
+}
+
+fn decode_base64(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let stripped: Vec<u8> = bytes
+        .iter()
+        .filter(|c| !c.is_ascii_whitespace())
+        .cloned()
+        .collect();
+    BASE64
+        .decode(stripped)
+        .map_err(|err| Error::ContainerError(err.to_string()))
+}
+
+fn extract_from_zip(bytes: &[u8], max_size: usize) -> Result<Vec<u8>, Error> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))
+        .map_err(|err| Error::ContainerError(err.to_string()))?;
+    let files: Vec<String> = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(String::from)
+        .collect();
+    let messages: Vec<&String> = files
+        .iter()
+        .filter(|name| name.to_ascii_lowercase().ends_with(".msg"))
+        .collect();
+    // Prefer the only .msg file, or the only file of the archive.
+    let name = match (messages.len(), files.len()) {
+        (1, _) => messages[0],
+        (0, 1) => &files[0],
+        (n, _) => {
+            return Err(Error::ContainerError(format!(
+                "archive holds {} messages, expected one",
+                n
+            )))
+        }
+    };
+    let file = archive
+        .by_name(name)
+        .map_err(|err| Error::ContainerError(err.to_string()))?;
+    // The declared size is not trusted, the file is read
+    // until it goes past the limit.
+    let mut buff = vec![];
+    file.take((max_size as u64).saturating_add(1)).read_to_end(&mut buff)?;
+    if buff.len() > max_size {
+        return Err(Error::LimitExceeded(format!(
+            "archived file larger than {} bytes",
+            max_size
+        )));
+    }
+    Ok(buff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mime_part_body, unwrap, Container, BASE64};
+    use crate::parser::error::Error;
+    use base64::Engine;
+    use std::io::Write;
+    use zip::{write::FileOptions, ZipWriter};
+
+    fn zip_of(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_detect() {
+        let msg = std::fs::read("data/unicode.msg").unwrap();
+        assert_eq!(Container::detect(&msg), Some(Container::Ole));
+        assert_eq!(Container::detect(&zip_of(&[("a.msg", b"")])), Some(Container::Zip));
+        assert_eq!(Container::detect(b"0M8R4KGx\r\nGuE="), Some(Container::Base64));
+        assert_eq!(Container::detect(b"{\"msg\": 1}"), None);
+    }
+
+    #[test]
+    fn test_unwrap_zip() {
+        let msg = std::fs::read("data/unicode.msg").unwrap();
+        let zipped = zip_of(&[("notes.txt", b"hello"), ("mail.MSG", &msg)]);
+        assert_eq!(unwrap(&zipped, usize::MAX).unwrap().to_vec(), msg);
+
+        let zipped = zip_of(&[("a.msg", &msg), ("b.msg", &msg)]);
+        assert_eq!(
+            unwrap(&zipped, usize::MAX).unwrap_err().to_string(),
+            "Unable to unwrap container: archive holds 2 messages, expected one"
+        );
+    }
+
+    #[test]
+    fn test_unwrap_zip_limit() {
+        // Compresses to about a kilobyte
+        let bomb = vec![0u8; 1024 * 1024];
+        let zipped = zip_of(&[("mail.msg", &bomb)]);
+        assert!(zipped.len() < 4096);
+        let err = unwrap(&zipped, 64 * 1024).unwrap_err();
+        assert!(matches!(err, Error::LimitExceeded(_)));
+        assert_eq!(unwrap(&zipped, bomb.len()).unwrap().len(), bomb.len());
+    }
+
+    #[test]
+    fn test_unwrap_base64() {
+        let msg = std::fs::read("data/unicode.msg").unwrap();
+        let encoded = BASE64.encode(&msg);
+        // MIME style line wrapping
+        let wrapped = encoded
+            .as_bytes()
+            .chunks(76)
+            .collect::<Vec<&[u8]>>()
+            .join(&b"\r\n"[..]);
+        assert_eq!(unwrap(&wrapped, usize::MAX).unwrap().to_vec(), msg);
+
+        // Base64 encoded zip
+        let zipped = BASE64.encode(zip_of(&[("mail.msg", &msg)]));
+        assert_eq!(unwrap(zipped.as_bytes(), usize::MAX).unwrap().to_vec(), msg);
+    }
+
+    #[test]
+    fn test_mime_part_body() {
+        let msg = std::fs::read("data/unicode.msg").unwrap();
+        let mut part = b"Content-Type: application/vnd.ms-outlook;\r\n\
+            \tname=\"Fwd.msg\"\r\n\
+            Content-Transfer-Encoding: base64\r\n\r\n"
+            .to_vec();
+        let encoded = BASE64.encode(&msg);
+        part.extend(encoded.as_bytes().chunks(76).collect::<Vec<_>>().join(&b"\r\n"[..]));
+        assert_eq!(mime_part_body(&part).unwrap().to_vec(), msg);
+
+        // Unencoded, with LF line breaks
+        let mut part = b"Content-Type: application/octet-stream\n\
+            Content-Disposition: attachment; filename=\"Fwd.MSG\"\n\n"
+            .to_vec();
+        part.extend(&msg);
+        assert_eq!(mime_part_body(&part).unwrap().to_vec(), msg);
+
+        let err = mime_part_body(b"Content-Type: image/png\r\n\r\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unable to unwrap container: MIME part of type \"image/png\" is not a message"
+        );
+        assert!(mime_part_body(b"Content-Type: application/octet-stream\r\n\r\n").is_err());
+        assert!(mime_part_body(b"Content-Type: application/vnd.ms-outlook").is_err());
+    }
+}
@@ -1,10 +1,15 @@
 use std::io::Read;
 
-use hex;
+use chrono::{DateTime, Utc};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+use serde::{Deserialize, Serialize};
 
 use crate::ole::EntrySlice;
 
-use super::error::{DataTypeError, Error};
+use super::{
+    error::{DataTypeError, Error},
+    time::filetime_to_utc,
+};
 
 // DataType corresponds to decoded property values
 // as specified in this document.
@@ -13,6 +18,56 @@ use super::error::{DataTypeError, Error};
 pub enum DataType {
     PtypString(String),
     PtypBinary(Vec<u8>),
+    PtypInteger16(i16),
+    PtypInteger32(i32),
+    PtypInteger64(i64),
+    PtypBoolean(bool),
+    // FILETIME, 100-nanosecond intervals since January 1, 1601
+    PtypTime(u64),
+    // Placeholder for a property whose value could not be computed
+    PtypErrorCode(u32),
+    PtypNull,
+    PtypMultipleInteger32(Vec<i32>),
+    // PtypMultipleString and PtypMultipleString8, read from
+    // the streams of their values as PtypString is
+    PtypMultipleString(Vec<String>),
+    PtypMultipleBinary(Vec<Vec<u8>>),
+}
+
+impl DataType {
+    // Value of the integer types, widened to i64.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            DataType::PtypInteger16(value) => Some(value as i64),
+            DataType::PtypInteger32(value) => Some(value as i64),
+            DataType::PtypInteger64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // Whether the value is empty, as its string is, e.g. an empty
+    // PtypString or PtypBinary, or PtypNull. Numbers never are.
+    pub fn is_empty(&self) -> bool {
+        match *self {
+            DataType::PtypString(ref string) => string.is_empty(),
+            DataType::PtypBinary(ref bytes) => bytes.is_empty(),
+            DataType::PtypNull => true,
+            DataType::PtypMultipleInteger32(ref values) => values.is_empty(),
+            DataType::PtypMultipleString(ref values) => values.iter().all(String::is_empty),
+            DataType::PtypMultipleBinary(ref values) => values.iter().all(Vec::is_empty),
+            _ => false,
+        }
+    }
+
+    // Value of PtypTime as a UTC date. The FILETIME is kept
+    // as is in the variant, as dates out of the range of
+    // DateTime are not lost.
+    pub fn as_datetime(&self) -> Option<DateTime<Utc>> {
+        match *self {
+            DataType::PtypTime(filetime) => filetime_to_utc(filetime),
+            _ => None,
+        }
+    }
 }
 
 impl From<&DataType> for String {
@@ -20,59 +75,471 @@ impl From<&DataType> for String {
         match *data {
             DataType::PtypBinary(ref bytes) => hex::encode(bytes),
             DataType::PtypString(ref string) => string.to_string(),
+            DataType::PtypInteger16(value) => value.to_string(),
+            DataType::PtypInteger32(value) => value.to_string(),
+            DataType::PtypInteger64(value) => value.to_string(),
+            DataType::PtypBoolean(value) => value.to_string(),
+            DataType::PtypTime(value) => value.to_string(),
+            DataType::PtypErrorCode(code) => format!("0x{:08X}", code),
+            DataType::PtypNull => String::new(),
+            DataType::PtypMultipleInteger32(ref values) => values
+                .iter()
+                .map(i32::to_string)
+                .collect::<Vec<_>>()
+                .join("; "),
+            DataType::PtypMultipleString(ref values) => values.join("; "),
+            DataType::PtypMultipleBinary(ref values) => values
+                .iter()
+                .map(hex::encode)
+                .collect::<Vec<_>>()
+                .join("; "),
         }
     }
 }
 
+// Property type codes defined in MS-OXCDATA 2.11.1,
+// whether they are decoded or not.
+const KNOWN_TYPE_CODES: [&str; 34] = [
+    "0x0000", "0x0001", "0x0002", "0x0003", "0x0004", "0x0005", "0x0006", "0x0007", "0x000A",
+    "0x000B", "0x000D", "0x0014", "0x001E", "0x001F", "0x0040", "0x0048", "0x00FB", "0x00FD",
+    "0x00FE", "0x0102", "0x1002", "0x1003", "0x1004", "0x1005", "0x1006", "0x1007", "0x1014",
+    "0x101E", "0x101F", "0x1040", "0x1048", "0x1102", "0x0080", "0x00FF",
+];
+
+// Property type codes decoded into a DataType, others are skipped.
+pub(crate) const DECODED_TYPE_CODES: [&str; 14] = [
+    "0x0001", "0x0002", "0x0003", "0x000A", "0x000B", "0x0014", "0x001E", "0x001F", "0x0040",
+    "0x0102", "0x1003", "0x101E", "0x101F", "0x1102",
+];
+
+// Type codes of multiple valued properties whose values each have
+// a stream, along with the type code of these values.
+// Refer to MS-OXMSG 2.1.4.1.3
+const ELEMENT_TYPE_CODES: [(&str, &str); 3] = [
+    ("0x101E", "0x001E"),
+    ("0x101F", "0x001F"),
+    ("0x1102", "0x0102"),
+];
+
+// Type code of the values of a multiple valued property,
+// none when they are not in streams of their own.
+pub(crate) fn element_type_code(code: &str) -> Option<&'static str> {
+    ELEMENT_TYPE_CODES
+        .iter()
+        .find(|(multiple, _)| *multiple == code)
+        .map(|(_, element)| *element)
+}
+
+// Value of a multiple valued property from the values of its
+// streams, in the order of their index. Values of another type,
+// such as the PtypNull of those which could not be read, are left
+// empty so that the others keep their index.
+pub(crate) fn multiple_value(
+    code: &str,
+    elements: impl Iterator<Item = DataType>,
+) -> Option<DataType> {
+    match element_type_code(code)? {
+        "0x0102" => Some(DataType::PtypMultipleBinary(
+            elements
+                .map(|element| match element {
+                    DataType::PtypBinary(bytes) => bytes,
+                    _ => vec![],
+                })
+                .collect(),
+        )),
+        _ => Some(DataType::PtypMultipleString(
+            elements
+                .map(|element| match element {
+                    DataType::PtypString(string) => string,
+                    _ => String::new(),
+                })
+                .collect(),
+        )),
+    }
+}
+
+// Whether the code is a property type defined in MS-OXCDATA.
+pub fn is_known_type(code: &str) -> bool {
+    KNOWN_TYPE_CODES.contains(&code)
+}
+
+// StringDecoder overrides how the bytes of string properties
+// become Strings, e.g. for legacy encodings such as EBCDIC or
+// double encoded UTF-8. Returning None falls back to the default
+// decoding. Set with ParseOptions::string_decoder.
+pub trait StringDecoder: Send + Sync {
+    // PtypString (0x001F) values, UTF-16 little-endian by default
+    fn decode_unicode(&self, _bytes: &[u8]) -> Option<String> {
+        None
+    }
+
+    // PtypString8 (0x001E) values, in the code page of the message
+    // by default, see decode_string8
+    fn decode_ansi(&self, _bytes: &[u8]) -> Option<String> {
+        None
+    }
+}
+
+impl std::fmt::Debug for dyn StringDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("StringDecoder")
+    }
+}
+
 // PytpDecoder converts a byte sequence
 // into primitive type DataType.
 pub struct PtypDecoder {}
 
 impl PtypDecoder {
-    pub fn decode(entry_slice: &mut EntrySlice, code: &str) -> Result<DataType, Error> {
-        let mut buff = vec![0u8; entry_slice.len()];
-        entry_slice.read(&mut buff)?;
+    // Reads at most max_size bytes of the value.
+    // Strings are cut on a character boundary.
+    pub(crate) fn read_value(
+        entry_slice: &mut EntrySlice,
+        code: &str,
+        max_size: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let mut buff = vec![0u8; entry_slice.len().min(max_size)];
+        entry_slice.read_exact(&mut buff)?;
+        if code == "0x001F" && buff.len() < entry_slice.len() {
+            buff.truncate(buff.len() & !1);
+            // Leave out the high surrogate of a split pair
+            if let [.., low, high] = buff[..] {
+                if (0xD800..0xDC00).contains(&u16::from_le_bytes([low, high])) {
+                    buff.truncate(buff.len() - 2);
+                }
+            }
+        }
+        Ok(buff)
+    }
+
+    // Reads at most max_size bytes of the value, see read_value,
+    // and decodes them, see decode_value. The parser reads values
+    // apart from decoding them, so as to check the bytes read.
+    #[cfg(test)]
+    pub fn decode(
+        entry_slice: &mut EntrySlice,
+        code: &str,
+        max_size: usize,
+        string_decoder: Option<&dyn StringDecoder>,
+        code_page: Option<i64>,
+    ) -> Result<DataType, Error> {
+        let buff = Self::read_value(entry_slice, code, max_size)?;
+        Self::decode_value(&buff, code, string_decoder, code_page)
+    }
+
+    // Strings are decoded by string_decoder if any, PtypString8
+    // values being otherwise in code_page, see decode_string8.
+    pub(crate) fn decode_value(
+        buff: &[u8],
+        code: &str,
+        string_decoder: Option<&dyn StringDecoder>,
+        code_page: Option<i64>,
+    ) -> Result<DataType, Error> {
+        let custom = string_decoder.and_then(|decoder| match code {
+            "0x001F" => decoder.decode_unicode(buff),
+            "0x001E" => decoder.decode_ansi(buff),
+            _ => None,
+        });
+        if let Some(decoded) = custom {
+            return Ok(DataType::PtypString(decoded));
+        }
         match code {
-            "0x001F" => decode_ptypstring(&buff),
-            "0x0102" => decode_ptypbinary(&buff),
+            "0x001F" => decode_ptypstring(buff),
+            "0x001E" => decode_ptypstring8(buff, code_page),
+            "0x0102" => decode_ptypbinary(buff),
+            "0x000A" => decode_ptyperrorcode(buff),
+            "0x0001" => Ok(DataType::PtypNull),
+            // Fixed length values written to a stream of their own,
+            // padded with zeros as in the property stream
+            "0x0002" | "0x0003" | "0x0014" | "0x000B" | "0x0040" => {
+                let mut value = [0u8; 8];
+                for (byte, read) in value.iter_mut().zip(buff) {
+                    *byte = *read;
+                }
+                Self::decode_fixed(&value, code)
+            }
+            "0x1003" => decode_ptypmultipleinteger32(buff),
+            // Lengths of the values, which are in streams of their own
+            "0x101E" | "0x101F" => Ok(DataType::PtypMultipleString(vec![])),
+            "0x1102" => Ok(DataType::PtypMultipleBinary(vec![])),
             _ => Err(DataTypeError::UnknownCode(code.to_string()).into()),
         }
     }
+
+    // Decodes the 8 bytes value of a fixed length property,
+    // as found in the property stream. Values shorter than
+    // 8 bytes are stored in the lowest bytes.
+    pub fn decode_fixed(value: &[u8; 8], code: &str) -> Result<DataType, Error> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(value);
+        let value = u64::from_le_bytes(bytes);
+        match code {
+            "0x0002" => Ok(DataType::PtypInteger16(value as i16)),
+            "0x0003" => Ok(DataType::PtypInteger32(value as i32)),
+            "0x0014" => Ok(DataType::PtypInteger64(value as i64)),
+            "0x000B" => Ok(DataType::PtypBoolean(value as u16 != 0)),
+            "0x0040" => Ok(DataType::PtypTime(value)),
+            "0x000A" => Ok(DataType::PtypErrorCode(value as u32)),
+            "0x0001" => Ok(DataType::PtypNull),
+            _ => Err(DataTypeError::UnknownCode(code.to_string()).into()),
+        }
+    }
+}
+
+fn decode_ptyperrorcode(buff: &[u8]) -> Result<DataType, Error> {
+    // 4 bytes, little-endian
+    let mut bytes = [0u8; 4];
+    for (byte, value) in bytes.iter_mut().zip(buff) {
+        *byte = *value;
+    }
+    Ok(DataType::PtypErrorCode(u32::from_le_bytes(bytes)))
 }
 
-fn decode_ptypbinary(buff: &Vec<u8>) -> Result<DataType, Error> {
+fn decode_ptypbinary(buff: &[u8]) -> Result<DataType, Error> {
     Ok(DataType::PtypBinary(buff.to_vec()))
 }
 
-fn decode_ptypstring(buff: &Vec<u8>) -> Result<DataType, Error> {
+fn decode_ptypmultipleinteger32(buff: &[u8]) -> Result<DataType, Error> {
+    // 4 bytes values, little-endian
+    Ok(DataType::PtypMultipleInteger32(
+        buff.chunks_exact(4)
+            .map(|value| i32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+            .collect(),
+    ))
+}
+
+// Utf16Encoding tells how a PtypString value was written.
+// Values are little-endian without a BOM, as MS-OXCDATA requires,
+// but some third-party generators write a BOM or big-endian values.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Utf16Encoding {
+    LittleEndian,
+    LittleEndianBom,
+    BigEndianBom,
+    // No BOM, told by the ASCII characters read as CJK ones
+    BigEndian,
+}
+
+impl std::fmt::Display for Utf16Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Utf16Encoding::LittleEndian => "UTF-16LE",
+            Utf16Encoding::LittleEndianBom => "UTF-16LE with BOM",
+            Utf16Encoding::BigEndianBom => "UTF-16BE with BOM",
+            Utf16Encoding::BigEndian => "UTF-16BE",
+        })
+    }
+}
+
+// Bytes of a value looked at to tell its byte order
+pub(crate) const UTF16_SAMPLE_SIZE: usize = 1024;
+
+// Encoding of a PtypString value, from its BOM or else from the start
+// of its characters: big-endian ASCII read as little-endian gives
+// characters such as U+4800, whose low byte is zero.
+pub fn utf16_encoding(buff: &[u8]) -> Utf16Encoding {
+    match buff {
+        [0xFF, 0xFE, ..] => return Utf16Encoding::LittleEndianBom,
+        [0xFE, 0xFF, ..] => return Utf16Encoding::BigEndianBom,
+        _ => {}
+    }
+    let sample = &buff[..buff.len().min(UTF16_SAMPLE_SIZE)];
+    let units: Vec<&[u8]> = sample
+        .chunks_exact(2)
+        .filter(|unit| unit != &[0, 0])
+        .collect();
+    let swapped = units
+        .iter()
+        .filter(|unit| unit[0] == 0 && unit[1] < 0x80)
+        .count();
+    if units.len() >= 2 && swapped * 4 >= units.len() * 3 {
+        Utf16Encoding::BigEndian
+    } else {
+        Utf16Encoding::LittleEndian
+    }
+}
+
+// Code units of a PtypString value. Byte sequence is in little-endian
+// format, unless told otherwise by a BOM or by its characters.
+fn utf16_units(buff: &[u8]) -> Vec<u16> {
+    let encoding = utf16_encoding(buff);
+    let (buff, big_endian) = match encoding {
+        Utf16Encoding::LittleEndian => (buff, false),
+        Utf16Encoding::LittleEndianBom => (&buff[2..], false),
+        Utf16Encoding::BigEndianBom => (&buff[2..], true),
+        Utf16Encoding::BigEndian => (buff, true),
+    };
+    // A last odd byte is half a code unit, left out
+    // as when the value is cut, see PtypDecoder::read_value
+    buff.chunks_exact(2)
+        .map(|duo| {
+            let duo = [duo[0], duo[1]];
+            if big_endian {
+                u16::from_be_bytes(duo)
+            } else {
+                u16::from_le_bytes(duo)
+            }
+        })
+        .collect()
+}
+
+// Number of code units of a PtypString value which are not valid
+// UTF-16, such as unpaired surrogates, each decoded as U+FFFD.
+pub(crate) fn invalid_utf16_count(buff: &[u8]) -> usize {
+    char::decode_utf16(utf16_units(buff))
+        .filter(|c| c.is_err())
+        .count()
+}
+
+fn decode_ptypstring(buff: &[u8]) -> Result<DataType, Error> {
     // PtypString
-    // Byte sequence is in little-endian format
-    // Use UTF-16 String decode
-    let mut buff_iter = buff.iter();
-    let mut buffu16 = Vec::new();
-    loop {
-        let c1 = match buff_iter.next() {
-            Some(c) => c,
-            None => {
-                break;
-            },
-        };
-        let duo = match buff_iter.next() {
-            Some(c2) => [*c1, *c2],
-            None => [*c1, 0_u8],
-        };
-        buffu16.push(u16::from_le_bytes(duo));
+    // Use UTF-16 String decode, replacing invalid
+    // code units, see ParseWarning::ReplacementCharacters
+    Ok(DataType::PtypString(String::from_utf16_lossy(&utf16_units(buff))))
+}
+
+fn decode_ptypstring8(buff: &[u8], code_page: Option<i64>) -> Result<DataType, Error> {
+    // PtypString8
+    // Characters of the code page of the message, see decode_string8
+    Ok(DataType::PtypString(decode_string8(buff, code_page)))
+}
+
+// Text of single byte characters, trailing nulls left out,
+// read as UTF-8 if valid or else as Latin-1.
+fn decode_single_byte(buff: &[u8]) -> String {
+    let buff = match buff.iter().rposition(|&c| c != 0) {
+        Some(end) => &buff[..=end],
+        None => &[],
+    };
+    match std::str::from_utf8(buff) {
+        Ok(decoded) => decoded.to_string(),
+        Err(_) => buff.iter().map(|&c| c as char).collect(),
     }
-    match String::from_utf16(&buffu16) {
-        // Remove all terminated null character
-        Ok(decoded) => Ok(DataType::PtypString(decoded)),
-        Err(err) => Err(DataTypeError::Utf16Err(err).into()),
+}
+
+// Code page identifiers of Unicode encodings.
+// Refer to MS-OXCMSG 2.2.1.56.4 and the Windows code page list
+const CP_UTF16LE: i64 = 1200;
+const CP_UTF16BE: i64 = 1201;
+const CP_UTF8: i64 = 65001;
+
+// Labels of the encodings of the other Windows code pages
+// found in messages. ISO-8859-1 is read as windows-1252,
+// its superset, as browsers do.
+const CODE_PAGE_LABELS: [(i64, &str); 37] = [
+    (CP_UTF8, "utf-8"),
+    (866, "ibm866"),
+    (874, "windows-874"),
+    (932, "shift_jis"),
+    (936, "gbk"),
+    (949, "euc-kr"),
+    (950, "big5"),
+    (1250, "windows-1250"),
+    (1251, "windows-1251"),
+    (1252, "windows-1252"),
+    (1253, "windows-1253"),
+    (1254, "windows-1254"),
+    (1255, "windows-1255"),
+    (1256, "windows-1256"),
+    (1257, "windows-1257"),
+    (1258, "windows-1258"),
+    (10000, "macintosh"),
+    (10007, "x-mac-cyrillic"),
+    (20866, "koi8-r"),
+    (20932, "euc-jp"),
+    (20936, "gb2312"),
+    (21866, "koi8-u"),
+    (28591, "iso-8859-1"),
+    (28592, "iso-8859-2"),
+    (28593, "iso-8859-3"),
+    (28594, "iso-8859-4"),
+    (28595, "iso-8859-5"),
+    (28596, "iso-8859-6"),
+    (28597, "iso-8859-7"),
+    (28598, "iso-8859-8"),
+    (28599, "iso-8859-9"),
+    (28603, "iso-8859-13"),
+    (28605, "iso-8859-15"),
+    (50220, "iso-2022-jp"),
+    (51932, "euc-jp"),
+    (51949, "euc-kr"),
+    (54936, "gb18030"),
+];
+
+// Encoding of a Windows code page, if known.
+pub(crate) fn code_page_encoding(code_page: i64) -> Option<&'static Encoding> {
+    match code_page {
+        CP_UTF16LE => Some(UTF_16LE),
+        CP_UTF16BE => Some(UTF_16BE),
+        _ => CODE_PAGE_LABELS
+            .iter()
+            .find(|(known, _)| *known == code_page)
+            .and_then(|(_, label)| Encoding::for_label(label.as_bytes())),
     }
 }
 
+// Text of a PtypString8 value, written in the code page of the
+// message, trailing nulls left out. Without a known code page,
+// or with a Unicode one, which does not apply to 8-bit strings,
+// it is read as UTF-8 if valid or else as Latin-1.
+pub(crate) fn decode_string8(buff: &[u8], code_page: Option<i64>) -> String {
+    match code_page.and_then(code_page_encoding) {
+        Some(encoding) if encoding != UTF_16LE && encoding != UTF_16BE => encoding
+            .decode_without_bom_handling(buff)
+            .0
+            .trim_end_matches('\0')
+            .to_string(),
+        _ => decode_single_byte(buff),
+    }
+}
+
+// Text of a binary value written in a code page, such as the
+// HTML body. Other than Unicode code pages are read as PtypString8 is.
+pub(crate) fn decode_code_page(buff: &[u8], code_page: Option<i64>) -> String {
+    let units = |big_endian: bool| -> Vec<u16> {
+        buff.chunks_exact(2)
+            .map(|c| {
+                if big_endian {
+                    u16::from_be_bytes([c[0], c[1]])
+                } else {
+                    u16::from_le_bytes([c[0], c[1]])
+                }
+            })
+            .collect()
+    };
+    let decoded = match code_page {
+        Some(CP_UTF8) => String::from_utf8_lossy(buff).into_owned(),
+        Some(CP_UTF16LE) => String::from_utf16_lossy(&units(false)),
+        Some(CP_UTF16BE) => String::from_utf16_lossy(&units(true)),
+        _ => return decode_string8(buff, code_page),
+    };
+    // Leaving out a BOM, if any
+    decoded
+        .trim_start_matches('\u{FEFF}')
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+// Whether an ANSI value is what the Unicode value becomes once
+// converted, characters missing from the code page being
+// replaced by question marks.
+pub fn is_ansi_variant(unicode: &str, ansi: &str) -> bool {
+    unicode.chars().count() == ansi.chars().count()
+        && unicode
+            .chars()
+            .zip(ansi.chars())
+            .all(|(u, a)| u == a || a == '?')
+}
+
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
-    use super::{DataType, PtypDecoder, decode_ptypstring};
+    use super::{
+        decode_code_page, decode_ptypmultipleinteger32, decode_ptyperrorcode, decode_ptypstring,
+        decode_ptypstring8, decode_string8, element_type_code, invalid_utf16_count, multiple_value,
+        is_ansi_variant,
+        is_known_type, utf16_encoding, DataType, PtypDecoder, Utf16Encoding,
+    };
     use crate::ole::Reader;
 
     #[test]
@@ -83,7 +550,7 @@ mod tests {
         let entry = parser.iterate().next().unwrap();
 
         let mut slice = parser.get_entry_slice(entry).unwrap();
-        let res = PtypDecoder::decode(&mut slice, "1234");
+        let res = PtypDecoder::decode(&mut slice, "1234", usize::MAX, None, None);
         assert_eq!(res.is_err(), true);
         let err = res.unwrap_err();
         assert_eq!(
@@ -92,6 +559,151 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_decode_fixed() {
+        let value = [0x2A, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF];
+        assert_eq!(
+            PtypDecoder::decode_fixed(&value, "0x0003").unwrap(),
+            DataType::PtypInteger32(42)
+        );
+        assert_eq!(
+            PtypDecoder::decode_fixed(&value, "0x0002").unwrap(),
+            DataType::PtypInteger16(42)
+        );
+        assert_eq!(
+            PtypDecoder::decode_fixed(&[1, 0, 0, 0, 0, 0, 0, 0], "0x000B").unwrap(),
+            DataType::PtypBoolean(true)
+        );
+        assert_eq!(
+            PtypDecoder::decode_fixed(&[0xFF; 8], "0x0014").unwrap(),
+            DataType::PtypInteger64(-1)
+        );
+        assert_eq!(DataType::PtypInteger32(42).as_i64(), Some(42));
+        assert!(PtypDecoder::decode_fixed(&value, "0x0005").is_err());
+    }
+
+    #[test]
+    fn test_error_code_and_null() {
+        // MAPI_E_NOT_FOUND
+        let value = [0x0F, 0x01, 0x04, 0x80, 0, 0, 0, 0];
+        let decoded = PtypDecoder::decode_fixed(&value, "0x000A").unwrap();
+        assert_eq!(decoded, DataType::PtypErrorCode(0x8004010F));
+        assert_eq!(String::from(&decoded), "0x8004010F");
+        assert_eq!(
+            decode_ptyperrorcode(&value[..4]).unwrap(),
+            DataType::PtypErrorCode(0x8004010F)
+        );
+        assert_eq!(
+            PtypDecoder::decode_fixed(&[0; 8], "0x0001").unwrap(),
+            DataType::PtypNull
+        );
+        assert_eq!(String::from(&DataType::PtypNull), "");
+        assert!(DataType::PtypNull.is_empty());
+        assert!(!decoded.is_empty());
+    }
+
+    #[test]
+    fn test_is_empty() {
+        assert!(DataType::PtypString(String::new()).is_empty());
+        assert!(!DataType::PtypString("a".to_string()).is_empty());
+        assert!(DataType::PtypBinary(vec![]).is_empty());
+        assert!(!DataType::PtypInteger32(0).is_empty());
+        assert!(!DataType::PtypBoolean(false).is_empty());
+        assert!(DataType::PtypMultipleString(vec![String::new()]).is_empty());
+        assert!(!DataType::PtypMultipleInteger32(vec![0]).is_empty());
+        assert!(DataType::PtypMultipleBinary(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_multiple_values() {
+        let decoded = decode_ptypmultipleinteger32(&[1, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 7]);
+        assert_eq!(decoded.unwrap(), DataType::PtypMultipleInteger32(vec![1, -1]));
+
+        let strings = ["Red", "Blue"].map(|s| DataType::PtypString(s.to_string()));
+        let value = multiple_value("0x101F", strings.iter().cloned()).unwrap();
+        assert_eq!(String::from(&value), "Red; Blue");
+        let binaries = [vec![0xAB], vec![0x01, 0x02]].map(DataType::PtypBinary);
+        let value = multiple_value("0x1102", binaries.iter().cloned()).unwrap();
+        assert_eq!(String::from(&value), "ab; 0102");
+        let elements = [DataType::PtypNull, DataType::PtypString("Blue".to_string())];
+        let value = multiple_value("0x101F", elements.iter().cloned()).unwrap();
+        assert_eq!(String::from(&value), "; Blue");
+        assert_eq!(element_type_code("0x101E"), Some("0x001E"));
+        assert_eq!(element_type_code("0x1003"), None);
+    }
+
+    #[test]
+    fn test_decode_limited() {
+        let path = "data/test_email.msg";
+        let parser = Reader::from_path(path).unwrap();
+
+        let entry_of_a_ptypstring = parser.iterate().nth(125).unwrap();
+        let mut slice = parser.get_entry_slice(entry_of_a_ptypstring).unwrap();
+        let decoded = PtypDecoder::decode(&mut slice, "0x001F", 13, None, None).unwrap();
+        assert_eq!(decoded, DataType::PtypString("marirs".to_string()));
+
+        let mut slice = parser.get_entry_slice(entry_of_a_ptypstring).unwrap();
+        let decoded = PtypDecoder::decode(&mut slice, "0x0102", 4, None, None).unwrap();
+        assert_eq!(decoded, DataType::PtypBinary(vec![0x6D, 0, 0x61, 0]));
+    }
+
+    #[test]
+    fn test_decode_empty_and_odd() {
+        use crate::testing::Writer;
+
+        let decode = |code: &str, bytes: &[u8]| {
+            let mut writer = Writer::new();
+            writer.add_stream("value", bytes);
+            let bytes = writer.to_bytes();
+            let parser = Reader::new(&bytes[..]).unwrap();
+            let entry = parser.iterate().find(|entry| entry.name() == "value").unwrap();
+            let mut slice = parser.get_entry_slice(entry).unwrap();
+            PtypDecoder::decode(&mut slice, code, usize::MAX, None, None).unwrap()
+        };
+        let string = |value: &str| DataType::PtypString(value.to_string());
+        let empty = [
+            ("0x001F", string("")),
+            ("0x001E", string("")),
+            ("0x0102", DataType::PtypBinary(vec![])),
+            ("0x000A", DataType::PtypErrorCode(0)),
+            ("0x0001", DataType::PtypNull),
+            ("0x0002", DataType::PtypInteger16(0)),
+            ("0x0003", DataType::PtypInteger32(0)),
+            ("0x0014", DataType::PtypInteger64(0)),
+            ("0x000B", DataType::PtypBoolean(false)),
+            ("0x0040", DataType::PtypTime(0)),
+            ("0x1003", DataType::PtypMultipleInteger32(vec![])),
+            ("0x101F", DataType::PtypMultipleString(vec![])),
+            ("0x101E", DataType::PtypMultipleString(vec![])),
+            ("0x1102", DataType::PtypMultipleBinary(vec![])),
+        ];
+        for (code, value) in empty {
+            assert_eq!(decode(code, &[]), value, "{}", code);
+        }
+
+        let odd = [
+            // Half a code unit is left out
+            ("0x001F", string("ab")),
+            ("0x001E", string("a\0b\0c")),
+            ("0x0102", DataType::PtypBinary(vec![0x61, 0, 0x62, 0, 0x63])),
+            ("0x000A", DataType::PtypErrorCode(0x620061)),
+            ("0x0003", DataType::PtypInteger32(0x620061)),
+            ("0x000B", DataType::PtypBoolean(true)),
+            ("0x1003", DataType::PtypMultipleInteger32(vec![0x620061])),
+        ];
+        for (code, value) in odd {
+            assert_eq!(decode(code, &[0x61, 0, 0x62, 0, 0x63]), value, "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_is_known_type() {
+        assert!(is_known_type("0x001F"));
+        assert!(is_known_type("0x101F"));
+        assert!(is_known_type("0x0048"));
+        assert!(!is_known_type("0x1234"));
+    }
+
     #[test]
     fn test_ptypstring() {
         let path = "data/test_email.msg";
@@ -99,7 +711,7 @@ mod tests {
 
         let entry_of_a_ptypstring = parser.iterate().nth(125).unwrap();
         let mut ptypstring_slice = parser.get_entry_slice(entry_of_a_ptypstring).unwrap();
-        let ptypstring_decoded = PtypDecoder::decode(&mut ptypstring_slice, "0x001F").unwrap();
+        let ptypstring_decoded = PtypDecoder::decode(&mut ptypstring_slice, "0x001F", usize::MAX, None, None).unwrap();
         assert_eq!(
             ptypstring_decoded,
             DataType::PtypString("marirs@outlook.com".to_string())
@@ -134,4 +746,97 @@ mod tests {
         assert_eq!(s, DataType::PtypString("Réponse".to_string()));
         assert_ne!(s, DataType::PtypString("Réponse".to_string()));
     }
+
+    #[test]
+    fn test_decode_ptypstring_invalid() {
+        // "a", unpaired high surrogate, "b", unpaired low surrogate
+        let raw = [0x61, 0x00, 0x00, 0xD8, 0x62, 0x00, 0x00, 0xDC];
+        assert_eq!(
+            decode_ptypstring(&raw).unwrap(),
+            DataType::PtypString("a\u{FFFD}b\u{FFFD}".to_string())
+        );
+        assert_eq!(invalid_utf16_count(&raw), 2);
+        // A genuine replacement character is not counted
+        assert_eq!(invalid_utf16_count(&[0xFD, 0xFF, 0x61, 0x00]), 0);
+    }
+
+    #[test]
+    fn test_decode_ptypstring_byte_order() {
+        let utf16 = |text: &str, big_endian: bool| -> Vec<u8> {
+            text.encode_utf16()
+                .flat_map(|c| if big_endian { c.to_be_bytes() } else { c.to_le_bytes() })
+                .collect()
+        };
+        let cases = [
+            (utf16("\u{FEFF}Hello", false), Utf16Encoding::LittleEndianBom),
+            (utf16("\u{FEFF}Hello", true), Utf16Encoding::BigEndianBom),
+            (utf16("Hello, world\0", true), Utf16Encoding::BigEndian),
+            (utf16("Hello, world\0", false), Utf16Encoding::LittleEndian),
+        ];
+        for (bytes, encoding) in cases {
+            assert_eq!(utf16_encoding(&bytes), encoding);
+            let expected = if encoding == Utf16Encoding::BigEndian
+                || encoding == Utf16Encoding::LittleEndian
+            {
+                "Hello, world\0"
+            } else {
+                "Hello"
+            };
+            assert_eq!(
+                decode_ptypstring(&bytes).unwrap(),
+                DataType::PtypString(expected.to_string())
+            );
+        }
+        // CJK text is little-endian
+        assert_eq!(utf16_encoding(&utf16("电子邮件", false)), Utf16Encoding::LittleEndian);
+    }
+
+    #[test]
+    fn test_decode_ptypstring8() {
+        assert_eq!(
+            decode_ptypstring8(b"Hello\0", None).unwrap(),
+            DataType::PtypString("Hello".to_string())
+        );
+        // Not UTF-8, read as Latin-1
+        assert_eq!(
+            decode_ptypstring8(&[0x52, 0xE9, 0x70, 0x6F, 0x6E, 0x73, 0x65], None).unwrap(),
+            DataType::PtypString("Réponse".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_string8() {
+        // "Ответ" in windows-1251, "返信" in Shift_JIS and "回复" in GBK
+        assert_eq!(decode_string8(b"\xCE\xF2\xE2\xE5\xF2\0", Some(1251)), "Ответ");
+        assert_eq!(decode_string8(b"\x95\xD4\x90\x4D", Some(932)), "返信");
+        assert_eq!(decode_string8(b"\xBB\xD8\xB8\xB4", Some(936)), "回复");
+        // In windows-1252, 0x80 is the euro sign, not a C1 control
+        assert_eq!(decode_string8(b"5 \x80", Some(1252)), "5 €");
+        assert_eq!(decode_string8(b"5 \x80", Some(28591)), "5 €");
+        // Unknown and Unicode code pages fall back to UTF-8 or Latin-1
+        assert_eq!(decode_string8(b"R\xE9ponse", Some(1)), "Réponse");
+        assert_eq!(decode_string8("Réponse".as_bytes(), Some(1200)), "Réponse");
+    }
+
+    #[test]
+    fn test_decode_code_page() {
+        let html = "<p>Réponse</p>";
+        assert_eq!(decode_code_page(html.as_bytes(), Some(65001)), html);
+        let utf16: Vec<u8> = html.encode_utf16().chain([0]).flat_map(u16::to_le_bytes).collect();
+        assert_eq!(decode_code_page(&utf16, Some(1200)), html);
+        let mut utf8 = b"\xEF\xBB\xBF".to_vec();
+        utf8.extend_from_slice(html.as_bytes());
+        assert_eq!(decode_code_page(&utf8, Some(65001)), html);
+        // Single byte code pages, or none
+        assert_eq!(decode_code_page(b"<p>R\xE9ponse</p>\0", Some(1252)), html);
+        assert_eq!(decode_code_page(html.as_bytes(), None), html);
+    }
+
+    #[test]
+    fn test_is_ansi_variant() {
+        assert!(is_ansi_variant("Réponse", "Réponse"));
+        assert!(is_ansi_variant("Ответ: Hi", "?????: Hi"));
+        assert!(!is_ansi_variant("Invoice", "Invoice paid"));
+        assert!(!is_ansi_variant("Invoice", "Involce"));
+    }
 }
@@ -0,0 +1,169 @@
+use std::{collections::BTreeSet, fs::File, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ole;
+
+use super::{
+    error::Error,
+    storage::{Properties, Storages},
+};
+
+// ChangeKind tells how a property differs between two parses.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+// PropertyChange is a property which differs between two copies
+// of a message. Values are as in Outlook, binary ones hex encoded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PropertyChange {
+    pub storage: String,     // "" for the message, else e.g. "__recip_version1.0_#00000001"
+    pub name: String,        // e.g. "Subject"
+    pub kind: ChangeKind,
+    pub old: Option<String>, // None when added
+    pub new: Option<String>, // None when removed
+}
+
+impl Storages {
+    // Properties added, removed or changed in other, storage by
+    // storage. Recipients and attachments are matched by the number
+    // of their storage, so that removing one does not shift the
+    // others. Embedded messages are left out.
+    pub fn diff(&self, other: &Storages) -> Vec<PropertyChange> {
+        let mut changes = vec![];
+        Self::diff_properties("", &self.root, &other.root, &mut changes);
+        let groups = [
+            (
+                "__recip_version1.0_#",
+                (&self.recipient_ids, &self.recipients),
+                (&other.recipient_ids, &other.recipients),
+            ),
+            (
+                "__attach_version1.0_#",
+                (&self.attachment_ids, &self.attachments),
+                (&other.attachment_ids, &other.attachments),
+            ),
+        ];
+        let empty = Properties::new();
+        for (prefix, (old_ids, old), (new_ids, new)) in groups.iter() {
+            let ids: BTreeSet<u32> = old_ids.iter().chain(new_ids.iter()).copied().collect();
+            for id in ids {
+                Self::diff_properties(
+                    &format!("{}{:08X}", prefix, id),
+                    Self::by_id(old_ids, old, id).unwrap_or(&empty),
+                    Self::by_id(new_ids, new, id).unwrap_or(&empty),
+                    &mut changes,
+                );
+            }
+        }
+        changes
+    }
+
+    // Properties of the storage numbered id.
+    fn by_id<'a>(ids: &[u32], storages: &'a [Properties], id: u32) -> Option<&'a Properties> {
+        let idx = ids.iter().position(|x| *x == id)?;
+        storages.get(idx)
+    }
+
+    fn diff_properties(
+        storage: &str,
+        old: &Properties,
+        new: &Properties,
+        changes: &mut Vec<PropertyChange>,
+    ) {
+        let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+        for name in names {
+            let (kind, old, new) = match (old.get(name), new.get(name)) {
+                (Some(old), Some(new)) if old == new => continue,
+                (Some(old), Some(new)) => (ChangeKind::Changed, Some(old), Some(new)),
+                (Some(old), None) => (ChangeKind::Removed, Some(old), None),
+                (None, Some(new)) => (ChangeKind::Added, None, Some(new)),
+                (None, None) => continue,
+            };
+            changes.push(PropertyChange {
+                storage: storage.to_string(),
+                name: name.to_string(),
+                kind,
+                old: old.map(String::from),
+                new: new.map(String::from),
+            });
+        }
+    }
+}
+
+// MessageDiff lists the properties which differ between two
+// copies of a message, e.g. to tell whether the "same" message
+// found in two mailboxes was tampered with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageDiff {
+    pub changes: Vec<PropertyChange>, // See Storages::diff
+}
+
+impl MessageDiff {
+    fn create(old: &ole::Reader, new: &ole::Reader) -> Self {
+        Self {
+            changes: Storages::processed(old).diff(&Storages::processed(new)),
+        }
+    }
+
+    pub fn from_paths<P: AsRef<Path>, Q: AsRef<Path>>(old: P, new: Q) -> Result<Self, Error> {
+        let old = ole::Reader::new(File::open(old)?)?;
+        let new = ole::Reader::new(File::open(new)?)?;
+        Ok(Self::create(&old, &new))
+    }
+
+    pub fn from_slices(old: &[u8], new: &[u8]) -> Result<Self, Error> {
+        Ok(Self::create(&ole::Reader::new(old)?, &ole::Reader::new(new)?))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChangeKind, MessageDiff, PropertyChange};
+    use crate::testing::MessageBuilder;
+
+    #[test]
+    fn test_diff() {
+        let diff = MessageDiff::from_paths("data/unicode.msg", "data/unicode.msg").unwrap();
+        assert!(diff.is_empty());
+
+        let old = MessageBuilder::new()
+            .subject("Invoice")
+            .body("Pay to account 1234")
+            .recipient("Jane Doe", "jane@example.com")
+            .recipient("Bob", "bob@example.com")
+            .build();
+        let new = MessageBuilder::new()
+            .subject("Invoice")
+            .body("Pay to account 9876")
+            .recipient("Jane Doe", "jane@example.com")
+            .attachment("notes.txt", "text/plain", b"notes")
+            .build();
+        let diff = MessageDiff::from_slices(&old, &new).unwrap();
+        assert_eq!(
+            diff.changes[0],
+            PropertyChange {
+                storage: String::new(),
+                name: "Body".to_string(),
+                kind: ChangeKind::Changed,
+                old: Some("Pay to account 1234".to_string()),
+                new: Some("Pay to account 9876".to_string()),
+            }
+        );
+        let storages: Vec<(&str, ChangeKind)> = diff.changes[1..]
+            .iter()
+            .map(|change| (change.storage.as_str(), change.kind))
+            .collect();
+        assert!(storages.contains(&("__recip_version1.0_#00000001", ChangeKind::Removed)));
+        assert!(storages.contains(&("__attach_version1.0_#00000000", ChangeKind::Added)));
+        assert!(storages.iter().all(|(storage, _)| *storage != "__recip_version1.0_#00000000"));
+    }
+}
@@ -0,0 +1,828 @@
+use std::collections::BTreeSet;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Datelike;
+
+use super::{
+    error::Error,
+    headers::content_id,
+    outlook::{Attachment, Outlook, Person, TransportHeaders},
+    time::Timestamp,
+};
+
+// Encoded lines are at most 76 characters long.
+// Refer to RFC 2045 6.7 and 6.8
+const MAX_LINE_LEN: usize = 76;
+
+// Lines longer than this cannot be sent unencoded.
+// Refer to RFC 5322 2.1.1
+const MAX_UNENCODED_LINE_LEN: usize = 998;
+
+// Bytes of text per encoded word, so that each stays within
+// 75 characters once base64 encoded. Refer to RFC 2047 2
+const MAX_ENCODED_WORD_BYTES: usize = 45;
+
+// Content-Type of attachments without "AttachMimeTag"
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+const MSG_CONTENT_TYPE: &str = "application/vnd.ms-outlook";
+
+// Characters which cannot appear in a display name unquoted.
+// Refer to RFC 5322 3.2.3
+const SPECIALS: &str = "()<>[]:;@\\,.\"";
+
+// TransferEncoding refers to the Content-Transfer-Encoding of a body part.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferEncoding {
+    SevenBit,
+    QuotedPrintable,
+    Base64,
+}
+
+impl TransferEncoding {
+    pub fn from_header(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "7bit" => Some(TransferEncoding::SevenBit),
+            "quoted-printable" => Some(TransferEncoding::QuotedPrintable),
+            "base64" => Some(TransferEncoding::Base64),
+            _ => None,
+        }
+    }
+
+    pub fn as_header(&self) -> &'static str {
+        match self {
+            TransferEncoding::SevenBit => "7bit",
+            TransferEncoding::QuotedPrintable => "quoted-printable",
+            TransferEncoding::Base64 => "base64",
+        }
+    }
+
+    // Encoding picked regardless of any header hint: 7bit when
+    // the body allows it, quoted-printable for mostly ASCII text
+    // and base64 otherwise.
+    pub fn canonical(body: &[u8]) -> Self {
+        let is_7bit = body.iter().all(|&c| c < 0x80 && c != 0)
+            && body
+                .split(|&c| c == b'\n')
+                .all(|line| line.len() <= MAX_UNENCODED_LINE_LEN);
+        if is_7bit {
+            return TransferEncoding::SevenBit;
+        }
+        let non_ascii = body.iter().filter(|&&c| c >= 0x80).count();
+        if non_ascii * 4 < body.len() {
+            TransferEncoding::QuotedPrintable
+        } else {
+            TransferEncoding::Base64
+        }
+    }
+
+    pub fn encode(&self, body: &[u8]) -> String {
+        match self {
+            TransferEncoding::SevenBit => String::from_utf8_lossy(body).into_owned(),
+            TransferEncoding::QuotedPrintable => encode_quoted_printable(body),
+            TransferEncoding::Base64 => encode_base64(body),
+        }
+    }
+}
+
+// EmlOptions controls how a message is written as EML.
+//
+// Options are set with chained calls:
+// EmlOptions::new().canonical_encoding(true)
+#[derive(Debug, Clone, Default)]
+pub struct EmlOptions {
+    pub(crate) canonical_encoding: bool,
+}
+
+impl EmlOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Ignores the Content-Transfer-Encoding of the transport
+    // headers and encodes bodies canonically.
+    pub fn canonical_encoding(mut self, value: bool) -> Self {
+        self.canonical_encoding = value;
+        self
+    }
+
+    // Encoding of the body of a message with these headers.
+    // The original encoding is kept where the transport headers
+    // record it, so that the body is written back as it was sent.
+    // Headers of multipart messages only describe the top level
+    // part, which says nothing of the body part.
+    pub fn body_encoding(&self, headers: &TransportHeaders, body: &[u8]) -> TransferEncoding {
+        let is_multipart = headers
+            .content_type
+            .trim_start()
+            .to_ascii_lowercase()
+            .starts_with("multipart/");
+        let hint = TransferEncoding::from_header(&headers.content_transfer_encoding);
+        match hint {
+            Some(encoding) if !self.canonical_encoding && !is_multipart => {
+                // 7bit cannot hold the body any longer
+                if encoding == TransferEncoding::SevenBit
+                    && TransferEncoding::canonical(body) != TransferEncoding::SevenBit
+                {
+                    return TransferEncoding::canonical(body);
+                }
+                encoding
+            }
+            _ => TransferEncoding::canonical(body),
+        }
+    }
+}
+
+// BodyPart holds the MIME headers of the body of a message.
+// Exchange keeps the Content-ID and Content-Location of the body
+// when converting a multipart/related message to MAPI, so that
+// the parts referring to it can be related again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodyPart {
+    pub content_type: String,
+    pub encoding: TransferEncoding,
+    pub content_id: String,       // "BodyContentId"
+    pub content_location: String, // "BodyContentLocation"
+}
+
+impl BodyPart {
+    pub fn new(outlook: &Outlook, content_type: &str, encoding: TransferEncoding) -> Self {
+        Self {
+            content_type: content_type.to_string(),
+            encoding,
+            content_id: outlook.body_content_id.trim().to_string(),
+            content_location: outlook.body_content_location.trim().to_string(),
+        }
+    }
+
+    // Content-ID in angle brackets. Refer to RFC 2392
+    fn angle_content_id(&self) -> Option<String> {
+        if self.content_id.is_empty() {
+            return None;
+        }
+        let id = self.content_id.trim_start_matches('<').trim_end_matches('>');
+        Some(format!("<{}>", id))
+    }
+
+    pub fn headers(&self) -> String {
+        let mut headers = format!(
+            "Content-Type: {}\r\nContent-Transfer-Encoding: {}\r\n",
+            self.content_type,
+            self.encoding.as_header()
+        );
+        if let Some(id) = self.angle_content_id() {
+            headers.push_str(&format!("Content-ID: {}\r\n", id));
+        }
+        if !self.content_location.is_empty() {
+            headers.push_str(&format!("Content-Location: {}\r\n", self.content_location));
+        }
+        headers
+    }
+
+    // Parameters of the multipart/related part holding the body,
+    // whose start is the body itself. Refer to RFC 2387 3
+    pub fn related_parameters(&self, boundary: &str) -> String {
+        let mut parameters = format!(
+            "type=\"{}\"; boundary=\"{}\"",
+            self.content_type
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim(),
+            boundary
+        );
+        if let Some(id) = self.angle_content_id() {
+            parameters.push_str(&format!("; start=\"{}\"", id));
+        }
+        parameters
+    }
+}
+
+// Part is a MIME entity being written: its header fields,
+// each ending with CRLF, and its encoded body.
+struct Part {
+    headers: String,
+    body: String,
+}
+
+impl Part {
+    // A multipart entity of the parts, whose boundary appears in none
+    // of them. parameters gives the parameters of its Content-Type
+    // from the boundary. Refer to RFC 2046 5.1
+    fn multipart<F>(subtype: &str, parts: Vec<Part>, parameters: F) -> Self
+    where
+        F: Fn(&str) -> String,
+    {
+        let boundary = (0..)
+            .map(|n| format!("=_msg_parser_{}", n))
+            .find(|boundary| {
+                parts
+                    .iter()
+                    .all(|part| !part.headers.contains(boundary) && !part.body.contains(boundary))
+            })
+            .unwrap_or_default();
+        let mut body = String::new();
+        for part in parts {
+            body.push_str(&format!("--{}\r\n{}\r\n{}\r\n", boundary, part.headers, part.body));
+        }
+        body.push_str(&format!("--{}--\r\n", boundary));
+        Self {
+            headers: format!("Content-Type: multipart/{}; {}\r\n", subtype, parameters(&boundary)),
+            body,
+        }
+    }
+}
+
+// Writes the message as EML, see Outlook::to_eml.
+pub(crate) fn write_message(outlook: &Outlook, options: &EmlOptions) -> Result<Vec<u8>, Error> {
+    let transport = match outlook.transport_text() {
+        "" => outlook
+            .raw_headers
+            .as_ref()
+            .and_then(|raw| raw.text().ok())
+            .unwrap_or_default(),
+        text => text.to_string(),
+    };
+    let mut fields = transport_fields(&transport);
+    if fields.is_empty() {
+        fields = synthesized_fields(outlook);
+    }
+    let message = message_part(outlook, options)?;
+    let eml = format!("{}MIME-Version: 1.0\r\n{}\r\n{}", fields, message.headers, message.body);
+    Ok(eml.into_bytes())
+}
+
+// Whether a header field describes the MIME structure of the
+// message as sent, which is written anew.
+fn is_mime_field(name: &str) -> bool {
+    let name = name.trim().to_ascii_lowercase();
+    name == "mime-version" || name.starts_with("content-")
+}
+
+// Fields of the transport headers, but for the MIME ones,
+// each with its continuation lines and ending with CRLF.
+fn transport_fields(text: &str) -> String {
+    let mut fields = String::new();
+    let mut keep = false;
+    for line in text.trim_start().lines() {
+        if line.trim().is_empty() {
+            break;
+        }
+        if !line.starts_with([' ', '\t']) {
+            // Lines which are not fields, such as the "Microsoft Mail
+            // Internet Headers" banner, are left out
+            keep = line.split_once(':').is_some_and(|(name, _)| {
+                !name.is_empty() && !name.contains(' ') && !is_mime_field(name)
+            });
+        }
+        if keep {
+            fields.push_str(line);
+            fields.push_str("\r\n");
+        }
+    }
+    fields
+}
+
+// Fields written from the properties of messages without
+// transport headers, such as drafts.
+fn synthesized_fields(outlook: &Outlook) -> String {
+    let mut fields = String::new();
+    let mut push = |name: &str, value: String| {
+        if !value.is_empty() {
+            fields.push_str(&format!("{}: {}\r\n", name, value));
+        }
+    };
+    // Date is required, so the time of delivery or else of creation
+    // stands in for that of sending. Years RFC 2822 cannot represent
+    // are passed over.
+    let creation = outlook.timestamps.get("CreationTime").and_then(Timestamp::to_utc);
+    let date = [outlook.sent_at, outlook.received_at, creation]
+        .iter()
+        .flatten()
+        .find(|date| (0..=9999).contains(&date.year()))
+        .copied();
+    if let Some(date) = date {
+        push("Date", date.to_rfc2822());
+    }
+    push("From", address_list(std::slice::from_ref(&outlook.sender)));
+    push("To", address_list(&outlook.to));
+    push("Cc", address_list(&outlook.cc));
+    push("Bcc", address_list(&outlook.bcc_recipients));
+    push("Subject", encode_text(&outlook.subject));
+    push("Message-ID", outlook.headers.message_id.trim().to_string());
+    push("In-Reply-To", outlook.in_reply_to.trim().to_string());
+    push("References", outlook.references.trim().to_string());
+    fields
+}
+
+// Addresses of a header field, one per line.
+fn address_list(persons: &[Person]) -> String {
+    persons
+        .iter()
+        .filter(|person| !person.email.trim().is_empty())
+        .map(|person| {
+            let (name, email) = (person.name.trim(), person.email.trim());
+            if name.is_empty() || name == email {
+                email.to_string()
+            } else {
+                format!("{} <{}>", display_name(name), email)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",\r\n ")
+}
+
+// Display name of an address, quoted or encoded as needed.
+fn display_name(name: &str) -> String {
+    if !name.is_ascii() {
+        return encode_text(name);
+    }
+    if name.contains(|c: char| SPECIALS.contains(c)) {
+        return format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""));
+    }
+    name.to_string()
+}
+
+// Text of a header field, as encoded words unless ASCII.
+// Line breaks are left out. Refer to RFC 2047
+fn encode_text(text: &str) -> String {
+    let text: String = text.chars().filter(|c| *c != '\r' && *c != '\n').collect();
+    if text.is_ascii() {
+        return text;
+    }
+    let mut words = vec![];
+    let mut word = String::new();
+    for c in text.chars() {
+        if word.len() + c.len_utf8() > MAX_ENCODED_WORD_BYTES {
+            words.push(std::mem::take(&mut word));
+        }
+        word.push(c);
+    }
+    words.push(word);
+    words
+        .iter()
+        .map(|word| format!("=?UTF-8?B?{}?=", STANDARD.encode(word)))
+        .collect::<Vec<String>>()
+        .join("\r\n ")
+}
+
+// Parameter of a header field, such as the filename of an attachment,
+// encoded when it is not a plain ASCII string. Refer to RFC 2231 4
+fn parameter(name: &str, value: &str) -> String {
+    if value.chars().all(|c| (' '..='~').contains(&c) && c != '"' && c != '\\') {
+        return format!("{}=\"{}\"", name, value);
+    }
+    let encoded: String = value
+        .bytes()
+        .map(|byte| match byte {
+            b'.' | b'-' | b'_' => (byte as char).to_string(),
+            _ if byte.is_ascii_alphanumeric() => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+    format!("{}*=UTF-8''{}", name, encoded)
+}
+
+// Text with CRLF line breaks, as MIME requires.
+fn crlf(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\n', "\r\n")
+}
+
+// Body of the message: its text and HTML bodies, the attachments
+// the HTML body shows and the other attachments, nested as
+//   multipart/mixed
+//     multipart/related
+//       multipart/alternative
+//         text/plain
+//         text/html
+//       inline attachments
+//     attachments
+// where parts with a single child are left out.
+fn message_part(outlook: &Outlook, options: &EmlOptions) -> Result<Part, Error> {
+    let html = outlook
+        .body_html
+        .as_deref()
+        .filter(|html| !html.trim().is_empty());
+    let text_part = |content_type: &str, text: &str, body_part: bool| {
+        let text = crlf(text);
+        let encoding = options.body_encoding(&outlook.headers, text.as_bytes());
+        let mut part = BodyPart::new(outlook, content_type, encoding);
+        // The Content-ID of the body belongs to the body shown
+        if !body_part {
+            part.content_id.clear();
+            part.content_location.clear();
+        }
+        Part {
+            headers: part.headers(),
+            body: encoding.encode(text.as_bytes()),
+        }
+    };
+    let (body, html_part) = match html {
+        Some(html) if outlook.body.trim().is_empty() => {
+            let part = text_part("text/html; charset=utf-8", html, true);
+            (part, true)
+        }
+        Some(html) => {
+            let parts = vec![
+                text_part("text/plain; charset=utf-8", &outlook.body, false),
+                text_part("text/html; charset=utf-8", html, true),
+            ];
+            let part = Part::multipart("alternative", parts, |boundary| {
+                format!("boundary=\"{}\"", boundary)
+            });
+            (part, false)
+        }
+        None => (text_part("text/plain; charset=utf-8", &outlook.body, true), false),
+    };
+
+    let inline = html.map_or(BTreeSet::new(), |html| inline_attachments(outlook, html));
+    let mut inline_parts = vec![];
+    let mut attachment_parts = vec![];
+    for (idx, attachment) in outlook.attachments.iter().enumerate() {
+        let is_inline = inline.contains(&idx);
+        if let Some(part) = attachment_part(attachment, is_inline, options)? {
+            if is_inline {
+                inline_parts.push(part);
+            } else {
+                attachment_parts.push(part);
+            }
+        }
+    }
+
+    let body = if inline_parts.is_empty() {
+        body
+    } else {
+        let html = BodyPart::new(outlook, "text/html", TransferEncoding::SevenBit);
+        let mut parts = vec![body];
+        parts.append(&mut inline_parts);
+        Part::multipart("related", parts, |boundary| {
+            if html_part {
+                html.related_parameters(boundary)
+            } else {
+                format!("type=\"multipart/alternative\"; boundary=\"{}\"", boundary)
+            }
+        })
+    };
+    if attachment_parts.is_empty() {
+        return Ok(body);
+    }
+    let mut parts = vec![body];
+    parts.append(&mut attachment_parts);
+    Ok(Part::multipart("mixed", parts, |boundary| format!("boundary=\"{}\"", boundary)))
+}
+
+// Indices of the attachments referred to by the cid: URLs of the
+// HTML body, e.g. the images of a signature.
+fn inline_attachments(outlook: &Outlook, html: &str) -> BTreeSet<usize> {
+    let lower = html.to_ascii_lowercase();
+    lower
+        .match_indices("cid:")
+        .filter_map(|(start, _)| {
+            let url = html[start..]
+                .split(|c: char| c == '"' || c == '\'' || c == ')' || c.is_whitespace())
+                .next()?;
+            let attachment = outlook.inline_attachment(url)?;
+            outlook
+                .attachments
+                .iter()
+                .position(|other| std::ptr::eq(other, attachment))
+        })
+        .collect()
+}
+
+// Part of an attachment, none for those without content in the
+// message, such as references to files or filtered payloads.
+// Messages embedded as storages are written as EML themselves,
+// while .msg files attached by value are kept as they are.
+fn attachment_part(
+    attachment: &Attachment,
+    inline: bool,
+    options: &EmlOptions,
+) -> Result<Option<Part>, Error> {
+    let name = attachment.name.trim();
+    if let Some(message) = attachment
+        .embedded_message
+        .as_ref()
+        .filter(|_| attachment.payload.is_empty())
+    {
+        let eml = write_message(message, options)?;
+        let file_name = if name.to_ascii_lowercase().ends_with(".eml") || name.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.eml", name)
+        };
+        let mut headers = String::from("Content-Type: message/rfc822\r\n");
+        headers.push_str(&disposition("attachment", &file_name));
+        return Ok(Some(Part {
+            headers,
+            body: String::from_utf8_lossy(&eml).trim_end().to_string(),
+        }));
+    }
+    if attachment.payload.is_empty() {
+        return Ok(None);
+    }
+    let payload = hex::decode(&attachment.payload)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string()))?;
+    let mime_tag = attachment.mime_tag.trim();
+    let content_type = if mime_tag.contains('/') {
+        mime_tag
+    } else if name.to_ascii_lowercase().ends_with(".msg") {
+        MSG_CONTENT_TYPE
+    } else {
+        DEFAULT_CONTENT_TYPE
+    };
+    let mut headers = format!("Content-Type: {}", content_type);
+    if !name.is_empty() {
+        headers.push_str(&format!(";\r\n {}", parameter("name", name)));
+    }
+    headers.push_str("\r\nContent-Transfer-Encoding: base64\r\n");
+    headers.push_str(&disposition(if inline { "inline" } else { "attachment" }, name));
+    let id = content_id(&attachment.content_id);
+    if !id.is_empty() {
+        headers.push_str(&format!("Content-ID: <{}>\r\n", id));
+    }
+    Ok(Some(Part {
+        headers,
+        body: encode_base64(&payload),
+    }))
+}
+
+fn disposition(kind: &str, file_name: &str) -> String {
+    if file_name.is_empty() {
+        return format!("Content-Disposition: {}\r\n", kind);
+    }
+    format!(
+        "Content-Disposition: {};\r\n {}\r\n",
+        kind,
+        parameter("filename", file_name)
+    )
+}
+
+fn encode_base64(body: &[u8]) -> String {
+    let encoded = STANDARD.encode(body);
+    let lines: Vec<&str> = encoded
+        .as_bytes()
+        .chunks(MAX_LINE_LEN)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect();
+    lines.join("\r\n")
+}
+
+// Refer to RFC 2045 6.7
+fn encode_quoted_printable(body: &[u8]) -> String {
+    let mut encoded = String::with_capacity(body.len() * 2);
+    let mut line_len = 0;
+    let mut i = 0;
+    while i < body.len() {
+        let c = body[i];
+        // Line breaks are kept as is
+        if c == b'\r' && body.get(i + 1) == Some(&b'\n') {
+            encoded.push_str("\r\n");
+            line_len = 0;
+            i += 2;
+            continue;
+        }
+        // Whitespaces at the end of a line are encoded
+        let ends_line = matches!(body.get(i + 1), None | Some(b'\r') | Some(b'\n'));
+        let is_literal = (c == b' ' || c == b'\t') && !ends_line
+            || (33..=126).contains(&c) && c != b'=';
+        let token = if is_literal {
+            (c as char).to_string()
+        } else {
+            format!("={:02X}", c)
+        };
+        // Soft line break
+        if line_len + token.len() > MAX_LINE_LEN - 1 {
+            encoded.push_str("=\r\n");
+            line_len = 0;
+        }
+        encoded.push_str(&token);
+        line_len += token.len();
+        i += 1;
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        encode_quoted_printable, encode_text, parameter, transport_fields, BodyPart, EmlOptions,
+        TransferEncoding,
+    };
+    use crate::parser::{
+        outlook::{Outlook, TransportHeaders},
+        options::ParseOptions,
+        raw_headers::RawHeadersEncoding,
+    };
+    use crate::testing::{MessageBuilder, PropertyValue};
+
+    fn headers(content_type: &str, encoding: &str) -> TransportHeaders {
+        TransportHeaders {
+            content_type: content_type.to_string(),
+            date: String::new(),
+            message_id: String::new(),
+            reply_to: String::new(),
+            content_transfer_encoding: encoding.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_canonical() {
+        assert_eq!(TransferEncoding::canonical(b"Hello\r\n"), TransferEncoding::SevenBit);
+        assert_eq!(
+            TransferEncoding::canonical("Réponse à la question".as_bytes()),
+            TransferEncoding::QuotedPrintable
+        );
+        assert_eq!(
+            TransferEncoding::canonical("日本語のテキスト".as_bytes()),
+            TransferEncoding::Base64
+        );
+        assert_eq!(
+            TransferEncoding::canonical(&[b'a'; 1000]),
+            TransferEncoding::QuotedPrintable
+        );
+    }
+
+    #[test]
+    fn test_quoted_printable() {
+        assert_eq!(encode_quoted_printable("a=b é".as_bytes()), "a=3Db =C3=A9");
+        assert_eq!(encode_quoted_printable(b"end \r\nnext"), "end=20\r\nnext");
+        let long = encode_quoted_printable(&[b'a'; 100]);
+        assert_eq!(long, format!("{}=\r\n{}", "a".repeat(75), "a".repeat(25)));
+    }
+
+    #[test]
+    fn test_body_encoding() {
+        let body = b"Plain text body";
+        let plain = headers("text/plain; charset=utf-8", "base64");
+
+        // Original encoding is kept
+        let options = EmlOptions::new();
+        assert_eq!(options.body_encoding(&plain, body), TransferEncoding::Base64);
+        let encoded = options.body_encoding(&plain, body).encode(body);
+        assert_eq!(encoded, "UGxhaW4gdGV4dCBib2R5");
+
+        // Unless forced
+        let canonical = EmlOptions::new().canonical_encoding(true);
+        assert_eq!(canonical.body_encoding(&plain, body), TransferEncoding::SevenBit);
+
+        // Multipart headers give no hint on the body
+        let multipart = headers("multipart/alternative; boundary=b", "quoted-printable");
+        assert_eq!(options.body_encoding(&multipart, body), TransferEncoding::SevenBit);
+
+        // 7bit is not kept for 8bit bodies
+        let seven_bit = headers("text/plain", "7bit");
+        assert_eq!(
+            options.body_encoding(&seven_bit, "Réponse à la question".as_bytes()),
+            TransferEncoding::QuotedPrintable
+        );
+    }
+
+    #[test]
+    fn test_body_part() {
+        let mut outlook = Outlook::from_path("data/unicode.msg").unwrap();
+        let part = BodyPart::new(&outlook, "text/html; charset=utf-8", TransferEncoding::Base64);
+        assert_eq!(
+            part.headers(),
+            "Content-Type: text/html; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n"
+        );
+        assert_eq!(
+            part.related_parameters("b1"),
+            "type=\"text/html\"; boundary=\"b1\""
+        );
+
+        outlook.body_content_id = "body@example.com".to_string();
+        outlook.body_content_location = "http://example.com/body.html".to_string();
+        let part = BodyPart::new(&outlook, "text/html", TransferEncoding::QuotedPrintable);
+        assert_eq!(
+            part.headers(),
+            "Content-Type: text/html\r\nContent-Transfer-Encoding: quoted-printable\r\n\
+             Content-ID: <body@example.com>\r\n\
+             Content-Location: http://example.com/body.html\r\n"
+        );
+        assert_eq!(
+            part.related_parameters("b1"),
+            "type=\"text/html\"; boundary=\"b1\"; start=\"<body@example.com>\""
+        );
+    }
+
+    #[test]
+    fn test_header_encoding() {
+        assert_eq!(encode_text("Budget\r\n Q3"), "Budget Q3");
+        assert_eq!(encode_text("Réponse"), "=?UTF-8?B?UsOpcG9uc2U=?=");
+        // Split between characters
+        let long = encode_text(&"é".repeat(30));
+        assert_eq!(long.split("\r\n ").count(), 2);
+        assert!(long.lines().all(|line| line.trim().len() <= 75));
+
+        assert_eq!(parameter("filename", "a b.txt"), "filename=\"a b.txt\"");
+        assert_eq!(parameter("filename", "résumé.pdf"), "filename*=UTF-8''r%C3%A9sum%C3%A9.pdf");
+        assert_eq!(parameter("filename", "a\"b"), "filename*=UTF-8''a%22b");
+    }
+
+    #[test]
+    fn test_transport_fields() {
+        let text = "Microsoft Mail Internet Headers Version 2.0\r\n\
+                    Subject: Budget\r\n\
+                    Content-Type: multipart/mixed;\r\n\
+                    \tboundary=\"b1\"\r\n\
+                    To: a@example.com,\r\n b@example.com\r\n\
+                    MIME-Version: 1.0\r\n\r\n\
+                    Body: not a field\r\n";
+        assert_eq!(
+            transport_fields(text),
+            "Subject: Budget\r\nTo: a@example.com,\r\n b@example.com\r\n"
+        );
+    }
+
+    #[test]
+    fn test_synthesized_headers() {
+        let bytes = MessageBuilder::new()
+            .subject("Réunion")
+            .body("Hello")
+            .sender("Doe, John", "john@example.com")
+            .recipient("Jane", "jane@example.com")
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        let eml = String::from_utf8(outlook.to_eml().unwrap()).unwrap();
+        assert_eq!(
+            eml,
+            "From: \"Doe, John\" <john@example.com>\r\n\
+             To: Jane <jane@example.com>\r\n\
+             Subject: =?UTF-8?B?UsOpdW5pb24=?=\r\n\
+             MIME-Version: 1.0\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\
+             Content-Transfer-Encoding: 7bit\r\n\
+             \r\n\
+             Hello"
+        );
+    }
+
+    #[test]
+    fn test_to_eml() {
+        let outlook = Outlook::from_path("data/attachment.msg").unwrap();
+        let eml = String::from_utf8(outlook.to_eml().unwrap()).unwrap();
+        let (headers, body) = eml.split_once("\r\n\r\n").unwrap();
+        // Transport headers are kept, but for the MIME ones
+        assert!(headers.contains("\r\nMessage-ID: <DM5PR06MB3530CD95FEF722045944E0E385820@"));
+        assert_eq!(headers.matches("MIME-Version:").count(), 1);
+        assert!(headers.ends_with("Content-Type: multipart/mixed; boundary=\"=_msg_parser_2\""));
+        assert!(body.contains(
+            "Content-Type: multipart/related; type=\"multipart/alternative\"; \
+             boundary=\"=_msg_parser_1\""
+        ));
+        assert!(body.contains("Content-Type: multipart/alternative; boundary=\"=_msg_parser_0\""));
+        // Images shown by the HTML body
+        assert!(body.contains(
+            "Content-Type: image/png;\r\n name=\"image001.png\"\r\n\
+             Content-Transfer-Encoding: base64\r\n\
+             Content-Disposition: inline;\r\n filename=\"image001.png\"\r\n\
+             Content-ID: <image001.png@01D63E5A.C9557CD0>\r\n"
+        ));
+        // A document attached by value, although stored as OLE
+        assert!(body.contains(
+            "Content-Type: application/msword;\r\n name=\"loan_proposal.doc\"\r\n\
+             Content-Transfer-Encoding: base64\r\n\
+             Content-Disposition: attachment;\r\n filename=\"loan_proposal.doc\"\r\n"
+        ));
+        assert!(body.ends_with("\r\n--=_msg_parser_2--\r\n"));
+
+        // Embedded messages
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        let eml = String::from_utf8(outlook.to_eml().unwrap()).unwrap();
+        assert_eq!(eml.matches("Content-Type: message/rfc822").count(), 1);
+        let msg_file = "Content-Type: application/vnd.ms-outlook;\r\n name=\"Test Email.msg\"";
+        assert!(eml.contains(msg_file));
+    }
+
+    #[test]
+    fn test_raw_headers() {
+        // Headers kept as raw bytes only, as for a message from JSON
+        let options = ParseOptions::new().raw_headers(RawHeadersEncoding::Plain);
+        let outlook = Outlook::from_path_with_options("data/attachment.msg", &options).unwrap();
+        let outlook: Outlook = serde_json::from_str(&outlook.to_json().unwrap()).unwrap();
+        let eml = String::from_utf8(outlook.to_eml().unwrap()).unwrap();
+        assert!(eml.contains("\r\nMessage-ID: <DM5PR06MB3530CD95FEF722045944E0E385820@"));
+    }
+
+    #[test]
+    fn test_date_out_of_range() {
+        // Found by fuzzing: a submit time past year 9999
+        let bytes = MessageBuilder::new()
+            .subject("Hello")
+            .property("ClientSubmitTime", PropertyValue::Time(u64::MAX))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        let eml = String::from_utf8(outlook.to_eml().unwrap()).unwrap();
+        assert!(!eml.contains("Date:"));
+
+        // The creation time stands in for it
+        let bytes = MessageBuilder::new()
+            .subject("Hello")
+            .property("ClientSubmitTime", PropertyValue::Time(u64::MAX))
+            .property("CreationTime", PropertyValue::Time(132_000_000_000_000_000))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        let eml = String::from_utf8(outlook.to_eml().unwrap()).unwrap();
+        assert!(eml.starts_with("Date: Wed, 17 Apr 2019 18:40:00 +0000\r\n"));
+    }
+}
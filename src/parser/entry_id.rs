@@ -0,0 +1,116 @@
+// One-off EntryIDs address a recipient directly, without an
+// address book entry. Refer to MS-OXCDATA 2.2.5.1
+const ONE_OFF_PROVIDER_UID: [u8; 16] = [
+    0x81, 0x2B, 0x1F, 0xA4, 0xBE, 0xA3, 0x10, 0x19, 0x9D, 0x6E, 0x00, 0xDD, 0x01, 0x0F, 0x54, 0x02,
+];
+// Flags, ProviderUID, Version and entry flags
+const ONE_OFF_HEADER_SIZE: usize = 24;
+// Strings are UTF-16 when set, else 8-bit
+const ONE_OFF_UNICODE: u16 = 0x8000;
+
+// OneOffEntryId holds the recipient written in a one-off EntryID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OneOffEntryId {
+    pub display_name: String,
+    pub address_type: String, // e.g. "SMTP"
+    pub email_address: String,
+}
+
+impl OneOffEntryId {
+    // Decodes a one-off EntryID, none for other kinds of EntryIDs
+    // or when it is truncated.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < ONE_OFF_HEADER_SIZE || bytes[4..20] != ONE_OFF_PROVIDER_UID {
+            return None;
+        }
+        let flags = u16::from_le_bytes([bytes[22], bytes[23]]);
+        let mut rest = &bytes[ONE_OFF_HEADER_SIZE..];
+        let mut next = || {
+            let (value, len) = if flags & ONE_OFF_UNICODE != 0 {
+                Self::read_unicode(rest)?
+            } else {
+                Self::read_ansi(rest)?
+            };
+            rest = &rest[len..];
+            Some(value)
+        };
+        Some(Self {
+            display_name: next()?,
+            address_type: next()?,
+            email_address: next()?,
+        })
+    }
+
+    // A null terminated UTF-16 string and the bytes it takes.
+    fn read_unicode(bytes: &[u8]) -> Option<(String, usize)> {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|unit| *unit != 0)
+            .collect();
+        let len = (units.len() + 1) * 2;
+        if len > bytes.len() {
+            return None;
+        }
+        Some((String::from_utf16_lossy(&units), len))
+    }
+
+    // A null terminated 8-bit string and the bytes it takes.
+    fn read_ansi(bytes: &[u8]) -> Option<(String, usize)> {
+        let end = bytes.iter().position(|b| *b == 0)?;
+        let value = match std::str::from_utf8(&bytes[..end]) {
+            Ok(value) => value.to_string(),
+            Err(_) => bytes[..end].iter().map(|b| *b as char).collect(),
+        };
+        Some((value, end + 1))
+    }
+
+    // The address, if SMTP.
+    pub fn smtp_address(&self) -> Option<&str> {
+        self.address_type
+            .eq_ignore_ascii_case("SMTP")
+            .then_some(self.email_address.as_str())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{OneOffEntryId, ONE_OFF_PROVIDER_UID};
+
+    // Builds a one-off EntryID of the given strings.
+    pub(crate) fn one_off(strings: [&str; 3], unicode: bool) -> Vec<u8> {
+        let mut bytes = vec![0u8; 4];
+        bytes.extend_from_slice(&ONE_OFF_PROVIDER_UID);
+        bytes.extend_from_slice(&[0x00, 0x00]);
+        bytes.extend_from_slice(if unicode { &[0x01, 0x90] } else { &[0x01, 0x10] });
+        for string in strings {
+            if unicode {
+                bytes.extend(string.encode_utf16().chain([0]).flat_map(u16::to_le_bytes));
+            } else {
+                bytes.extend(string.bytes().chain([0]));
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_one_off_entry_id() {
+        for unicode in [true, false] {
+            let bytes = one_off(["Jane Doe", "SMTP", "jane@example.com"], unicode);
+            let entry = OneOffEntryId::parse(&bytes).unwrap();
+            assert_eq!(entry.display_name, "Jane Doe");
+            assert_eq!(entry.address_type, "SMTP");
+            assert_eq!(entry.smtp_address(), Some("jane@example.com"));
+            // Truncated
+            assert!(OneOffEntryId::parse(&bytes[..bytes.len() - 2]).is_none());
+        }
+
+        let bytes = one_off(["Jane", "EX", "/o=Org/cn=Jane"], true);
+        assert_eq!(OneOffEntryId::parse(&bytes).unwrap().smtp_address(), None);
+
+        // Address book EntryIDs have another provider
+        let mut bytes = one_off(["Jane", "SMTP", "jane@example.com"], true);
+        bytes[4] = 0xDC;
+        assert!(OneOffEntryId::parse(&bytes).is_none());
+    }
+}
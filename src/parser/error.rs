@@ -8,8 +8,15 @@ use thiserror::Error as ThisError;
 
 use crate::ole::Error as OleError;
 
+use super::report::ParseWarning;
+
 // DataTypeError is used when decode fails in datatype.rs
+// Codes, see DataTypeError::code:
+//   201  UnknownCode
+//   202  Utf8Err
+//   203  Utf16Err
 #[derive(ThisError, Debug)]
+#[non_exhaustive]
 pub enum DataTypeError {
     UnknownCode(String),
     Utf8Err(#[from] std::string::FromUtf8Error),
@@ -26,21 +33,41 @@ impl std::fmt::Display for DataTypeError {
                 write!(
                     f,
                     "DataTypeError: Unable to decode bytes into UTF-8 string {}",
-                    err.to_string()
+                    err
                 )
             }
             DataTypeError::Utf16Err(ref err) => {
                 write!(
                     f,
                     "DataTypeError: Unable to decode bytes into UTF-16 string {}",
-                    err.to_string()
+                    err
                 )
             }
         }
     }
 }
 
+impl DataTypeError {
+    // Stable numeric code, from 200 to 299, see Error::code.
+    pub fn code(&self) -> u32 {
+        match *self {
+            DataTypeError::UnknownCode(_) => 201,
+            DataTypeError::Utf8Err(_) => 202,
+            DataTypeError::Utf16Err(_) => 203,
+        }
+    }
+}
+
+// Error of the parser. Codes, see Error::code:
+//   1    Io
+//   2    SerdeJsonError
+//   3    ContainerError
+//   4    LimitExceeded
+//   5    StrictViolation
+//   1xx  OleError, see ole::Error
+//   2xx  DataTypeError, see DataTypeError
 #[derive(ThisError, Debug)]
+#[non_exhaustive]
 pub enum Error {
     #[error(transparent)]
     DataTypeError(#[from] DataTypeError),
@@ -59,4 +86,105 @@ pub enum Error {
 
     #[error(transparent)]
     SerdeJsonError(#[from] SerdeError),
+
+    #[error("Unable to unwrap container: {0}")]
+    ContainerError(String),
+
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    #[error("Anomaly found in strict mode: {0}")]
+    StrictViolation(ParseWarning),
+}
+
+impl Error {
+    // Stable numeric code of the error, e.g. for FFI or log pipelines
+    // which cannot match messages. Codes are never reused.
+    //   1 to 99     errors of the parser
+    //   100 to 199  OLE errors, see ole::Error::code
+    //   200 to 299  decoding errors, see DataTypeError::code
+    pub fn code(&self) -> u32 {
+        match self {
+            Error::Io { .. } => 1,
+            Error::SerdeJsonError(_) => 2,
+            Error::ContainerError(_) => 3,
+            Error::LimitExceeded(_) => 4,
+            Error::StrictViolation(_) => 5,
+            Error::OleError { source } => source.code(),
+            Error::DataTypeError(error) => error.code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{DataTypeError, Error};
+    use crate::{ole, Outlook, ParseOptions, ParseWarning};
+
+    #[test]
+    fn test_code() {
+        let error = Outlook::from_path("data/missing.msg").unwrap_err();
+        assert_eq!(error.code(), 1);
+        let error = Outlook::from_slice(&[b'x'; 1024]).unwrap_err();
+        assert_eq!(error.code(), ole::Error::InvalidOLEFile.code());
+        assert_eq!(error.code(), 104);
+        let error = Outlook::from_path_with_options(
+            "data/test_email.msg",
+            &ParseOptions::new().max_storages(1),
+        )
+        .unwrap_err();
+        assert_eq!(error.code(), 4);
+        let error = Error::from(DataTypeError::UnknownCode("1234".to_string()));
+        assert_eq!(error.code(), 201);
+        let error = Error::from(ole::Error::MalformedChain);
+        assert_eq!(error.code(), 113);
+    }
+
+    #[test]
+    fn test_code_table() {
+        let io_error = || std::io::Error::from(std::io::ErrorKind::NotFound);
+        let ole_errors = vec![
+            (ole::Error::BadFileSize, 101),
+            (ole::Error::IOError(io_error()), 102),
+            (ole::Error::NotImplementedYet, 103),
+            (ole::Error::InvalidOLEFile, 104),
+            (ole::Error::BadSizeValue("size"), 105),
+            (ole::Error::EmptyMasterSectorAllocationTable, 106),
+            (ole::Error::NotSectorUsedBySAT, 107),
+            (ole::Error::NodeTypeUnknown, 108),
+            (ole::Error::BadRootStorageSize, 109),
+            (ole::Error::EmptyEntry, 110),
+            (ole::Error::EntryNotFound, 111),
+            (ole::Error::ShortSectorOutOfRange(0), 112),
+            (ole::Error::MalformedChain, 113),
+        ];
+        let data_type_errors = vec![
+            (DataTypeError::UnknownCode("1234".to_string()), 201),
+            (DataTypeError::Utf8Err(String::from_utf8(vec![0xFF]).unwrap_err()), 202),
+            (DataTypeError::Utf16Err(String::from_utf16(&[0xD800]).unwrap_err()), 203),
+        ];
+        let mut errors = vec![
+            (Error::from(io_error()), 1),
+            (Error::from(serde_json::from_str::<u8>("x").unwrap_err()), 2),
+            (Error::ContainerError("zip".to_string()), 3),
+            (Error::LimitExceeded("storages".to_string()), 4),
+            (Error::StrictViolation(ParseWarning::TruncatedDirectory { entries: 0 }), 5),
+        ];
+        for (error, code) in ole_errors {
+            assert_eq!(error.code(), code);
+            errors.push((Error::from(error), code));
+        }
+        for (error, code) in data_type_errors {
+            assert_eq!(error.code(), code);
+            errors.push((Error::from(error), code));
+        }
+        let mut codes = HashSet::new();
+        for (error, code) in &errors {
+            assert_eq!(error.code(), *code, "{}", error);
+            assert!(codes.insert(code), "code {} is used twice", code);
+        }
+        assert_eq!(codes.len(), 5 + 13 + 3);
+    }
 }
@@ -0,0 +1,382 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::Read,
+    path::Path,
+};
+
+use encoding_rs::{Decoder, UTF_16BE, UTF_16LE, WINDOWS_1252};
+
+use crate::ole::{EntryStream, EntryType, Reader};
+
+use super::{
+    decode::code_page_encoding,
+    error::Error,
+    outlook::{Outlook, Person, TransportHeaders},
+    storage::{normalize_name, Storages},
+};
+
+// Payloads are read this many bytes at a time by default.
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+// Streams of the plain text body, Unicode or else ANSI, and of
+// attachment payloads.
+const BODY_STREAM: &str = "__SUBSTG1.0_1000001F";
+const BODY_STREAM_ANSI: &str = "__SUBSTG1.0_1000001E";
+const ATTACH_DATA_STREAM: &str = "__SUBSTG1.0_37010102";
+
+// MsgEvent is an item of the message as read by MsgEvents.
+#[derive(Debug, PartialEq)]
+pub enum MsgEvent {
+    // Always the first event
+    HeaderParsed {
+        headers: TransportHeaders, // See Outlook.headers
+        sender: Person,            // See Outlook.sender
+        subject: String,           // "Subject"
+        message_class: String,     // "MessageClass"
+    },
+    Recipient(Person), // To, CC and BCC, see Outlook.to
+    BodyChunk(String), // Part of "Body"
+    AttachmentMeta {
+        index: usize, // Position in Outlook.attachments
+        name: String, // See Attachment.name
+        mime: String, // "AttachMimeTag"
+        size: usize,  // Payload size in bytes, 0 for embedded messages
+    },
+    AttachmentChunk {
+        index: usize,  // See AttachmentMeta.index
+        data: Vec<u8>, // Part of "AttachDataObject"
+    },
+}
+
+// Work left to do, in the order the events are yielded.
+enum Pending {
+    Event(MsgEvent),
+    Body(EntryStream, Vec<u8>),     // Stream and bytes left from the previous chunk
+    AnsiBody(EntryStream, Decoder), // Stream and decoder of the message code page
+    Attachment(usize, EntryStream),
+}
+
+// MsgEvents reads a message as a sequence of events:
+// headers first, then recipients, the body and the attachments.
+// Bodies and payloads are only read as their chunks are asked
+// for, so a consumer can stop early and skip reading them:
+//
+// for event in MsgEvents::from_path("mail.msg")? {
+//     if let MsgEvent::AttachmentMeta { mime, .. } = event? { ... }
+// }
+pub struct MsgEvents {
+    pending: VecDeque<Pending>,
+    chunk_size: usize,
+}
+
+impl MsgEvents {
+    fn create(parser: &Reader) -> Result<Self, Error> {
+        let mut storages = Storages::new(parser);
+        storages.process_metadata_streams(parser);
+        let outlook = Outlook::populate(&storages);
+
+        let mut pending = VecDeque::new();
+        pending.push_back(Pending::Event(MsgEvent::HeaderParsed {
+            headers: outlook.headers,
+            sender: outlook.sender,
+            subject: outlook.subject,
+            message_class: storages.get_val_from_root_or_default("MessageClass"),
+        }));
+        pending.extend((0..storages.recipients.len()).map(|idx| {
+            Pending::Event(MsgEvent::Recipient(Person::create_from_recipient(&storages, idx)))
+        }));
+
+        // Bodies and payloads are found by the path of their stream
+        let stream_path = |name: &str| {
+            parser.iterate().find(|entry| {
+                entry._type() == EntryType::UserStream
+                    && normalize_name(&parser.path(entry)) == name
+            })
+        };
+        if let Some(entry) = stream_path(BODY_STREAM) {
+            pending.push_back(Pending::Body(parser.get_entry_stream(entry)?, vec![]));
+        } else if let Some(entry) = stream_path(BODY_STREAM_ANSI) {
+            let decoder = Self::ansi_decoder(storages.code_page());
+            pending.push_back(Pending::AnsiBody(parser.get_entry_stream(entry)?, decoder));
+        }
+        for (index, attachment) in outlook.attachments.iter().enumerate() {
+            // Path of the attachment storage, from any of its properties
+            let storage = storages.attachment_paths[index]
+                .values()
+                .next()
+                .and_then(|path| path.rsplit_once('/'))
+                .map(|(storage, _)| normalize_name(storage));
+            let payload = match storage {
+                Some(storage) => stream_path(&format!("{}/{}", storage, ATTACH_DATA_STREAM)),
+                None => None,
+            };
+            let payload = match payload {
+                Some(entry) => Some(parser.get_entry_stream(entry)?),
+                None => None,
+            };
+            pending.push_back(Pending::Event(MsgEvent::AttachmentMeta {
+                index,
+                name: attachment.name.clone(),
+                mime: attachment.mime_tag.clone(),
+                size: payload.as_ref().map_or(0, EntryStream::len),
+            }));
+            if let Some(payload) = payload {
+                pending.push_back(Pending::Attachment(index, payload));
+            }
+        }
+        Ok(Self {
+            pending,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        let parser = Reader::new(file)?;
+        Self::create(&parser)
+    }
+
+    pub fn from_slice(slice: &[u8]) -> Result<Self, Error> {
+        let parser = Reader::new(slice)?;
+        Self::create(&parser)
+    }
+
+    // Number of bytes read from a body or a payload for
+    // each chunk, 64 KiB by default.
+    pub fn chunk_size(mut self, value: usize) -> Self {
+        self.chunk_size = value.max(2);
+        self
+    }
+
+    fn read_chunk(stream: &mut EntryStream, size: usize) -> Result<Vec<u8>, Error> {
+        let mut buff = vec![];
+        stream.take(size as u64).read_to_end(&mut buff)?;
+        Ok(buff)
+    }
+
+    // Decoder of an ANSI body in the code page of the message,
+    // windows-1252 when it is unknown or a Unicode one.
+    fn ansi_decoder(code_page: Option<i64>) -> Decoder {
+        let encoding = code_page
+            .and_then(code_page_encoding)
+            .filter(|encoding| *encoding != UTF_16LE && *encoding != UTF_16BE)
+            .unwrap_or(WINDOWS_1252);
+        encoding.new_decoder_without_bom_handling()
+    }
+
+    // Decodes a chunk of an ANSI body, the decoder keeping back
+    // the start of a character split with the next chunk.
+    fn decode_ansi_body(decoder: &mut Decoder, chunk: &[u8], last: bool) -> String {
+        let capacity = decoder.max_utf8_buffer_length(chunk.len()).unwrap_or(chunk.len());
+        let mut text = String::with_capacity(capacity);
+        let _ = decoder.decode_to_string(chunk, &mut text, last);
+        text
+    }
+
+    // Decodes the UTF-16 body read so far, keeping back a
+    // trailing odd byte or high surrogate for the next chunk.
+    fn decode_body(carry: &mut Vec<u8>, last: bool) -> String {
+        let mut end = carry.len() & !1;
+        let high_surrogate = |unit: u16| (0xD800..0xDC00).contains(&unit);
+        if !last
+            && end >= 2
+            && high_surrogate(u16::from_le_bytes([carry[end - 2], carry[end - 1]]))
+        {
+            end -= 2;
+        }
+        let units: Vec<u16> = carry[..end]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        carry.drain(..end);
+        String::from_utf16_lossy(&units)
+    }
+}
+
+impl Iterator for MsgEvents {
+    type Item = Result<MsgEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let chunk_size = self.chunk_size;
+            let event = match self.pending.front_mut()? {
+                Pending::Event(_) => match self.pending.pop_front() {
+                    Some(Pending::Event(event)) => Ok(event),
+                    _ => unreachable!(),
+                },
+                Pending::Body(stream, carry) => match Self::read_chunk(stream, chunk_size) {
+                    Ok(chunk) => {
+                        let last = chunk.is_empty();
+                        carry.extend(chunk);
+                        let text = Self::decode_body(carry, last);
+                        if last {
+                            self.pending.pop_front();
+                        }
+                        if text.is_empty() {
+                            continue;
+                        }
+                        Ok(MsgEvent::BodyChunk(text))
+                    }
+                    Err(err) => Err(err),
+                },
+                Pending::AnsiBody(stream, decoder) => match Self::read_chunk(stream, chunk_size) {
+                    Ok(chunk) => {
+                        let last = chunk.is_empty();
+                        let text = Self::decode_ansi_body(decoder, &chunk, last);
+                        if last {
+                            self.pending.pop_front();
+                        }
+                        if text.is_empty() {
+                            continue;
+                        }
+                        Ok(MsgEvent::BodyChunk(text))
+                    }
+                    Err(err) => Err(err),
+                },
+                Pending::Attachment(index, stream) => match Self::read_chunk(stream, chunk_size) {
+                    Ok(data) if data.is_empty() => {
+                        self.pending.pop_front();
+                        continue;
+                    }
+                    Ok(data) => Ok(MsgEvent::AttachmentChunk {
+                        index: *index,
+                        data,
+                    }),
+                    Err(err) => Err(err),
+                },
+            };
+            // Nothing more is read after an error
+            if event.is_err() {
+                self.pending.clear();
+            }
+            return Some(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MsgEvent, MsgEvents};
+    use crate::parser::outlook::Outlook;
+
+    fn collect(path: &str, chunk_size: usize) -> Vec<MsgEvent> {
+        MsgEvents::from_path(path)
+            .unwrap()
+            .chunk_size(chunk_size)
+            .collect::<Result<Vec<MsgEvent>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_events_match_outlook() {
+        for path in ["data/test_email.msg", "data/unicode.msg"] {
+            let outlook = Outlook::from_path(path).unwrap();
+            // Odd sizes split characters between chunks
+            let events = collect(path, 101);
+
+            match &events[0] {
+                MsgEvent::HeaderParsed { headers, sender, subject, .. } => {
+                    assert_eq!(headers, &outlook.headers);
+                    assert_eq!(sender, &outlook.sender);
+                    assert_eq!(subject, &outlook.subject);
+                }
+                event => panic!("unexpected first event {:?}", event),
+            }
+            let recipients: Vec<_> = events
+                .iter()
+                .filter_map(|event| match event {
+                    MsgEvent::Recipient(person) => Some(person.clone()),
+                    _ => None,
+                })
+                .collect();
+            let mut expected: Vec<_> = outlook
+                .to
+                .iter()
+                .chain(&outlook.cc)
+                .chain(&outlook.bcc_recipients)
+                .cloned()
+                .collect();
+            expected.sort_by_key(|person| person.storage_index);
+            assert_eq!(recipients, expected);
+
+            let body: String = events
+                .iter()
+                .filter_map(|event| match event {
+                    MsgEvent::BodyChunk(text) => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(body, outlook.body);
+
+            for (idx, attachment) in outlook.attachments.iter().enumerate() {
+                let mut payload = vec![];
+                for event in &events {
+                    match event {
+                        MsgEvent::AttachmentMeta { index, mime, size, .. } if *index == idx => {
+                            assert_eq!(mime, &attachment.mime_tag);
+                            assert_eq!(*size, attachment.payload.len() / 2);
+                        }
+                        MsgEvent::AttachmentChunk { index, data } if *index == idx => {
+                            assert!(data.len() <= 101);
+                            payload.extend(data);
+                        }
+                        _ => {}
+                    }
+                }
+                assert_eq!(hex::encode_upper(payload), attachment.payload.to_uppercase());
+            }
+        }
+    }
+
+    #[test]
+    fn test_ansi_body() {
+        use crate::testing::{MessageBuilder, PropertyValue};
+
+        // Body of a non-Unicode store, in its code page
+        let bytes = MessageBuilder::new()
+            .property("Body", PropertyValue::String8(b"\xCE\xF2\xE2\xE5\xF2 Q3".to_vec()))
+            .property("MessageCodepage", PropertyValue::Integer32(1251))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.body, "Ответ Q3");
+        let body: String = MsgEvents::from_slice(&bytes)
+            .unwrap()
+            .chunk_size(3)
+            .filter_map(|event| match event.unwrap() {
+                MsgEvent::BodyChunk(text) => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(body, outlook.body);
+
+        // Characters of several bytes split between chunks
+        let bytes = MessageBuilder::new()
+            .property("Body", PropertyValue::String8("Ответ".as_bytes().to_vec()))
+            .property("MessageCodepage", PropertyValue::Integer32(65001))
+            .build();
+        let body: String = MsgEvents::from_slice(&bytes)
+            .unwrap()
+            .chunk_size(3)
+            .filter_map(|event| match event.unwrap() {
+                MsgEvent::BodyChunk(text) => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(body, "Ответ");
+    }
+
+    #[test]
+    fn test_events_stop_early() {
+        let mut events = MsgEvents::from_path("data/test_email.msg").unwrap();
+        assert!(matches!(
+            events.next(),
+            Some(Ok(MsgEvent::HeaderParsed { .. }))
+        ));
+        let first_meta = events.find_map(|event| match event.unwrap() {
+            MsgEvent::AttachmentMeta { index, name, .. } => Some((index, name)),
+            _ => None,
+        });
+        assert_eq!(first_meta.unwrap().0, 0);
+    }
+}
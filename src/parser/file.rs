@@ -0,0 +1,115 @@
+use std::{fs::File, path::Path};
+
+use crate::ole;
+
+use super::{
+    error::Error,
+    kind::ItemKind,
+    options::ParseOptions,
+    outlook::{Attachment, Outlook, TransportHeaders},
+    storage::Storages,
+};
+
+// OutlookFile is an open message, read on demand: only the
+// properties of the message, recipients and attachments are
+// decoded when opened, and bodies and payloads as they are
+// asked for. It keeps the file parsed for its lifetime:
+//
+// let file = Outlook::open("mail.msg")?;
+// for idx in 0..file.attachment_count() {
+//     let attachment = file.attachment(idx)?;
+// }
+pub struct OutlookFile {
+    parser: ole::Reader<'static>,
+    storages: Storages, // Without bodies and payloads
+}
+
+impl OutlookFile {
+    pub(crate) fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        // Read on demand, so that the bodies and payloads left
+        // out are never read
+        let parser = ole::Reader::from_seekable(File::open(path)?)?;
+        let mut storages = Storages::new(&parser);
+        storages.process_metadata_streams(&parser);
+        Ok(Self { parser, storages })
+    }
+
+    // See Outlook.headers
+    pub fn headers(&self) -> TransportHeaders {
+        let class = self.storages.get_val_from_root_or_default("MessageClass");
+        let kind = ItemKind::from_class(&class);
+        let (_, headers, _) = Outlook::transport_headers(&self.storages, kind);
+        headers
+    }
+
+    // See Outlook.subject
+    pub fn subject(&self) -> String {
+        self.storages.get_val_from_root_or_default("Subject")
+    }
+
+    // Storages of the bodies only.
+    fn body_storages(&self) -> Storages {
+        let mut storages = Storages::new(&self.parser);
+        storages.process_body_streams(&self.parser);
+        storages
+    }
+
+    // See Outlook.body, read each time it is called.
+    pub fn body(&self) -> String {
+        self.body_storages().get_val_from_root_or_default("Body")
+    }
+
+    // See Outlook.body_html, read each time it is called.
+    pub fn body_html(&self) -> Option<String> {
+        Outlook::html_body(&self.body_storages())
+    }
+
+    pub fn attachment_count(&self) -> usize {
+        self.storages.attachment_ids.len()
+    }
+
+    // Attachment at idx in Outlook.attachments, payload and embedded
+    // message included, none when out of range. Read each time it is
+    // called, with the limits of the default ParseOptions.
+    pub fn attachment(&self, idx: usize) -> Result<Option<Attachment>, Error> {
+        let id = match self.storages.attachment_ids.get(idx) {
+            Some(id) => *id,
+            None => return Ok(None),
+        };
+        let mut storages = Storages::new(&self.parser);
+        storages.process_attachment_streams(&self.parser, id);
+        let mut attachment = Attachment::create(&storages, 0);
+        let options = ParseOptions::default();
+        let embedded = Outlook::embedded_message(&self.parser, &storages, 0, &options, 0, &mut 0)?;
+        attachment.embedded_message = embedded.map(Box::new);
+        Ok(Some(attachment))
+    }
+
+    // The whole message, as parsed by Outlook::from_path.
+    pub fn outlook(&self) -> Result<Outlook, Error> {
+        Outlook::from_reader(&self.parser, &ParseOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Outlook;
+
+    #[test]
+    fn test_outlook_file() {
+        for path in ["data/test_email.msg", "data/unicode.msg"] {
+            let outlook = Outlook::from_path(path).unwrap();
+            let file = Outlook::open(path).unwrap();
+            assert_eq!(file.headers(), outlook.headers);
+            assert_eq!(file.subject(), outlook.subject);
+            assert_eq!(file.body(), outlook.body);
+            assert_eq!(file.body_html(), outlook.body_html);
+            assert_eq!(file.attachment_count(), outlook.attachments.len());
+            for (idx, attachment) in outlook.attachments.iter().enumerate() {
+                assert_eq!(file.attachment(idx).unwrap().as_ref(), Some(attachment));
+            }
+            assert!(file.attachment(outlook.attachments.len()).unwrap().is_none());
+            assert_eq!(file.outlook().unwrap(), outlook);
+        }
+    }
+}
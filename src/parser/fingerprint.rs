@@ -0,0 +1,147 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::Read,
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::ole::{EntryType, Reader};
+
+use super::{error::Error, storage::normalize_name};
+
+// Names of the streams, in the form of normalize_name.
+const PROPERTIES_STREAM: &str = "__PROPERTIES_VERSION1.0";
+const VALUE_STREAM_PREFIX: &str = "__SUBSTG1.0_";
+// Prefixes of the recipient and attachment storages
+const OBJECT_STORAGE_PREFIXES: [&str; 2] = ["__RECIP_VERSION1.0_", "__ATTACH_VERSION1.0_"];
+
+// Size of an entry of the property stream. Refer to MS-OXMSG 2.4.2
+const PROPERTY_ENTRY_SIZE: usize = 16;
+
+// StructureFingerprint describes how a message file is laid out,
+// rather than what it says, so that files can be clustered by
+// the generator which wrote them, e.g. an Outlook version or a
+// library. Embedded messages are included.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StructureFingerprint {
+    pub major_version: u16,        // Of the OLE file, 3 or 4
+    pub storages: usize,           // Root storage excluded
+    pub streams: usize,
+    pub mini_streams: usize,       // Streams held in the mini stream
+    pub mini_stream_bytes: u64,    // Size of the streams above
+    pub regular_stream_bytes: u64, // Size of the other streams
+    pub value_streams: usize,      // "__substg1.0_" streams
+    pub property_types: BTreeMap<String, usize>, // Property stream entries by type, e.g. "0x001F"
+}
+
+impl StructureFingerprint {
+    pub fn from_reader(parser: &Reader) -> Self {
+        let names: HashMap<u32, (String, EntryType)> = parser
+            .iterate()
+            .map(|entry| (entry.id(), (normalize_name(entry.name()), entry._type())))
+            .collect();
+        let mut fingerprint = Self {
+            major_version: parser.major_version(),
+            ..Self::default()
+        };
+        for entry in parser.iterate() {
+            match entry._type() {
+                EntryType::UserStorage => {
+                    fingerprint.storages += 1;
+                    continue;
+                }
+                EntryType::UserStream => {}
+                _ => continue,
+            }
+            fingerprint.streams += 1;
+            if entry.is_short() {
+                fingerprint.mini_streams += 1;
+                fingerprint.mini_stream_bytes += entry.len() as u64;
+            } else {
+                fingerprint.regular_stream_bytes += entry.len() as u64;
+            }
+            let name = normalize_name(entry.name());
+            if name.starts_with(VALUE_STREAM_PREFIX) {
+                fingerprint.value_streams += 1;
+            }
+            if name != PROPERTIES_STREAM {
+                continue;
+            }
+            let parent = entry.parent_node().and_then(|id| names.get(&id));
+            let header_size = match parent {
+                Some((_, EntryType::RootStorage)) if entry.len() % PROPERTY_ENTRY_SIZE == 8 => 24,
+                Some((_, EntryType::RootStorage)) => 32,
+                Some((name, _)) if OBJECT_STORAGE_PREFIXES.iter().any(|p| name.starts_with(p)) => {
+                    8
+                }
+                // Embedded messages
+                _ => 24,
+            };
+            let mut stream = vec![];
+            let read = parser
+                .get_entry_slice(entry)
+                .map(|mut slice| slice.read_to_end(&mut stream));
+            if !matches!(read, Ok(Ok(_))) {
+                continue;
+            }
+            let properties = stream.get(header_size..).unwrap_or_default();
+            for property in properties.chunks_exact(PROPERTY_ENTRY_SIZE) {
+                let code = u16::from_le_bytes([property[0], property[1]]);
+                *fingerprint
+                    .property_types
+                    .entry(format!("0x{:04X}", code))
+                    .or_insert(0) += 1;
+            }
+        }
+        fingerprint
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let parser = Reader::from_file(File::open(path)?)?;
+        Ok(Self::from_reader(&parser))
+    }
+
+    // Share of the streams held in the mini stream, 0 without streams.
+    pub fn mini_stream_ratio(&self) -> f64 {
+        if self.streams == 0 {
+            return 0.0;
+        }
+        self.mini_streams as f64 / self.streams as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StructureFingerprint;
+    use crate::testing::MessageBuilder;
+    use crate::ole::Reader;
+
+    #[test]
+    fn test_fingerprint() {
+        let bytes = MessageBuilder::new()
+            .subject("Hello")
+            .body(&"a".repeat(4096))
+            .recipient("Jane", "jane@example.com")
+            .build();
+        let fingerprint = StructureFingerprint::from_reader(&Reader::new(&bytes[..]).unwrap());
+        assert_eq!(fingerprint.major_version, 3);
+        // Named property mapping and recipient
+        assert_eq!(fingerprint.storages, 2);
+        // Root and recipient properties, five strings and the empty
+        // streams of the named property mapping
+        assert_eq!(fingerprint.streams, 10);
+        assert_eq!(fingerprint.value_streams, 8);
+        // Only the body is large enough for sectors of its own
+        assert_eq!(fingerprint.mini_streams, 6);
+        assert_eq!(fingerprint.regular_stream_bytes, 8192);
+        assert_eq!(fingerprint.property_types["0x001F"], 5);
+        assert_eq!(fingerprint.property_types["0x0003"], 1);
+
+        let fingerprint = StructureFingerprint::from_path("data/test_email.msg").unwrap();
+        assert!(fingerprint.mini_stream_ratio() > 0.5);
+        assert_eq!(fingerprint, StructureFingerprint::from_path("data/test_email.msg").unwrap());
+        assert_ne!(fingerprint, StructureFingerprint::from_path("data/unicode.msg").unwrap());
+    }
+}
@@ -0,0 +1,25 @@
+// Helpers shared by the tests of the parser.
+use crate::ole::Reader;
+
+use super::{decode::DataType, storage::Storages};
+
+// Storages of data/unicode.msg, with the root properties
+// overridden by props.
+pub(crate) fn storages(props: Vec<(&str, DataType)>) -> Storages {
+    let parser = Reader::from_path("data/unicode.msg").unwrap();
+    let mut storages = Storages::processed(&parser);
+    for (key, value) in props {
+        storages.root.insert(key.to_string(), value);
+    }
+    storages
+}
+
+// Offset of the directory entry named name, looked for in the
+// 128-byte slots following the 512-byte header of the file.
+pub(crate) fn entry_offset(bytes: &[u8], name: &str) -> usize {
+    let name: Vec<u8> = name.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    (512..bytes.len())
+        .step_by(128)
+        .find(|&offset| bytes[offset..].starts_with(&name))
+        .unwrap()
+}
@@ -0,0 +1,263 @@
+use super::outlook::Person;
+
+// Returns the unfolded value of the first header field `name`,
+// matched regardless of case. Refer to RFC 5322 2.2.3
+pub fn header_value(text: &str, name: &str) -> Option<String> {
+    let mut lines = text.split("\r\n").map(|line| line.trim_end_matches('\n'));
+    while let Some(line) = lines.next() {
+        let (field, value) = match line.split_once(':') {
+            Some(pair) => pair,
+            None => continue,
+        };
+        if !field.trim().eq_ignore_ascii_case(name) || line.starts_with([' ', '\t']) {
+            continue;
+        }
+        let mut value = value.trim().to_string();
+        // Continuation lines start with a whitespace
+        for next in lines.by_ref() {
+            if !next.starts_with([' ', '\t']) {
+                break;
+            }
+            if !value.is_empty() {
+                value.push(' ');
+            }
+            value.push_str(next.trim());
+        }
+        return Some(value);
+    }
+    None
+}
+
+// Message-IDs of a field such as In-Reply-To or References, without
+// their angle brackets. Refer to RFC 5322 3.6.4
+pub fn message_ids(text: &str) -> Vec<String> {
+    let ids: Vec<String> = text
+        .split('<')
+        .skip(1)
+        .filter_map(|part| part.split_once('>'))
+        .map(|(id, _)| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect();
+    if !ids.is_empty() || text.contains('<') {
+        return ids;
+    }
+    // Ids written without brackets
+    text.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|id| !id.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+// Content-ID of a cid: URL as found in HTML bodies, or of a
+// Content-ID header, without angle brackets. URLs are percent
+// decoded. Refer to RFC 2392 2
+pub fn content_id(url_or_id: &str) -> String {
+    let text = url_or_id.trim();
+    let id = match text.get(..4) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("cid:") => percent_decode(&text[4..]),
+        _ => text.to_string(),
+    };
+    id.trim().trim_start_matches('<').trim_end_matches('>').trim().to_string()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+        match hex.filter(|_| bytes[i] == b'%').map(|hex| u8::from_str_radix(hex, 16)) {
+            Some(Ok(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Splits an address list on the commas outside of quoted strings,
+// comments and angle brackets.
+fn split_addresses(list: &str) -> Vec<String> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut in_angle = false;
+    let mut comment_depth = 0usize;
+    let mut escaped = false;
+    for c in list.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes || comment_depth > 0 => escaped = true,
+            '"' if comment_depth == 0 => in_quotes = !in_quotes,
+            '(' if !in_quotes => comment_depth += 1,
+            ')' if !in_quotes && comment_depth > 0 => comment_depth -= 1,
+            '<' if !in_quotes && comment_depth == 0 => in_angle = true,
+            '>' if !in_quotes && comment_depth == 0 => in_angle = false,
+            // Group syntax: "name: a@x, b@y;"
+            ':' | ';' if !in_quotes && !in_angle && comment_depth == 0 => {
+                if c == ':' {
+                    current.clear();
+                } else {
+                    parts.push(std::mem::take(&mut current));
+                }
+                continue;
+            }
+            ',' if !in_quotes && !in_angle && comment_depth == 0 => {
+                parts.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    parts.push(current);
+    parts
+        .into_iter()
+        .map(|part| part.trim().to_string())
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+// Removes comments and the quotes around a display name.
+fn clean_name(name: &str) -> String {
+    let mut cleaned = String::new();
+    let mut comment_depth = 0usize;
+    for c in name.chars() {
+        match c {
+            '(' => comment_depth += 1,
+            ')' if comment_depth > 0 => comment_depth -= 1,
+            '"' | '\\' => {}
+            _ if comment_depth == 0 => cleaned.push(c),
+            _ => {}
+        }
+    }
+    cleaned.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn parse_address(address: &str) -> Person {
+    match (address.find('<'), address.rfind('>')) {
+        (Some(start), Some(end)) if start < end => Person::new(
+            clean_name(&address[..start]),
+            address[start + 1..end].trim().to_string(),
+        ),
+        _ => {
+            // Bare address, possibly followed by a comment holding the name
+            let email = clean_name(&address.replace(['(', ')'], " "));
+            let mut words = email.split_whitespace();
+            let addr = words.next().unwrap_or_default().to_string();
+            let name = words.collect::<Vec<&str>>().join(" ");
+            if addr.contains('@') {
+                Person::new(name, addr)
+            } else {
+                Person::new(clean_name(address), String::new())
+            }
+        }
+    }
+}
+
+// Parses an address list such as the value of the To, Cc
+// or Reply-To headers. Refer to RFC 5322 3.4
+//
+// Outlook also writes display names holding commas without
+// quoting them, e.g. Doe, John <john@doe.com>: a part without
+// any address is joined with the part that follows it.
+pub fn parse_address_list(list: &str) -> Vec<Person> {
+    let mut persons = vec![];
+    let mut pending = String::new();
+    for part in split_addresses(list) {
+        let part = if pending.is_empty() {
+            part
+        } else {
+            format!("{}, {}", std::mem::take(&mut pending), part)
+        };
+        if !part.contains('@') {
+            pending = part;
+            continue;
+        }
+        persons.push(parse_address(&part));
+    }
+    if !pending.is_empty() {
+        persons.push(parse_address(&pending));
+    }
+    persons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_id, header_value, message_ids, parse_address_list, Person};
+
+    fn person(name: &str, email: &str) -> Person {
+        Person::new(name.to_string(), email.to_string())
+    }
+
+    #[test]
+    fn test_header_value() {
+        let text = "To: a@x.com\r\nCc: \"Doe, John\" <j@x.com>,\r\n\tJane <jane@x.com>\r\nBcc: b@x.com\r\n";
+        assert_eq!(
+            header_value(text, "cc").unwrap(),
+            "\"Doe, John\" <j@x.com>, Jane <jane@x.com>"
+        );
+        assert_eq!(header_value(text, "Bcc").unwrap(), "b@x.com");
+        assert!(header_value(text, "Reply-To").is_none());
+        // Value on the next line only
+        let text = "Message-ID:\r\n <a@x.com>\r\n";
+        assert_eq!(header_value(text, "Message-ID").unwrap(), "<a@x.com>");
+    }
+
+    #[test]
+    fn test_message_ids() {
+        assert_eq!(
+            message_ids("<a@x.com>\r\n <b@x.com> (comment) <c@x.com>"),
+            vec!["a@x.com", "b@x.com", "c@x.com"]
+        );
+        assert_eq!(message_ids("a@x.com, b@x.com"), vec!["a@x.com", "b@x.com"]);
+        assert!(message_ids("").is_empty());
+    }
+
+    #[test]
+    fn test_content_id() {
+        assert_eq!(content_id("cid:image001.png@01D63E5A"), "image001.png@01D63E5A");
+        assert_eq!(content_id("CID:my%20logo%40x.com"), "my logo@x.com");
+        assert_eq!(content_id(" <logo@x.com> "), "logo@x.com");
+        // Ids are percent decoded in URLs only
+        assert_eq!(content_id("100%25"), "100%25");
+        assert_eq!(content_id("cid:100%2"), "100%2");
+    }
+
+    #[test]
+    fn test_parse_address_list() {
+        assert_eq!(
+            parse_address_list("\"Doe, John\" <j@x.com>, Jane Roe <jane@x.com>"),
+            vec![person("Doe, John", "j@x.com"), person("Jane Roe", "jane@x.com")]
+        );
+        // Unquoted comma in display name
+        assert_eq!(
+            parse_address_list("Doe, John <j@x.com>, a@x.com"),
+            vec![person("Doe, John", "j@x.com"), person("", "a@x.com")]
+        );
+        // Comments and groups
+        assert_eq!(
+            parse_address_list("team: a@x.com (Alice), Bob <b@x.com>;, c@x.com"),
+            vec![
+                person("Alice", "a@x.com"),
+                person("Bob", "b@x.com"),
+                person("", "c@x.com")
+            ]
+        );
+        assert_eq!(
+            parse_address_list("\"Smith \\\"JS\\\" John\" <js@x.com>"),
+            vec![person("Smith JS John", "js@x.com")]
+        );
+        assert_eq!(parse_address_list("Undisclosed recipients"), vec![person("Undisclosed recipients", "")]);
+        assert!(parse_address_list("").is_empty());
+    }
+}
@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+
+// Message class prefixes of the kinds of items, matched regardless
+// of case. Longer prefixes come first.
+// Refer to MS-OXCMSG 2.2.1.3 and MS-OXPROPS "MessageClass"
+const CLASS_PREFIXES: [(&str, ItemKind); 11] = [
+    ("IPM.Schedule.Meeting", ItemKind::Meeting),
+    ("IPM.Appointment", ItemKind::Appointment),
+    ("IPM.Contact", ItemKind::Contact),
+    ("IPM.DistList", ItemKind::DistributionList),
+    ("IPM.TaskRequest", ItemKind::TaskRequest),
+    ("IPM.Task", ItemKind::Task),
+    ("IPM.StickyNote", ItemKind::StickyNote),
+    ("IPM.Activity", ItemKind::Journal),
+    ("IPM.Post", ItemKind::Post),
+    ("IPM.Note", ItemKind::Note),
+    ("REPORT.", ItemKind::Report),
+];
+
+// ItemKind tells what kind of Outlook item a message is,
+// from its message class. A missing or empty class is a note,
+// as are messages parsed before kinds were told apart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ItemKind {
+    #[default]
+    Note,
+    Post,
+    Report,
+    Appointment,
+    // Meeting requests, updates, responses and cancellations
+    Meeting,
+    TaskRequest,
+    Contact,
+    DistributionList,
+    Task,
+    StickyNote,
+    Journal,
+    // Any other class, including custom ones
+    Other,
+}
+
+impl ItemKind {
+    pub fn from_class(class: &str) -> Self {
+        if class.trim().is_empty() {
+            return ItemKind::Note;
+        }
+        let matches = |prefix: &str| {
+            let rest = match class.get(..prefix.len()) {
+                Some(head) if head.eq_ignore_ascii_case(prefix) => &class[prefix.len()..],
+                _ => return false,
+            };
+            // "IPM.Note" is not a prefix of "IPM.Notes"
+            prefix.ends_with('.') || rest.is_empty() || rest.starts_with('.')
+        };
+        CLASS_PREFIXES
+            .iter()
+            .find(|(prefix, _)| matches(prefix))
+            .map_or(ItemKind::Other, |(_, kind)| *kind)
+    }
+
+    // Whether items of this kind are sent, so that they have
+    // transport headers, a sender and recipients. Contacts, tasks,
+    // notes and journal entries are only ever stored.
+    pub fn is_sent(self) -> bool {
+        !matches!(
+            self,
+            ItemKind::Contact
+                | ItemKind::DistributionList
+                | ItemKind::Task
+                | ItemKind::StickyNote
+                | ItemKind::Journal
+        )
+    }
+
+    // Whether items of this kind are calendar items, see Appointment.
+    pub fn is_calendar(self) -> bool {
+        matches!(self, ItemKind::Appointment | ItemKind::Meeting)
+    }
+}
+
+// Values of "AttachMethod".
+// Refer to MS-OXCMSG 2.2.2.9
+const ATTACH_BY_VALUE: i32 = 0x01;
+const ATTACH_BY_REFERENCE: i32 = 0x02;
+const ATTACH_BY_REFERENCE_ONLY: i32 = 0x04;
+const ATTACH_EMBEDDED_MESSAGE: i32 = 0x05;
+const ATTACH_OLE: i32 = 0x06;
+const ATTACH_BY_WEB_REFERENCE: i32 = 0x07;
+
+// AttachmentKind tells how an attachment holds its content,
+// from its attach method. Only files attached by value have
+// their content in Attachment.payload.
+// Attachments parsed before kinds were told apart are by value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum AttachmentKind {
+    // No content yet, as while an attachment is created
+    None,
+    #[default]
+    ByValue,
+    // A file on a shared drive, in "AttachLongPathname"
+    ByReference,
+    ByReferenceOnly,
+    // A message, see Attachment.embedded_message
+    EmbeddedMessage,
+    // An OLE object, such as an embedded spreadsheet, stored
+    // as a storage rather than as a file
+    OleObject,
+    // A file on a web service, such as OneDrive
+    WebReference,
+    // Any other method, such as the obsolete afByReferenceResolve
+    Other(i32),
+}
+
+impl AttachmentKind {
+    pub fn from_method(method: i32) -> Self {
+        match method {
+            0 => AttachmentKind::None,
+            ATTACH_BY_VALUE => AttachmentKind::ByValue,
+            ATTACH_BY_REFERENCE => AttachmentKind::ByReference,
+            ATTACH_BY_REFERENCE_ONLY => AttachmentKind::ByReferenceOnly,
+            ATTACH_EMBEDDED_MESSAGE => AttachmentKind::EmbeddedMessage,
+            ATTACH_OLE => AttachmentKind::OleObject,
+            ATTACH_BY_WEB_REFERENCE => AttachmentKind::WebReference,
+            method => AttachmentKind::Other(method),
+        }
+    }
+
+    // Whether the content is a file outside of the message.
+    pub fn is_reference(self) -> bool {
+        matches!(
+            self,
+            AttachmentKind::ByReference
+                | AttachmentKind::ByReferenceOnly
+                | AttachmentKind::WebReference
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttachmentKind, ItemKind};
+
+    #[test]
+    fn test_from_class() {
+        assert_eq!(ItemKind::from_class("IPM.Note"), ItemKind::Note);
+        assert_eq!(ItemKind::from_class("ipm.note.SMIME.MultipartSigned"), ItemKind::Note);
+        assert_eq!(ItemKind::from_class("IPM.Schedule.Meeting.Request"), ItemKind::Meeting);
+        assert_eq!(ItemKind::from_class("IPM.Appointment"), ItemKind::Appointment);
+        assert_eq!(ItemKind::from_class("IPM.Contact"), ItemKind::Contact);
+        assert_eq!(ItemKind::from_class("IPM.Task"), ItemKind::Task);
+        assert_eq!(ItemKind::from_class("IPM.TaskRequest.Accept"), ItemKind::TaskRequest);
+        assert_eq!(ItemKind::from_class("REPORT.IPM.Note.NDR"), ItemKind::Report);
+        assert_eq!(ItemKind::from_class("IPM.Notes"), ItemKind::Other);
+        assert_eq!(ItemKind::from_class("IPM"), ItemKind::Other);
+        assert_eq!(ItemKind::from_class(""), ItemKind::Note);
+        assert_eq!(ItemKind::from_class(" "), ItemKind::Note);
+        assert!(!ItemKind::Contact.is_sent());
+        assert!(ItemKind::Meeting.is_sent() && ItemKind::Meeting.is_calendar());
+    }
+
+    #[test]
+    fn test_from_method() {
+        assert_eq!(AttachmentKind::from_method(1), AttachmentKind::ByValue);
+        assert_eq!(AttachmentKind::from_method(5), AttachmentKind::EmbeddedMessage);
+        assert_eq!(AttachmentKind::from_method(6), AttachmentKind::OleObject);
+        assert_eq!(AttachmentKind::from_method(3), AttachmentKind::Other(3));
+        assert!(AttachmentKind::from_method(7).is_reference());
+        assert!(!AttachmentKind::from_method(1).is_reference());
+    }
+}
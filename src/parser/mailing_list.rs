@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+use super::{headers::header_value, storage::Storages};
+
+// Value of List-Unsubscribe-Post for one-click unsubscription.
+// Refer to RFC 8058
+const ONE_CLICK: &str = "List-Unsubscribe=One-Click";
+
+// MailingList holds the list-management fields of a message sent
+// through a mailing list. Refer to RFC 2919 and RFC 2369
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MailingList {
+    pub id: String,               // List-Id, without angle brackets, e.g. "dev.lists.example.org"
+    pub name: String,             // Description of the list before its List-Id, if any
+    pub unsubscribe: Vec<String>, // "ListUnsubscribe", or List-Unsubscribe header, URIs in order
+    pub one_click: bool,          // List-Unsubscribe-Post allows one-click unsubscription
+    pub subscribe: Vec<String>,   // "ListSubscribe", or List-Subscribe header
+    pub help: Vec<String>,        // "ListHelp", or List-Help header
+}
+
+impl MailingList {
+    // List fields of the message, none when it has none of them.
+    // Properties are preferred over headers, as for threading fields.
+    pub(crate) fn create(storages: &Storages, headers_text: &str) -> Option<Self> {
+        let field = |key: &str, header: &str| {
+            let value = storages.get_val_from_root_or_default(key);
+            if !value.trim().is_empty() {
+                return value;
+            }
+            header_value(headers_text, header).unwrap_or_default()
+        };
+        let (id, name) = list_id(&header_value(headers_text, "List-Id").unwrap_or_default());
+        let list = Self {
+            id,
+            name,
+            unsubscribe: list_uris(&field("ListUnsubscribe", "List-Unsubscribe")),
+            one_click: header_value(headers_text, "List-Unsubscribe-Post")
+                .is_some_and(|value| value.eq_ignore_ascii_case(ONE_CLICK)),
+            subscribe: list_uris(&field("ListSubscribe", "List-Subscribe")),
+            help: list_uris(&field("ListHelp", "List-Help")),
+        };
+        (list != Self::default()).then_some(list)
+    }
+}
+
+// Identifier and description of a List-Id field, such as
+// "Dev list" <dev.lists.example.org>. Refer to RFC 2919 3
+fn list_id(value: &str) -> (String, String) {
+    match value.split_once('<') {
+        Some((name, rest)) => {
+            let id = rest.split('>').next().unwrap_or_default();
+            (id.trim().to_string(), name.trim().trim_matches('"').trim().to_string())
+        }
+        None => (value.trim().to_string(), String::new()),
+    }
+}
+
+// URIs of a list field such as List-Unsubscribe, without their
+// angle brackets, comments left out. Refer to RFC 2369 2
+fn list_uris(value: &str) -> Vec<String> {
+    let uris: Vec<String> = value
+        .split('<')
+        .skip(1)
+        .filter_map(|part| part.split_once('>'))
+        .map(|(uri, _)| uri.chars().filter(|c| !c.is_whitespace()).collect::<String>())
+        .filter(|uri| !uri.is_empty())
+        .collect();
+    if !uris.is_empty() || value.contains('<') || value.trim().is_empty() {
+        return uris;
+    }
+    // A single URI written without brackets
+    vec![value.trim().to_string()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{list_id, list_uris};
+
+    #[test]
+    fn test_list_id() {
+        let parse = |value: &str| {
+            let (id, name) = list_id(value);
+            format!("{}|{}", id, name)
+        };
+        assert_eq!(parse("\"Dev list\" <dev.lists.example.org>"), "dev.lists.example.org|Dev list");
+        assert_eq!(parse("<announce.example.org>"), "announce.example.org|");
+        assert_eq!(parse("announce.example.org"), "announce.example.org|");
+        assert_eq!(parse(""), "|");
+    }
+
+    #[test]
+    fn test_list_uris() {
+        assert_eq!(
+            list_uris("<mailto:leave@example.org?subject=bye>, (Web) <https://example.org/u/1>"),
+            ["mailto:leave@example.org?subject=bye", "https://example.org/u/1"]
+        );
+        // Folded within a URI
+        assert_eq!(list_uris("<https://example.org/ u/1>"), ["https://example.org/u/1"]);
+        assert_eq!(list_uris("mailto:leave@example.org"), ["mailto:leave@example.org"]);
+        assert!(list_uris(" ").is_empty());
+        assert!(list_uris("<>").is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+
+use super::outlook::{Attachment, Outlook};
+
+// AttachmentMetadata describes an attachment without its payload.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttachmentMetadata {
+    pub name: String,   // See Attachment.name
+    pub mime: String,   // "AttachMimeTag"
+    pub size: usize,    // Payload size in bytes
+    pub sha256: String, // Hex encoded digest of the payload
+}
+
+impl From<&Attachment> for AttachmentMetadata {
+    fn from(attachment: &Attachment) -> Self {
+        // Payloads are hex encoded binary properties
+        let payload = hex::decode(&attachment.payload)
+            .unwrap_or_else(|_| attachment.payload.as_bytes().to_vec());
+        Self {
+            name: attachment.name.clone(),
+            mime: attachment.mime_tag.clone(),
+            size: payload.len(),
+            sha256: hex::encode(Sha256::digest(&payload)),
+        }
+    }
+}
+
+// MetadataOnly serializes a message with attachments reduced
+// to their metadata, keeping payloads out of the output:
+// serde_json::to_string(&MetadataOnly(&outlook))
+// Embedded messages are serialized the same way, under the
+// attachment they are stored in. Other fields are as in Outlook.
+pub struct MetadataOnly<'a>(pub &'a Outlook);
+
+// Attachment as MetadataOnly serializes it, see AttachmentMetadata.
+#[derive(Serialize)]
+struct AttachmentEntry<'a> {
+    name: String,
+    mime: String,
+    size: usize,
+    sha256: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    embedded_message: Option<MetadataOnly<'a>>,
+}
+
+impl<'a> From<&'a Attachment> for AttachmentEntry<'a> {
+    fn from(attachment: &'a Attachment) -> Self {
+        let metadata = AttachmentMetadata::from(attachment);
+        Self {
+            name: metadata.name,
+            mime: metadata.mime,
+            size: metadata.size,
+            sha256: metadata.sha256,
+            embedded_message: attachment.embedded_message.as_deref().map(MetadataOnly),
+        }
+    }
+}
+
+impl Serialize for MetadataOnly<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let outlook = self.0;
+        let attachments: Vec<AttachmentEntry> =
+            outlook.attachments.iter().map(AttachmentEntry::from).collect();
+        let mut state = serializer.serialize_struct("Outlook", 37)?;
+        state.serialize_field("headers", &outlook.headers)?;
+        state.serialize_field("headers_present", &outlook.headers_present)?;
+        match &outlook.raw_headers {
+            Some(raw_headers) => state.serialize_field("raw_headers", raw_headers)?,
+            None => state.skip_field("raw_headers")?,
+        }
+        state.serialize_field("sender", &outlook.sender)?;
+        state.serialize_field("to", &outlook.to)?;
+        state.serialize_field("cc", &outlook.cc)?;
+        state.serialize_field("bcc", &outlook.bcc)?;
+        state.serialize_field("bcc_recipients", &outlook.bcc_recipients)?;
+        state.serialize_field("subject", &outlook.subject)?;
+        state.serialize_field("subject_ansi", &outlook.subject_ansi)?;
+        state.serialize_field("subject_prefix", &outlook.subject_prefix)?;
+        state.serialize_field("in_reply_to", &outlook.in_reply_to)?;
+        state.serialize_field("references", &outlook.references)?;
+        match &outlook.mailing_list {
+            Some(mailing_list) => state.serialize_field("mailing_list", mailing_list)?,
+            None => state.skip_field("mailing_list")?,
+        }
+        state.serialize_field("conversation_index", &outlook.conversation_index)?;
+        state.serialize_field("body", &outlook.body)?;
+        state.serialize_field("body_html", &outlook.body_html)?;
+        state.serialize_field("rtf_compressed", &outlook.rtf_compressed)?;
+        state.serialize_field("body_content_id", &outlook.body_content_id)?;
+        state.serialize_field("body_content_location", &outlook.body_content_location)?;
+        state.serialize_field("attachments", &attachments)?;
+        state.serialize_field("message_size", &outlook.message_size)?;
+        state.serialize_field("property_count", &outlook.property_count)?;
+        state.serialize_field("property_header", &outlook.property_header)?;
+        state.serialize_field("report", &outlook.report)?;
+        state.serialize_field("kind", &outlook.kind)?;
+        state.serialize_field("appointment", &outlook.appointment)?;
+        state.serialize_field("system_message", &outlook.system_message)?;
+        state.serialize_field("icon_index", &outlook.icon_index)?;
+        state.serialize_field("last_verb", &outlook.last_verb)?;
+        state.serialize_field("sync_keys", &outlook.sync_keys)?;
+        state.serialize_field("timestamps", &outlook.timestamps)?;
+        state.serialize_field("sent_at", &outlook.sent_at)?;
+        state.serialize_field("received_at", &outlook.received_at)?;
+        state.serialize_field("headers_source", &outlook.headers_source())?;
+        state.serialize_field("client_info", outlook.client_info())?;
+        state.serialize_field("message_class", outlook.message_class())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttachmentMetadata, MetadataOnly};
+    use crate::parser::outlook::Outlook;
+
+    #[test]
+    fn test_metadata_only() {
+        let outlook = Outlook::from_path("data/unicode.msg").unwrap();
+        let json = serde_json::to_value(MetadataOnly(&outlook)).unwrap();
+        assert_eq!(json["subject"], "Test for TIF files");
+
+        let attachments = json["attachments"].as_array().unwrap();
+        assert_eq!(attachments.len(), outlook.attachments.len());
+        let metadata: AttachmentMetadata =
+            serde_json::from_value(attachments[0].clone()).unwrap();
+        assert_eq!(metadata, AttachmentMetadata::from(&outlook.attachments[0]));
+        assert_eq!(metadata.size, outlook.attachments[0].payload.len() / 2);
+        assert_eq!(metadata.sha256.len(), 64);
+        assert!(attachments[0].get("payload").is_none());
+
+        // Other fields are as in the full output
+        let mut full = serde_json::to_value(&outlook).unwrap();
+        let mut reduced = json.clone();
+        full.as_object_mut().unwrap().remove("attachments");
+        reduced.as_object_mut().unwrap().remove("attachments");
+        assert_eq!(reduced, full);
+
+        // Much smaller than the full output
+        let full = outlook.to_json().unwrap();
+        let reduced = serde_json::to_string(&MetadataOnly(&outlook)).unwrap();
+        assert!(reduced.len() * 10 < full.len());
+    }
+
+    #[test]
+    fn test_metadata_only_embedded() {
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        let (idx, embedded) = outlook
+            .attachments
+            .iter()
+            .enumerate()
+            .find_map(|(idx, a)| Some((idx, a.embedded_message.as_deref()?)))
+            .unwrap();
+        let json = serde_json::to_value(MetadataOnly(&outlook)).unwrap();
+        let attachment = &json["attachments"][idx];
+        assert!(attachment.get("payload").is_none());
+        let expected = serde_json::to_value(MetadataOnly(embedded)).unwrap();
+        assert_eq!(attachment["embedded_message"], expected);
+        assert_eq!(attachment["embedded_message"]["subject"], embedded.subject.as_str());
+        for attachment in attachment["embedded_message"]["attachments"].as_array().unwrap() {
+            assert!(attachment.get("payload").is_none());
+            assert!(attachment.get("sha256").is_some());
+        }
+        // Other attachments have none
+        assert!(json["attachments"][1 - idx].get("embedded_message").is_none());
+    }
+
+    #[test]
+    fn test_attachment_metadata() {
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        let metadata = AttachmentMetadata::from(&outlook.attachments[1]);
+        assert_eq!(metadata.name, outlook.attachments[1].name);
+        assert_eq!(metadata.mime, outlook.attachments[1].mime_tag);
+    }
+}
@@ -1,10 +1,90 @@
+mod appointment;
+pub use appointment::{Appointment, AttendeeResponse, BusyStatus, ResponseStatus};
+mod capabilities;
+pub use capabilities::{capabilities, Capabilities};
 mod constants;
+pub use constants::PropIdNameMap;
+mod container;
 mod decode;
+pub use decode::{DataType, StringDecoder, Utf16Encoding};
+mod diff;
+pub use diff::{ChangeKind, MessageDiff, PropertyChange};
+mod eml;
+mod entry_id;
+mod file;
+#[cfg(test)]
+mod fixtures;
+pub use file::OutlookFile;
+mod fingerprint;
+pub use fingerprint::StructureFingerprint;
+mod headers;
+mod kind;
+pub use kind::{AttachmentKind, ItemKind};
+mod named;
+pub use eml::{BodyPart, EmlOptions, TransferEncoding};
 mod storage;
+pub use storage::{Attachments, Properties, PropertyPaths, Recipients, StorageType, Storages};
 mod stream;
 
 mod error;
 pub use error::{DataTypeError, Error};
 
+mod opt;
+pub use opt::{OutlookOpt, PersonOpt, TransportHeadersOpt};
+
+mod options;
+pub use options::ParseOptions;
+
+mod summary;
+pub use summary::MessageSummary;
+
+mod events;
+pub use events::{MsgEvent, MsgEvents};
+
+mod mailing_list;
+pub use mailing_list::MailingList;
+
+mod metadata;
+pub use metadata::{AttachmentMetadata, MetadataOnly};
+
+mod property_set;
+
+mod raw_headers;
+pub use raw_headers::{RawHeaders, RawHeadersEncoding};
+
+mod report;
+pub use report::{ParseMetrics, ParseReport, ParseWarning};
+
+mod rtf;
+
+mod response;
+pub use response::{LastVerb, ResponseKind, Verb};
+
+mod scan;
+pub use scan::{AttachmentScan, ContentScanner, ScanTarget};
+
+mod search;
+pub use search::SearchScope;
+
+mod source;
+pub use source::{FieldSource, SourceKind};
+
+mod sync;
+pub use sync::{SyncKeys, Xid};
+
+mod system;
+pub use system::{ReportRecipient, SystemMessage, SystemMessageKind};
+
+mod text;
+pub use text::AttachmentTextExtractor;
+
+mod time;
+pub use time::{TimePolicy, Timestamp};
+
+mod verify;
+pub use verify::{Discrepancy, PropertyHeader};
+
 mod outlook;
-pub use outlook::{Attachment, Outlook, Person, TransportHeaders};
+pub use outlook::{
+    Attachment, ClientInfo, EmbeddedMessages, HeadersSource, Outlook, Person, TransportHeaders,
+};
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use crate::ole::{EntryType, Reader};
+
+use super::storage::normalize_name;
+
+// Property sets of the named properties.
+// Refer to MS-OXPROPS 1.3.2
+pub const PS_MAPI: &str = "00020328-0000-0000-C000-000000000046";
+pub const PS_PUBLIC_STRINGS: &str = "00020329-0000-0000-C000-000000000046";
+pub const PSETID_APPOINTMENT: &str = "00062002-0000-0000-C000-000000000046";
+pub const PSETID_COMMON: &str = "00062008-0000-0000-C000-000000000046";
+pub const PSETID_MEETING: &str = "6ED8DA90-450B-101B-98DA-00AA003F1305";
+
+// Named properties with a canonical name, by property set and LID.
+const KNOWN_NAMED_PROPERTIES: [(&str, u32, &str); 19] = [
+    (PSETID_APPOINTMENT, 0x8201, "AppointmentSequence"),
+    (PSETID_APPOINTMENT, 0x8203, "AppointmentLastSequence"),
+    (PSETID_APPOINTMENT, 0x8204, "ChangeHighlight"),
+    (PSETID_APPOINTMENT, 0x8205, "BusyStatus"),
+    (PSETID_APPOINTMENT, 0x8208, "Location"),
+    (PSETID_APPOINTMENT, 0x820D, "AppointmentStartWhole"),
+    (PSETID_APPOINTMENT, 0x820E, "AppointmentEndWhole"),
+    (PSETID_APPOINTMENT, 0x8213, "AppointmentDuration"),
+    (PSETID_APPOINTMENT, 0x8215, "AppointmentSubType"),
+    (PSETID_APPOINTMENT, 0x8217, "AppointmentStateFlags"),
+    (PSETID_APPOINTMENT, 0x8218, "ResponseStatus"),
+    (PSETID_APPOINTMENT, 0x8223, "Recurring"),
+    (PSETID_APPOINTMENT, 0x825D, "AppointmentUnsendableRecipients"),
+    (PSETID_MEETING, 0x0002, "Where"),
+    (PSETID_MEETING, 0x0003, "GlobalObjectId"),
+    (PSETID_MEETING, 0x0023, "CleanGlobalObjectId"),
+    (PSETID_MEETING, 0x0026, "MeetingType"),
+    (PSETID_COMMON, 0x8503, "ReminderSet"),
+    (PSETID_COMMON, 0x8580, "InternetAccountName"),
+];
+
+// Storage and streams of the property name mapping.
+// Refer to MS-OXMSG 2.2.3
+const NAMEID_STORAGE: &str = "__NAMEID_VERSION1.0";
+const GUID_STREAM: &str = "__SUBSTG1.0_00020102";
+const ENTRY_STREAM: &str = "__SUBSTG1.0_00030102";
+const STRING_STREAM: &str = "__SUBSTG1.0_00040102";
+
+// Named properties have ids from 0x8000 onwards.
+pub const NAMED_PROPERTY_ID_START: u32 = 0x8000;
+
+// NamedPropertyKey identifies a property within its property set,
+// either by a numerical LID or by a name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NamedPropertyKey {
+    Id(u32),
+    Name(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedProperty {
+    pub guid: String,
+    pub key: NamedPropertyKey,
+}
+
+impl NamedProperty {
+    // Known LIDs are given their canonical name, and properties
+    // named by a string are keyed by that string.
+    pub fn canonical_name(&self) -> Option<String> {
+        match &self.key {
+            NamedPropertyKey::Id(lid) => KNOWN_NAMED_PROPERTIES
+                .iter()
+                .find(|(guid, id, _)| *guid == self.guid && id == lid)
+                .map(|(_, _, name)| name.to_string()),
+            NamedPropertyKey::Name(name) => Some(name.clone()),
+        }
+    }
+}
+
+// NamedPropertyMap maps the property ids from 0x8000
+// to the named property they were assigned to.
+#[derive(Debug, Default)]
+pub struct NamedPropertyMap {
+    map: HashMap<u32, NamedProperty>,
+}
+
+// Formats a GUID as stored in the file, its first three
+// fields being little-endian.
+pub(crate) fn format_guid(bytes: &[u8]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{}-{}",
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        u16::from_le_bytes([bytes[4], bytes[5]]),
+        u16::from_le_bytes([bytes[6], bytes[7]]),
+        hex::encode_upper(&bytes[8..10]),
+        hex::encode_upper(&bytes[10..16]),
+    )
+}
+
+impl NamedPropertyMap {
+    fn read_stream(parser: &Reader, storage_id: u32, name: &str) -> Vec<u8> {
+        parser
+            .streams_of(storage_id)
+            .find(|entry| normalize_name(entry.name()) == name)
+            .and_then(|entry| parser.get_entry_slice_by_id(entry.id()).ok())
+            .unwrap_or_default()
+    }
+
+    // Reads the mapping of the file. Embedded messages use
+    // the mapping of the top level message.
+    pub fn from_reader(parser: &Reader) -> Self {
+        let root_id = parser
+            .iterate()
+            .find(|entry| entry._type() == EntryType::RootStorage)
+            .map_or(0, |entry| entry.id());
+        let storage_id = match parser.storages().find(|entry| {
+            entry.parent_node() == Some(root_id) && normalize_name(entry.name()) == NAMEID_STORAGE
+        }) {
+            Some(entry) => entry.id(),
+            None => return Self::default(),
+        };
+        let guids: Vec<String> = Self::read_stream(parser, storage_id, GUID_STREAM)
+            .chunks_exact(16)
+            .map(format_guid)
+            .collect();
+        let strings = Self::read_stream(parser, storage_id, STRING_STREAM);
+
+        let mut map = HashMap::new();
+        for entry in Self::read_stream(parser, storage_id, ENTRY_STREAM).chunks_exact(8) {
+            let name_id = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]);
+            let index_and_kind = u16::from_le_bytes([entry[4], entry[5]]);
+            let prop_index = u16::from_le_bytes([entry[6], entry[7]]) as u32;
+            // 1 and 2 refer to PS_MAPI and PS_PUBLIC_STRINGS,
+            // others to the GUID stream from 3 onwards.
+            let guid = match index_and_kind >> 1 {
+                1 => PS_MAPI.to_string(),
+                2 => PS_PUBLIC_STRINGS.to_string(),
+                index => match guids.get((index as usize).wrapping_sub(3)) {
+                    Some(guid) => guid.clone(),
+                    None => continue,
+                },
+            };
+            let key = if index_and_kind & 1 == 0 {
+                NamedPropertyKey::Id(name_id)
+            } else {
+                // Length of the UTF-16 name, then the name
+                let offset = name_id as usize;
+                let len = match strings.get(offset..offset + 4) {
+                    Some(len) => u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize,
+                    None => continue,
+                };
+                let name = match strings.get(offset + 4..offset + 4 + len) {
+                    Some(name) => name,
+                    None => continue,
+                };
+                let name: Vec<u16> = name
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                NamedPropertyKey::Name(String::from_utf16_lossy(&name))
+            };
+            map.insert(NAMED_PROPERTY_ID_START + prop_index, NamedProperty { guid, key });
+        }
+        Self { map }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn get(&self, prop_id: u32) -> Option<&NamedProperty> {
+        self.map.get(&prop_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_guid, NamedProperty, NamedPropertyKey, NamedPropertyMap, PSETID_MEETING};
+    use crate::ole::Reader;
+
+    #[test]
+    fn test_format_guid() {
+        let bytes = [
+            0x90, 0xDA, 0xD8, 0x6E, 0x0B, 0x45, 0x1B, 0x10, 0x98, 0xDA, 0x00, 0xAA, 0x00, 0x3F,
+            0x13, 0x05,
+        ];
+        assert_eq!(format_guid(&bytes), PSETID_MEETING);
+    }
+
+    #[test]
+    fn test_canonical_name() {
+        let global_id = NamedProperty {
+            guid: PSETID_MEETING.to_string(),
+            key: NamedPropertyKey::Id(0x0003),
+        };
+        assert_eq!(global_id.canonical_name().unwrap(), "GlobalObjectId");
+        let unknown = NamedProperty {
+            guid: PSETID_MEETING.to_string(),
+            key: NamedPropertyKey::Id(0x7777),
+        };
+        assert!(unknown.canonical_name().is_none());
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let parser = Reader::from_path("data/test_email.msg").unwrap();
+        let named = NamedPropertyMap::from_reader(&parser);
+        assert!(!named.is_empty());
+        assert!(named.get(0x7FFF).is_none());
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+use super::{
+    error::Error,
+    headers::header_value,
+    kind::ItemKind,
+    outlook::{Attachment, HeadersSource, Outlook, Person},
+    storage::{has_aliased, Storages},
+};
+
+// Outlook fields read from a single root property.
+const ROOT_FIELDS: [(&str, &str); 9] = [
+    ("sender.name", "SenderName"),
+    ("sender.email", "SenderSmtpAddress"),
+    ("bcc", "DisplayBcc"),
+    ("subject", "Subject"),
+    ("conversation_index", "ConversationIndex"),
+    ("body", "Body"),
+    ("rtf_compressed", "RtfCompressed"),
+    ("body_content_id", "BodyContentId"),
+    ("body_content_location", "BodyContentLocation"),
+];
+
+// Outlook fields left out of items which are never sent,
+// along with the threading and header fields.
+const SENT_ONLY_FIELDS: [&str; 2] = ["sender.name", "sender.email"];
+
+// Outlook fields read from a root property, or else a header.
+pub(crate) const THREADING_FIELDS: [(&str, &str, &str); 2] = [
+    ("in_reply_to", "InReplyToId", "In-Reply-To"),
+    ("references", "InternetReferences", "References"),
+];
+
+// TransportHeaders fields, with the header and the property
+// they are read from, if any, see HeadersSource.
+pub(crate) const HEADER_FIELDS: [(&str, &str, Option<&str>); 5] = [
+    ("headers.content_type", "Content-Type", None),
+    ("headers.date", "Date", None),
+    ("headers.message_id", "Message-ID", Some("InternetMessageId")),
+    ("headers.reply_to", "Reply-To", Some("ReplyRecipientNames")),
+    ("headers.content_transfer_encoding", "Content-Transfer-Encoding", None),
+];
+
+// Fields of Outlook whose property, or header, is absent from the
+// message, by path such as "headers.date". See Outlook::to_opt.
+// Fields of sent items only are absent from other kinds of items.
+pub(crate) fn absent_fields(
+    storages: &Storages,
+    kind: ItemKind,
+    headers_text: &str,
+    headers_source: HeadersSource,
+) -> BTreeSet<String> {
+    if !kind.is_sent() {
+        let mut absent = absent_fields(storages, ItemKind::Note, "", HeadersSource::Absent);
+        absent.extend(SENT_ONLY_FIELDS.iter().map(|field| field.to_string()));
+        absent.extend(THREADING_FIELDS.iter().map(|(field, _, _)| field.to_string()));
+        absent.extend(HEADER_FIELDS.iter().map(|(field, _, _)| field.to_string()));
+        return absent;
+    }
+    let in_root = |key: &str| has_aliased(&storages.root, key);
+    let in_headers = |header: &str| header_value(headers_text, header).is_some();
+    let mut absent: Vec<&str> = ROOT_FIELDS
+        .iter()
+        .filter(|(_, key)| !in_root(key))
+        .map(|(field, _)| *field)
+        .collect();
+    absent.extend(
+        THREADING_FIELDS
+            .iter()
+            .filter(|(_, key, header)| !in_root(key) && !in_headers(header))
+            .map(|(field, _, _)| *field),
+    );
+    absent.extend(
+        HEADER_FIELDS
+            .iter()
+            .filter(|(_, header, key)| match headers_source {
+                HeadersSource::Transport => !in_headers(header),
+                _ => !key.is_some_and(in_root),
+            })
+            .map(|(field, _, _)| *field),
+    );
+    absent.into_iter().map(String::from).collect()
+}
+
+// TransportHeadersOpt is TransportHeaders, with None for the
+// absent headers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransportHeadersOpt<'a> {
+    pub content_type: Option<&'a str>,
+    pub date: Option<&'a str>,
+    pub message_id: Option<&'a str>,
+    pub reply_to: Option<&'a str>,
+    pub content_transfer_encoding: Option<&'a str>,
+}
+
+// PersonOpt is the sender, with None for absent properties.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PersonOpt<'a> {
+    pub name: Option<&'a str>,
+    pub email: Option<&'a str>,
+}
+
+// OutlookOpt is a view of Outlook telling absent properties
+// from empty ones: the first are None, and null in JSON.
+// Recipients and attachments are lists, empty when absent, and
+// headers and sender are views of their own with None fields.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutlookOpt<'a> {
+    pub headers: TransportHeadersOpt<'a>,
+    pub sender: PersonOpt<'a>,
+    pub to: &'a [Person],
+    pub cc: &'a [Person],
+    pub bcc: Option<&'a str>,
+    pub bcc_recipients: &'a [Person],
+    pub subject: Option<&'a str>,
+    pub in_reply_to: Option<&'a str>,
+    pub references: Option<&'a str>,
+    pub conversation_index: Option<&'a str>,
+    pub body: Option<&'a str>,
+    pub body_html: Option<&'a str>,
+    pub rtf_compressed: Option<&'a str>,
+    pub body_content_id: Option<&'a str>,
+    pub body_content_location: Option<&'a str>,
+    pub attachments: &'a [Attachment],
+    pub message_size: Option<u64>,
+}
+
+impl<'a> OutlookOpt<'a> {
+    pub(crate) fn create(outlook: &'a Outlook, absent: &BTreeSet<String>) -> Self {
+        let opt = |field: &str, value: &'a str| (!absent.contains(field)).then_some(value);
+        let headers = &outlook.headers;
+        Self {
+            headers: TransportHeadersOpt {
+                content_type: opt("headers.content_type", &headers.content_type),
+                date: opt("headers.date", &headers.date),
+                message_id: opt("headers.message_id", &headers.message_id),
+                reply_to: opt("headers.reply_to", &headers.reply_to),
+                content_transfer_encoding: opt(
+                    "headers.content_transfer_encoding",
+                    &headers.content_transfer_encoding,
+                ),
+            },
+            sender: PersonOpt {
+                name: opt("sender.name", &outlook.sender.name),
+                email: opt("sender.email", &outlook.sender.email),
+            },
+            to: &outlook.to,
+            cc: &outlook.cc,
+            bcc: opt("bcc", &outlook.bcc),
+            bcc_recipients: &outlook.bcc_recipients,
+            subject: opt("subject", &outlook.subject),
+            in_reply_to: opt("in_reply_to", &outlook.in_reply_to),
+            references: opt("references", &outlook.references),
+            conversation_index: opt("conversation_index", &outlook.conversation_index),
+            body: opt("body", &outlook.body),
+            body_html: outlook.body_html.as_deref(),
+            rtf_compressed: opt("rtf_compressed", &outlook.rtf_compressed),
+            body_content_id: opt("body_content_id", &outlook.body_content_id),
+            body_content_location: opt("body_content_location", &outlook.body_content_location),
+            attachments: &outlook.attachments,
+            message_size: outlook.message_size,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        testing::{MessageBuilder, PropertyValue},
+        Outlook,
+    };
+
+    #[test]
+    fn test_to_opt() {
+        let bytes = MessageBuilder::new()
+            .subject("")
+            .sender("John Doe", "john@example.com")
+            .headers("Date: Mon, 18 Nov 2013 10:26:24 +0200\r\nReply-To: \r\n")
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        let opt = outlook.to_opt();
+        assert_eq!(opt.subject, Some(""));
+        assert_eq!(opt.body, None);
+        assert_eq!(outlook.body, "");
+        assert_eq!(opt.sender.name, Some("John Doe"));
+        assert_eq!(opt.headers.date, Some("Mon, 18 Nov 2013 10:26:24 +0200"));
+        assert_eq!(opt.headers.reply_to, Some(""));
+        assert_eq!(opt.headers.message_id, None);
+        assert_eq!(opt.in_reply_to, None);
+
+        let json = opt.to_json().unwrap();
+        assert!(json.contains(r#""subject":"""#));
+        assert!(json.contains(r#""body":null"#));
+
+        // Neither headers nor the properties they are rebuilt from
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        let opt = outlook.to_opt();
+        assert_eq!(opt.headers.date, None);
+        assert_eq!(opt.headers.message_id, None);
+        // Read from its alias, "SentRepresentingSmtpAddress"
+        assert_eq!(opt.sender.email, Some("marirs@outlook.com"));
+
+        let bytes = MessageBuilder::new()
+            .property("InternetMessageId", PropertyValue::String("<a@b>".to_string()))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.to_opt().headers.message_id, Some("<a@b>"));
+    }
+}
@@ -0,0 +1,240 @@
+use std::sync::Arc;
+
+use super::{
+    decode::StringDecoder, raw_headers::RawHeadersEncoding, scan::ContentScanner,
+    storage::Properties, text::AttachmentTextExtractor, time::TimePolicy,
+};
+
+// Embedded messages nested deeper than this are rejected by default.
+const DEFAULT_MAX_EMBEDDED_DEPTH: usize = 8;
+
+// Storages allowed across a message and all of its
+// embedded messages by default.
+const DEFAULT_MAX_STORAGES: usize = 4096;
+
+// Bytes extracted from a zip archive by default, see
+// ParseOptions::max_container_size.
+const DEFAULT_MAX_CONTAINER_SIZE: usize = 256 * 1024 * 1024;
+
+// ParseOptions controls how Outlook reads a message.
+//
+// Options are set with chained calls:
+// ParseOptions::new().unwrap_containers(true)
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    pub(crate) unwrap_containers: bool,
+    pub(crate) max_container_size: usize,
+    pub(crate) max_embedded_depth: usize,
+    pub(crate) max_storages: usize,
+    pub(crate) strict: bool,
+    pub(crate) max_property_size: Option<usize>,
+    pub(crate) time_policy: TimePolicy,
+    pub(crate) attachment_filter: AttachmentFilter,
+    pub(crate) string_decoder: Option<Arc<dyn StringDecoder>>,
+    pub(crate) attachment_text_extractor: Option<Arc<dyn AttachmentTextExtractor>>,
+    pub(crate) content_scanner: Option<Arc<dyn ContentScanner>>,
+    pub(crate) raw_headers: Option<RawHeadersEncoding>,
+    pub(crate) preserve_nuls: bool,
+    pub(crate) retain_properties: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            unwrap_containers: false,
+            max_container_size: DEFAULT_MAX_CONTAINER_SIZE,
+            max_embedded_depth: DEFAULT_MAX_EMBEDDED_DEPTH,
+            max_storages: DEFAULT_MAX_STORAGES,
+            strict: false,
+            max_property_size: None,
+            time_policy: TimePolicy::default(),
+            attachment_filter: AttachmentFilter::default(),
+            string_decoder: None,
+            attachment_text_extractor: None,
+            content_scanner: None,
+            raw_headers: None,
+            preserve_nuls: false,
+            retain_properties: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Detects a message wrapped in a zip archive or encoded
+    // in base64, and unwraps it before parsing.
+    pub fn unwrap_containers(mut self, value: bool) -> Self {
+        self.unwrap_containers = value;
+        self
+    }
+
+    // Maximum size in bytes of a message extracted from a zip
+    // archive, whatever size the archive declares, so that a zip
+    // bomb is not decompressed. Larger ones fail with
+    // Error::LimitExceeded. 256 MiB by default.
+    pub fn max_container_size(mut self, value: usize) -> Self {
+        self.max_container_size = value;
+        self
+    }
+
+    // Maximum nesting of embedded messages.
+    // Deeper messages fail with Error::LimitExceeded.
+    pub fn max_embedded_depth(mut self, value: usize) -> Self {
+        self.max_embedded_depth = value;
+        self
+    }
+
+    // Maximum number of storages in a message, embedded
+    // messages included. More fail with Error::LimitExceeded.
+    pub fn max_storages(mut self, value: usize) -> Self {
+        self.max_storages = value;
+        self
+    }
+
+    // Fails with Error::StrictViolation on the first anomaly
+    // recorded in the report, embedded messages included,
+    // instead of returning a partial message.
+    pub fn strict(mut self, value: bool) -> Self {
+        self.strict = value;
+        self
+    }
+
+    // Maximum size in bytes of a property value, e.g. a body.
+    // Larger values are truncated, which is recorded in the report
+    // as ParseWarning::PropertyTruncated. Unlimited by default.
+    pub fn max_property_size(mut self, value: usize) -> Self {
+        self.max_property_size = Some(value);
+        self
+    }
+
+    // How timestamps are converted, UTC by default.
+    // Raw FILETIME values are kept whatever the policy.
+    pub fn time_policy(mut self, value: TimePolicy) -> Self {
+        self.time_policy = value;
+        self
+    }
+
+    // Decodes only the attachment payloads matching one of the
+    // patterns, either an extension (".png") or a MIME type
+    // ("image/png", or "image/*" for all images).
+    // Other payloads are left empty, see Attachment.payload_filtered.
+    pub fn allow_attachments(mut self, patterns: &[&str]) -> Self {
+        self.attachment_filter
+            .allow
+            .extend(patterns.iter().map(|p| p.to_ascii_lowercase()));
+        self
+    }
+
+    // Leaves empty the attachment payloads matching one of the
+    // patterns, as given to allow_attachments, e.g. ".exe".
+    // Denied patterns win over allowed ones.
+    pub fn deny_attachments(mut self, patterns: &[&str]) -> Self {
+        self.attachment_filter
+            .deny
+            .extend(patterns.iter().map(|p| p.to_ascii_lowercase()));
+        self
+    }
+
+    // Decodes string properties with decoder, falling back to
+    // the default decoding where it returns None.
+    pub fn string_decoder<D: StringDecoder + 'static>(mut self, decoder: D) -> Self {
+        self.string_decoder = Some(Arc::new(decoder));
+        self
+    }
+
+    // Extracts the text of attachment payloads with extractor,
+    // see Attachment.text and Outlook::full_text.
+    pub fn attachment_text_extractor<E: AttachmentTextExtractor + 'static>(
+        mut self,
+        extractor: E,
+    ) -> Self {
+        self.attachment_text_extractor = Some(Arc::new(extractor));
+        self
+    }
+
+    // Scans the attachment payloads with scanner as they are read,
+    // see Attachment.classification. Payloads left out by
+    // allow_attachments or deny_attachments are scanned all the
+    // same, so that they need not be kept in memory. The scanner
+    // may leave out others, see ContentScanner::keep_payload.
+    pub fn content_scanner<S: ContentScanner + 'static>(mut self, scanner: S) -> Self {
+        self.content_scanner = Some(Arc::new(scanner));
+        self
+    }
+
+    // Keeps the transport headers as received, in encoding,
+    // see Outlook.raw_headers. Left out by default.
+    pub fn raw_headers(mut self, encoding: RawHeadersEncoding) -> Self {
+        self.raw_headers = Some(encoding);
+        self
+    }
+
+    // Keeps the NUL characters of string properties. By default
+    // strings end at their first NUL, and those with characters
+    // after it are recorded as ParseWarning::EmbeddedNul.
+    pub fn preserve_nuls(mut self, value: bool) -> Self {
+        self.preserve_nuls = value;
+        self
+    }
+
+    // Keeps the decoded properties of the message, recipients and
+    // attachments included, see Outlook::property. Left out by
+    // default, as they hold the bodies and payloads a second time.
+    pub fn retain_properties(mut self, value: bool) -> Self {
+        self.retain_properties = value;
+        self
+    }
+}
+
+// AttachmentFilter selects the attachments whose payload is
+// decoded, from their extension and MIME type.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AttachmentFilter {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl AttachmentFilter {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    fn matches(pattern: &str, extension: &str, mime: &str) -> bool {
+        match pattern.strip_suffix("/*") {
+            Some(mime_type) => mime.split('/').next() == Some(mime_type),
+            None if pattern.contains('/') => pattern == mime,
+            None => !extension.is_empty() && pattern.trim_start_matches('.') == extension,
+        }
+    }
+
+    // Whether the payload of the attachment with these
+    // properties is to be decoded.
+    pub(crate) fn allows(&self, props: &Properties) -> bool {
+        let value = |key: &str| -> String {
+            props.get(key).map_or(String::new(), |x| x.into())
+        };
+        let mut extension = value("AttachExtension");
+        if extension.is_empty() {
+            let name = ["AttachLongFilename", "AttachFilename"]
+                .iter()
+                .map(|key| value(key))
+                .find(|name| !name.is_empty())
+                .unwrap_or_default();
+            extension = name
+                .rsplit_once('.')
+                .map_or(String::new(), |(_, ext)| ext.to_string());
+        }
+        let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+        let mime = value("AttachMimeTag").trim().to_ascii_lowercase();
+
+        let matching = |patterns: &Vec<String>| {
+            patterns
+                .iter()
+                .any(|pattern| Self::matches(pattern, &extension, &mime))
+        };
+        !matching(&self.deny) && (self.allow.is_empty() || matching(&self.allow))
+    }
+}
@@ -1,18 +1,49 @@
 use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
     fs::File,
-    path::Path
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
 };
 
+use chrono::{DateTime, Utc};
+#[cfg(feature = "legacy")]
 use regex::Regex;
 
 use serde::{Deserialize, Serialize};
-use serde_json;
 
-use crate::ole;
+use crate::{ole, FileNamePolicy};
 
 use super::{
+    appointment::Appointment,
+    kind::{AttachmentKind, ItemKind},
+    container,
+    eml::{self, EmlOptions},
+    decode::{decode_code_page, DataType},
+    rtf::{decompress, html_from_rtf},
+    entry_id::OneOffEntryId,
     error::Error,
+    file::OutlookFile,
+    headers::{content_id, header_value, message_ids, parse_address_list},
+    mailing_list::MailingList,
+    opt::{absent_fields, OutlookOpt},
+    raw_headers::RawHeaders,
+    options::ParseOptions,
+    property_set::PropertySet,
+    report::ParseReport,
+    response::{LastVerb, ResponseKind, Verb, ICON_FORWARDED, ICON_REPLIED},
+    search::SearchScope,
+    opt::HEADER_FIELDS,
+    source::{FieldSource, SourceKind},
+    sync::SyncKeys,
+    system::SystemMessage,
+    text::extract_text,
+    time::{root_timestamps, TimePolicy, Timestamp},
+    verify::{Declared, Discrepancy, PropertyHeader},
     storage::{
+        get_aliased,
+        get_aliased_key,
         Properties,
         Storages
     }
@@ -21,19 +52,29 @@ use super::{
 type Name = String;
 type Email = String;
 
+// Sources of the fields of Outlook, see Outlook::field_source.
+type FieldSources = BTreeMap<String, FieldSource>;
+
+// Values of "RecipientType" for CC and BCC recipients
+const MAPI_CC: i64 = 0x02;
+const MAPI_BCC: i64 = 0x03;
+
 // TransportHeaders contains transport specific message
 // envelope information for the email.
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct TransportHeaders {
     pub content_type: String,
     pub date: String,
     pub message_id: String,
     pub reply_to: String,
+    #[serde(default)]
+    pub content_transfer_encoding: String,
 }
 
 impl TransportHeaders {
+    #[cfg(feature = "legacy")]
     fn extract_field(text: &str, re: Regex) -> String {
-        if text.len() == 0 {
+        if text.is_empty() {
             return String::from("");
         }
         let caps = re.captures(text);
@@ -44,6 +85,21 @@ impl TransportHeaders {
             .unwrap_or(String::from(""))
     }
 
+    // Folded values are unfolded.
+    #[cfg(not(feature = "legacy"))]
+    pub fn create_from_headers_text(text: &str) -> Self {
+        let field = |name: &str| header_value(text, name).unwrap_or_default();
+        Self {
+            content_type: field("Content-Type"),
+            date: field("Date"),
+            message_id: field("Message-ID"),
+            reply_to: field("Reply-To"),
+            content_transfer_encoding: field("Content-Transfer-Encoding"),
+        }
+    }
+
+    // Folded values are kept as is.
+    #[cfg(feature = "legacy")]
     pub fn create_from_headers_text(text: &str) -> Self {
         // Case-insensitive match
         Self {
@@ -51,7 +107,7 @@ impl TransportHeaders {
                 text,
                 Regex::new(r"(?i)Content-Type: (.*(\n\s.*)*)\r\n").unwrap(),
             ),
-            date: Self::extract_field(&text, Regex::new(r"(?i)Date: (.*(\n\s.*)*)\r\n").unwrap()),
+            date: Self::extract_field(text, Regex::new(r"(?i)Date: (.*(\n\s.*)*)\r\n").unwrap()),
             message_id: Self::extract_field(
                 text,
                 Regex::new(r"(?i)Message-ID: (.*(\n\s.*)*)\r\n").unwrap(),
@@ -60,52 +116,303 @@ impl TransportHeaders {
                 text,
                 Regex::new(r"(?i)Reply-To: (.*(\n\s.*)*)\r\n").unwrap(),
             ),
+            content_transfer_encoding: Self::extract_field(
+                text,
+                Regex::new(r"(?im)^Content-Transfer-Encoding: (.*)\r\n").unwrap(),
+            ),
+        }
+    }
+
+    // Rebuilds the headers from message properties, for messages
+    // without "TransportMessageHeaders" (e.g. drafts or messages
+    // exported from a store).
+    fn create_from_props(storages: &Storages) -> Self {
+        Self {
+            content_type: String::new(),
+            date: String::new(),
+            message_id: storages.get_val_from_root_or_default("InternetMessageId"),
+            reply_to: storages.get_val_from_root_or_default("ReplyRecipientNames"),
+            content_transfer_encoding: String::new(),
+        }
+    }
+
+    // Persons listed in the Reply-To header.
+    pub fn reply_to_list(&self) -> Vec<Person> {
+        parse_address_list(&self.reply_to)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.content_type.is_empty()
+            && self.date.is_empty()
+            && self.message_id.is_empty()
+            && self.reply_to.is_empty()
+            && self.content_transfer_encoding.is_empty()
+    }
+}
+
+// HeadersSource tells where TransportHeaders were taken from,
+// so that header derived data can be trusted accordingly.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum HeadersSource {
+    // Parsed from "TransportMessageHeaders"
+    Transport,
+    // Rebuilt from message properties
+    Synthesized,
+    // Neither transport headers nor properties to rebuild them from
+    #[default]
+    Absent,
+}
+
+// ClientInfo identifies the applications which created and last
+// modified the message, and the mail client reported in the headers.
+// These values are set by the clients themselves, so a mismatch
+// between them hints at a forged message.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClientInfo {
+    pub creator_name: String,       // "CreatorName"
+    pub last_modifier_name: String, // "LastModifierName"
+    pub mailer: String,             // "X-Mailer" header, or "User-Agent"
+}
+
+impl ClientInfo {
+    #[cfg(not(feature = "legacy"))]
+    fn mailer(headers_text: &str) -> String {
+        header_value(headers_text, "X-Mailer")
+            .or_else(|| header_value(headers_text, "User-Agent"))
+            .unwrap_or_default()
+    }
+
+    #[cfg(feature = "legacy")]
+    fn mailer(headers_text: &str) -> String {
+        let mut mailer = TransportHeaders::extract_field(
+            headers_text,
+            Regex::new(r"(?im)^X-Mailer: (.*)\r\n").unwrap(),
+        );
+        if mailer.is_empty() {
+            mailer = TransportHeaders::extract_field(
+                headers_text,
+                Regex::new(r"(?im)^User-Agent: (.*)\r\n").unwrap(),
+            );
+        }
+        mailer
+    }
+
+    fn create(storages: &Storages, headers_text: &str) -> Self {
+        Self {
+            creator_name: storages.get_val_from_root_or_default("CreatorName"),
+            last_modifier_name: storages.get_val_from_root_or_default("LastModifierName"),
+            mailer: Self::mailer(headers_text),
         }
     }
 }
 
 // Person represents either Sender or Receiver.
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Person {
     pub name: Name,
     pub email: Email,
+    // Number of its storage, __recip_version1.0_#NNNNNNNN,
+    // for recipients only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_index: Option<u32>,
+    // Email has the syntax of an address, see Person::is_valid_email
+    #[serde(default)]
+    pub valid: bool,
+}
+
+impl From<(&str, &str)> for Person {
+    // From a name and an email.
+    fn from((name, email): (&str, &str)) -> Self {
+        Self::new(name, email)
+    }
 }
 
 impl Person {
-    fn new(name: Name, email: Email) -> Self {
-        Self { name, email }
+    pub fn new(name: impl Into<Name>, email: impl Into<Email>) -> Self {
+        let email = email.into();
+        Self {
+            name: name.into(),
+            valid: Self::is_valid_email(&email),
+            email,
+            storage_index: None,
+        }
+    }
+
+    // Basic syntax check of an address, local@domain, without
+    // whitespace. Exchange addresses such as "/o=Org/cn=Jane" fail.
+    pub fn is_valid_email(email: &str) -> bool {
+        let (local, domain) = match email.split_once('@') {
+            Some(parts) => parts,
+            None => return false,
+        };
+        !local.is_empty()
+            && !domain.is_empty()
+            && !domain.contains('@')
+            && !domain.starts_with('.')
+            && !domain.ends_with('.')
+            && !domain.contains("..")
+            && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+    }
+    // Keys are looked up along with their aliases,
+    // e.g. "SmtpAddress" falls back to "EmailAddress".
+    pub(crate) fn create_from_props(props: &Properties, name_key: &str, email_key: &str) -> Self {
+        let name: String = get_aliased(props, name_key).map_or(String::new(), |x| x.into());
+        let email: String = get_aliased(props, email_key).map_or(String::new(), |x| x.into());
+        Self::new(name, email)
     }
-    fn create_from_props(props: &Properties, name_key: &str, email_keys: Vec<&str>) -> Self {
-        let name: String = props.get(name_key).map_or(String::new(), |x| x.into());
-        // Get the fist email that can be found in props given email_keys.
-        let email = email_keys
+
+    // A recipient, with what is missing read from its one-off
+    // EntryID, the only copy of some BCC recipients.
+    pub(crate) fn create_from_recipient(storages: &Storages, idx: usize) -> Self {
+        let props = &storages.recipients[idx];
+        let mut person = Self::create_from_props(props, "DisplayName", "SmtpAddress");
+        person.storage_index = storages.recipient_ids.get(idx).copied();
+        if !person.name.is_empty() && !person.email.is_empty() {
+            return person;
+        }
+        let entry = ["RecipientEntryId", "EntryId"]
             .iter()
-            .map(|&key| props.get(key).map_or(String::new(), |x| x.into()))
-            .find(|x| x.len() > 0)
-            .unwrap_or(String::from(""));
-        Self { name, email }
+            .find_map(|key| match props.get(*key) {
+                Some(DataType::PtypBinary(bytes)) => OneOffEntryId::parse(bytes),
+                _ => None,
+            });
+        if let Some(entry) = entry {
+            if person.name.is_empty() {
+                person.name = entry.display_name.clone();
+            }
+            if person.email.is_empty() {
+                person.email = entry.smtp_address().unwrap_or_default().to_string();
+                person.valid = Self::is_valid_email(&person.email);
+            }
+        }
+        person
     }
 }
 
 // Attachment represents attachment object in the mail.
+//
+// An attachment has up to three names: `long_name`, the full
+// file name, `short_name`, its 8.3 form, and `display_name`.
+// `name` is the first of them which is set, in this order.
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Attachment {
     pub display_name: String, // "DisplayName"
     pub payload: String,      // "AttachDataObject"
     pub extension: String,    // "AttachExtension"
     pub mime_tag: String,     // "AttachMimeTag"
-    pub file_name: String,    // "AttachFilename"
+    #[deprecated(note = "same as short_name, use short_name or name")]
+    pub file_name: String,    // "AttachFilename", 8.3 name kept for compatibility, see name
+    #[serde(default)]
+    pub short_name: String,   // "AttachFilename"
+    #[serde(default)]
+    pub long_name: String,    // "AttachLongFilename"
+    #[serde(default)]
+    pub name: String,         // Long, else short, else display name, as save_to uses
+    #[serde(default)]
+    pub content_id: String,     // "AttachContentId", see Outlook::inline_attachment
+    #[serde(default)]
+    pub method: Option<i32>,    // "AttachMethod"
+    #[serde(default)]
+    pub kind: AttachmentKind,   // From method, see AttachmentKind
+    #[serde(default)]
+    pub payload_filtered: bool, // Payload left out, see ParseOptions::allow_attachments
+    #[serde(default)]
+    pub storage_index: u32,     // Number of its storage, __attach_version1.0_#NNNNNNNN
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,   // See ParseOptions::attachment_text_extractor
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classification: Vec<String>, // See ParseOptions::content_scanner
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedded_message: Option<Box<Outlook>>, // Attached .msg, if any
 }
 
 impl Attachment {
-    fn create(storages: &Storages, idx: usize) -> Self {
+    #[allow(deprecated)] // file_name is still filled in
+    pub(crate) fn create(storages: &Storages, idx: usize) -> Self {
+        let display_name = storages.get_val_from_attachment_or_default(idx, "DisplayName");
+        let short_name = storages.get_val_from_attachment_or_default(idx, "AttachFilename");
+        let long_name = storages.get_val_from_attachment_or_default(idx, "AttachLongFilename");
+        let name = [&long_name, &short_name, &display_name]
+            .iter()
+            .find(|name| !name.trim().is_empty())
+            .map_or(String::new(), |name| name.to_string());
+        let method = storages
+            .attachments
+            .get(idx)
+            .and_then(|props| props.get("AttachMethod"))
+            .and_then(DataType::as_i64)
+            .map(|method| method as i32);
+        // Without a method, embedded messages are still told apart
+        let kind = match method {
+            Some(method) => AttachmentKind::from_method(method),
+            None if storages.embedded_messages.contains_key(&idx) => {
+                AttachmentKind::EmbeddedMessage
+            }
+            None => AttachmentKind::ByValue,
+        };
         Self {
-            display_name: storages.get_val_from_attachment_or_default(idx, "DisplayName"),
             payload: storages.get_val_from_attachment_or_default(idx, "AttachDataObject"),
             extension: storages.get_val_from_attachment_or_default(idx, "AttachExtension"),
             mime_tag: storages.get_val_from_attachment_or_default(idx, "AttachMimeTag"),
-            file_name: storages.get_val_from_attachment_or_default(idx, "AttachFilename"),
+            file_name: short_name.clone(),
+            display_name,
+            short_name,
+            long_name,
+            name,
+            content_id: storages.get_val_from_attachment_or_default(idx, "AttachContentId"),
+            method,
+            kind,
+            payload_filtered: storages.filtered_attachments.contains(&idx),
+            storage_index: storages.attachment_ids.get(idx).copied().unwrap_or_default(),
+            text: None,
+            classification: storages
+                .classifications
+                .get(&idx)
+                .cloned()
+                .unwrap_or_default(),
+            embedded_message: None,
+        }
+    }
+
+    // Writes the payload to a file under dir, named after the
+    // attachment as made safe by sanitize_filename, and returns
+    // its path. An existing file is overwritten.
+    pub fn save_to<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        policy: FileNamePolicy,
+    ) -> Result<PathBuf, Error> {
+        let payload = hex::decode(&self.payload).map_err(|err| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string())
+        })?;
+        let path = dir.as_ref().join(crate::sanitize_filename(&self.name, policy));
+        std::fs::write(&path, payload)?;
+        Ok(path)
+    }
+}
+
+// EmbeddedMessages iterates depth first over a message
+// and the messages attached to it, along with their depth.
+pub struct EmbeddedMessages<'a> {
+    stack: Vec<(usize, &'a Outlook)>,
+    max_depth: usize,
+}
+
+impl<'a> Iterator for EmbeddedMessages<'a> {
+    type Item = (usize, &'a Outlook);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (depth, outlook) = self.stack.pop()?;
+        if depth < self.max_depth {
+            let children = outlook
+                .attachments
+                .iter()
+                .rev()
+                .filter_map(|attach| attach.embedded_message.as_deref())
+                .map(|embedded| (depth + 1, embedded));
+            self.stack.extend(children);
         }
+        Some((depth, outlook))
     }
 }
 
@@ -114,118 +421,813 @@ impl Attachment {
 // MS-OXPROPS.
 // https://docs.microsoft.com/en-us/openspecs/exchange_server_protocols/ms-oxprops/f6ab1613-aefe-447d-a49c-18217230b148
 // Note: Prefixes are omitted for brevity.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Outlook {
     pub headers: TransportHeaders,    // "TransportMessageHeader"
+    #[serde(default)]
+    pub headers_present: bool,        // "TransportMessageHeaders" is set, even if empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_headers: Option<RawHeaders>, // "TransportMessageHeaders" as is, see ParseOptions
     pub sender: Person,               // "SenderName" , "SenderSmtpAddress"/"SenderEmailAddress"
     pub to: Vec<Person>,              // "DisplayName", "SmtpAddress"/"EmailAddress"
-    pub cc: Vec<Person>,              // "DisplayCc"
+    pub cc: Vec<Person>,              // See Outlook::recipients
     pub bcc: Name,                    // "DisplayBcc"
+    #[serde(default)]
+    pub bcc_recipients: Vec<Person>,  // See Outlook::recipients
     pub subject: String,              // "Subject"
+    #[serde(default)]
+    pub subject_ansi: Option<String>, // ANSI "Subject" (PR_SUBJECT_A), if any
+    #[serde(default)]
+    pub subject_prefix: String,       // "SubjectPrefix", e.g. "RE: "
+    #[serde(default)]
+    pub in_reply_to: String,          // "InReplyToId", or In-Reply-To header
+    #[serde(default)]
+    pub references: String,           // "InternetReferences", or References header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mailing_list: Option<MailingList>, // List-Id and list-management fields, if any
+    #[serde(default)]
+    pub conversation_index: String,   // "ConversationIndex", hex encoded
     pub body: String,                 // "Body"
+    #[serde(default)]
+    pub body_html: Option<String>,    // "Html", in the code page of the message
     pub rtf_compressed: String,       // "RtfCompressed"
+    #[serde(default)]
+    pub body_content_id: String,      // "BodyContentId"
+    #[serde(default)]
+    pub body_content_location: String, // "BodyContentLocation"
     pub attachments: Vec<Attachment>, // See Attachment struct
+    #[serde(default)]
+    pub message_size: Option<u64>,    // "MessageSizeExtended"
+    #[serde(default)]
+    pub property_count: usize,        // Properties decoded, recipients and attachments included
+    #[serde(default)]
+    pub property_header: Option<PropertyHeader>, // Header of "__properties_version1.0"
+    #[serde(default)]
+    pub report: ParseReport,          // Anomalies recovered from while parsing
+    #[serde(default)]
+    pub kind: ItemKind,               // Kind of item, from "MessageClass"
+    #[serde(default)]
+    pub appointment: Option<Appointment>, // Meeting objects only, see Appointment struct
+    #[serde(default)]
+    pub system_message: Option<SystemMessage>, // Reports, out of office and rules only
+    #[serde(default)]
+    pub icon_index: Option<i32>,      // "IconIndex", see Outlook::was_replied
+    #[serde(default)]
+    pub last_verb: Option<LastVerb>,  // "LastVerbExecuted" and its time
+    #[serde(default)]
+    pub sync_keys: Option<SyncKeys>,  // "SearchKey", "ChangeKey" and "PredecessorChangeList"
+    #[serde(default)]
+    pub timestamps: BTreeMap<String, Timestamp>, // Properties of type PtypTime, by name
+    #[serde(default)]
+    pub sent_at: Option<DateTime<Utc>>,     // "ClientSubmitTime"
+    #[serde(default)]
+    pub received_at: Option<DateTime<Utc>>, // "MessageDeliveryTime"
+    #[serde(default)]
+    headers_source: HeadersSource,    // See Outlook::headers_source
+    #[serde(default)]
+    client_info: ClientInfo,          // See Outlook::client_info
+    #[serde(default)]
+    message_class: String,            // "MessageClass", see Outlook::message_class
+    #[serde(skip)]
+    declared: Declared,               // See Outlook::verify
+    #[serde(skip)]
+    absent: BTreeSet<String>,         // See Outlook::to_opt
+    #[serde(skip)]
+    sources: BTreeMap<String, FieldSource>, // See Outlook::field_source
+    #[serde(skip)]
+    properties: PropertySet,          // See Outlook::property
+    #[serde(skip)]
+    transport_text: String,           // "TransportMessageHeaders", see Outlook::to_eml
 }
 
 impl Outlook {
-    fn extract_cc_from_headers(header_text: &str) -> Vec<Person> {
-        // Format in header is:
-        // CC: NAME <EMAIL>, NAME <EMAIL> \r\n
-        let re = Regex::new(r"(?i)CC: .*(\r\n\t)?.*\r\n").unwrap();
-        let caps = re.captures(header_text);
-        if caps.is_none() {
-            return vec![];
-        }
-        let cap = caps.unwrap().get(0).unwrap().as_str();
-        // Remove first 3 chars
-        // Split at ",", then trim and clean each string
-        // We should be left with ["NAME <EMAIL", "NAME <EMAIL"]
-        let cc_list = &cap[3..]
-            .split(",")
-            .map(|x| x.trim().replace('>', ""))
-            .collect::<Vec<String>>();
-
-        let mut cc_persons: Vec<Person> = vec![];
-        for cc in cc_list.iter() {
-            let name_email_pair: Vec<&str> = cc.split("<").map(|x| x.trim()).collect();
-            let person = if name_email_pair.len() < 2 {
-                // In the unlikely event that there's no email provided.
-                Person::new(name_email_pair[0].to_string(), "".to_string())
+    // Recipients split into To, CC and BCC by the low byte of
+    // "RecipientType", the rest being flags, with To for unknown
+    // types. Without any type, CC recipients are read from the
+    // Cc header, and the others are all To recipients.
+    // Refer to MS-OXPROPS, PidTagRecipientType
+    fn recipients(
+        storages: &Storages,
+        headers_text: &str,
+    ) -> (Vec<Person>, Vec<Person>, Vec<Person>, FieldSources) {
+        let mut to = vec![];
+        let mut cc = vec![];
+        let mut bcc = vec![];
+        let mut typed = false;
+        for (idx, props) in storages.recipients.iter().enumerate() {
+            let person = Person::create_from_recipient(storages, idx);
+            let recipient_type = props.get("RecipientType").and_then(DataType::as_i64);
+            typed |= recipient_type.is_some();
+            match recipient_type.map(|value| value & 0xFF) {
+                Some(MAPI_CC) => cc.push(person),
+                Some(MAPI_BCC) => bcc.push(person),
+                _ => to.push(person),
+            }
+        }
+        if !typed {
+            cc = Outlook::extract_cc_from_headers(headers_text);
+        }
+        let mut sources = FieldSources::new();
+        let recipient_type = FieldSource::new(SourceKind::Property, "RecipientType", !typed);
+        for (field, persons) in [("to", &to), ("bcc_recipients", &bcc)] {
+            if !persons.is_empty() {
+                sources.insert(field.to_string(), recipient_type.clone());
+            }
+        }
+        if !cc.is_empty() {
+            let source = if typed {
+                recipient_type
             } else {
-                Person::new(
-                    name_email_pair[0].replace('"', ""),
-                    name_email_pair[1].to_string(),
-                )
+                FieldSource::new(SourceKind::Header, "Cc", true)
             };
-            cc_persons.push(person);
+            sources.insert("cc".to_string(), source);
+        }
+        (to, cc, bcc, sources)
+    }
+
+    fn extract_cc_from_headers(header_text: &str) -> Vec<Person> {
+        header_value(header_text, "Cc")
+            .map(|list| parse_address_list(&list))
+            .unwrap_or_default()
+    }
+
+    // Value of a root property, or else of a header, along with
+    // where it was read from.
+    fn threading_field(
+        storages: &Storages,
+        headers_text: &str,
+        key: &str,
+        header: &str,
+    ) -> (String, Option<FieldSource>) {
+        let value = storages.get_val_from_root_or_default(key);
+        if !value.is_empty() {
+            return (value, Some(FieldSource::new(SourceKind::Property, key, false)));
+        }
+        match header_value(headers_text, header) {
+            Some(value) => (value, Some(FieldSource::new(SourceKind::Header, header, true))),
+            None => (String::new(), None),
+        }
+    }
+
+    // Sender of a sent item, from "SenderName" and "SenderSmtpAddress"
+    // or their aliases, along with the properties read.
+    fn sender(storages: &Storages) -> (Person, FieldSources) {
+        let mut sources = FieldSources::new();
+        let mut field = |field: &str, key: &str| match get_aliased_key(&storages.root, key) {
+            Some((alias, value)) => {
+                let fallback = !alias.eq_ignore_ascii_case(key);
+                let source = FieldSource::new(SourceKind::Property, alias, fallback);
+                sources.insert(field.to_string(), source);
+                String::from(value)
+            }
+            None => String::new(),
+        };
+        let name = field("sender.name", "SenderName");
+        let email = field("sender.email", "SenderSmtpAddress");
+        (Person::new(name, email), sources)
+    }
+
+    // Binary HTML bodies are text in "InternetCodepage", or
+    // else "MessageCodepage". Some writers use a string instead.
+    // Without "Html", the HTML encapsulated in the RTF body, if any.
+    pub(crate) fn html_body(storages: &Storages) -> Option<String> {
+        Self::html_body_with_source(storages).map(|(html, _)| html)
+    }
+
+    // Same as html_body, along with where the body was read from.
+    fn html_body_with_source(storages: &Storages) -> Option<(String, FieldSource)> {
+        let source = match storages.root.get("Html") {
+            Some(_) => FieldSource::new(SourceKind::Property, "Html", false),
+            None => FieldSource::new(SourceKind::Derived, "RtfCompressed", true),
+        };
+        Some((Self::decode_html_body(storages)?, source))
+    }
+
+    fn decode_html_body(storages: &Storages) -> Option<String> {
+        let html = match storages.root.get("Html") {
+            Some(html) => html,
+            None => match storages.root.get("RtfCompressed")? {
+                DataType::PtypBinary(bytes) => return html_from_rtf(&decompress(bytes)?),
+                _ => return None,
+            },
+        };
+        match html {
+            DataType::PtypBinary(bytes) => {
+                let code_page = ["InternetCodepage", "MessageCodepage"]
+                    .iter()
+                    .find_map(|key| storages.root.get(*key).and_then(DataType::as_i64));
+                Some(decode_code_page(bytes, code_page))
+            }
+            DataType::PtypString(html) => Some(html.clone()),
+            _ => None,
         }
-        cc_persons
     }
 
-    fn populate(storages: &Storages) -> Self {
+    // Raw transport headers, along with the headers read from them
+    // or else rebuilt from properties.
+    pub(crate) fn transport_headers(
+        storages: &Storages,
+        kind: ItemKind,
+    ) -> (String, TransportHeaders, HeadersSource) {
+        let (headers_text, headers, source, _) =
+            Self::transport_headers_with_sources(storages, kind);
+        (headers_text, headers, source)
+    }
+
+    // Same as transport_headers, along with the header, or the
+    // property, each field of the headers was read from.
+    fn transport_headers_with_sources(
+        storages: &Storages,
+        kind: ItemKind,
+    ) -> (String, TransportHeaders, HeadersSource, FieldSources) {
+        let mut sources = FieldSources::new();
+        // Contacts, tasks and the like have no transport headers, sender
+        // or threading, whatever properties a copy may have left over.
+        if !kind.is_sent() {
+            let headers = TransportHeaders::default();
+            return (String::new(), headers, HeadersSource::Absent, sources);
+        }
         let headers_text = storages.get_val_from_root_or_default("TransportMessageHeaders");
-        let headers = TransportHeaders::create_from_headers_text(&headers_text);
+        if !headers_text.trim().is_empty() {
+            let headers = TransportHeaders::create_from_headers_text(&headers_text);
+            for (field, header, _) in HEADER_FIELDS.iter() {
+                if header_value(&headers_text, header).is_some() {
+                    let source = FieldSource::new(SourceKind::Header, header, false);
+                    sources.insert(field.to_string(), source);
+                }
+            }
+            return (headers_text, headers, HeadersSource::Transport, sources);
+        }
+        let headers = TransportHeaders::create_from_props(storages);
+        if headers.is_empty() {
+            return (headers_text, headers, HeadersSource::Absent, sources);
+        }
+        // Rebuilt from properties, for lack of transport headers
+        for (field, _, key) in HEADER_FIELDS.iter() {
+            let key = match key {
+                Some(key) if !storages.get_val_from_root_or_default(key).is_empty() => key,
+                _ => continue,
+            };
+            sources.insert(field.to_string(), FieldSource::new(SourceKind::Property, key, true));
+        }
+        (headers_text, headers, HeadersSource::Synthesized, sources)
+    }
+
+    pub(crate) fn populate(storages: &Storages) -> Self {
+        let message_class = storages.get_val_from_root_or_default("MessageClass");
+        let kind = ItemKind::from_class(&message_class);
+        let sent = kind.is_sent();
+        let (headers_text, headers, headers_source, mut sources) =
+            Self::transport_headers_with_sources(storages, kind);
+        let start = Instant::now();
+        let attachments = (0..storages.attachments.len())
+            .map(|i| Attachment::create(storages, i))
+            .collect();
+        let mut report = storages.report.clone();
+        report.metrics.attachment_decode = start.elapsed();
+
+        let timestamps = root_timestamps(storages, TimePolicy::default(), &headers.date);
+        let (to, cc, bcc_recipients, recipient_sources) =
+            Self::recipients(storages, &headers_text);
+        sources.extend(recipient_sources);
+        let body_html = Self::html_body_with_source(storages).map(|(html, source)| {
+            sources.insert("body_html".to_string(), source);
+            html
+        });
+        let (sender, in_reply_to, references) = if sent {
+            let (sender, sender_sources) = Self::sender(storages);
+            sources.extend(sender_sources);
+            let mut threading_field = |field: &str, key: &str, header: &str| {
+                let (value, source) = Self::threading_field(storages, &headers_text, key, header);
+                if let Some(source) = source {
+                    sources.insert(field.to_string(), source);
+                }
+                value
+            };
+            let in_reply_to = threading_field("in_reply_to", "InReplyToId", "In-Reply-To");
+            let references = threading_field("references", "InternetReferences", "References");
+            (sender, in_reply_to, references)
+        } else {
+            (Person::new("", ""), String::new(), String::new())
+        };
 
-        // Outlook::extract_cc_from_headers(&headers_text);
         Self {
             headers,
-            sender: Person::create_from_props(
-                &storages.root,
-                "SenderName",
-                vec!["SenderSmtpAddress", "SenderEmailAddress"],
-            ),
-            to: storages
-                .recipients
-                .iter()
-                .map(|recip_map| {
-                    Person::create_from_props(
-                        recip_map,
-                        "DisplayName",
-                        vec!["SmtpAddress", "EmailAddress"],
-                    )
-                })
-                .collect(),
-            cc: Outlook::extract_cc_from_headers(&headers_text),
+            headers_present: sent && storages.root.contains_key("TransportMessageHeaders"),
+            raw_headers: None,
+            sender,
+            to,
+            cc,
             bcc: storages.get_val_from_root_or_default("DisplayBcc"),
+            bcc_recipients,
             subject: storages.get_val_from_root_or_default("Subject"),
+            subject_ansi: storages.root_ansi.get("Subject").map(|x| x.into()),
+            subject_prefix: storages.get_val_from_root_or_default("SubjectPrefix"),
+            in_reply_to,
+            references,
+            mailing_list: if sent {
+                MailingList::create(storages, &headers_text)
+            } else {
+                None
+            },
+            conversation_index: storages.get_val_from_root_or_default("ConversationIndex"),
             body: storages.get_val_from_root_or_default("Body"),
+            body_html,
             rtf_compressed: storages.get_val_from_root_or_default("RtfCompressed"),
-            attachments: storages
-                .attachments
-                .iter()
-                .enumerate()
-                .map(|(i, _)| Attachment::create(storages, i))
-                .collect(),
+            body_content_id: storages.get_val_from_root_or_default("BodyContentId"),
+            body_content_location: storages.get_val_from_root_or_default("BodyContentLocation"),
+            attachments,
+            message_size: storages
+                .root
+                .get("MessageSizeExtended")
+                .and_then(|size| size.as_i64())
+                .filter(|size| *size >= 0)
+                .map(|size| size as u64),
+            property_count: storages.property_count(),
+            property_header: storages.property_header,
+            report,
+            kind,
+            appointment: Appointment::create(storages),
+            system_message: SystemMessage::create(storages),
+            icon_index: storages
+                .root
+                .get("IconIndex")
+                .and_then(DataType::as_i64)
+                .map(|index| index as i32),
+            last_verb: LastVerb::create(storages),
+            sync_keys: SyncKeys::create(storages),
+            timestamps,
+            sent_at: storages.root.get("ClientSubmitTime").and_then(DataType::as_datetime),
+            received_at: storages.root.get("MessageDeliveryTime").and_then(DataType::as_datetime),
+            headers_source,
+            client_info: ClientInfo::create(storages, &headers_text),
+            message_class,
+            declared: Declared::create(storages),
+            absent: absent_fields(storages, kind, &headers_text, headers_source),
+            sources,
+            properties: PropertySet::default(),
+            transport_text: headers_text,
         }
     }
 
-    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
-        let file = File::open(path)?;
-        let parser = ole::Reader::new(file)?;
-        let mut storages = Storages::new(&parser);
-        storages.process_streams(&parser);
+    // Transport headers as read, empty for messages without them
+    // and those deserialized from JSON.
+    pub(crate) fn transport_text(&self) -> &str {
+        &self.transport_text
+    }
+
+    // Where the headers come from.
+    pub fn headers_source(&self) -> HeadersSource {
+        self.headers_source
+    }
+
+    // Where a field with several possible sources was read from, by
+    // path as in Outlook::to_opt, e.g. "sender.email" or "headers.date".
+    // None for fields without a value, or with a single source, and
+    // for messages deserialized from JSON.
+    pub fn field_source(&self, field: &str) -> Option<&FieldSource> {
+        self.sources.get(field)
+    }
+
+    pub fn field_sources(&self) -> &BTreeMap<String, FieldSource> {
+        &self.sources
+    }
+
+    // Message class as written, e.g. "IPM.Schedule.Meeting.Request",
+    // for classes kind does not tell apart, such as custom forms.
+    pub fn message_class(&self) -> &str {
+        &self.message_class
+    }
+
+    // Applications reported to have written the message.
+    pub fn client_info(&self) -> &ClientInfo {
+        &self.client_info
+    }
+
+    // Value of a property of the message, for those without a field.
+    // Properties are given by canonical name as in PropIdNameMap,
+    // e.g. "Sensitivity", or by id or tag in hex, e.g. "0x0036" or
+    // "0x00360003". Unicode values are preferred over ANSI ones.
+    // Properties are only kept with ParseOptions::retain_properties,
+    // and messages deserialized from JSON have none.
+    pub fn property(&self, name_or_tag: &str) -> Option<&DataType> {
+        self.properties.root(name_or_tag)
+    }
+
+    // Properties of the message by canonical name, in name order.
+    pub fn properties(&self) -> impl Iterator<Item = (&str, &DataType)> {
+        self.properties.root_properties().into_iter()
+    }
+
+    // Value of a property of the recipient at idx, recipients being
+    // in the order of their storages, see Person::storage_index.
+    pub fn recipient_property(&self, idx: usize, name_or_tag: &str) -> Option<&DataType> {
+        self.properties.recipient(idx, name_or_tag)
+    }
+
+    pub fn recipient_properties(&self, idx: usize) -> impl Iterator<Item = (&str, &DataType)> {
+        self.properties.recipient_properties(idx).into_iter()
+    }
+
+    // Value of a property of the attachment at idx in attachments.
+    pub fn attachment_property(&self, idx: usize, name_or_tag: &str) -> Option<&DataType> {
+        self.properties.attachment(idx, name_or_tag)
+    }
+
+    pub fn attachment_properties(&self, idx: usize) -> impl Iterator<Item = (&str, &DataType)> {
+        self.properties.attachment_properties(idx).into_iter()
+    }
+
+    // Cross-checks what the message declares about itself with
+    // what was found: its size, attachment and recipient counts.
+    // Empty when they agree. Embedded messages are checked on
+    // their own, see walk_embedded. Messages deserialized from
+    // JSON declare nothing.
+    pub fn verify(&self) -> Vec<Discrepancy> {
+        // CC recipients of the Cc header have no storage
+        let recipients = self
+            .to
+            .iter()
+            .chain(&self.cc)
+            .chain(&self.bcc_recipients)
+            .filter(|person| person.storage_index.is_some())
+            .count();
+        self.declared
+            .verify(self.message_size, recipients, self.attachments.len())
+    }
+
+    // Key shared by the messages of a thread, the first that is known of:
+    // - "id:" and the first Message-ID of References, the thread's first message
+    // - "id:" and the Message-ID of In-Reply-To, for replies without References
+    // - "id:" and the message's own Message-ID, for the first message
+    // - "conversation:" and the 22 bytes header of ConversationIndex in hex,
+    //   for messages without Message-IDs, such as drafts
+    // Message-IDs are lowercased and without angle brackets.
+    // Empty when none is known.
+    pub fn thread_key(&self) -> String {
+        let message_id = |text: &str| message_ids(text).into_iter().next();
+        let id = message_id(&self.references)
+            .or_else(|| message_id(&self.in_reply_to))
+            .or_else(|| message_id(&self.headers.message_id));
+        if let Some(id) = id {
+            return format!("id:{}", id.to_lowercase());
+        }
+        // Refer to MS-OXOMSG 2.2.1.3
+        let index = self.conversation_index.to_uppercase();
+        match index.get(..44) {
+            Some(header) => format!("conversation:{}", header),
+            None => String::new(),
+        }
+    }
+
+    // Message-IDs of References, without their angle brackets,
+    // from the first message of the thread to the parent.
+    pub fn reference_ids(&self) -> Vec<String> {
+        message_ids(&self.references)
+    }
+
+    // Attachment a cid: URL of the HTML body refers to, e.g.
+    // <img src="cid:image001.png@01D63E5A.C9557CD0">, matched on its
+    // Content-ID. The Content-ID itself can be given instead.
+    pub fn inline_attachment(&self, url: &str) -> Option<&Attachment> {
+        let id = content_id(url);
+        if id.is_empty() {
+            return None;
+        }
+        self.attachments
+            .iter()
+            .find(|attach| content_id(&attach.content_id) == id)
+    }
+
+    // Whether the message is new, a reply or a forward, from the
+    // prefix of its subject, or else from In-Reply-To, which only
+    // replies have once the prefix is left out.
+    pub fn response_kind(&self) -> ResponseKind {
+        match ResponseKind::from_subject(&self.subject_prefix, &self.subject) {
+            Some(kind) => kind,
+            None if self.in_reply_to.is_empty() => ResponseKind::New,
+            None => ResponseKind::Reply,
+        }
+    }
+
+    pub fn is_reply(&self) -> bool {
+        self.response_kind() == ResponseKind::Reply
+    }
+
+    pub fn is_forward(&self) -> bool {
+        self.response_kind() == ResponseKind::Forward
+    }
+
+    // Whether the message was replied to, or forwarded, by the owner
+    // of the mailbox it was saved from, as told by its last verb, or
+    // else by its icon. Only the last of these actions is known, see
+    // Outlook::last_verb for when it was taken.
+    pub fn was_replied(&self) -> bool {
+        match self.last_verb {
+            Some(last) => matches!(last.verb, Verb::ReplyToSender | Verb::ReplyToAll),
+            None => self.icon_index == Some(ICON_REPLIED),
+        }
+    }
+
+    pub fn was_forwarded(&self) -> bool {
+        match self.last_verb {
+            Some(last) => last.verb == Verb::Forward,
+            None => self.icon_index == Some(ICON_FORWARDED),
+        }
+    }
+
+    // Whether needle is found, ignoring case, in the parts of the
+    // message selected by scope. Embedded messages are searched
+    // on their own, see walk_embedded.
+    pub fn contains(&self, needle: &str, scope: &SearchScope) -> bool {
+        scope.matches(self, &needle.to_lowercase())
+    }
+
+    // Text of the message for indexing: its subject and body, then
+    // the names of its attachments along with their text, see
+    // ParseOptions::attachment_text_extractor, then the full text
+    // of its embedded messages. Parts are separated by blank lines.
+    pub fn full_text(&self) -> String {
+        let mut parts: Vec<Cow<str>> = vec![
+            Cow::Borrowed(&self.subject),
+            Cow::Borrowed(&self.body),
+        ];
+        for attachment in &self.attachments {
+            parts.push(Cow::Borrowed(&attachment.name));
+            if let Some(text) = &attachment.text {
+                parts.push(Cow::Borrowed(text));
+            }
+            if let Some(embedded) = &attachment.embedded_message {
+                parts.push(Cow::Owned(embedded.full_text()));
+            }
+        }
+        parts.retain(|part| !part.trim().is_empty());
+        parts.join("\n\n")
+    }
+
+    pub(crate) fn from_reader(
+        parser: &ole::Reader,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        let mut storage_count = 0;
+        let outlook =
+            Self::from_storages(parser, Storages::new(parser), options, 0, &mut storage_count)?
+                .with_file_metrics(parser);
+        if options.strict {
+            let warning = outlook
+                .walk_embedded(usize::MAX)
+                .find_map(|(_, msg)| msg.report.warnings.first());
+            if let Some(warning) = warning {
+                return Err(Error::StrictViolation(warning.clone()));
+            }
+        }
+        Ok(outlook)
+    }
+
+    // Limits are checked before processing streams, so that
+    // a crafted file cannot nest messages without bound.
+    fn from_storages(
+        parser: &ole::Reader,
+        storages: Storages,
+        options: &ParseOptions,
+        depth: usize,
+        storage_count: &mut usize,
+    ) -> Result<Self, Error> {
+        if depth > options.max_embedded_depth {
+            return Err(Error::LimitExceeded(format!(
+                "embedded messages nested deeper than {}",
+                options.max_embedded_depth
+            )));
+        }
+        *storage_count += storages.storage_count();
+        if *storage_count > options.max_storages {
+            return Err(Error::LimitExceeded(format!(
+                "more than {} storages",
+                options.max_storages
+            )));
+        }
+
+        let mut storages = storages.with_options(options);
+        storages.process_streams(parser);
+        let mut outlook = Self::populate(&storages);
+        if let Some(encoding) = options.raw_headers {
+            outlook.raw_headers = match storages.root.get("TransportMessageHeaders") {
+                Some(DataType::PtypString(text)) => Some(RawHeaders::create(text, encoding)?),
+                _ => None,
+            };
+        }
+        if options.time_policy != TimePolicy::default() {
+            outlook.timestamps =
+                root_timestamps(&storages, options.time_policy, &outlook.headers.date);
+        }
 
-        let outlook = Self::populate(&storages);
+        let start = Instant::now();
+        for (idx, attachment) in outlook.attachments.iter_mut().enumerate() {
+            let embedded =
+                Self::embedded_message(parser, &storages, idx, options, depth, storage_count)?;
+            attachment.embedded_message = embedded.map(Box::new);
+            if let Some(extractor) = &options.attachment_text_extractor {
+                attachment.text = extract_text(extractor.as_ref(), attachment);
+            }
+        }
+        outlook.report.metrics.attachment_decode += start.elapsed();
+        if options.retain_properties {
+            outlook.properties = PropertySet::create(storages);
+        }
         Ok(outlook)
     }
 
+    // Sets the time spent on the file the message is stored in.
+    fn with_file_metrics(mut self, parser: &ole::Reader) -> Self {
+        self.report.metrics.header_parse = parser.header_time;
+        self.report.metrics.sat_build = parser.sat_time;
+        self
+    }
+
+    // Message of the attachment at idx, if any, which is either
+    // embedded as a storage, or attached by value as a .msg file.
+    pub(crate) fn embedded_message(
+        parser: &ole::Reader,
+        storages: &Storages,
+        idx: usize,
+        options: &ParseOptions,
+        depth: usize,
+        storage_count: &mut usize,
+    ) -> Result<Option<Self>, Error> {
+        if let Some(&storage_id) = storages.embedded_messages.get(&idx) {
+            let storages = Storages::with_root(parser, storage_id);
+            return Self::from_storages(parser, storages, options, depth + 1, storage_count)
+                .map(Some);
+        }
+        match storages
+            .get_binary_from_attachment(idx, "AttachDataObject")
+            .filter(|bytes| bytes.starts_with(&ole::constants::IDENTIFIER))
+            .and_then(|bytes| ole::Reader::new(bytes).ok())
+        {
+            Some(embedded) => {
+                let storages = Storages::new(&embedded);
+                Self::from_storages(&embedded, storages, options, depth + 1, storage_count)
+                    .map(|outlook| Some(outlook.with_file_metrics(&embedded)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    // Iterates over the message and its embedded messages,
+    // up to max_depth levels of nesting.
+    pub fn walk_embedded(&self, max_depth: usize) -> EmbeddedMessages<'_> {
+        EmbeddedMessages {
+            stack: vec![(0, self)],
+            max_depth,
+        }
+    }
+
+    // The file is read at once, but for those larger than 1 GiB,
+    // read on demand so that they do not have to fit in memory.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_path_with_options(path, &ParseOptions::default())
+    }
+
+    // Opens a message to read its parts on demand, see OutlookFile.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<OutlookFile, Error> {
+        OutlookFile::open(path)
+    }
+
+    pub fn from_path_with_options<P: AsRef<Path>>(
+        path: P,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        // Containers are unwrapped in memory
+        if options.unwrap_containers {
+            let bytes = std::fs::read(path)?;
+            return Self::from_slice_with_options(&bytes, options);
+        }
+        let parser = ole::Reader::from_file(File::open(path)?)?;
+        Self::from_reader(&parser, options)
+    }
+
     pub fn from_slice(slice: &[u8]) -> Result<Self, Error> {
+        Self::from_slice_with_options(slice, &ParseOptions::default())
+    }
+
+    pub fn from_slice_with_options(slice: &[u8], options: &ParseOptions) -> Result<Self, Error> {
+        if options.unwrap_containers {
+            let unwrapped = container::unwrap(slice, options.max_container_size)?;
+            let parser = ole::Reader::new(&unwrapped[..])?;
+            return Self::from_reader(&parser, options);
+        }
         let parser = ole::Reader::new(slice)?;
-        let mut storages = Storages::new(&parser);
-        storages.process_streams(&parser);
+        Self::from_reader(&parser, options)
+    }
 
-        let outlook = Self::populate(&storages);
-        Ok(outlook)
+    // Parses a message attached to an EML file, as clients forward
+    // messages, given the MIME part holding it along with its
+    // headers: application/vnd.ms-outlook, usually base64 encoded.
+    pub fn from_mime_part(part: &[u8]) -> Result<Self, Error> {
+        Self::from_mime_part_with_options(part, &ParseOptions::default())
+    }
+
+    pub fn from_mime_part_with_options(part: &[u8], options: &ParseOptions) -> Result<Self, Error> {
+        Self::from_slice_with_options(&container::mime_part_body(part)?, options)
+    }
+
+    // Parses a message held in memory and shared between threads,
+    // e.g. an Arc<Vec<u8>>, without copying it.
+    pub fn from_shared<T>(data: Arc<T>) -> Result<Self, Error>
+    where
+        T: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        Self::from_shared_with_options(data, &ParseOptions::default())
+    }
+
+    pub fn from_shared_with_options<T>(data: Arc<T>, options: &ParseOptions) -> Result<Self, Error>
+    where
+        T: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        if options.unwrap_containers {
+            // Only a wrapped message needs its own buffer
+            let unwrapped = container::unwrap((*data).as_ref(), options.max_container_size)?;
+            if let Cow::Owned(unwrapped) = unwrapped {
+                let parser = ole::Reader::new(&unwrapped[..])?;
+                return Self::from_reader(&parser, options);
+            }
+        }
+        let parser = ole::Reader::from_shared(data)?;
+        Self::from_reader(&parser, options)
+    }
+
+    // The message as EML, in the format of RFC 5322 and MIME, with the
+    // transport headers it was received with, or else headers written
+    // from its properties. Bodies are written as multipart/alternative,
+    // the attachments shown by the HTML body as multipart/related, and
+    // embedded messages as message/rfc822. Attachments without content
+    // in the message, such as references, are left out.
+    pub fn to_eml(&self) -> Result<Vec<u8>, Error> {
+        self.to_eml_with_options(&EmlOptions::default())
+    }
+
+    pub fn to_eml_with_options(&self, options: &EmlOptions) -> Result<Vec<u8>, Error> {
+        eml::write_message(self, options)
+    }
+
+    // The body as Markdown, converted from the HTML body if any,
+    // e.g. for knowledge-base ingestion. Messages with a text body
+    // only have it with Markdown characters escaped, so that it reads
+    // as is, and those without a body an empty string.
+    #[cfg(feature = "markdown")]
+    pub fn body_markdown(&self) -> String {
+        match self.body_html.as_deref().filter(|html| !html.trim().is_empty()) {
+            Some(html) => html2md::parse_html(html).trim().to_string(),
+            None => escape_markdown(&self.body),
+        }
     }
 
     pub fn to_json(&self) -> Result<String, Error> {
         Ok(serde_json::to_string(self)?)
     }
+
+    // View of the message where absent properties are None
+    // rather than empty, see OutlookOpt. Messages deserialized
+    // from JSON have none absent.
+    pub fn to_opt(&self) -> OutlookOpt<'_> {
+        OutlookOpt::create(self, &self.absent)
+    }
+}
+
+// Escapes the characters of text which Markdown would read as
+// emphasis, links, code, HTML or tables, and the starts of lines
+// it would read as headings, quotes or lists.
+#[cfg(feature = "markdown")]
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_start_matches([' ', '\t']);
+        escaped.push_str(&line[..line.len() - content.len()]);
+        let digits = content.bytes().take_while(u8::is_ascii_digit).count();
+        let marker = match content.as_bytes().get(digits) {
+            Some(b'.') | Some(b')') if digits > 0 => Some(digits),
+            Some(b'-') | Some(b'+') | Some(b'=') if digits == 0 => Some(0),
+            _ => None,
+        };
+        for (idx, c) in content.char_indices() {
+            let special = matches!(
+                c,
+                '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' | '~'
+            );
+            if special || marker == Some(idx) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+    }
+    escaped
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::len_zero)]
 mod tests {
     use super::{Outlook, Person, TransportHeaders};
+    use crate::parser::fixtures::entry_offset;
 
     #[test]
     fn test_invalid_file() {
@@ -256,7 +1258,8 @@ mod tests {
                 content_type: String::new(),
                 date: String::new(),
                 message_id: String::new(),
-                reply_to: String::new()
+                reply_to: String::new(),
+                content_transfer_encoding: String::new(),
             }
         );
     }
@@ -265,42 +1268,58 @@ mod tests {
     fn test_test_email() {
         let path = "data/test_email.msg";
         let outlook = Outlook::from_path(path).unwrap();
+        // No sender properties, only the SentRepresenting ones
         assert_eq!(
             outlook.sender,
             Person {
-                name: "".to_string(),
-                email: "".to_string()
+                name: "marirs@outlook.com".to_string(),
+                email: "marirs@outlook.com".to_string(),
+                storage_index: None,
+                valid: true,
             }
         );
-        assert_eq!(
-            outlook.to,
-            vec![
-                Person {
-                    name: "marirs@outlook.com".to_string(),
-                    email: "marirs@outlook.com".to_string()
-                },
-                Person {
-                    name: "Sriram Govindan".to_string(),
-                    email: "marirs@aol.in".to_string()
-                },
-                Person {
-                    name: "marirs@outlook.in".to_string(),
-                    email: "marirs@outlook.in".to_string()
-                },
-                Person {
-                    name: "Sriram Govindan".to_string(),
-                    email: "marirs@aol.in".to_string()
-                },
-                Person {
-                    name: "Sriram Govindan".to_string(),
-                    email: "marirs@outlook.com".to_string()
-                },
-                Person {
-                    name: "marirs@outlook.in".to_string(),
-                    email: "marirs@outlook.in".to_string()
-                },
-            ]
-        );
+        // Split by "RecipientType", the BCC recipients being those of "DisplayBcc"
+        let recipients = [
+            Person {
+                name: "Sriram Govindan".to_string(),
+                email: "marirs@gmail.com".to_string(),
+                storage_index: Some(0),
+                valid: true,
+            },
+            Person {
+                name: "Sriram Govindan".to_string(),
+                email: "marirs@aol.in".to_string(),
+                storage_index: Some(1),
+                valid: true,
+            },
+            Person {
+                name: "marirs@outlook.in".to_string(),
+                email: "marirs@outlook.in".to_string(),
+                storage_index: Some(2),
+                valid: true,
+            },
+            Person {
+                name: "Sriram Govindan".to_string(),
+                email: "marirs@aol.in".to_string(),
+                storage_index: Some(3),
+                valid: true,
+            },
+            Person {
+                name: "Sriram Govindan".to_string(),
+                email: "marirs@outlook.com".to_string(),
+                storage_index: Some(4),
+                valid: true,
+            },
+            Person {
+                name: "marirs@outlook.in".to_string(),
+                email: "marirs@outlook.in".to_string(),
+                storage_index: Some(5),
+                valid: true,
+            },
+        ];
+        assert_eq!(outlook.to, recipients[..1]);
+        assert_eq!(outlook.cc, recipients[1..3]);
+        assert_eq!(outlook.bcc_recipients, recipients[3..]);
 
         assert_eq!(
             outlook.subject,
@@ -314,6 +1333,7 @@ mod tests {
                 date: String::new(),
                 message_id: String::new(),
                 reply_to: String::new(),
+                content_transfer_encoding: String::new(),
             }
         );
 
@@ -330,45 +1350,61 @@ mod tests {
     }
 
     #[test]
+    #[allow(deprecated)] // Checks file_name
     fn test_test_email_2() {
         let path = "data/test_email.msg";
         let outlook = Outlook::from_path(path).unwrap();
+        // No sender properties, only the SentRepresenting ones
         assert_eq!(
             outlook.sender,
             Person {
-                name: "".to_string(),
-                email: "".to_string()
+                name: "marirs@outlook.com".to_string(),
+                email: "marirs@outlook.com".to_string(),
+                storage_index: None,
+                valid: true,
             }
         );
-        assert_eq!(
-            outlook.to,
-            vec![
-                Person {
-                    name: "marirs@outlook.com".to_string(),
-                    email: "marirs@outlook.com".to_string()
-                },
-                Person {
-                    name: "Sriram Govindan".to_string(),
-                    email: "marirs@aol.in".to_string()
-                },
-                Person {
-                    name: "marirs@outlook.in".to_string(),
-                    email: "marirs@outlook.in".to_string()
-                },
-                Person {
-                    name: "Sriram Govindan".to_string(),
-                    email: "marirs@aol.in".to_string()
-                },
-                Person {
-                    name: "Sriram Govindan".to_string(),
-                    email: "marirs@outlook.com".to_string()
-                },
-                Person {
-                    name: "marirs@outlook.in".to_string(),
-                    email: "marirs@outlook.in".to_string()
-                },
-            ]
-        );
+        let recipients = [
+            Person {
+                name: "Sriram Govindan".to_string(),
+                email: "marirs@gmail.com".to_string(),
+                storage_index: Some(0),
+                valid: true,
+            },
+            Person {
+                name: "Sriram Govindan".to_string(),
+                email: "marirs@aol.in".to_string(),
+                storage_index: Some(1),
+                valid: true,
+            },
+            Person {
+                name: "marirs@outlook.in".to_string(),
+                email: "marirs@outlook.in".to_string(),
+                storage_index: Some(2),
+                valid: true,
+            },
+            Person {
+                name: "Sriram Govindan".to_string(),
+                email: "marirs@aol.in".to_string(),
+                storage_index: Some(3),
+                valid: true,
+            },
+            Person {
+                name: "Sriram Govindan".to_string(),
+                email: "marirs@outlook.com".to_string(),
+                storage_index: Some(4),
+                valid: true,
+            },
+            Person {
+                name: "marirs@outlook.in".to_string(),
+                email: "marirs@outlook.in".to_string(),
+                storage_index: Some(5),
+                valid: true,
+            },
+        ];
+        assert_eq!(outlook.to, recipients[..1]);
+        assert_eq!(outlook.cc, recipients[1..3]);
+        assert_eq!(outlook.bcc_recipients, recipients[3..]);
         assert_eq!(
             outlook.subject,
             String::from("Test Email")
@@ -437,6 +1473,57 @@ mod tests {
     }
 
     #[test]
+    fn test_inline_attachment() {
+        let outlook = Outlook::from_path("data/attachment.msg").unwrap();
+        let ids: Vec<&str> = outlook.attachments.iter().map(|a| a.content_id.as_str()).collect();
+        assert_eq!(ids[1..], ["image001.png@01D63E5A.C9557CD0", "image002.jpg@01D63E5A.C9557CD0"]);
+
+        let html = outlook.body_html.as_deref().unwrap();
+        assert!(html.contains("cid:image001.png@01D63E5A.C9557CD0"));
+        let inline = outlook.inline_attachment("cid:image001.png@01D63E5A.C9557CD0");
+        assert_eq!(inline.map(|a| a.name.as_str()), Some("image001.png"));
+        let inline = outlook.inline_attachment("<image002.jpg@01D63E5A.C9557CD0>");
+        assert_eq!(inline.map(|a| a.name.as_str()), Some("image002.jpg"));
+        assert!(outlook.inline_attachment("cid:image003.png@01D63E5A.C9557CD0").is_none());
+        assert!(outlook.inline_attachment("cid:").is_none());
+
+        // Attachments without Content-ID
+        let outlook = Outlook::from_path("data/test_email_2.msg").unwrap();
+        assert!(outlook.attachments.iter().all(|a| a.content_id.is_empty()));
+        assert!(outlook.inline_attachment("").is_none());
+    }
+
+    #[test]
+    fn test_attachment_kind() {
+        use super::{Attachment, AttachmentKind};
+
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        let kinds: Vec<(Option<i32>, AttachmentKind)> =
+            outlook.attachments.iter().map(|a| (a.method, a.kind)).collect();
+        assert_eq!(
+            kinds,
+            [
+                (Some(5), AttachmentKind::EmbeddedMessage),
+                (Some(1), AttachmentKind::ByValue),
+                // A .msg file attached as a file
+                (Some(1), AttachmentKind::ByValue),
+            ]
+        );
+        assert!(outlook.attachments[0].payload.is_empty());
+        assert!(outlook.attachments[0].embedded_message.is_some());
+
+        let mut json = serde_json::to_value(&outlook.attachments[0]).unwrap();
+        assert_eq!(json["kind"], "EmbeddedMessage");
+        // Attachments parsed before kinds were told apart
+        let object = json.as_object_mut().unwrap();
+        object.remove("kind");
+        object.remove("method");
+        let attachment: Attachment = serde_json::from_value(json).unwrap();
+        assert_eq!(attachment.kind, AttachmentKind::ByValue);
+    }
+
+    #[test]
+    #[allow(deprecated)] // Checks file_name
     fn test_attachment_msg() {
         let path = "data/attachment.msg";
         let outlook = Outlook::from_path(path).unwrap();
@@ -494,6 +1581,16 @@ mod tests {
                 "image002.jpg".to_string()
             ]
         );
+        // Long names are preferred over 8.3 names
+        let doc = &outlook.attachments[0];
+        assert_eq!(doc.short_name, "loan_p~1.doc");
+        assert_eq!(doc.long_name, "loan_proposal.doc");
+        assert_eq!(doc.name, doc.long_name);
+
+        let json = serde_json::to_value(doc).unwrap();
+        for key in ["name", "short_name", "long_name", "display_name"] {
+            assert!(json.get(key).is_some());
+        }
     }
 
     #[test]
@@ -504,30 +1601,31 @@ mod tests {
             outlook.sender,
             Person {
                 name: "Brian Zhou".to_string(),
-                email: "brizhou@gmail.com".to_string()
+                email: "brizhou@gmail.com".to_string(),
+                storage_index: None,
+                valid: true,
             }
         );
         assert_eq!(
             outlook.to,
-            vec![
-                Person {
-                    name: "brianzhou@me.com".to_string(),
-                    email: "brianzhou@me.com".to_string()
-                },
-                Person {
-                    name: "Brian Zhou".to_string(),
-                    email: "brizhou@gmail.com".to_string(),
-                }
-            ]
+            vec![Person {
+                name: "brianzhou@me.com".to_string(),
+                email: "brianzhou@me.com".to_string(),
+                storage_index: Some(0),
+                valid: true,
+            }]
         );
 
         assert_eq!(
             outlook.cc,
-            vec![Person::new(
-                "Brian Zhou".to_string(),
-                "brizhou@gmail.com".to_string()
-            ),]
+            vec![Person {
+                name: "Brian Zhou".to_string(),
+                email: "brizhou@gmail.com".to_string(),
+                storage_index: Some(1),
+                valid: true,
+            }]
         );
+        assert!(outlook.bcc_recipients.is_empty());
         assert_eq!(outlook.subject, String::from("Test for TIF files"));
         assert_eq!(
             outlook.headers,
@@ -536,23 +1634,1353 @@ mod tests {
                 date: "Mon, 18 Nov 2013 10:26:24 +0200".to_string(),
                 message_id: "<CADtJ4eNjQSkGcBtVteCiTF+YFG89+AcHxK3QZ=-Mt48xygkvdQ@mail.gmail.com>"
                     .to_string(),
-                reply_to: String::from("")
+                reply_to: String::from(""),
+                content_transfer_encoding: String::from(""),
             }
         );
         assert_eq!(outlook.rtf_compressed.starts_with("bc020000b908"), true);
     }
 
     #[test]
-    fn test_multiple_cc() {
+    fn test_case_insensitive_entry_names() {
+        // Emulate generators writing names with a different case:
+        // upper case prefixes, lower case property tags.
+        let mut bytes = std::fs::read("data/unicode.msg").unwrap();
+        for offset in (512..bytes.len() - 128).step_by(128) {
+            let entry = &mut bytes[offset..offset + 128];
+            if entry[0..4] != [b'_', 0, b'_', 0] || !(1..=2).contains(&entry[66]) {
+                continue;
+            }
+            for (i, c) in entry[..64].iter_mut().step_by(2).enumerate() {
+                if i < 12 {
+                    c.make_ascii_uppercase();
+                } else {
+                    c.make_ascii_lowercase();
+                }
+            }
+        }
+        let original = Outlook::from_path("data/unicode.msg").unwrap();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.subject, original.subject);
+        assert_eq!(outlook.sender, original.sender);
+        assert_eq!(outlook.to, original.to);
+        assert_eq!(outlook.headers, original.headers);
+        assert_eq!(outlook.attachments, original.attachments);
+    }
+
+    #[test]
+    fn test_from_slice_with_options() {
+        use super::ParseOptions;
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let bytes = std::fs::read("data/unicode.msg").unwrap();
+        let encoded = STANDARD.encode(&bytes);
+
+        // Containers are left alone by default
+        let err = Outlook::from_slice_with_options(encoded.as_bytes(), &ParseOptions::new());
+        assert!(err.is_err());
+
+        let options = ParseOptions::new().unwrap_containers(true);
+        let outlook = Outlook::from_slice_with_options(encoded.as_bytes(), &options).unwrap();
+        assert_eq!(outlook.subject, String::from("Test for TIF files"));
+
+        // Bare messages are still accepted
+        let outlook = Outlook::from_slice_with_options(&bytes, &options).unwrap();
+        assert_eq!(outlook.subject, String::from("Test for TIF files"));
+    }
+
+    #[test]
+    fn test_from_mime_part() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let bytes = std::fs::read("data/unicode.msg").unwrap();
+        let part = format!(
+            "Content-Type: application/vnd.ms-outlook; name=\"Fwd.msg\"\r\n\
+             Content-Disposition: attachment; filename=\"Fwd.msg\"\r\n\
+             Content-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+            STANDARD.encode(&bytes)
+        );
+        let outlook = Outlook::from_mime_part(part.as_bytes()).unwrap();
+        assert_eq!(outlook.subject, "Test for TIF files");
+
+        let err = Outlook::from_mime_part(b"Content-Type: text/plain\r\n\r\nHello").unwrap_err();
+        assert!(matches!(err, super::Error::ContainerError(_)));
+    }
+
+    #[test]
+    fn test_from_shared() {
+        use super::ParseOptions;
+        use std::sync::Arc;
+
+        let original = Outlook::from_path("data/test_email.msg").unwrap();
+        let data = Arc::new(std::fs::read("data/test_email.msg").unwrap());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let data = Arc::clone(&data);
+                std::thread::spawn(move || Outlook::from_shared(data).unwrap())
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap().to_json().unwrap(), original.to_json().unwrap());
+        }
+        assert_eq!(Arc::strong_count(&data), 1);
+
+        let options = ParseOptions::new().unwrap_containers(true);
+        let outlook = Outlook::from_shared_with_options(data, &options).unwrap();
+        assert_eq!(outlook.subject, original.subject);
+        let encoded = Arc::new(base64::Engine::encode(
+            &base64::engine::general_purpose::STANDARD,
+            std::fs::read("data/unicode.msg").unwrap(),
+        ));
+        let outlook = Outlook::from_shared_with_options(encoded, &options).unwrap();
+        assert_eq!(outlook.subject, "Test for TIF files");
+    }
+
+    #[test]
+    fn test_empty_entries() {
+        // The body stream is Empty, other properties are read
+        let outlook = Outlook::from_path("data/empty_entries.msg").unwrap();
+        assert_eq!(outlook.subject, "Empty entries");
+        assert_eq!(outlook.body, "");
+        assert_eq!(outlook.to[0].email, "jane@example.com");
+        assert_eq!(outlook.attachments[0].name, "notes.txt");
+    }
+
+    #[test]
+    fn test_nested_message_storage() {
+        // Hide the root property stream, so that the root is no longer
+        // a message storage: the first embedded message is found instead.
+        let mut bytes = std::fs::read("data/test_email.msg").unwrap();
+        let offset = entry_offset(&bytes, "__properties_version1.0");
+        bytes[offset + 44] = b'X';
+
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(
+            outlook.subject,
+            "1 Days Left\u{2014}35% off cloud space, upgrade now!"
+        );
+        assert_eq!(outlook.to.len(), 1);
+        assert_eq!(outlook.to[0].email, "marirs@outlook.com");
+    }
+
+    #[test]
+    fn test_partially_committed() {
+        use super::super::ParseWarning;
+
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        assert!(!outlook.report.is_partially_committed());
+
+        // Declare a body larger than the sectors allocated to it
+        let mut bytes = std::fs::read("data/test_email.msg").unwrap();
+        let offset = entry_offset(&bytes, "__substg1.0_1000001F");
+        bytes[offset + 120..offset + 124].copy_from_slice(&[0x00, 0x08, 0x00, 0x00]);
+
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert!(outlook.report.is_partially_committed());
+        assert_eq!(
+            outlook.report.warnings,
+            vec![ParseWarning::IncompleteStream {
+                name: "__substg1.0_1000001F".to_string(),
+                path: "__substg1.0_1000001F".to_string(),
+                declared_size: 0x800,
+            }]
+        );
+        // The truncated body is not returned
+        assert_eq!(outlook.body, "");
+    }
+
+    #[test]
+    fn test_headers_source() {
+        use super::HeadersSource;
+
+        let outlook = Outlook::from_path("data/unicode.msg").unwrap();
+        assert_eq!(outlook.headers_source(), HeadersSource::Transport);
+
+        // No transport headers, rebuilt from "InternetMessageId"
+        let outlook = Outlook::from_path("data/test_email_3.msg").unwrap();
+        assert_eq!(outlook.headers_source(), HeadersSource::Synthesized);
+        assert_eq!(
+            outlook.headers.message_id,
+            "<PSAPR02MB48375DC9668A924D7006F6ECFD6D9@PSAPR02MB4837.apcprd02.prod.outlook.com>"
+        );
+        assert_eq!(outlook.headers.date, "");
+
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        assert_eq!(outlook.headers_source(), HeadersSource::Absent);
+        assert_eq!(outlook.headers.message_id, "");
+        assert!(!outlook.headers_present);
+    }
+
+    #[test]
+    fn test_metrics() {
+        use crate::ParseOptions;
+
         let path = "data/test_email.msg";
         let outlook = Outlook::from_path(path).unwrap();
+        let metrics = outlook.report.metrics;
+        let size = std::fs::metadata(path).unwrap().len();
+        assert!(metrics.bytes_read > 0 && metrics.bytes_read < size);
+        assert!(!metrics.stream_decode.is_zero());
+        assert!(!(metrics.header_parse + metrics.sat_build).is_zero());
+
+        // Payloads left out are not read
+        let options = ParseOptions::new().allow_attachments(&[".none"]);
+        let filtered = Outlook::from_path_with_options(path, &options).unwrap();
+        assert!(filtered.report.metrics.bytes_read < metrics.bytes_read);
+        // Metrics do not tell reports apart
+        assert_eq!(Outlook::from_path(path).unwrap().report, outlook.report);
+    }
+
+    #[test]
+    fn test_raw_headers() {
+        use crate::{ParseOptions, RawHeadersEncoding};
+
+        let path = "data/unicode.msg";
+        let outlook = Outlook::from_path(path).unwrap();
+        assert_eq!(outlook.raw_headers, None);
+        assert!(!outlook.to_json().unwrap().contains("raw_headers"));
+
+        let options = ParseOptions::new().raw_headers(RawHeadersEncoding::GzipBase64);
+        let outlook = Outlook::from_path_with_options(path, &options).unwrap();
+        let json = outlook.to_json().unwrap();
+        let parsed: Outlook = serde_json::from_str(&json).unwrap();
+        let text = parsed.raw_headers.unwrap().text().unwrap();
+        assert!(text.contains(&outlook.headers.message_id));
+
+        let options = ParseOptions::new().raw_headers(RawHeadersEncoding::Plain);
+        let plain = Outlook::from_path_with_options(path, &options).unwrap();
+        assert_eq!(plain.raw_headers.unwrap().data, text);
+
+        // Not set in messages without transport headers
+        let outlook = Outlook::from_path_with_options("data/test_email.msg", &options).unwrap();
+        assert_eq!(outlook.raw_headers, None);
+    }
+
+    #[test]
+    fn test_headers_present() {
+        use super::HeadersSource;
+        use crate::testing::MessageBuilder;
+
+        assert!(Outlook::from_path("data/unicode.msg").unwrap().headers_present);
+
+        // Set but empty, as written by some Outlook versions
+        let bytes = MessageBuilder::new().headers("").build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert!(outlook.headers_present);
+        assert_eq!(outlook.headers_source(), HeadersSource::Absent);
+
+        // Set, without any of the fields
+        let bytes = MessageBuilder::new().headers("X-Custom: value\r\n").build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert!(outlook.headers_present);
+        assert_eq!(outlook.headers_source(), HeadersSource::Transport);
+        assert_eq!(outlook.headers.message_id, "");
 
+        let outlook = Outlook::from_slice(&MessageBuilder::new().build()).unwrap();
+        assert!(!outlook.headers_present);
+    }
+
+    #[test]
+    fn test_person_new() {
+        let person = Person::from(("Jane Doe", "jane@example.com"));
+        assert_eq!(person, Person::new("Jane Doe".to_string(), "jane@example.com"));
+        assert!(person.valid);
+        assert_eq!(person.storage_index, None);
+
+        let invalid = [
+            "",
+            "jane",
+            "@example.com",
+            "jane@",
+            "jane@@example.com",
+            "jane@.com",
+            "jane doe@example.com",
+            "/o=Org/cn=Jane",
+        ];
+        for email in invalid {
+            assert!(!Person::new("Jane", email).valid, "{}", email);
+        }
+        assert!(Person::is_valid_email("jane.doe+tag@mail.example.com"));
+    }
+
+    #[test]
+    fn test_full_text() {
+        use crate::{testing::MessageBuilder, Attachment, AttachmentTextExtractor, ParseOptions};
+
+        struct PlainText;
+
+        impl AttachmentTextExtractor for PlainText {
+            fn extract(&self, attachment: &Attachment, payload: &[u8]) -> Option<String> {
+                (attachment.mime_tag == "text/plain")
+                    .then(|| String::from_utf8_lossy(payload).into_owned())
+            }
+        }
+
+        let inner = MessageBuilder::new().subject("Forwarded").body("Inner body").build();
+        let bytes = MessageBuilder::new()
+            .subject("Outer")
+            .body("Outer body")
+            .attachment("notes.txt", "text/plain", b"Some notes")
+            .attachment("image.png", "image/png", b"\x89PNG")
+            .attachment("inner.msg", "application/vnd.ms-outlook", &inner)
+            .build();
+
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert!(outlook.attachments.iter().all(|attach| attach.text.is_none()));
         assert_eq!(
-            outlook.cc,
-            vec![]
+            outlook.full_text(),
+            "Outer\n\nOuter body\n\nnotes.txt\n\nimage.png\n\ninner.msg\n\nForwarded\n\nInner body"
+        );
+
+        let options = ParseOptions::new()
+            .attachment_text_extractor(PlainText)
+            .deny_attachments(&["image/*"]);
+        let outlook = Outlook::from_slice_with_options(&bytes, &options).unwrap();
+        assert_eq!(outlook.attachments[0].text.as_deref(), Some("Some notes"));
+        assert_eq!(outlook.attachments[2].text, None);
+        assert_eq!(
+            outlook.full_text(),
+            "Outer\n\nOuter body\n\nnotes.txt\n\nSome notes\n\nimage.png\n\n\
+             inner.msg\n\nForwarded\n\nInner body"
+        );
+    }
+
+    #[test]
+    fn test_content_scanner() {
+        use crate::{testing::MessageBuilder, AttachmentScan, ContentScanner, ParseOptions};
+        use crate::ScanTarget;
+
+        // Labels the payloads holding "SECRET", along with their size
+        struct Secrets;
+        struct SecretScan {
+            seen: Vec<u8>,
+            found: bool,
+        }
+
+        impl ContentScanner for Secrets {
+            fn start(&self, target: &ScanTarget) -> Option<Box<dyn AttachmentScan>> {
+                (!target.mime_tag.starts_with("image/")).then(|| {
+                    Box::new(SecretScan {
+                        seen: vec![],
+                        found: false,
+                    }) as Box<dyn AttachmentScan>
+                })
+            }
+        }
+
+        impl AttachmentScan for SecretScan {
+            fn update(&mut self, chunk: &[u8]) {
+                // Keeps the end of the previous chunk, for matches across chunks
+                let start = self.seen.len().saturating_sub(5);
+                self.seen = self.seen[start..].iter().chain(chunk).copied().collect();
+                self.found |= self.seen.windows(6).any(|window| window == b"SECRET");
+            }
+
+            fn finish(self: Box<Self>) -> Vec<String> {
+                if self.found {
+                    vec!["secret".to_string()]
+                } else {
+                    vec![]
+                }
+            }
+        }
+
+        // Across the chunks of the scan
+        let mut data = vec![b'.'; 100_000];
+        data[65_533..65_539].copy_from_slice(b"SECRET");
+        let bytes = MessageBuilder::new()
+            .attachment("plans.bin", "application/octet-stream", &data)
+            .attachment("notes.txt", "text/plain", b"Nothing to see")
+            .attachment("photo.png", "image/png", b"SECRET")
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert!(outlook.attachments.iter().all(|a| a.classification.is_empty()));
+
+        let options = ParseOptions::new().content_scanner(Secrets);
+        let outlook = Outlook::from_slice_with_options(&bytes, &options).unwrap();
+        let labels: Vec<&[String]> = outlook
+            .attachments
+            .iter()
+            .map(|a| a.classification.as_slice())
+            .collect();
+        assert_eq!(labels, [&["secret".to_string()][..], &[], &[]]);
+        assert_eq!(outlook.attachments[0].payload, hex::encode(&data));
+
+        // Payloads left out are scanned all the same
+        let options = options.deny_attachments(&[".bin"]);
+        let outlook = Outlook::from_slice_with_options(&bytes, &options).unwrap();
+        assert!(outlook.attachments[0].payload.is_empty());
+        assert_eq!(outlook.attachments[0].classification, ["secret"]);
+
+        // Payloads are scanned whole, even when truncated
+        let options = ParseOptions::new().content_scanner(Secrets).max_property_size(1000);
+        let outlook = Outlook::from_slice_with_options(&bytes, &options).unwrap();
+        assert_eq!(outlook.attachments[0].payload, hex::encode(&data[..1000]));
+        assert_eq!(outlook.attachments[0].classification, ["secret"]);
+
+        // Payloads with secrets are left out once scanned
+        struct Redact;
+        impl ContentScanner for Redact {
+            fn start(&self, target: &ScanTarget) -> Option<Box<dyn AttachmentScan>> {
+                Secrets.start(target)
+            }
+
+            fn keep_payload(&self, _: &ScanTarget, labels: &[String]) -> bool {
+                labels.is_empty()
+            }
+        }
+        let options = ParseOptions::new().content_scanner(Redact);
+        let outlook = Outlook::from_slice_with_options(&bytes, &options).unwrap();
+        let filtered: Vec<bool> = outlook.attachments.iter().map(|a| a.payload_filtered).collect();
+        assert_eq!(filtered, [true, false, false]);
+        assert!(outlook.attachments[0].payload.is_empty());
+        assert_eq!(outlook.attachments[0].classification, ["secret"]);
+        assert_eq!(outlook.attachments[1].payload, hex::encode(b"Nothing to see"));
+    }
+
+    #[test]
+    fn test_replacement_characters() {
+        use crate::{testing::Writer, ParseOptions};
+
+        // Unpaired surrogates, as left by a lossy conversion
+        let subject = [0x48, 0x00, 0x00, 0xD8, 0x69, 0x00, 0x00, 0xDC];
+        let mut writer = Writer::new();
+        writer
+            .add_stream("__properties_version1.0", &[0u8; 32])
+            .add_stream("__substg1.0_0037001F", &subject)
+            .add_stream("__substg1.0_1000001F", &[0x00, 0xD8]);
+        let bytes = writer.to_bytes();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.subject, "H\u{FFFD}i\u{FFFD}");
+        assert_eq!(outlook.body, "\u{FFFD}");
+        let replacements = outlook.report.replacements();
+        assert_eq!(replacements.get("Subject"), Some(&2));
+        assert_eq!(replacements.get("Body"), Some(&1));
+
+        let options = ParseOptions::new().strict(true);
+        assert!(Outlook::from_slice_with_options(&bytes, &options).is_err());
+    }
+
+    #[test]
+    fn test_string_byte_order() {
+        use crate::{parser::report::ParseWarning, testing::Writer, Utf16Encoding};
+
+        let big_endian = |value: &str| -> Vec<u8> {
+            value.encode_utf16().flat_map(|c| c.to_be_bytes()).collect()
+        };
+        let mut bom = vec![0xFE, 0xFF];
+        bom.extend(big_endian("Body text"));
+        let mut writer = Writer::new();
+        writer
+            .add_stream("__properties_version1.0", &[0u8; 32])
+            .add_stream("__substg1.0_0037001F", &big_endian("Quarterly report"))
+            .add_stream("__substg1.0_1000001F", &bom);
+        let outlook = Outlook::from_slice(&writer.to_bytes()).unwrap();
+        assert_eq!(outlook.subject, "Quarterly report");
+        assert_eq!(outlook.body, "Body text");
+        let mut encodings: Vec<(String, Utf16Encoding)> = outlook
+            .report
+            .warnings
+            .iter()
+            .filter_map(|warning| match warning {
+                ParseWarning::StringEncoding { path, encoding } => Some((path.clone(), *encoding)),
+                _ => None,
+            })
+            .collect();
+        encodings.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            encodings,
+            vec![
+                ("__substg1.0_0037001F".to_string(), Utf16Encoding::BigEndian),
+                ("__substg1.0_1000001F".to_string(), Utf16Encoding::BigEndianBom),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_client_info() {
+        use super::super::storage::Storages;
+        use super::ClientInfo;
+        use crate::ole::Reader;
+
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        assert_eq!(
+            outlook.client_info(),
+            &ClientInfo {
+                creator_name: "".to_string(),
+                last_modifier_name: "Sriram Govindan".to_string(),
+                mailer: "".to_string(),
+            }
+        );
+
+        let parser = Reader::from_path("data/unicode.msg").unwrap();
+        let storages = Storages::new(&parser);
+        let headers = "Subject: Hi\r\nX-Mailer: Microsoft Outlook 16.0\r\nUser-Agent: Mutt\r\n";
+        assert_eq!(
+            ClientInfo::create(&storages, headers).mailer,
+            "Microsoft Outlook 16.0"
+        );
+        let headers = "Subject: Hi\r\nUser-Agent: Mutt/2.0\r\n";
+        assert_eq!(ClientInfo::create(&storages, headers).mailer, "Mutt/2.0");
+    }
+
+    #[test]
+    fn test_strict() {
+        use super::super::ParseWarning;
+        use super::{Error, ParseOptions};
+
+        let options = ParseOptions::new().strict(true);
+        assert!(Outlook::from_path_with_options("data/test_email.msg", &options).is_ok());
+
+        // Give the subject a property type undefined in MS-OXCDATA
+        let mut bytes = std::fs::read("data/unicode.msg").unwrap();
+        let offset = entry_offset(&bytes, "__substg1.0_0037001F");
+        bytes[offset + 32..offset + 40].copy_from_slice(&[b'1', 0, b'2', 0, b'3', 0, b'4', 0]);
+
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.subject, "");
+        let warning = ParseWarning::UnknownPropertyType {
+            path: "__substg1.0_00371234".to_string(),
+            code: "0x1234".to_string(),
+        };
+        assert_eq!(outlook.report.warnings, vec![warning.clone()]);
+
+        let err = Outlook::from_slice_with_options(&bytes, &options).unwrap_err();
+        assert!(matches!(err, Error::StrictViolation(ref w) if *w == warning));
+        assert_eq!(
+            err.to_string(),
+            "Anomaly found in strict mode: Property type 0x1234 of __substg1.0_00371234 is unknown"
         );
     }
 
+    #[test]
+    fn test_max_property_size() {
+        use super::super::ParseWarning;
+        use super::ParseOptions;
+
+        let full = Outlook::from_path("data/unicode.msg").unwrap();
+        let options = ParseOptions::new().max_property_size(64);
+        let outlook = Outlook::from_path_with_options("data/unicode.msg", &options).unwrap();
+        assert_eq!(outlook.subject, full.subject);
+        assert!(full.body.starts_with(&outlook.body));
+        assert_eq!(outlook.body.encode_utf16().count(), 32);
+        assert!(outlook.attachments[0].payload.len() <= 128);
+
+        let truncated: Vec<&ParseWarning> = outlook
+            .report
+            .warnings
+            .iter()
+            .filter(|w| matches!(w, ParseWarning::PropertyTruncated { .. }))
+            .collect();
+        assert!(truncated.contains(&&ParseWarning::PropertyTruncated {
+            path: "__substg1.0_1000001F".to_string(),
+            size: full.body.encode_utf16().count() * 2,
+            max_size: 64,
+        }));
+        assert_eq!(truncated.len(), outlook.report.warnings.len());
+        assert!(full.report.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_storage_index() {
+        use crate::testing::Writer;
+
+        let outlook = Outlook::from_path("data/attachment.msg").unwrap();
+        let indices: Vec<u32> = outlook.attachments.iter().map(|a| a.storage_index).collect();
+        assert_eq!(indices, vec![0, 1, 2]);
+        assert_eq!(outlook.sender.storage_index, None);
+
+        // Numbers with gaps, as left by removed attachments
+        let name = |value: &str| -> Vec<u8> {
+            value.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+        };
+        let mut writer = Writer::new();
+        writer
+            .add_stream("__properties_version1.0", &[0u8; 32])
+            .add_stream("__recip_version1.0_#00000004/__substg1.0_3001001F", &name("Jane"))
+            .add_stream("__attach_version1.0_#00000002/__substg1.0_3001001F", &name("a.txt"))
+            .add_stream("__attach_version1.0_#0000000A/__substg1.0_3001001F", &name("b.txt"));
+        let outlook = Outlook::from_slice(&writer.to_bytes()).unwrap();
+        assert_eq!(outlook.to[0].storage_index, Some(4));
+        assert_eq!(outlook.attachments[0].storage_index, 2);
+        assert_eq!(outlook.attachments[1].name, "b.txt");
+        assert_eq!(outlook.attachments[1].storage_index, 10);
+    }
+
+    #[test]
+    fn test_verify() {
+        use super::{Discrepancy, PropertyHeader};
+        use crate::testing::{MessageBuilder, PropertyValue, Writer};
+
+        for path in ["data/test_email.msg", "data/attachment.msg", "data/unicode.msg"] {
+            let outlook = Outlook::from_path(path).unwrap();
+            assert!(outlook.walk_embedded(usize::MAX).all(|(_, msg)| msg.verify().is_empty()));
+            // Left out of JSON output, as are absent fields and sources
+            let json = serde_json::to_value(&outlook).unwrap();
+            for key in ["declared", "absent", "sources"] {
+                assert!(json.get(key).is_none());
+            }
+        }
+
+        let bytes = MessageBuilder::new()
+            .body("Hello")
+            .recipient("Jane Doe", "jane@example.com")
+            .attachment("notes.txt", "text/plain", b"some notes")
+            .property("MessageSizeExtended", PropertyValue::Integer32(16))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        match outlook.verify().as_slice() {
+            [Discrepancy::MessageSize { declared: 16, actual }] => assert!(*actual > 16),
+            discrepancies => panic!("unexpected {:?}", discrepancies),
+        }
+
+        // Two recipients and an attachment declared, one recipient left
+        let mut header = vec![0u8; 32];
+        header[8] = 2;
+        header[12] = 1;
+        header[16] = 2;
+        header[20] = 1;
+        let mut writer = Writer::new();
+        writer
+            .add_stream("__properties_version1.0", &header)
+            .add_stream("__recip_version1.0_#00000000/__substg1.0_3001001F", &[0x41, 0]);
+        let outlook = Outlook::from_slice(&writer.to_bytes()).unwrap();
+        assert_eq!(
+            outlook.verify(),
+            vec![
+                Discrepancy::AttachmentCount {
+                    declared: 1,
+                    found: 0
+                },
+                Discrepancy::RecipientCount {
+                    declared: 2,
+                    found: 1
+                },
+            ]
+        );
+        assert_eq!(
+            outlook.property_header.unwrap(),
+            PropertyHeader {
+                next_recipient_id: 2,
+                next_attachment_id: 1,
+                recipient_count: 2,
+                attachment_count: 1,
+            }
+        );
+
+        // A recipient numbered past the next recipient id
+        let mut header = vec![0u8; 32];
+        header[8] = 1;
+        header[16] = 1;
+        let mut writer = Writer::new();
+        writer
+            .add_stream("__properties_version1.0", &header)
+            .add_stream("__recip_version1.0_#00000003/__substg1.0_3001001F", &[0x41, 0]);
+        let outlook = Outlook::from_slice(&writer.to_bytes()).unwrap();
+        assert_eq!(
+            outlook.verify(),
+            vec![Discrepancy::RecipientId {
+                next_id: 1,
+                found: 3
+            }]
+        );
+    }
+
+    #[test]
+    fn test_attachment_filter() {
+        use super::ParseOptions;
+
+        let path = "data/attachment.msg";
+        let full = Outlook::from_path(path).unwrap();
+        assert!(full.attachments.iter().all(|a| !a.payload_filtered));
+
+        let filtered = |options: ParseOptions| -> Vec<bool> {
+            let outlook = Outlook::from_path_with_options(path, &options).unwrap();
+            for (attachment, original) in outlook.attachments.iter().zip(&full.attachments) {
+                assert_eq!(attachment.display_name, original.display_name);
+                if attachment.payload_filtered {
+                    assert!(attachment.payload.is_empty());
+                } else {
+                    assert_eq!(attachment.payload, original.payload);
+                }
+            }
+            outlook.attachments.iter().map(|a| a.payload_filtered).collect()
+        };
+        // .doc, .png and .jpg
+        assert_eq!(
+            filtered(ParseOptions::new().allow_attachments(&["image/*"])),
+            vec![true, false, false]
+        );
+        assert_eq!(
+            filtered(ParseOptions::new().deny_attachments(&[".DOC", "image/jpeg"])),
+            vec![true, false, true]
+        );
+        assert_eq!(
+            filtered(
+                ParseOptions::new()
+                    .allow_attachments(&["png", "application/msword"])
+                    .deny_attachments(&["application/*"])
+            ),
+            vec![true, false, true]
+        );
+    }
+
+    #[test]
+    fn test_subject_ansi() {
+        use super::super::ParseWarning;
+        use crate::testing::{MessageBuilder, PropertyValue};
+
+        let ansi = |value: &[u8]| PropertyValue::String8(value.to_vec());
+        let bytes = MessageBuilder::new()
+            .property("Subject", ansi(b"R\xE9ponse ??"))
+            .subject("Réponse ✓✓")
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.subject, "Réponse ✓✓");
+        assert_eq!(outlook.subject_ansi.as_deref(), Some("Réponse ??"));
+        assert!(outlook.report.warnings.is_empty());
+
+        // Spoofed, the Unicode value is kept
+        let bytes = MessageBuilder::new()
+            .subject("Invoice")
+            .property("Subject", ansi(b"Payment overdue"))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.subject, "Invoice");
+        assert_eq!(
+            outlook.report.warnings,
+            vec![ParseWarning::StringVariantMismatch {
+                name: "Subject".to_string(),
+                unicode: "Invoice".to_string(),
+                ansi: "Payment overdue".to_string(),
+            }]
+        );
+
+        // ANSI only
+        let bytes = MessageBuilder::new().property("Subject", ansi(b"Hello")).build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.subject, "Hello");
+        assert!(outlook.report.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_one_off_recipient() {
+        use crate::{
+            ole::Reader,
+            parser::{decode::DataType, entry_id::tests::one_off, storage::Storages},
+        };
+
+        let parser = Reader::from_path("data/unicode.msg").unwrap();
+        let mut storages = Storages::new(&parser);
+        storages.process_streams(&parser);
+
+        // A BCC recipient known only by its one-off EntryID
+        let recipient = &mut storages.recipients[1];
+        for key in ["DisplayName", "SmtpAddress", "EmailAddress"] {
+            recipient.remove(key);
+        }
+        recipient.insert(
+            "RecipientEntryId".to_string(),
+            DataType::PtypBinary(one_off(["Jane Doe", "SMTP", "jane@example.com"], true)),
+        );
+        let outlook = Outlook::populate(&storages);
+        assert_eq!(outlook.cc[0].name, "Jane Doe");
+        assert_eq!(outlook.cc[0].email, "jane@example.com");
+        assert_eq!(outlook.cc[0].storage_index, Some(1));
+        assert_eq!(outlook.to[0], Outlook::from_path("data/unicode.msg").unwrap().to[0]);
+    }
+
+    #[test]
+    fn test_embedded_nul() {
+        use super::super::ParseWarning;
+        use crate::testing::{MessageBuilder, PropertyValue};
+        use crate::{DataType, ParseOptions};
+
+        let bytes = MessageBuilder::new()
+            .subject("Budget\0\0Q3")
+            .body("Body\0\0")
+            .property("Keyword", PropertyValue::MultipleString(vec!["a\0b".to_string()]))
+            .build();
+        let options = ParseOptions::new().retain_properties(true);
+        let outlook = Outlook::from_slice_with_options(&bytes, &options).unwrap();
+        assert_eq!(outlook.subject, "Budget");
+        assert_eq!(outlook.body, "Body");
+        assert_eq!(
+            outlook.property("Keyword"),
+            Some(&DataType::PtypMultipleString(vec!["a".to_string()]))
+        );
+        // Trailing NULs are not reported
+        assert_eq!(
+            outlook.report.warnings,
+            [
+                ParseWarning::EmbeddedNul {
+                    name: "Subject".to_string(),
+                    path: "__substg1.0_0037001F".to_string(),
+                    position: 6,
+                },
+                ParseWarning::EmbeddedNul {
+                    name: "Keyword".to_string(),
+                    path: "__substg1.0_3A0B101F-00000000".to_string(),
+                    position: 1,
+                },
+            ]
+        );
+
+        let options = ParseOptions::new().preserve_nuls(true);
+        let outlook = Outlook::from_slice_with_options(&bytes, &options).unwrap();
+        assert_eq!(outlook.subject, "Budget\0\0Q3");
+        assert_eq!(outlook.report.warnings.len(), 2);
+    }
+
+    #[test]
+    fn test_string_decoder() {
+        use crate::{ParseOptions, StringDecoder};
+        use crate::testing::{MessageBuilder, PropertyValue};
+
+        // Undoes UTF-8 encoded twice, read as Latin-1 in between
+        struct DoubleUtf8;
+        impl StringDecoder for DoubleUtf8 {
+            fn decode_ansi(&self, bytes: &[u8]) -> Option<String> {
+                let once = std::str::from_utf8(bytes).ok()?;
+                let latin1: Option<Vec<u8>> = once
+                    .trim_end_matches('\0')
+                    .chars()
+                    .map(|c| (c as u32 <= 0xFF).then_some(c as u8))
+                    .collect();
+                String::from_utf8(latin1?).ok()
+            }
+        }
+
+        let bytes = MessageBuilder::new()
+            .property("Subject", PropertyValue::String8("CafÃ©".as_bytes().to_vec()))
+            .body("Café")
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.subject, "CafÃ©");
+
+        let options = ParseOptions::new().string_decoder(DoubleUtf8);
+        let outlook = Outlook::from_slice_with_options(&bytes, &options).unwrap();
+        assert_eq!(outlook.subject, "Café");
+        // Unicode values keep the default decoding
+        assert_eq!(outlook.body, "Café");
+    }
+
+    #[cfg(not(feature = "legacy"))]
+    #[test]
+    fn test_headers_parser() {
+        let outlook = Outlook::from_path("data/test_email_1.msg").unwrap();
+        // Fields are matched by name, not as the end of another one
+        // such as X-MS-Exchange-CrossTenant-Network-Message-Id
+        assert_eq!(
+            outlook.headers.message_id,
+            "<PSAPR02MB4837B16144ECEDB641FCDE04FD6D9@PSAPR02MB4837.apcprd02.prod.outlook.com>"
+        );
+        assert_eq!(
+            outlook.thread_key(),
+            "id:psapr02mb4837b16144ecedb641fcde04fd6d9@psapr02mb4837.apcprd02.prod.outlook.com"
+        );
+    }
+
+    #[test]
+    fn test_mailing_list() {
+        use super::MailingList;
+        use crate::testing::{MessageBuilder, PropertyValue};
+
+        let bytes = MessageBuilder::new()
+            .headers(
+                "Message-ID: <c@x.com>\r\n\
+                 List-Id: Dev list <dev.lists.example.org>\r\n\
+                 List-Unsubscribe: <mailto:leave@example.org>,\r\n\
+                 \t<https://example.org/u/1>\r\n\
+                 List-Unsubscribe-Post: List-Unsubscribe=One-Click\r\n\
+                 List-Help: <https://example.org/help>\r\n",
+            )
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(
+            outlook.mailing_list,
+            Some(MailingList {
+                id: "dev.lists.example.org".to_string(),
+                name: "Dev list".to_string(),
+                unsubscribe: vec![
+                    "mailto:leave@example.org".to_string(),
+                    "https://example.org/u/1".to_string()
+                ],
+                one_click: true,
+                subscribe: vec![],
+                help: vec!["https://example.org/help".to_string()],
+            })
+        );
+
+        // The property is preferred over the header
+        let bytes = MessageBuilder::new()
+            .headers("List-Unsubscribe: <https://example.org/u/1>\r\n")
+            .property(
+                "ListUnsubscribe",
+                PropertyValue::String("<mailto:leave@example.org>".to_string()),
+            )
+            .build();
+        let list = Outlook::from_slice(&bytes).unwrap().mailing_list.unwrap();
+        assert_eq!(list.unsubscribe, ["mailto:leave@example.org"]);
+        assert!(list.id.is_empty());
+        assert!(!list.one_click);
+
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        assert_eq!(outlook.mailing_list, None);
+        let json = outlook.to_json().unwrap();
+        assert!(!json.contains("mailing_list"));
+    }
+
+    #[test]
+    fn test_thread_key() {
+        use crate::testing::{MessageBuilder, PropertyValue};
+
+        // Neither a reply nor replied to, its own Message-ID
+        let bytes = MessageBuilder::new().headers("Message-ID: <Own@x.com>\r\n").build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert!(outlook.in_reply_to.is_empty());
+        assert_eq!(outlook.thread_key(), "id:own@x.com");
+
+        let bytes = MessageBuilder::new()
+            .headers(
+                "Message-ID: <c@x.com>\r\n\
+                 In-Reply-To: <7YMRY74735EQVZWQAGCENQCPV0@protonmail.ch>\r\n",
+            )
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.in_reply_to, "<7YMRY74735EQVZWQAGCENQCPV0@protonmail.ch>");
+        assert_eq!(outlook.thread_key(), "id:7ymry74735eqvzwqagcenqcpv0@protonmail.ch");
+
+        // The first message of References is the root of the thread
+        let bytes = MessageBuilder::new()
+            .property("InReplyToId", PropertyValue::String("<b@x.com>".to_string()))
+            .property(
+                "InternetReferences",
+                PropertyValue::String("<A@x.com> <b@x.com>".to_string()),
+            )
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.thread_key(), "id:a@x.com");
+        assert_eq!(outlook.reference_ids(), ["A@x.com", "b@x.com"]);
+
+        let index: Vec<u8> = (0..27u8).collect();
+        let bytes = MessageBuilder::new()
+            .property("ConversationIndex", PropertyValue::Binary(index.clone()))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(
+            outlook.thread_key(),
+            format!("conversation:{}", hex::encode_upper(&index[..22]))
+        );
+        let outlook = Outlook::from_slice(&MessageBuilder::new().build()).unwrap();
+        assert_eq!(outlook.thread_key(), "");
+    }
+
+    #[test]
+    fn test_time_policy() {
+        use super::{ParseOptions, TimePolicy};
+
+        let utc = Outlook::from_path("data/unicode.msg").unwrap();
+        let submitted = &utc.timestamps["ClientSubmitTime"];
+        assert_eq!(submitted.offset, Some(0));
+        assert!(submitted.datetime.ends_with('Z'));
+
+        // Date: Mon, 18 Nov 2013 10:26:24 +0200
+        let options = ParseOptions::new().time_policy(TimePolicy::Original);
+        let outlook = Outlook::from_path_with_options("data/unicode.msg", &options).unwrap();
+        let original = &outlook.timestamps["ClientSubmitTime"];
+        assert_eq!(original.filetime, submitted.filetime);
+        assert_eq!(original.offset, Some(7200));
+        assert!(original.datetime.ends_with("+02:00"));
+        assert_eq!(original.to_utc(), submitted.to_utc());
+        assert_eq!(outlook.timestamps.len(), utc.timestamps.len());
+    }
+
+    #[test]
+    fn test_response_kind() {
+        use super::ResponseKind;
+        use crate::testing::{MessageBuilder, PropertyValue};
+
+        let outlook = Outlook::from_path("data/unicode.msg").unwrap();
+        assert_eq!(outlook.response_kind(), ResponseKind::New);
+        assert!(!outlook.is_reply() && !outlook.is_forward());
+
+        let bytes = MessageBuilder::new()
+            .subject("RE: Test for TIF files")
+            .property("SubjectPrefix", PropertyValue::String("RE: ".to_string()))
+            .property("IconIndex", PropertyValue::Integer32(0x106))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.subject_prefix, "RE: ");
+        assert!(outlook.is_reply());
+        assert!(outlook.was_forwarded() && !outlook.was_replied());
+
+        // Replies whose prefix was left out
+        let bytes = MessageBuilder::new()
+            .subject("Test for TIF files")
+            .headers("In-Reply-To: <abc@example.com>\r\n")
+            .build();
+        assert!(Outlook::from_slice(&bytes).unwrap().is_reply());
+
+        let bytes = MessageBuilder::new().subject("Fwd: Test for TIF files").build();
+        assert_eq!(Outlook::from_slice(&bytes).unwrap().response_kind(), ResponseKind::Forward);
+    }
+
+    #[test]
+    fn test_last_verb() {
+        use crate::{
+            testing::{MessageBuilder, PropertyValue},
+            Verb,
+        };
+
+        // 2013-11-18T08:26:24Z
+        let bytes = MessageBuilder::new()
+            .property("IconIndex", PropertyValue::Integer32(0x105))
+            .property("LastVerbExecuted", PropertyValue::Integer32(0x68))
+            .property("LastVerbExecutionTime", PropertyValue::Time(130292367840000000))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.icon_index, Some(0x105));
+        let last_verb = outlook.last_verb.unwrap();
+        assert_eq!(last_verb.verb, Verb::Forward);
+        assert_eq!(last_verb.executed_at.unwrap().to_rfc3339(), "2013-11-18T08:26:24+00:00");
+        // The last verb is preferred over the icon
+        assert!(outlook.was_forwarded() && !outlook.was_replied());
+
+        let bytes = MessageBuilder::new()
+            .property("LastVerbExecuted", PropertyValue::Integer32(0x67))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.last_verb.unwrap().verb, Verb::ReplyToAll);
+        assert_eq!(outlook.last_verb.unwrap().executed_at, None);
+        assert!(outlook.was_replied());
+        assert!(Outlook::from_path("data/unicode.msg").unwrap().last_verb.is_none());
+    }
+
+    #[test]
+    fn test_sync_keys() {
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        let keys = outlook.sync_keys.unwrap();
+        assert_eq!(keys.search_key.map(|key| key.len()), Some(32));
+        let change_key = keys.change_key.unwrap();
+        // The last change is one of the changes seen
+        let list = keys.predecessor_change_list.unwrap();
+        assert!(list.contains(&change_key));
+
+        // Only a search key
+        let outlook = Outlook::from_path("data/unicode.msg").unwrap();
+        let keys = outlook.sync_keys.unwrap();
+        assert!(keys.search_key.is_some());
+        assert_eq!(keys.change_key, None);
+    }
+
+    #[test]
+    fn test_sent_received_at() {
+        let outlook = Outlook::from_path("data/unicode.msg").unwrap();
+        let sent_at = outlook.sent_at.unwrap();
+        assert_eq!(Some(sent_at), outlook.timestamps["ClientSubmitTime"].to_utc());
+        // Date: Mon, 18 Nov 2013 10:26:24 +0200
+        assert_eq!(sent_at.to_rfc3339(), "2013-11-18T08:26:24+00:00");
+        assert_eq!(
+            outlook.received_at,
+            outlook.timestamps.get("MessageDeliveryTime").and_then(|time| time.to_utc())
+        );
+        let json: serde_json::Value = serde_json::from_str(&outlook.to_json().unwrap()).unwrap();
+        assert_eq!(json["sent_at"], "2013-11-18T08:26:24Z");
+    }
+
+    #[test]
+    fn test_walk_embedded() {
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        let walked: Vec<(usize, &str)> = outlook
+            .walk_embedded(8)
+            .map(|(depth, msg)| (depth, msg.subject.as_str()))
+            .collect();
+        assert_eq!(
+            walked,
+            vec![
+                (0, "Test Email"),
+                (1, "1 Days Left\u{2014}35% off cloud space, upgrade now!"),
+                (1, "Test Email"),
+                (2, "1 Days Left\u{2014}35% off cloud space, upgrade now!"),
+            ]
+        );
+        assert_eq!(outlook.walk_embedded(1).count(), 3);
+
+        // Embedded recipients are kept apart from the parent ones
+        let embedded = outlook.attachments[0].embedded_message.as_ref().unwrap();
+        assert_eq!(embedded.to.len(), 1);
+        assert_eq!(embedded.to[0].email, "marirs@outlook.com");
+
+        // Depth limited
+        assert_eq!(outlook.walk_embedded(0).count(), 1);
+    }
+
+    #[test]
+    fn test_embedded_limits() {
+        use super::{Error, ParseOptions};
+        let bytes = std::fs::read("data/test_email.msg").unwrap();
+        // Embedded messages go two levels deep
+        let options = ParseOptions::new().max_embedded_depth(2);
+        assert!(Outlook::from_slice_with_options(&bytes, &options).is_ok());
+
+        let options = ParseOptions::new().max_embedded_depth(1);
+        let err = Outlook::from_slice_with_options(&bytes, &options).unwrap_err();
+        assert!(matches!(err, Error::LimitExceeded(_)));
+        assert_eq!(
+            err.to_string(),
+            "Limit exceeded: embedded messages nested deeper than 1"
+        );
+
+        let options = ParseOptions::new().max_storages(4);
+        let err = Outlook::from_slice_with_options(&bytes, &options).unwrap_err();
+        assert_eq!(err.to_string(), "Limit exceeded: more than 4 storages");
+    }
+
+    #[test]
+    fn test_message_size() {
+        let outlook = Outlook::from_path("data/unicode.msg").unwrap();
+        assert_eq!(outlook.message_size, None);
+        assert_eq!(outlook.property_count, 92);
+
+        // Turn the root "MessageFlags" entry of the property stream
+        // into a "MessageSizeExtended" one.
+        let mut bytes = std::fs::read("data/unicode.msg").unwrap();
+        let tag = [0x03, 0x00, 0x07, 0x0E];
+        let offset = bytes.windows(4).position(|x| x == tag).unwrap();
+        bytes[offset + 2] = 0x08;
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.message_size, Some(0x30011));
+        assert_eq!(outlook.property_count, 92);
+    }
+
+    #[test]
+    fn test_item_kind() {
+        use crate::{testing::MessageBuilder, HeadersSource, ItemKind};
+
+        let message = |class: &str| {
+            MessageBuilder::new()
+                .message_class(class)
+                .subject("Jane Doe")
+                .headers("Message-ID: <abc@example.com>\r\nIn-Reply-To: <xyz@example.com>\r\n")
+                .sender("John Doe", "john@example.com")
+                .build()
+        };
+        let outlook = Outlook::from_slice(&message("IPM.Note")).unwrap();
+        assert_eq!(outlook.kind, ItemKind::Note);
+        assert_eq!(outlook.message_class(), "IPM.Note");
+        assert_eq!(outlook.sender.email, "john@example.com");
+        assert_eq!(outlook.in_reply_to, "<xyz@example.com>");
+
+        // Leftovers of a copy are not read as mail fields
+        let outlook = Outlook::from_slice(&message("IPM.Contact")).unwrap();
+        assert_eq!(outlook.kind, ItemKind::Contact);
+        assert_eq!(outlook.message_class(), "IPM.Contact");
+        assert_eq!(outlook.subject, "Jane Doe");
+        assert!(outlook.sender.email.is_empty());
+        assert!(outlook.headers.message_id.is_empty());
+        assert!(outlook.in_reply_to.is_empty());
+        assert_eq!(outlook.headers_source(), HeadersSource::Absent);
+        assert!(outlook.appointment.is_none());
+        let opt = outlook.to_opt();
+        assert_eq!(opt.sender.email, None);
+        assert_eq!(opt.headers.message_id, None);
+
+        let outlook = Outlook::from_slice(&message("ipm.appointment")).unwrap();
+        assert_eq!(outlook.kind, ItemKind::Appointment);
+        assert!(outlook.appointment.is_some());
+        assert_eq!(outlook.sender.email, "john@example.com");
+
+        // Custom forms are told apart by their class only
+        let outlook = Outlook::from_slice(&message("IPM.Note.Contoso.Expense")).unwrap();
+        assert_eq!(outlook.kind, ItemKind::Note);
+        assert_eq!(outlook.message_class(), "IPM.Note.Contoso.Expense");
+        let parsed: Outlook = serde_json::from_str(&outlook.to_json().unwrap()).unwrap();
+        assert_eq!(parsed.message_class(), "IPM.Note.Contoso.Expense");
+    }
+
+    #[test]
+    fn test_save_attachment() {
+        use crate::{testing::MessageBuilder, FileNamePolicy};
+
+        let bytes = MessageBuilder::new()
+            .attachment("Q3: plan?.txt", "text/plain", b"Plan")
+            .attachment("con.txt", "text/plain", b"Device")
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        let dir = std::env::temp_dir()
+            .join(format!("msg_parser_save_attachment_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = outlook.attachments[0].save_to(&dir, FileNamePolicy::Bracketed).unwrap();
+        assert_eq!(path, dir.join("Q3_ plan_.txt"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"Plan");
+        let path = outlook.attachments[0].save_to(&dir, FileNamePolicy::Remove).unwrap();
+        assert_eq!(path, dir.join("Q3 plan.txt"));
+        let path = outlook.attachments[1].save_to(&dir, FileNamePolicy::Bracketed).unwrap();
+        assert_eq!(path, dir.join("_con.txt"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"Device");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_property() {
+        use crate::testing::{MessageBuilder, PropertyValue};
+        use crate::{DataType, ParseOptions};
+
+        let bytes = MessageBuilder::new()
+            .subject("Hello")
+            .property("Sensitivity", PropertyValue::Integer32(2))
+            .recipient("Jane Doe", "jane@example.com")
+            .attachment("notes.txt", "text/plain", b"notes")
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.property("Subject"), None);
+        assert_eq!(outlook.properties().count(), 0);
+
+        let options = ParseOptions::new().retain_properties(true);
+        let outlook = Outlook::from_slice_with_options(&bytes, &options).unwrap();
+        let sensitivity = Some(&DataType::PtypInteger32(2));
+        assert_eq!(outlook.property("Sensitivity"), sensitivity);
+        assert_eq!(outlook.property("0x0036"), sensitivity);
+        assert_eq!(outlook.property("0x00360003"), sensitivity);
+        assert_eq!(outlook.property("Subject"), Some(&DataType::PtypString("Hello".into())));
+        assert_eq!(outlook.property("Importance"), None);
+        assert_eq!(outlook.property("0x"), None);
+        let names: Vec<&str> = outlook.properties().map(|(name, _)| name).collect();
+        assert_eq!(names, ["MessageClass", "Sensitivity", "Subject"]);
+
+        assert_eq!(
+            outlook.recipient_property(0, "0x39FE001F"),
+            Some(&DataType::PtypString("jane@example.com".into()))
+        );
+        assert_eq!(outlook.recipient_property(1, "SmtpAddress"), None);
+        assert_eq!(
+            outlook.attachment_property(0, "AttachMimeTag"),
+            Some(&DataType::PtypString("text/plain".into()))
+        );
+        assert!(outlook
+            .attachment_properties(0)
+            .any(|(name, _)| name == "AttachLongFilename"));
+        assert_eq!(outlook.recipient_properties(1).count(), 0);
+
+        // Not part of JSON output
+        let parsed: Outlook = serde_json::from_str(&outlook.to_json().unwrap()).unwrap();
+        assert_eq!(parsed.property("Sensitivity"), None);
+        assert_eq!(parsed.to_json().unwrap(), outlook.to_json().unwrap());
+    }
+
+    #[test]
+    fn test_string8_code_page() {
+        use crate::testing::{MessageBuilder, PropertyValue};
+
+        // Subject of a non-Unicode store, before or after the code page
+        let bytes = MessageBuilder::new()
+            .property("Subject", PropertyValue::String8(b"\xCE\xF2\xE2\xE5\xF2".to_vec()))
+            .property("MessageCodepage", PropertyValue::Integer32(1251))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.subject, "Ответ");
+
+        // The code page of the bodies otherwise
+        let bytes = MessageBuilder::new()
+            .property("InternetCodepage", PropertyValue::Integer32(932))
+            .property("Body", PropertyValue::String8(b"\x95\xD4\x90\x4D".to_vec()))
+            .build();
+        assert_eq!(Outlook::from_slice(&bytes).unwrap().body, "返信");
+    }
+
+    #[test]
+    fn test_body_html() {
+        use crate::testing::{MessageBuilder, PropertyValue};
+
+        let outlook = Outlook::from_slice(&MessageBuilder::new().body("Hi").build()).unwrap();
+        assert_eq!(outlook.body_html, None);
+        // Encapsulated in the RTF body
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        let html = outlook.body_html.unwrap();
+        assert!(html.starts_with("<html xmlns:v="));
+        assert!(html.ends_with("<o:p>&nbsp;</o:p></span></p></div></body></html>"));
+
+        let html = |bytes: &[u8], code_page: i32| {
+            let bytes = MessageBuilder::new()
+                .property("Html", PropertyValue::Binary(bytes.to_vec()))
+                .property("InternetCodepage", PropertyValue::Integer32(code_page))
+                .build();
+            Outlook::from_slice(&bytes).unwrap().body_html
+        };
+        let body = "<html><body>Réponse</body></html>";
+        assert_eq!(html(body.as_bytes(), 65001).as_deref(), Some(body));
+        let latin1: Vec<u8> = body.chars().map(|c| c as u8).collect();
+        assert_eq!(html(&latin1, 28591).as_deref(), Some(body));
+
+        // Encapsulated in the RTF body, left uncompressed
+        let rtf = b"{\\rtf1\\ansi\\fromhtml1 {\\*\\htmltag <p>}Hi{\\*\\htmltag </p>}}";
+        let mut compressed = ((rtf.len() + 12) as u32).to_le_bytes().to_vec();
+        compressed.extend_from_slice(&(rtf.len() as u32).to_le_bytes());
+        compressed.extend_from_slice(b"MELA\0\0\0\0");
+        compressed.extend_from_slice(rtf);
+        let bytes = MessageBuilder::new()
+            .property("RtfCompressed", PropertyValue::Binary(compressed))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.body_html.as_deref(), Some("<p>Hi</p>"));
+    }
+
+    #[test]
+    fn test_generated_names() {
+        // Upper case prefixes, lower case tags and names padded
+        // with a space, as some generators write them
+        let outlook = Outlook::from_path("data/generated_names.msg").unwrap();
+        assert_eq!(outlook.subject, "Generated names");
+        assert_eq!(outlook.body, "Entry names as written by other generators.");
+        assert_eq!(outlook.sender.email, "john@example.com");
+        assert_eq!(outlook.to[0].email, "jane@example.com");
+        assert_eq!(outlook.attachments[0].name, "notes.txt");
+        assert_eq!(outlook.attachments[0].payload, hex::encode(b"notes"));
+    }
+
+    #[test]
+    fn test_multiple_cc() {
+        // No transport headers, the CC recipients are found by type
+        let path = "data/test_email.msg";
+        let outlook = Outlook::from_path(path).unwrap();
+        assert!(!outlook.headers_present);
+        let emails: Vec<&str> = outlook.cc.iter().map(|cc| cc.email.as_str()).collect();
+        assert_eq!(emails, ["marirs@aol.in", "marirs@outlook.in"]);
+    }
+
+    #[test]
+    fn test_recipient_types() {
+        use crate::{
+            ole::Reader,
+            parser::{decode::DataType, storage::Storages},
+        };
+
+        let parser = Reader::from_path("data/unicode.msg").unwrap();
+        let mut storages = Storages::new(&parser);
+        storages.process_streams(&parser);
+        // BCC, along with the flag of resent recipients
+        storages.recipients[0].insert(
+            "RecipientType".to_string(),
+            DataType::PtypInteger32(0x1000_0003),
+        );
+        let outlook = Outlook::populate(&storages);
+        assert!(outlook.to.is_empty());
+        assert_eq!(outlook.bcc_recipients[0].email, "brianzhou@me.com");
+        assert_eq!(outlook.cc[0].email, "brizhou@gmail.com");
+        assert!(outlook.verify().is_empty());
+
+        // Without types, CC recipients are those of the Cc header
+        for recipient in &mut storages.recipients {
+            recipient.remove("RecipientType");
+        }
+        let outlook = Outlook::populate(&storages);
+        assert_eq!(outlook.to.len(), 2);
+        assert_eq!(outlook.cc, [Person::new("Brian Zhou", "brizhou@gmail.com")]);
+        assert!(outlook.verify().is_empty());
+    }
+
     #[test]
     fn test_to_json() {
         let path = "data/test_email.msg";
@@ -560,4 +2988,35 @@ mod tests {
         let json = outlook.to_json().unwrap();
         assert_eq!(json.len() > 0, true);
     }
+
+    #[cfg(feature = "markdown")]
+    #[test]
+    fn test_body_markdown() {
+        use crate::testing::{MessageBuilder, PropertyValue};
+
+        let html = "<html><body><h1>Budget</h1><p>See <b>Q3</b> and \
+                    <a href=\"https://example.com/q3\">the plan</a></p>\
+                    <ul><li>Travel</li><li>Hardware</li></ul></body></html>";
+        let bytes = MessageBuilder::new()
+            .body("Budget")
+            .property("Html", PropertyValue::String(html.to_string()))
+            .build();
+        let markdown = Outlook::from_slice(&bytes).unwrap().body_markdown();
+        assert!(markdown.starts_with("Budget\n=========="));
+        assert!(markdown.contains("See **Q3** and [the plan](https://example.com/q3)"));
+        assert!(markdown.contains("* Travel\n* Hardware"));
+
+        // Text bodies read as is
+        let bytes = MessageBuilder::new()
+            .body("# Plain *text*, [1] <b>\n- 3. item\n2. a_b\n10) c")
+            .build();
+        assert_eq!(
+            Outlook::from_slice(&bytes).unwrap().body_markdown(),
+            "\\# Plain \\*text\\*, \\[1\\] \\<b\\>\n\\- 3. item\n2\\. a\\_b\n10\\) c"
+        );
+
+        // HTML recovered from the RTF body
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        assert!(!outlook.body_markdown().contains("<div"));
+    }
 }
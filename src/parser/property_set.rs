@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use super::{
+    decode::DataType,
+    storage::{Properties, Storages},
+};
+
+// PropertySet holds the decoded properties of a message, so that
+// those Outlook has no field for can be read, see Outlook::property.
+// Properties are left out of JSON output, and only kept with
+// ParseOptions::retain_properties.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct PropertySet {
+    root: Properties,
+    recipients: Vec<Properties>,
+    attachments: Vec<Properties>,
+    // Canonical names of the properties above, by property id.
+    // Named properties have ids specific to the file, so they
+    // are only found by name.
+    names: HashMap<u16, String>,
+}
+
+impl PropertySet {
+    pub(crate) fn create(storages: Storages) -> Self {
+        let mut names = HashMap::new();
+        let keys = storages
+            .root
+            .keys()
+            .chain(storages.recipients.iter().flat_map(|props| props.keys()))
+            .chain(storages.attachments.iter().flat_map(|props| props.keys()));
+        for key in keys {
+            if let Some(tag) = storages.property_tag(key) {
+                names.insert((tag >> 16) as u16, key.clone());
+            }
+        }
+        Self {
+            root: storages.root,
+            recipients: storages.recipients,
+            attachments: storages.attachments,
+            names,
+        }
+    }
+
+    // Canonical name of a property given by its id or tag in hex,
+    // e.g. "0x0037" or "0x0037001F". The type of a tag is not
+    // checked, as values are kept by name.
+    fn tag_name(&self, tag: &str) -> Option<&str> {
+        let hex = tag
+            .strip_prefix("0x")
+            .or_else(|| tag.strip_prefix("0X"))
+            .unwrap_or(tag);
+        let id = match hex.len() {
+            4 => u16::from_str_radix(hex, 16).ok()?,
+            8 => (u32::from_str_radix(hex, 16).ok()? >> 16) as u16,
+            _ => return None,
+        };
+        self.names.get(&id).map(String::as_str)
+    }
+
+    fn get<'a>(&self, props: Option<&'a Properties>, name_or_tag: &str) -> Option<&'a DataType> {
+        let props = props?;
+        props
+            .get(name_or_tag)
+            .or_else(|| props.get(self.tag_name(name_or_tag)?))
+    }
+
+    // Properties in the order of their names.
+    fn sorted(props: Option<&Properties>) -> Vec<(&str, &DataType)> {
+        let mut sorted: Vec<(&str, &DataType)> = props
+            .into_iter()
+            .flatten()
+            .map(|(name, value)| (name.as_str(), value))
+            .collect();
+        sorted.sort_by_key(|(name, _)| *name);
+        sorted
+    }
+
+    pub(crate) fn root(&self, name_or_tag: &str) -> Option<&DataType> {
+        self.get(Some(&self.root), name_or_tag)
+    }
+
+    pub(crate) fn recipient(&self, idx: usize, name_or_tag: &str) -> Option<&DataType> {
+        self.get(self.recipients.get(idx), name_or_tag)
+    }
+
+    pub(crate) fn attachment(&self, idx: usize, name_or_tag: &str) -> Option<&DataType> {
+        self.get(self.attachments.get(idx), name_or_tag)
+    }
+
+    pub(crate) fn root_properties(&self) -> Vec<(&str, &DataType)> {
+        Self::sorted(Some(&self.root))
+    }
+
+    pub(crate) fn recipient_properties(&self, idx: usize) -> Vec<(&str, &DataType)> {
+        Self::sorted(self.recipients.get(idx))
+    }
+
+    pub(crate) fn attachment_properties(&self, idx: usize) -> Vec<(&str, &DataType)> {
+        Self::sorted(self.attachments.get(idx))
+    }
+}
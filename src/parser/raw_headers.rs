@@ -0,0 +1,76 @@
+use std::io::{self, Read, Write};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use super::error::Error;
+
+// RawHeadersEncoding tells how RawHeaders.data is written.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RawHeadersEncoding {
+    // As is
+    Plain,
+    // Gzip compressed, then base64 encoded
+    GzipBase64,
+}
+
+// RawHeaders holds the header block of "TransportMessageHeaders"
+// as received, folding, line endings and MIME boundaries included,
+// for header analysis to be run again from the JSON output.
+// See ParseOptions::raw_headers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RawHeaders {
+    pub encoding: RawHeadersEncoding,
+    pub data: String,
+}
+
+impl RawHeaders {
+    pub(crate) fn create(text: &str, encoding: RawHeadersEncoding) -> Result<Self, Error> {
+        let data = match encoding {
+            RawHeadersEncoding::Plain => text.to_string(),
+            RawHeadersEncoding::GzipBase64 => {
+                let mut encoder = GzEncoder::new(vec![], Compression::default());
+                encoder.write_all(text.as_bytes())?;
+                STANDARD.encode(encoder.finish()?)
+            }
+        };
+        Ok(Self { encoding, data })
+    }
+
+    // The header block, decoded.
+    pub fn text(&self) -> Result<String, Error> {
+        match self.encoding {
+            RawHeadersEncoding::Plain => Ok(self.data.clone()),
+            RawHeadersEncoding::GzipBase64 => {
+                let compressed = STANDARD
+                    .decode(&self.data)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                let mut text = String::new();
+                GzDecoder::new(&compressed[..]).read_to_string(&mut text)?;
+                Ok(text)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RawHeaders, RawHeadersEncoding};
+
+    #[test]
+    fn test_raw_headers() {
+        let text = "Content-Type: multipart/alternative;\r\n\tboundary=\"b1\"\r\n\r\n";
+        let plain = RawHeaders::create(text, RawHeadersEncoding::Plain).unwrap();
+        assert_eq!(plain.data, text);
+        let gzip = RawHeaders::create(text, RawHeadersEncoding::GzipBase64).unwrap();
+        assert_ne!(gzip.data, text);
+        assert_eq!(gzip.text().unwrap(), text);
+
+        let invalid = RawHeaders {
+            encoding: RawHeadersEncoding::GzipBase64,
+            data: "not base64!".to_string(),
+        };
+        assert!(invalid.text().is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use super::decode::Utf16Encoding;
+
+// ParseWarning describes an anomaly the parser recovered from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ParseWarning {
+    // The stream holds less data than its declared size,
+    // as left by a partially committed save.
+    IncompleteStream {
+        name: String,
+        // Path of the stream in the OLE file
+        path: String,
+        declared_size: usize,
+    },
+    // The chain of the stream is in the other allocation table
+    // than its size calls for, as left by some writers, and was
+    // read from there.
+    MisplacedStream {
+        // Path of the stream in the OLE file
+        path: String,
+        size: usize,
+    },
+    // The property type is not one defined in MS-OXCDATA,
+    // so the property is left out.
+    UnknownPropertyType {
+        // Path of the stream in the OLE file
+        path: String,
+        code: String,
+    },
+    // The property is larger than ParseOptions::max_property_size,
+    // so only the start of its value is kept.
+    PropertyTruncated {
+        // Path of the stream in the OLE file
+        path: String,
+        size: usize,
+        max_size: usize,
+    },
+    // The property has both a Unicode and an ANSI value which
+    // differ, e.g. a spoofed "Subject". The Unicode value is kept.
+    StringVariantMismatch {
+        name: String,
+        unicode: String,
+        ansi: String,
+    },
+    // The string is not little-endian UTF-16 without a BOM,
+    // and was decoded as the encoding it was found to be.
+    StringEncoding {
+        // Path of the stream in the OLE file
+        path: String,
+        encoding: Utf16Encoding,
+    },
+    // The string holds code units which are not valid UTF-16,
+    // such as unpaired surrogates, decoded as U+FFFD.
+    ReplacementCharacters {
+        name: String,
+        // Path of the stream in the OLE file
+        path: String,
+        count: usize,
+    },
+    // The file ends within its directory, as left by a truncated
+    // copy. Entries cut by the end of the file are lost, along
+    // with the properties, recipients and attachments below them.
+    TruncatedDirectory {
+        // Entries read from the directory
+        entries: usize,
+    },
+    // The string holds NUL characters followed by other characters,
+    // as padded by some writers. It is cut at the first NUL unless
+    // ParseOptions::preserve_nuls is set.
+    EmbeddedNul {
+        name: String,
+        // Path of the stream in the OLE file
+        path: String,
+        // Characters before the first NUL
+        position: usize,
+    },
+    // The storage holds several streams of the same property, as
+    // left by some drag-and-drop saves. The longest one is kept,
+    // the first of them on a tie.
+    DuplicateStream {
+        // Path of the stream in the OLE file
+        path: String,
+        // Sizes of the streams, in the order of the directory
+        sizes: Vec<usize>,
+    },
+    // The stream is named as a property stream, but its name does
+    // not end with a property tag of 8 hex digits, so it is left out.
+    InvalidStreamName {
+        // Path of the stream in the OLE file
+        path: String,
+    },
+    // A value of a multiple valued property could not be read from
+    // its stream. It is left empty, so that the values after it keep
+    // their index.
+    UnreadableElement {
+        // Path of the stream in the OLE file
+        path: String,
+        index: u32,
+    },
+}
+
+impl std::fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseWarning::IncompleteStream {
+                path,
+                declared_size,
+                ..
+            } => write!(
+                f,
+                "Stream {} holds less than its declared size of {} bytes",
+                path, declared_size
+            ),
+            ParseWarning::MisplacedStream { path, size } => write!(
+                f,
+                "Stream {} of {} bytes is in the other allocation table",
+                path, size
+            ),
+            ParseWarning::UnknownPropertyType { path, code } => {
+                write!(f, "Property type {} of {} is unknown", code, path)
+            }
+            ParseWarning::PropertyTruncated {
+                path,
+                size,
+                max_size,
+            } => write!(
+                f,
+                "Property {} of {} bytes truncated to {} bytes",
+                path, size, max_size
+            ),
+            ParseWarning::StringVariantMismatch { name, .. } => {
+                write!(f, "Property {} differs from its ANSI value", name)
+            }
+            ParseWarning::StringEncoding { path, encoding } => {
+                write!(f, "Property {} decoded as {}", path, encoding)
+            }
+            ParseWarning::ReplacementCharacters { path, count, .. } => write!(
+                f,
+                "Property {} has {} characters replaced by U+FFFD",
+                path, count
+            ),
+            ParseWarning::TruncatedDirectory { entries } => write!(
+                f,
+                "Directory is truncated, {} entries could be read",
+                entries
+            ),
+            ParseWarning::EmbeddedNul { path, position, .. } => write!(
+                f,
+                "Property {} holds a NUL after {} characters",
+                path, position
+            ),
+            ParseWarning::DuplicateStream { path, sizes } => write!(
+                f,
+                "Stream {} appears {} times, the longest is kept",
+                path,
+                sizes.len()
+            ),
+            ParseWarning::InvalidStreamName { path } => {
+                write!(f, "Stream {} has no valid property tag", path)
+            }
+            ParseWarning::UnreadableElement { path, index } => {
+                write!(f, "Value {} could not be read from {}", index, path)
+            }
+        }
+    }
+}
+
+// ParseMetrics tells where the time went while parsing a message,
+// to spot pathological files and regressions in large pipelines.
+// Embedded messages have metrics of their own, but for the time
+// spent on the file they are stored in, which is left at zero.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ParseMetrics {
+    pub header_parse: Duration,      // OLE header and directory entries
+    pub sat_build: Duration,         // Allocation tables
+    pub stream_decode: Duration,     // Streams of the message properties
+    pub attachment_decode: Duration, // Attachments, embedded messages included
+    pub bytes_read: u64,             // Size of the streams decoded
+}
+
+// ParseReport collects what happened while parsing a message.
+// Metrics vary from a run to the next, so they are left out of
+// JSON output and reports with the same warnings are equal.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ParseReport {
+    pub warnings: Vec<ParseWarning>,
+    #[serde(skip)]
+    pub metrics: ParseMetrics,
+}
+
+// Reports are compared by their warnings only: metrics depend on
+// the timing of the run, so that two parses of the same file would
+// otherwise never be equal.
+impl PartialEq for ParseReport {
+    fn eq(&self, other: &Self) -> bool {
+        self.warnings == other.warnings
+    }
+}
+
+impl ParseReport {
+    pub fn push(&mut self, warning: ParseWarning) {
+        self.warnings.push(warning);
+    }
+
+    // Characters replaced by U+FFFD, by property name,
+    // see ParseWarning::ReplacementCharacters.
+    pub fn replacements(&self) -> BTreeMap<&str, usize> {
+        let mut replacements = BTreeMap::new();
+        for warning in &self.warnings {
+            if let ParseWarning::ReplacementCharacters { name, count, .. } = warning {
+                *replacements.entry(name.as_str()).or_insert(0) += count;
+            }
+        }
+        replacements
+    }
+
+    pub fn is_partially_committed(&self) -> bool {
+        self.warnings
+            .iter()
+            .any(|w| matches!(w, ParseWarning::IncompleteStream { .. }))
+    }
+}
@@ -0,0 +1,127 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{decode::DataType, storage::Storages};
+
+// Subject prefixes of replies and forwards, as written by mail
+// clients in the most common languages, without their colon.
+const REPLY_PREFIXES: [&str; 11] = [
+    "RE", "AW", "SV", "VS", "ANTW", "RIF", "RES", "ODP", "YNT", "回复", "答复",
+];
+const FORWARD_PREFIXES: [&str; 12] = [
+    "FW", "FWD", "WG", "TR", "RV", "ENC", "VB", "DOORST", "PD", "İLT", "转发", "転送",
+];
+
+// Values of "IconIndex" of messages which were replied to,
+// and forwarded. Refer to MS-OXOMSG, PidTagIconIndex
+pub(crate) const ICON_REPLIED: i32 = 0x105;
+pub(crate) const ICON_FORWARDED: i32 = 0x106;
+
+// Values of "LastVerbExecuted".
+// Refer to MS-OXOMSG, PidTagLastVerbExecuted
+const VERB_REPLY_TO_SENDER: i32 = 0x66;
+const VERB_REPLY_TO_ALL: i32 = 0x67;
+const VERB_FORWARD: i32 = 0x68;
+
+// ResponseKind tells whether a message starts a conversation,
+// or replies to or forwards another message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResponseKind {
+    New,
+    Reply,
+    Forward,
+}
+
+impl ResponseKind {
+    // Kind given by the prefix of a subject, such as "RE: " or
+    // "Fwd[2]: ". "SubjectPrefix" is preferred when set, as
+    // subjects may have colons of their own.
+    pub(crate) fn from_subject(prefix: &str, subject: &str) -> Option<Self> {
+        let text = if prefix.trim().is_empty() { subject } else { prefix };
+        let (head, _) = text.split_once([':', '：'])?;
+        // Counters of replies to replies, e.g. "RE[2]"
+        let head = match head.trim().split_once('[') {
+            Some((head, counter)) if counter.trim_end_matches(']').parse::<u32>().is_ok() => head,
+            _ => head.trim(),
+        };
+        let matches = |prefixes: &[&str]| {
+            prefixes
+                .iter()
+                .any(|prefix| prefix.to_lowercase() == head.to_lowercase())
+        };
+        if matches(&REPLY_PREFIXES) {
+            Some(ResponseKind::Reply)
+        } else if matches(&FORWARD_PREFIXES) {
+            Some(ResponseKind::Forward)
+        } else {
+            None
+        }
+    }
+}
+
+// Verb is an action taken on a message by the owner of the
+// mailbox, such as replying to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Verb {
+    ReplyToSender,
+    ReplyToAll,
+    Forward,
+    Other(i32), // e.g. the response to a meeting request
+}
+
+impl From<i32> for Verb {
+    fn from(value: i32) -> Self {
+        match value {
+            VERB_REPLY_TO_SENDER => Verb::ReplyToSender,
+            VERB_REPLY_TO_ALL => Verb::ReplyToAll,
+            VERB_FORWARD => Verb::Forward,
+            value => Verb::Other(value),
+        }
+    }
+}
+
+// LastVerb is the last action taken on the saved copy of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LastVerb {
+    pub verb: Verb,                         // "LastVerbExecuted"
+    pub executed_at: Option<DateTime<Utc>>, // "LastVerbExecutionTime"
+}
+
+impl LastVerb {
+    pub(crate) fn create(storages: &Storages) -> Option<Self> {
+        let verb = storages.root.get("LastVerbExecuted")?.as_i64()? as i32;
+        Some(Self {
+            verb: verb.into(),
+            executed_at: storages
+                .root
+                .get("LastVerbExecutionTime")
+                .and_then(DataType::as_datetime),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResponseKind;
+
+    #[test]
+    fn test_from_subject() {
+        let kind = |subject: &str| ResponseKind::from_subject("", subject);
+        assert_eq!(kind("RE: Budget"), Some(ResponseKind::Reply));
+        assert_eq!(kind("aw: Budget"), Some(ResponseKind::Reply));
+        assert_eq!(kind("Re[3]: Budget"), Some(ResponseKind::Reply));
+        assert_eq!(kind("回复：预算"), Some(ResponseKind::Reply));
+        assert_eq!(kind("Fwd: Budget"), Some(ResponseKind::Forward));
+        assert_eq!(kind("WG: Budget"), Some(ResponseKind::Forward));
+        assert_eq!(kind("Budget: Q3"), None);
+        assert_eq!(kind("Budget"), None);
+        assert_eq!(kind("Ref[x]: Budget"), None);
+
+        // The prefix property is preferred
+        assert_eq!(
+            ResponseKind::from_subject("FW: ", "FW: RE: Budget"),
+            Some(ResponseKind::Forward)
+        );
+        assert_eq!(ResponseKind::from_subject("", "Agenda: RE: Budget"), None);
+    }
+}
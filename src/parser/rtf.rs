@@ -0,0 +1,323 @@
+use super::decode::decode_code_page;
+
+// Compression types of "RtfCompressed" values.
+// Refer to MS-OXRTFCP 2.1.3.1.1
+const COMPRESSED: u32 = 0x7546_5A4C; // "LZFu"
+const UNCOMPRESSED: u32 = 0x414C_454D; // "MELA"
+const HEADER_SIZE: usize = 16;
+
+// Dictionary of the decompressor, initialized with common RTF.
+// Refer to MS-OXRTFCP 2.1.2.1
+const DICTIONARY_SIZE: usize = 4096;
+const PREBUFFER: &[u8] = b"{\\rtf1\\ansi\\mac\\deff0\\deftab720{\\fonttbl;}{\\f0\\fnil \\froman \
+\\fswiss \\fmodern \\fscript \\fdecor MS Sans SerifSymbolArialTimes New RomanCourier\
+{\\colortbl\\red0\\green0\\blue0\r\n\\par \\pard\\plain\\f0\\fs20\\b\\i\\u\\tab\\tx";
+
+// Code page of RTF documents without "\ansicpg".
+const DEFAULT_CODE_PAGE: i64 = 1252;
+
+// Destinations whose text is not part of the document.
+const SKIPPED_DESTINATIONS: [&str; 8] = [
+    "fonttbl",
+    "colortbl",
+    "stylesheet",
+    "info",
+    "pict",
+    "object",
+    "header",
+    "footer",
+];
+
+// Decompresses an "RtfCompressed" value, none when its header
+// is unknown. Truncated values give what could be read.
+pub(crate) fn decompress(bytes: &[u8]) -> Option<Vec<u8>> {
+    if bytes.len() < HEADER_SIZE {
+        return None;
+    }
+    let read_u32 = |offset: usize| {
+        u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+    };
+    // Sizes are those of the compressed data along with the
+    // rest of the header, and of the RTF
+    let end = bytes.len().min(read_u32(0) as usize + 4);
+    let raw_size = read_u32(4) as usize;
+    match read_u32(8) {
+        COMPRESSED => {}
+        UNCOMPRESSED => {
+            let data = &bytes[HEADER_SIZE.min(end)..end];
+            return Some(data[..data.len().min(raw_size)].to_vec());
+        }
+        _ => return None,
+    }
+
+    let mut dictionary = [0u8; DICTIONARY_SIZE];
+    dictionary[..PREBUFFER.len()].copy_from_slice(PREBUFFER);
+    let mut write = PREBUFFER.len();
+    let mut rtf = Vec::new();
+    let mut pos = HEADER_SIZE;
+    'runs: while pos < end && rtf.len() < raw_size {
+        let control = bytes[pos];
+        pos += 1;
+        for bit in 0..8 {
+            if pos >= end {
+                break 'runs;
+            }
+            // A literal byte, or a reference to the dictionary
+            // of 12 bits of offset and 4 bits of length
+            let (offset, len) = if control & (1 << bit) == 0 {
+                pos += 1;
+                (None, 1)
+            } else if pos + 1 < end {
+                let reference = u16::from_be_bytes([bytes[pos], bytes[pos + 1]]) as usize;
+                pos += 2;
+                (Some(reference >> 4), (reference & 0xF) + 2)
+            } else {
+                break 'runs;
+            };
+            if offset == Some(write) {
+                // End of the data
+                break 'runs;
+            }
+            for i in 0..len {
+                let byte = match offset {
+                    Some(offset) => dictionary[(offset + i) % DICTIONARY_SIZE],
+                    None => bytes[pos - 1],
+                };
+                rtf.push(byte);
+                dictionary[write] = byte;
+                write = (write + 1) % DICTIONARY_SIZE;
+            }
+        }
+    }
+    rtf.truncate(raw_size);
+    Some(rtf)
+}
+
+// State of an RTF group, restored at its end.
+#[derive(Debug, Clone, Copy, Default)]
+struct Group {
+    skipped: bool,       // Text is not part of the document
+    html_tag: bool,      // "\*\htmltag" destination, written as is
+    suppressed: bool,    // "\htmlrtf", RTF only text
+    unicode_skip: usize, // "\uc", characters following "\u"
+}
+
+// Text written while de-encapsulating, the bytes of "\'hh"
+// being decoded in the code page of the document.
+struct Html {
+    text: String,
+    bytes: Vec<u8>,
+    code_page: i64,
+}
+
+impl Html {
+    fn flush(&mut self) {
+        if !self.bytes.is_empty() {
+            self.text.push_str(&decode_code_page(&self.bytes, Some(self.code_page)));
+            self.bytes.clear();
+        }
+    }
+
+    fn push(&mut self, text: &str) {
+        self.flush();
+        self.text.push_str(text);
+    }
+}
+
+// HTML encapsulated in an RTF document, as marked by "\fromhtml1".
+// Text of "\*\htmltag" destinations and text outside "\htmlrtf"
+// are written, the rest being the RTF rendering of the HTML.
+// Refer to MS-OXRTFEX 2.1.3
+pub(crate) fn html_from_rtf(rtf: &[u8]) -> Option<String> {
+    let mut html = Html {
+        text: String::new(),
+        bytes: vec![],
+        code_page: DEFAULT_CODE_PAGE,
+    };
+    let mut from_html = false;
+    let mut stack: Vec<Group> = vec![];
+    let mut group = Group {
+        unicode_skip: 1,
+        ..Group::default()
+    };
+    // Start of a group, whose first control word may be a destination
+    let mut destination = false;
+    let mut ignorable = false;
+    // Characters left to skip after "\u"
+    let mut to_skip = 0;
+    let mut pos = 0;
+    while pos < rtf.len() {
+        let byte = rtf[pos];
+        pos += 1;
+        let written = !group.skipped && (group.html_tag || !group.suppressed);
+        match byte {
+            b'{' => {
+                stack.push(group);
+                destination = true;
+                ignorable = false;
+                to_skip = 0;
+                continue;
+            }
+            b'}' => {
+                group = stack.pop()?;
+                destination = false;
+                to_skip = 0;
+                continue;
+            }
+            b'\r' | b'\n' => continue,
+            b'\\' => {}
+            _ => {
+                if to_skip > 0 {
+                    to_skip -= 1;
+                } else if written {
+                    html.bytes.push(byte);
+                }
+                destination = false;
+                continue;
+            }
+        }
+
+        let symbol = *rtf.get(pos)?;
+        if !symbol.is_ascii_alphabetic() {
+            pos += 1;
+            match symbol {
+                b'*' => {
+                    ignorable = true;
+                    continue;
+                }
+                b'\'' => {
+                    let hex = std::str::from_utf8(rtf.get(pos..pos + 2)?).ok()?;
+                    let value = u8::from_str_radix(hex, 16).ok()?;
+                    pos += 2;
+                    if to_skip > 0 {
+                        to_skip -= 1;
+                    } else if written {
+                        html.bytes.push(value);
+                    }
+                }
+                b'{' | b'}' | b'\\' if written => html.push(&(symbol as char).to_string()),
+                b'~' if written => html.push("\u{A0}"),
+                b'\r' | b'\n' if written => html.push("\r\n"),
+                _ => {}
+            }
+            destination = false;
+            continue;
+        }
+
+        // Control word, with an optional numeric parameter
+        // and a space delimiter which is part of it
+        let start = pos;
+        while rtf.get(pos).is_some_and(u8::is_ascii_alphabetic) {
+            pos += 1;
+        }
+        let word = std::str::from_utf8(&rtf[start..pos]).ok()?;
+        let param_start = pos;
+        if rtf.get(pos) == Some(&b'-') {
+            pos += 1;
+        }
+        while rtf.get(pos).is_some_and(u8::is_ascii_digit) {
+            pos += 1;
+        }
+        let param: Option<i64> = std::str::from_utf8(&rtf[param_start..pos])
+            .ok()
+            .and_then(|param| param.parse().ok());
+        if rtf.get(pos) == Some(&b' ') {
+            pos += 1;
+        }
+
+        if destination {
+            destination = false;
+            if word == "htmltag" {
+                group.html_tag = true;
+                group.suppressed = false;
+                continue;
+            }
+            if ignorable || SKIPPED_DESTINATIONS.contains(&word) {
+                group.skipped = true;
+            }
+        }
+        if word == "bin" {
+            // Binary data, left out
+            pos += param.unwrap_or(0).max(0) as usize;
+            continue;
+        }
+        if group.skipped {
+            continue;
+        }
+        to_skip = 0;
+        match word {
+            "fromhtml" => from_html = param == Some(1),
+            "ansicpg" => html.code_page = param.unwrap_or(DEFAULT_CODE_PAGE),
+            "htmlrtf" => group.suppressed = param != Some(0),
+            "uc" => group.unicode_skip = param.unwrap_or(1).max(0) as usize,
+            "u" => {
+                if let Some(code) = param {
+                    // Code points above 32767 are written as negatives
+                    let code = if code < 0 { code + 0x10000 } else { code };
+                    if written {
+                        let c = char::from_u32(code as u32).unwrap_or('\u{FFFD}');
+                        html.push(c.encode_utf8(&mut [0; 4]));
+                    }
+                    to_skip = group.unicode_skip;
+                }
+            }
+            "par" | "line" if written => html.push("\r\n"),
+            "tab" if written => html.push("\t"),
+            _ => {}
+        }
+    }
+    html.flush();
+    from_html.then_some(html.text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decompress, html_from_rtf, PREBUFFER};
+
+    #[test]
+    fn test_decompress() {
+        assert_eq!(PREBUFFER.len(), 207);
+        // Examples of MS-OXRTFCP 3.1 and 3.2
+        let compressed = hex::decode(
+            "2d0000002b0000004c5a4675f1c5c7a703000a00726370673132354232\
+             0af32068656c090020627705b06c647d0a800fa0",
+        )
+        .unwrap();
+        assert_eq!(
+            decompress(&compressed).unwrap(),
+            b"{\\rtf1\\ansi\\ansicpg1252\\pard hello world}\r\n"
+        );
+        let compressed =
+            hex::decode("1a0000001c0000004c5a4675e2d44b51410004205758595a0d6e7d010eb0").unwrap();
+        assert_eq!(
+            decompress(&compressed).unwrap(),
+            b"{\\rtf1 WXYZWXYZWXYZWXYZWXYZ}"
+        );
+
+        let mut uncompressed = hex::decode("160000000a0000004d454c4100000000").unwrap();
+        uncompressed.extend_from_slice(b"{\\rtf1 Hi}");
+        assert_eq!(decompress(&uncompressed).unwrap(), b"{\\rtf1 Hi}");
+        assert_eq!(decompress(b"{\\rtf1 Hi}"), None);
+    }
+
+    #[test]
+    fn test_html_from_rtf() {
+        let rtf = b"{\\rtf1\\ansi\\ansicpg1252\\fromhtml1 \\deff0{\\fonttbl\r\n\
+            {\\f0\\fswiss Arial;}}\r\n\
+            {\\*\\htmltag19 <html>}{\\*\\htmltag34 <head>}{\\*\\htmltag1 \\par }\
+            {\\*\\htmltag41 </head>}\\htmlrtf \\pard\\htmlrtf0 \r\n\
+            {\\*\\htmltag64 <p class=\"a\">}\\htmlrtf {\\htmlrtf0 Caf\\'e9 \\{\\u8364?\\}\
+            {\\*\\htmltag244 <o:p>}{\\*\\mhtmltag244 <o:p>}\\htmlrtf \\par\\htmlrtf0 \r\n\
+            {\\*\\htmltag84 &nbsp;}\\htmlrtf \\'a0\\htmlrtf0 }\
+            {\\*\\htmltag72 </p>}{\\*\\htmltag27 </html>}}";
+        assert_eq!(
+            html_from_rtf(rtf).unwrap(),
+            "<html><head>\r\n</head><p class=\"a\">Café {€}<o:p>&nbsp;</p></html>"
+        );
+
+        // Not encapsulated HTML
+        assert_eq!(html_from_rtf(b"{\\rtf1\\ansi\\fromtext Hello}"), None);
+        // Unbalanced groups
+        assert_eq!(html_from_rtf(b"{\\rtf1\\fromhtml1 }}"), None);
+    }
+}
@@ -0,0 +1,100 @@
+use std::io::Read;
+
+use crate::ole::{Entry, EntrySlice};
+
+use super::{
+    decode::DataType,
+    storage::{get_first, Properties},
+};
+
+// Size of the chunks of payload given to AttachmentScan::update.
+const SCAN_CHUNK_SIZE: usize = 64 * 1024;
+
+// ScanTarget describes the attachment whose payload is scanned,
+// as known from its properties.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanTarget {
+    pub name: String,       // "AttachLongFilename", "AttachFilename" or "DisplayName"
+    pub extension: String,  // "AttachExtension"
+    pub mime_tag: String,   // "AttachMimeTag"
+    pub storage_index: u32, // Number of its storage, __attach_version1.0_#NNNNNNNN
+    pub size: usize,        // Size of the payload
+}
+
+// ContentScanner classifies the payload of each attachment while
+// the message is parsed, e.g. for data loss prevention, the payload
+// being read in chunks from the file. Set with
+// ParseOptions::content_scanner.
+pub trait ContentScanner: Send + Sync {
+    // Starts the scan of an attachment, None to leave it out
+    fn start(&self, target: &ScanTarget) -> Option<Box<dyn AttachmentScan>>;
+
+    // Whether the payload of a scanned attachment is kept, given its
+    // labels. Those which are not are left empty as if left out by
+    // ParseOptions::deny_attachments. All are kept by default.
+    fn keep_payload(&self, _target: &ScanTarget, _labels: &[String]) -> bool {
+        true
+    }
+}
+
+impl std::fmt::Debug for dyn ContentScanner {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("ContentScanner")
+    }
+}
+
+// AttachmentScan is the scan of one attachment, given its
+// payload in order, chunk by chunk.
+pub trait AttachmentScan {
+    fn update(&mut self, chunk: &[u8]);
+
+    // Labels of the content, e.g. "credit-card-number",
+    // see Attachment.classification
+    fn finish(self: Box<Self>) -> Vec<String>;
+}
+
+// Starts the scan of the payload stream of the attachment with these
+// properties, none when the scanner leaves it out. The payload is
+// then given to the scan as it is read, see scan_value and scan_rest.
+pub(crate) fn start_scan(
+    scanner: &dyn ContentScanner,
+    entry: &Entry,
+    props: Option<&Properties>,
+    storage_index: u32,
+) -> Option<(ScanTarget, Box<dyn AttachmentScan>)> {
+    let value = |keys: &[&str]| {
+        props
+            .and_then(|props| get_first(props, keys))
+            .map_or(String::new(), |value| value.into())
+    };
+    let target = ScanTarget {
+        name: value(&["AttachLongFilename", "AttachFilename", "DisplayName"]),
+        extension: value(&["AttachExtension"]),
+        mime_tag: value(&["AttachMimeTag"]),
+        storage_index,
+        size: entry.len(),
+    };
+    let scan = scanner.start(&target)?;
+    Some((target, scan))
+}
+
+// Gives the scan the bytes of a payload decoded from its stream.
+pub(crate) fn scan_value(value: &DataType, scan: &mut dyn AttachmentScan) {
+    if let DataType::PtypBinary(bytes) = value {
+        for chunk in bytes.chunks(SCAN_CHUNK_SIZE) {
+            scan.update(chunk);
+        }
+    }
+}
+
+// Gives the scan what is left to read of the payload stream, e.g.
+// past ParseOptions::max_property_size, up to where it could be read.
+pub(crate) fn scan_rest(slice: &mut EntrySlice, scan: &mut dyn AttachmentScan) {
+    let mut chunk = vec![0u8; SCAN_CHUNK_SIZE];
+    while let Ok(read) = slice.read(&mut chunk) {
+        if read == 0 {
+            break;
+        }
+        scan.update(&chunk[..read]);
+    }
+}
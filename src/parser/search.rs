@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use super::{
+    outlook::{Outlook, Person},
+    text::{extract_text, AttachmentTextExtractor},
+};
+
+// SearchScope selects the parts of a message Outlook::contains
+// looks into. All but the attachment text by default.
+//
+// Parts are set with chained calls:
+// SearchScope::new().headers(false)
+#[derive(Debug, Clone)]
+pub struct SearchScope {
+    subject: bool,
+    bodies: bool,
+    headers: bool,
+    attachment_names: bool,
+    attachment_text: Option<Arc<dyn AttachmentTextExtractor>>,
+}
+
+impl Default for SearchScope {
+    fn default() -> Self {
+        Self {
+            subject: true,
+            bodies: true,
+            headers: true,
+            attachment_names: true,
+            attachment_text: None,
+        }
+    }
+}
+
+impl SearchScope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subject(mut self, value: bool) -> Self {
+        self.subject = value;
+        self
+    }
+
+    // The plain text body, and the text of reports.
+    pub fn bodies(mut self, value: bool) -> Self {
+        self.bodies = value;
+        self
+    }
+
+    // Transport header fields, threading fields, and the
+    // names and addresses of the sender and recipients.
+    pub fn headers(mut self, value: bool) -> Self {
+        self.headers = value;
+        self
+    }
+
+    // Any of the names of the attachments.
+    pub fn attachment_names(mut self, value: bool) -> Self {
+        self.attachment_names = value;
+        self
+    }
+
+    // Looks into the text that extractor finds in attachment payloads,
+    // or the text already extracted while parsing, if any.
+    // Payloads left out by the attachment filter are skipped.
+    pub fn attachment_text<E: AttachmentTextExtractor + 'static>(mut self, extractor: E) -> Self {
+        self.attachment_text = Some(Arc::new(extractor));
+        self
+    }
+
+    // Texts of the message within the scope, but the attachment
+    // text, which is extracted only when nothing else matches.
+    fn texts<'a>(&self, outlook: &'a Outlook) -> Vec<&'a str> {
+        let mut texts = vec![];
+        if self.subject {
+            texts.push(outlook.subject.as_str());
+        }
+        if self.bodies {
+            texts.push(outlook.body.as_str());
+            if let Some(system_message) = &outlook.system_message {
+                texts.push(system_message.text.as_str());
+            }
+        }
+        if self.headers {
+            let headers = &outlook.headers;
+            texts.extend([
+                headers.content_type.as_str(),
+                headers.date.as_str(),
+                headers.message_id.as_str(),
+                headers.reply_to.as_str(),
+                headers.content_transfer_encoding.as_str(),
+                outlook.in_reply_to.as_str(),
+                outlook.references.as_str(),
+                outlook.bcc.as_str(),
+            ]);
+            let people = std::iter::once(&outlook.sender)
+                .chain(&outlook.to)
+                .chain(&outlook.cc)
+                .chain(&outlook.bcc_recipients);
+            for Person { name, email, .. } in people {
+                texts.extend([name.as_str(), email.as_str()]);
+            }
+        }
+        if self.attachment_names {
+            for attachment in &outlook.attachments {
+                texts.extend([
+                    attachment.name.as_str(),
+                    attachment.long_name.as_str(),
+                    attachment.short_name.as_str(),
+                    attachment.display_name.as_str(),
+                ]);
+            }
+        }
+        texts
+    }
+
+    // Whether needle, lowercased, is found in the message.
+    pub(crate) fn matches(&self, outlook: &Outlook, needle: &str) -> bool {
+        let found = |text: &str| text.to_lowercase().contains(needle);
+        if self.texts(outlook).into_iter().any(found) {
+            return true;
+        }
+        let extractor = match &self.attachment_text {
+            Some(extractor) => extractor,
+            None => return false,
+        };
+        outlook.attachments.iter().any(|attachment| match &attachment.text {
+            Some(text) => found(text),
+            None => extract_text(extractor.as_ref(), attachment).is_some_and(|text| found(&text)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SearchScope;
+    use crate::{testing::MessageBuilder, Attachment, AttachmentTextExtractor, Outlook};
+
+    // Payloads of text/plain attachments, as is.
+    struct PlainText;
+
+    impl AttachmentTextExtractor for PlainText {
+        fn extract(&self, attachment: &Attachment, payload: &[u8]) -> Option<String> {
+            (attachment.mime_tag == "text/plain")
+                .then(|| String::from_utf8_lossy(payload).into_owned())
+        }
+    }
+
+    #[test]
+    fn test_contains() {
+        let bytes = MessageBuilder::new()
+            .subject("Quarterly Results")
+            .body("Account number 12345")
+            .headers("Message-ID: <abc@Example.com>\r\n")
+            .sender("John Doe", "john@example.com")
+            .recipient("Jane Doe", "jane@example.com")
+            .attachment("Forecast.xlsx", "application/octet-stream", b"CONFIDENTIAL")
+            .attachment("notes.txt", "text/plain", b"Project Falcon")
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        let scope = SearchScope::new();
+        assert!(outlook.contains("quarterly", &scope));
+        assert!(outlook.contains("ACCOUNT NUMBER", &scope));
+        assert!(outlook.contains("abc@example", &scope));
+        assert!(outlook.contains("jane@", &scope));
+        assert!(outlook.contains("forecast.XLSX", &scope));
+        assert!(!outlook.contains("falcon", &scope));
+        assert!(!outlook.contains("not there", &scope));
+
+        assert!(!outlook.contains("quarterly", &SearchScope::new().subject(false)));
+        assert!(!outlook.contains("12345", &SearchScope::new().bodies(false)));
+        assert!(!outlook.contains("jane@", &SearchScope::new().headers(false)));
+        assert!(!outlook.contains("forecast", &SearchScope::new().attachment_names(false)));
+
+        let scope = SearchScope::new().attachment_text(PlainText);
+        assert!(outlook.contains("falcon", &scope));
+        // Only text/plain attachments have a text
+        assert!(!outlook.contains("confidential", &scope));
+    }
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+// SourceKind tells what kind of data a field was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SourceKind {
+    // A property of the message or of its recipients
+    Property,
+    // A field of "TransportMessageHeaders"
+    Header,
+    // Computed from another property, e.g. HTML from the RTF body
+    Derived,
+}
+
+// FieldSource tells where the value of an Outlook field came from,
+// for fields with several sources, so that automated systems can
+// weight the value accordingly. See Outlook::field_source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldSource {
+    pub kind: SourceKind,
+    pub name: String,   // Property or header, e.g. "SenderSmtpAddress" or "In-Reply-To"
+    pub fallback: bool, // The preferred source has no value, a less reliable one was used
+}
+
+impl FieldSource {
+    pub(crate) fn new(kind: SourceKind, name: &str, fallback: bool) -> Self {
+        Self {
+            kind,
+            name: name.to_string(),
+            fallback,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FieldSource, SourceKind};
+    use crate::{
+        testing::{MessageBuilder, PropertyValue},
+        Outlook,
+    };
+
+    #[test]
+    fn test_field_sources() {
+        let source = |kind: SourceKind, name: &str, fallback: bool| {
+            Some(FieldSource::new(kind, name, fallback))
+        };
+        let bytes = MessageBuilder::new()
+            .sender("John Doe", "john@example.com")
+            .recipient("Jane", "jane@example.com")
+            .headers("Date: Mon, 18 Nov 2013 10:26:24 +0200\r\nIn-Reply-To: <a@b>\r\n")
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        let field = |path: &str| outlook.field_source(path).cloned();
+        assert_eq!(field("sender.email"), source(SourceKind::Property, "SenderSmtpAddress", false));
+        assert_eq!(field("headers.date"), source(SourceKind::Header, "Date", false));
+        assert_eq!(field("in_reply_to"), source(SourceKind::Header, "In-Reply-To", true));
+        assert_eq!(field("to"), source(SourceKind::Property, "RecipientType", false));
+        // No value, no source
+        assert_eq!(field("headers.message_id"), None);
+        assert_eq!(field("references"), None);
+        assert_eq!(field("subject"), None);
+
+        // Read from aliases and rebuilt headers
+        let bytes = MessageBuilder::new()
+            .property("SentRepresentingName", PropertyValue::String("John".to_string()))
+            .property("InternetMessageId", PropertyValue::String("<a@b>".to_string()))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        let field = |path: &str| outlook.field_source(path).cloned();
+        assert_eq!(
+            field("sender.name"),
+            source(SourceKind::Property, "SentRepresentingName", true)
+        );
+        assert_eq!(field("sender.email"), None);
+        assert_eq!(
+            field("headers.message_id"),
+            source(SourceKind::Property, "InternetMessageId", true)
+        );
+
+        // HTML encapsulated in the RTF body
+        let outlook = Outlook::from_path("data/test_email.msg").unwrap();
+        let field = |path: &str| outlook.field_source(path).cloned();
+        assert_eq!(field("body_html"), source(SourceKind::Derived, "RtfCompressed", true));
+        assert_eq!(
+            field("sender.email"),
+            source(SourceKind::Property, "SentRepresentingEmailAddress", true)
+        );
+
+        let bytes = MessageBuilder::new()
+            .property("Html", PropertyValue::String("<p>Hello</p>".to_string()))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(
+            outlook.field_source("body_html").cloned(),
+            source(SourceKind::Property, "Html", false)
+        );
+
+        // Items which are not sent have recipients and bodies all the same
+        let bytes = MessageBuilder::new()
+            .message_class("IPM.Task")
+            .sender("John Doe", "john@example.com")
+            .recipient("Jane", "jane@example.com")
+            .property("Html", PropertyValue::String("<p>Hello</p>".to_string()))
+            .build();
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        let field = |path: &str| outlook.field_source(path).cloned();
+        assert_eq!(field("to"), source(SourceKind::Property, "RecipientType", false));
+        assert_eq!(field("body_html"), source(SourceKind::Property, "Html", false));
+        assert_eq!(field("sender.email"), None);
+        assert_eq!(field("cc"), None);
+    }
+}
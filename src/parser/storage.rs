@@ -1,6 +1,8 @@
 use std::{
-    collections::HashMap,
-    u32::MAX,
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Read,
+    sync::Arc,
+    time::Instant,
 };
 
 use hex::decode;
@@ -9,13 +11,21 @@ use crate::ole::{Entry, EntryType, Reader};
 
 use super::{
     constants::PropIdNameMap,
-    decode::DataType,
-    stream::Stream
+    named::NamedPropertyMap,
+    options::{AttachmentFilter, ParseOptions},
+    decode::{
+        invalid_utf16_count, is_ansi_variant, is_known_type, multiple_value, utf16_encoding,
+        DataType, PtypDecoder, StringDecoder, Utf16Encoding, UTF16_SAMPLE_SIZE,
+    },
+    report::{ParseReport, ParseWarning},
+    scan::{scan_rest, scan_value, start_scan, AttachmentScan, ContentScanner},
+    stream::Stream,
+    verify::PropertyHeader
 };
 
 // StorageType refers to major components in Message object.
 // Refer to MS-OXPROPS 1.3.3
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum StorageType {
     // u32 refers to its index
     Recipient(u32),
@@ -35,8 +45,8 @@ impl StorageType {
         let mut base = 1u32;
         let mut sum = 0u32;
         for &num in decoded.iter().rev() {
-            sum = sum + num as u32 * base;
-            if base >= MAX / 256 {
+            sum += num as u32 * base;
+            if base >= u32::MAX / 256 {
                 break;
             }
             base *= 256;
@@ -45,15 +55,16 @@ impl StorageType {
     }
 
     pub fn create(name: &str) -> Option<Self> {
-        if name.starts_with("__recip_version1.0_") {
+        let name = normalize_name(name);
+        if name.starts_with("__RECIP_VERSION1.0_") {
             // Extract the digits after '#' in __recip_version1.0_#00000000
             // Remaining digits is the index of Recipient.
-            let id = name.split("#").collect::<Vec<&str>>()[1];
+            let id = name.split('#').nth(1)?;
             let id_as_num = StorageType::convert_id_to_u32(id)?;
             return Some(StorageType::Recipient(id_as_num));
         }
-        if name.starts_with("__attach_version1.0_") {
-            let id = name.split("#").collect::<Vec<&str>>()[1];
+        if name.starts_with("__ATTACH_VERSION1.0_") {
+            let id = name.split('#').nth(1)?;
             let id_as_num = StorageType::convert_id_to_u32(id)?;
             return Some(StorageType::Attachment(id_as_num));
         }
@@ -61,49 +72,198 @@ impl StorageType {
     }
 }
 
+// Some generators write storage and stream names with an unexpected case
+// or with trailing padding. Names are compared in their upper case form,
+// with surrounding whitespaces and NULs removed.
+pub(crate) fn normalize_name(name: &str) -> String {
+    name.trim_matches(|c: char| c.is_whitespace() || c == '\0')
+        .to_ascii_uppercase()
+}
+
+// Prefix of the "MessageClass" stream, either PtypString or PtypString8.
+const MESSAGE_CLASS_STREAM: &str = "__SUBSTG1.0_001A";
+
+// Name of the storage holding an embedded message,
+// i.e. "AttachDataObject" stored as PtypObject.
+const EMBEDDED_MESSAGE_STORAGE: &str = "__SUBSTG1.0_3701000D";
+
 // EntryStorageMap represents HashMap of ole::Entry id and its StorageType
+// for the storages of one message: the message storage itself and its
+// direct recipient and attachment storages.
 #[derive(Debug)]
 struct EntryStorageMap {
     map: HashMap<u32, StorageType>,
+    // Attachment index and entry id of the embedded messages
+    embedded: HashMap<u32, u32>,
 }
 
 impl EntryStorageMap {
     pub fn new(parser: &Reader) -> Self {
+        let root_id = parser
+            .iterate()
+            .find(|entry| entry._type() == EntryType::RootStorage)
+            .map_or(0, |entry| entry.id());
+        Self::with_root(parser, Self::find_message_storage(parser, root_id))
+    }
+
+    // A message storage holds the property stream and the message class.
+    fn is_message_storage(parser: &Reader, id: u32) -> bool {
+        let mut has_properties = false;
+        let mut has_class = false;
+        for entry in parser.streams_of(id) {
+            let name = normalize_name(entry.name());
+            has_properties |= Stream::is_properties(&name);
+            has_class |= name.starts_with(MESSAGE_CLASS_STREAM);
+        }
+        has_properties && has_class
+    }
+
+    // Some containers (e.g. journal items) nest the message under
+    // a storage instead of the root. The shallowest message storage
+    // is used then, the root being kept if none is found.
+    fn find_message_storage(parser: &Reader, root_id: u32) -> u32 {
+        if Self::is_message_storage(parser, root_id) {
+            return root_id;
+        }
+        parser
+            .storages()
+            .filter(|entry| Self::is_message_storage(parser, entry.id()))
+            .min_by_key(|entry| parser.path(entry).matches('/').count())
+            .map_or(root_id, |entry| entry.id())
+    }
+
+    pub fn with_root(parser: &Reader, root_id: u32) -> Self {
         let mut storage_map: HashMap<u32, StorageType> = HashMap::new();
-        for entry in parser.iterate() {
-            match entry._type() {
-                EntryType::RootStorage => {
-                    storage_map.insert(entry.id(), StorageType::RootEntry);
-                }
-                EntryType::UserStorage => {
-                    StorageType::create(entry.name())
-                        .and_then(|storage| storage_map.insert(entry.id(), storage));
-                }
-                _ => {
-                    continue;
-                }
+        storage_map.insert(root_id, StorageType::RootEntry);
+        for entry in parser.storages() {
+            if entry.parent_node() != Some(root_id) {
+                continue;
+            }
+            StorageType::create(entry.name())
+                .and_then(|storage| storage_map.insert(entry.id(), storage));
+        }
+
+        // Embedded messages are storages under their attachment storage.
+        let mut embedded: HashMap<u32, u32> = HashMap::new();
+        for entry in parser.storages() {
+            if normalize_name(entry.name()) != EMBEDDED_MESSAGE_STORAGE {
+                continue;
             }
+            if let Some(StorageType::Attachment(id)) =
+                entry.parent_node().and_then(|parent| storage_map.get(&parent))
+            {
+                embedded.insert(*id, entry.id());
+            }
+        }
+        Self {
+            map: storage_map,
+            embedded,
         }
-        Self { map: storage_map }
     }
 
     pub fn get_storage_type(&self, parent_id: Option<u32>) -> Option<&StorageType> {
         self.map.get(&parent_id?)
     }
+
+    // Size of the header of the property stream in a storage.
+    // Refer to MS-OXMSG 2.4.1
+    // A message storage has a 32 bytes header at the top level
+    // and 24 bytes when embedded, which is told by the stream length
+    // since the message storage is not always the root.
+    fn properties_header_size(storage: &StorageType, len: usize) -> usize {
+        match storage {
+            StorageType::RootEntry if len % 16 == 8 => 24,
+            StorageType::RootEntry => 32,
+            _ => 8,
+        }
+    }
 }
 
+// Property ids of the message bodies:
+// "Body", "RtfCompressed" and "Html".
+const BODY_PROPERTY_IDS: [&str; 3] = ["0x1000", "0x1009", "0x1013"];
+
+// Type code of ANSI strings, PtypString8.
+const PTYP_STRING8: &str = "0x001E";
+
+// Type code of Unicode strings, PtypString.
+const PTYP_STRING: &str = "0x001F";
+
+// Property id of the attachment payload, "AttachDataObject".
+const ATTACH_DATA_PROPERTY_ID: &str = "0x3701";
+
 // Properties is a Map is a collection of Message object elements.
 pub type Properties = HashMap<String, DataType>;
 
+// Properties tried in turn when looking up the first of each
+// list, by order of preference.
+const PROPERTY_ALIASES: [&[&str]; 3] = [
+    &[
+        "SenderSmtpAddress",
+        "SenderEmailAddress",
+        "SentRepresentingSmtpAddress",
+        "SentRepresentingEmailAddress",
+    ],
+    &["SenderName", "SentRepresentingName"],
+    // Recipients
+    &["SmtpAddress", "EmailAddress"],
+];
+
+// Value of the first of keys which is set and not empty.
+// Properties are keyed by canonical name, e.g. "SenderSmtpAddress",
+// so keys are looked up as given.
+pub fn get_first<'a>(props: &'a Properties, keys: &[&str]) -> Option<&'a DataType> {
+    keys.iter().find_map(|key| props.get(*key).filter(|value| !value.is_empty()))
+}
+
+// Value of key, or of its aliases when not set.
+pub fn get_aliased<'a>(props: &'a Properties, key: &str) -> Option<&'a DataType> {
+    get_aliased_key(props, key).map(|(_, value)| value)
+}
+
+// Same as get_aliased, along with the key or alias the value is read from.
+pub(crate) fn get_aliased_key<'a, 'k>(
+    props: &'a Properties,
+    key: &'k str,
+) -> Option<(&'k str, &'a DataType)> {
+    let keys = match PROPERTY_ALIASES.iter().find(|aliases| aliases[0].eq_ignore_ascii_case(key)) {
+        Some(aliases) => *aliases,
+        None => std::slice::from_ref(&key),
+    };
+    keys.iter()
+        .find_map(|alias| Some((*alias, get_first(props, &[alias])?)))
+}
+
+// Whether key or one of its aliases is set, even if empty.
+pub fn has_aliased(props: &Properties, key: &str) -> bool {
+    let is_set = |alias: &&str| props.contains_key(*alias);
+    match PROPERTY_ALIASES.iter().find(|aliases| aliases[0].eq_ignore_ascii_case(key)) {
+        Some(aliases) => aliases.iter().any(is_set),
+        None => is_set(&key),
+    }
+}
+
 // Recipients represent array of Recipient objects in Message.
 pub type Recipients = Vec<Properties>;
 
 // Attachments represent array of Attachment object in Message
 pub type Attachments = Vec<Properties>;
 
+// PropertyPaths maps a property to the OLE path of the stream
+// it was decoded from, e.g. "__recip_version1.0_#00000000/__substg1.0_39FE001F".
+pub type PropertyPaths = HashMap<String, String>;
+
 // Storages is a collection of Storage
 // object containing their decoded stream
 // values for respective properties.
+//
+// It is the data model Outlook is built from, for those who need
+// their own view of a message:
+//
+//     let parser = msg_parser::ole::Reader::from_path("data/test_email.msg")?;
+//     let mut storages = Storages::new(&parser).with_options(&ParseOptions::new());
+//     storages.process_streams(&parser);
+//     let subject = storages.root.get("Subject");
 #[derive(Debug)]
 pub struct Storages {
     storage_map: EntryStorageMap,
@@ -112,92 +272,740 @@ pub struct Storages {
     pub recipients: Recipients,
     // Mail properties
     pub root: Properties,
+    // ANSI values of the root string properties, which are
+    // in root only when there is no Unicode value.
+    pub root_ansi: Properties,
+    // Stream paths of root, recipients and attachments properties
+    pub root_paths: PropertyPaths,
+    pub recipient_paths: Vec<PropertyPaths>,
+    pub attachment_paths: Vec<PropertyPaths>,
+    // Numbers of the recipient and attachment storages, by index,
+    // e.g. 2 for __attach_version1.0_#00000002. Missing numbers
+    // leave gaps, so they may not match the index.
+    pub recipient_ids: Vec<u32>,
+    pub attachment_ids: Vec<u32>,
+    // Anomalies found while processing
+    pub report: ParseReport,
+    // Entry ids of the embedded message storages,
+    // by index in attachments.
+    pub embedded_messages: HashMap<usize, u32>,
+    // Indices in attachments of those whose payload was
+    // left out by the attachment filter.
+    pub filtered_attachments: HashSet<usize>,
+    // Labels of the payloads, by index in attachments,
+    // see ParseOptions::content_scanner
+    pub classifications: HashMap<usize, Vec<String>>,
+    // Header of the message property stream, none when unreadable
+    pub property_header: Option<PropertyHeader>,
+    // Total size of the streams of the message, embedded messages excluded
+    pub stream_size: u64,
+    // Values larger than this are truncated
+    max_property_size: Option<usize>,
+    attachment_filter: AttachmentFilter,
+    string_decoder: Option<Arc<dyn StringDecoder>>,
+    content_scanner: Option<Arc<dyn ContentScanner>>,
+    preserve_nuls: bool,
+    // Code page of the PtypString8 values, see Storages::read_code_page
+    code_page: Option<i64>,
 }
 
 impl Storages {
-    fn to_arr(map: HashMap<u32, Properties>) -> Vec<Properties> {
-        let mut tuples: Vec<(u32, Properties)> = map
-            .into_iter()
-            .map(|(k, v)| (k, v))
-            .collect::<Vec<(u32, Properties)>>();
-        tuples.sort_by(|a, b| a.0.cmp(&b.0));
-        tuples.into_iter().map(|x| x.1).collect::<Vec<Properties>>()
+    // Code page of the PtypString8 values, once streams are processed.
+    pub(crate) fn code_page(&self) -> Option<i64> {
+        self.code_page
+    }
+
+    // Values are in the order of their storage number,
+    // which keys are kept in.
+    fn to_arr<T>(map: BTreeMap<u32, T>) -> Vec<T> {
+        map.into_values().collect()
     }
 
-    fn create_stream(&self, parser: &Reader, entry: &Entry) -> Option<Stream> {
-        let parent = self.storage_map.get_storage_type(entry.parent_node())?;
+    // An attachment payload is given to scan as it is read, whole
+    // even when its value is truncated.
+    fn create_stream(
+        &mut self,
+        parser: &Reader,
+        entry: &Entry,
+        parent: &StorageType,
+        scan: Option<&mut dyn AttachmentScan>,
+    ) -> Option<Stream> {
         let mut slice = parser.get_entry_slice(entry).ok()?;
-        Stream::create(entry.name(), &mut slice, &self.prop_map, parent)
+        let max_size = self.max_property_size.unwrap_or(usize::MAX);
+        // The value is read once, then decoded and checked
+        let buff = Stream::value_code(entry.name())
+            .and_then(|code| PtypDecoder::read_value(&mut slice, &code, max_size).ok());
+        let stream = buff.as_deref().and_then(|buff| {
+            Stream::from_value(
+                entry.name(),
+                buff,
+                &self.prop_map,
+                parent,
+                self.string_decoder.as_deref(),
+                self.code_page,
+            )
+        });
+        if let Some(scan) = scan {
+            if let Some(stream) = &stream {
+                scan_value(&stream.value, scan);
+            }
+            scan_rest(&mut slice, scan);
+        }
+        let (mut stream, buff) = stream.zip(buff)?;
+        if self.string_decoder.is_none() {
+            self.check_string_encoding(parser, entry, &buff);
+            if matches!(&stream.value, DataType::PtypString(value) if value.contains('\u{FFFD}')) {
+                self.count_replacements(parser, entry, &stream.key, &buff);
+            }
+        }
+        if let DataType::PtypString(value) = &mut stream.value {
+            self.trim_nuls(parser, entry, &stream.key, value);
+        }
+        if slice.len() > max_size {
+            self.report.push(ParseWarning::PropertyTruncated {
+                path: parser.path(entry),
+                size: slice.len(),
+                max_size,
+            });
+        }
+        Some(stream)
+    }
+
+    // Reports the PtypString values not written as MS-OXCDATA requires,
+    // telling their encoding from the start of the value.
+    fn check_string_encoding(&mut self, parser: &Reader, entry: &Entry, value: &[u8]) {
+        match Stream::extract_id_and_datatype(entry.name()) {
+            Some((_, code)) if code == PTYP_STRING => {}
+            _ => return,
+        }
+        let encoding = utf16_encoding(&value[..value.len().min(UTF16_SAMPLE_SIZE)]);
+        if encoding != Utf16Encoding::LittleEndian {
+            self.report.push(ParseWarning::StringEncoding {
+                path: parser.path(entry),
+                encoding,
+            });
+        }
+    }
+
+    // Reports the code units of a PtypString value which were not
+    // valid UTF-16, and were decoded as replacement characters.
+    fn count_replacements(&mut self, parser: &Reader, entry: &Entry, name: &str, value: &[u8]) {
+        match Stream::extract_id_and_datatype(entry.name()) {
+            Some((_, code)) if code == PTYP_STRING => {}
+            _ => return,
+        }
+        let count = invalid_utf16_count(value);
+        if count > 0 {
+            self.report.push(ParseWarning::ReplacementCharacters {
+                name: name.to_string(),
+                path: parser.path(entry),
+                count,
+            });
+        }
+    }
+
+    // Cuts a string at its first NUL, unless preserve_nuls is set.
+    // Trailing NULs terminate strings, others are reported.
+    fn trim_nuls(&mut self, parser: &Reader, entry: &Entry, name: &str, value: &mut String) {
+        let offset = match value.find('\0') {
+            Some(offset) => offset,
+            None => return,
+        };
+        if value[offset..].contains(|c| c != '\0') {
+            self.report.push(ParseWarning::EmbeddedNul {
+                name: name.to_string(),
+                path: parser.path(entry),
+                position: value[..offset].chars().count(),
+            });
+        }
+        if !self.preserve_nuls {
+            value.truncate(offset);
+        }
+    }
+
+    fn create_streams_from_properties(
+        &mut self,
+        parser: &Reader,
+        entry: &Entry,
+        parent: &StorageType,
+    ) -> Vec<Stream> {
+        let header_size = EntryStorageMap::properties_header_size(parent, entry.len());
+        if let StorageType::RootEntry = parent {
+            self.read_property_header(parser, entry);
+        }
+        let mut slice = match parser.get_entry_slice(entry) {
+            Ok(slice) => slice,
+            Err(_) => return vec![],
+        };
+        let (streams, unknown_types) =
+            Stream::create_from_properties(&mut slice, header_size, &self.prop_map, parent);
+        for code in unknown_types {
+            self.report.push(ParseWarning::UnknownPropertyType {
+                path: parser.path(entry),
+                code,
+            });
+        }
+        streams
+    }
+
+    // Fields of the message header, at the same offsets at the
+    // top level and when embedded. Refer to MS-OXMSG 2.4.1.1
+    fn read_property_header(&mut self, parser: &Reader, entry: &Entry) {
+        let mut header = [0u8; 24];
+        let read = parser
+            .get_entry_slice(entry)
+            .ok()
+            .and_then(|mut slice| slice.read_exact(&mut header).ok());
+        if read.is_none() {
+            return;
+        }
+        let field = |offset: usize| {
+            u32::from_le_bytes([
+                header[offset],
+                header[offset + 1],
+                header[offset + 2],
+                header[offset + 3],
+            ])
+        };
+        self.property_header = Some(PropertyHeader {
+            next_recipient_id: field(8),
+            next_attachment_id: field(12),
+            recipient_count: field(16),
+            attachment_count: field(20),
+        });
     }
 
     pub fn process_streams(&mut self, parser: &Reader) {
-        let mut recipients_map: HashMap<u32, Properties> = HashMap::new();
-        let mut attachments_map: HashMap<u32, Properties> = HashMap::new();
+        self.process_streams_matching(parser, |_, _| true);
+    }
+
+    // Same as process_streams, leaving out attachments and bodies
+    // which make up the bulk of most messages.
+    pub fn process_header_streams(&mut self, parser: &Reader) {
+        self.process_streams_matching(parser, |parent, name| match parent {
+            StorageType::Attachment(_) => false,
+            // Invalid names are let through to be reported
+            _ => match Stream::extract_id_and_datatype(name) {
+                Some((prop_id, _)) if Stream::is_stream(name) => {
+                    !BODY_PROPERTY_IDS.contains(&prop_id.as_str())
+                }
+                _ => true,
+            },
+        });
+    }
+
+    // Same as process_streams, leaving out bodies and attachment
+    // payloads, which can then be streamed.
+    pub fn process_metadata_streams(&mut self, parser: &Reader) {
+        self.process_streams_matching(parser, |parent, name| {
+            let prop_id = match Stream::extract_id_and_datatype(name) {
+                Some((prop_id, _)) if Stream::is_stream(name) => prop_id,
+                _ => return true,
+            };
+            match parent {
+                StorageType::Attachment(_) => prop_id != ATTACH_DATA_PROPERTY_ID,
+                _ => !BODY_PROPERTY_IDS.contains(&prop_id.as_str()),
+            }
+        });
+    }
+
+    // Only the bodies and the fixed length properties of the
+    // message, to read the bodies left out above.
+    pub fn process_body_streams(&mut self, parser: &Reader) {
+        self.process_streams_matching(parser, |parent, name| {
+            *parent == StorageType::RootEntry
+                && match Stream::extract_id_and_datatype(name) {
+                    Some((prop_id, _)) if Stream::is_stream(name) => {
+                        BODY_PROPERTY_IDS.contains(&prop_id.as_str())
+                    }
+                    _ => true,
+                }
+        });
+    }
+
+    // Only the properties of the attachment storage numbered id,
+    // payload included, which is then at index 0 in attachments.
+    pub fn process_attachment_streams(&mut self, parser: &Reader, id: u32) {
+        self.process_streams_matching(parser, |parent, _| *parent == StorageType::Attachment(id));
+    }
+
+    // Code page of the PtypString8 values of the message, read from
+    // its property stream before any of them is decoded. That of the
+    // bodies, "InternetCodepage", is used when it is not set.
+    fn read_code_page(&self, parser: &Reader) -> Option<i64> {
+        let root = StorageType::RootEntry;
+        let entry = parser.iterate().find(|entry| {
+            Stream::is_properties(entry.name())
+                && self.storage_map.get_storage_type(entry.parent_node()) == Some(&root)
+        })?;
+        let header_size = EntryStorageMap::properties_header_size(&root, entry.len());
+        let mut slice = parser.get_entry_slice(entry).ok()?;
+        let (streams, _) =
+            Stream::create_from_properties(&mut slice, header_size, &self.prop_map, &root);
+        ["MessageCodepage", "InternetCodepage"].iter().find_map(|key| {
+            streams
+                .iter()
+                .find(|stream| stream.key == *key)
+                .and_then(|stream| stream.value.as_i64())
+        })
+    }
+
+    fn process_streams_matching<F>(&mut self, parser: &Reader, filter: F)
+    where
+        F: Fn(&StorageType, &str) -> bool,
+    {
+        let start = Instant::now();
+        self.code_page = self.read_code_page(parser);
+        for entry in parser.incomplete_entries() {
+            if self.storage_map.get_storage_type(entry.parent_node()).is_none() {
+                continue;
+            }
+            self.report.push(ParseWarning::IncompleteStream {
+                name: entry.name().to_string(),
+                path: parser.path(entry),
+                declared_size: entry.len(),
+            });
+        }
+        for entry in parser.misplaced_entries() {
+            if self.storage_map.get_storage_type(entry.parent_node()).is_none() {
+                continue;
+            }
+            self.report.push(ParseWarning::MisplacedStream {
+                path: parser.path(entry),
+                size: entry.len(),
+            });
+        }
+        // Properties and their stream paths by storage number
+        let mut recipients_map: BTreeMap<u32, (Properties, PropertyPaths)> = BTreeMap::new();
+        let mut attachments_map: BTreeMap<u32, (Properties, PropertyPaths)> = BTreeMap::new();
+        // Payloads are filtered once the attachment properties are known
+        let mut payloads: Vec<(&Entry, StorageType)> = vec![];
+        // Values of multiple valued properties by type code, storage
+        // and property, in the order of their index
+        let mut elements: BTreeMap<(String, StorageType, String), BTreeMap<u32, DataType>> =
+            BTreeMap::new();
+        // Path and sizes of the value streams by storage and name,
+        // see ParseWarning::DuplicateStream
+        let mut seen: BTreeMap<(StorageType, String), (String, Vec<usize>)> = BTreeMap::new();
+        self.stream_size = 0;
         for entry in parser.iterate() {
             if let EntryType::UserStream = entry._type() {
+                let parent = match self.storage_map.get_storage_type(entry.parent_node()) {
+                    Some(parent) => parent.clone(),
+                    None => continue,
+                };
+                self.stream_size += entry.len() as u64;
+                if !filter(&parent, entry.name()) {
+                    continue;
+                }
                 // Decode stream from slice.
                 // Skip if failed.
-                let stream_res = self.create_stream(&parser, &entry);
-                if stream_res.is_none() {
+                let streams = if Stream::is_properties(entry.name()) {
+                    self.create_streams_from_properties(parser, entry, &parent)
+                } else if Stream::is_stream(entry.name()) {
+                    let (prop_id, code) = match Stream::extract_id_and_datatype(entry.name()) {
+                        Some(tag) => tag,
+                        None => {
+                            self.report.push(ParseWarning::InvalidStreamName {
+                                path: parser.path(entry),
+                            });
+                            continue;
+                        }
+                    };
+                    // Of streams with the same name, the longest is kept
+                    let (_, sizes) = seen
+                        .entry((parent.clone(), normalize_name(entry.name())))
+                        .or_insert_with(|| (parser.path(entry), vec![]));
+                    let longest = sizes.iter().copied().max();
+                    sizes.push(entry.len());
+                    if longest.is_some_and(|longest| entry.len() <= longest) {
+                        continue;
+                    }
+                    if matches!(parent, StorageType::Attachment(_))
+                        && prop_id == ATTACH_DATA_PROPERTY_ID
+                        && (!self.attachment_filter.is_empty() || self.content_scanner.is_some())
+                    {
+                        payloads.push((entry, parent));
+                        continue;
+                    }
+                    if !is_known_type(&code) {
+                        self.report.push(ParseWarning::UnknownPropertyType {
+                            path: parser.path(entry),
+                            code,
+                        });
+                    }
+                    self.create_stream(parser, entry, &parent, None)
+                        .into_iter()
+                        .collect()
+                } else {
                     continue;
+                };
+                self.report.metrics.bytes_read += entry.len() as u64;
+                let path = parser.path(entry);
+                let code = Stream::is_stream(entry.name())
+                    .then(|| Stream::extract_id_and_datatype(entry.name()))
+                    .flatten()
+                    .map(|(_, code)| code);
+                let ansi = code.as_deref() == Some(PTYP_STRING8);
+                let element = Stream::element_index(entry.name()).zip(code);
+
+                // The value of a multiple valued property which could not
+                // be read keeps its index, see ParseWarning::UnreadableElement
+                let key = match &element {
+                    Some(_) if streams.is_empty() => Stream::extract_id_and_datatype(entry.name())
+                        .and_then(|(prop_id, _)| self.prop_map.get_canonical_name(&prop_id)),
+                    _ => None,
+                };
+                if let (Some(key), Some((index, code))) = (key, &element) {
+                    elements
+                        .entry((code.clone(), parent.clone(), key))
+                        .or_default()
+                        .insert(*index, DataType::PtypNull);
+                    self.report.push(ParseWarning::UnreadableElement {
+                        path: path.clone(),
+                        index: *index,
+                    });
                 }
-                let stream = stream_res.unwrap();
 
                 // Populate maps accordingly
-                match stream.parent {
-                    StorageType::RootEntry => {
-                        self.root.insert(stream.key, stream.value);
+                for stream in streams {
+                    if let Some((index, code)) = &element {
+                        let (index, code) = (*index, code.clone());
+                        elements
+                            .entry((code, stream.parent, stream.key))
+                            .or_default()
+                            .insert(index, stream.value);
+                        continue;
                     }
-                    StorageType::Recipient(id) => {
-                        let recipient_map = recipients_map.entry(id).or_insert(HashMap::new());
-                        (*recipient_map).insert(stream.key, stream.value);
+                    let path = path.clone();
+                    // Unicode values are preferred over ANSI ones
+                    if ansi {
+                        let has_value = match stream.parent {
+                            StorageType::RootEntry => {
+                                self.root_ansi.insert(stream.key.clone(), stream.value.clone());
+                                self.root.contains_key(&stream.key)
+                            }
+                            StorageType::Recipient(id) => recipients_map
+                                .get(&id)
+                                .is_some_and(|(props, _)| props.contains_key(&stream.key)),
+                            StorageType::Attachment(id) => attachments_map
+                                .get(&id)
+                                .is_some_and(|(props, _)| props.contains_key(&stream.key)),
+                        };
+                        if has_value {
+                            continue;
+                        }
                     }
-                    StorageType::Attachment(id) => {
-                        let attachment_map = attachments_map.entry(id).or_insert(HashMap::new());
-                        (*attachment_map).insert(stream.key, stream.value);
+                    match stream.parent {
+                        StorageType::RootEntry => {
+                            self.root_paths.insert(stream.key.clone(), path);
+                            self.root.insert(stream.key, stream.value);
+                        }
+                        StorageType::Recipient(id) => {
+                            let (recipient_map, recipient_paths) =
+                                recipients_map.entry(id).or_default();
+                            recipient_paths.insert(stream.key.clone(), path);
+                            recipient_map.insert(stream.key, stream.value);
+                        }
+                        StorageType::Attachment(id) => {
+                            let (attachment_map, attachment_paths) =
+                                attachments_map.entry(id).or_default();
+                            attachment_paths.insert(stream.key.clone(), path);
+                            attachment_map.insert(stream.key, stream.value);
+                        }
                     }
                 }
             }
         }
+        for (path, sizes) in seen.into_values() {
+            if sizes.len() > 1 {
+                self.report.push(ParseWarning::DuplicateStream { path, sizes });
+            }
+        }
+        // PtypMultipleString values come after PtypMultipleString8
+        // ones, so that Unicode values are preferred
+        for ((code, parent, key), values) in elements {
+            let value = match multiple_value(&code, values.into_values()) {
+                Some(value) => value,
+                None => continue,
+            };
+            match parent {
+                StorageType::RootEntry => {
+                    self.root.insert(key, value);
+                }
+                StorageType::Recipient(id) => {
+                    recipients_map.entry(id).or_default().0.insert(key, value);
+                }
+                StorageType::Attachment(id) => {
+                    attachments_map.entry(id).or_default().0.insert(key, value);
+                }
+            }
+        }
+        self.check_ansi_values();
+
+        let mut filtered_ids = HashSet::new();
+        let mut classifications = HashMap::new();
+        for (entry, parent) in payloads {
+            let id = match parent {
+                StorageType::Attachment(id) => id,
+                _ => continue,
+            };
+            let props = attachments_map.get(&id).map(|(props, _)| props);
+            let allowed = self.attachment_filter.is_empty()
+                || props.is_some_and(|props| self.attachment_filter.allows(props));
+            let scanner = self.content_scanner.clone();
+            let mut scan = scanner
+                .as_deref()
+                .and_then(|scanner| start_scan(scanner, entry, props, id));
+            // The payload is read once, scanned as it is decoded,
+            // or only scanned when left out
+            let mut stream = None;
+            if allowed {
+                self.report.metrics.bytes_read += entry.len() as u64;
+                let scan: Option<&mut dyn AttachmentScan> = match &mut scan {
+                    Some((_, scan)) => Some(scan.as_mut()),
+                    None => None,
+                };
+                stream = self.create_stream(parser, entry, &parent, scan);
+            } else if let Some((_, scan)) = &mut scan {
+                self.report.metrics.bytes_read += entry.len() as u64;
+                if let Ok(mut slice) = parser.get_entry_slice(entry) {
+                    scan_rest(&mut slice, scan.as_mut());
+                }
+            }
+            let mut kept = allowed;
+            if let (Some(scanner), Some((target, scan))) = (&scanner, scan) {
+                let labels = scan.finish();
+                kept &= scanner.keep_payload(&target, &labels);
+                classifications.insert(id, labels);
+            }
+            if !kept {
+                filtered_ids.insert(id);
+                continue;
+            }
+            let stream = match stream {
+                Some(stream) => stream,
+                None => continue,
+            };
+            let (attachment_map, attachment_paths) = attachments_map.entry(id).or_default();
+            attachment_paths.insert(stream.key.clone(), parser.path(entry));
+            attachment_map.insert(stream.key, stream.value);
+        }
+
+        // Attachments which are embedded messages
+        let attachment_ids: Vec<&u32> = attachments_map.keys().collect();
+        self.filtered_attachments = attachment_ids
+            .iter()
+            .enumerate()
+            .filter(|(_, id)| filtered_ids.contains(**id))
+            .map(|(idx, _)| idx)
+            .collect();
+        self.classifications = attachment_ids
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, id)| Some((idx, classifications.remove(*id)?)))
+            .collect();
+        self.attachment_ids = attachment_ids.iter().map(|id| **id).collect();
+        self.embedded_messages = attachment_ids
+            .into_iter()
+            .enumerate()
+            .filter_map(|(idx, id)| Some((idx, *self.storage_map.embedded.get(id)?)))
+            .collect();
+
         // Update storages
-        self.recipients = Self::to_arr(recipients_map);
-        self.attachments = Self::to_arr(attachments_map);
+        self.recipient_ids = recipients_map.keys().copied().collect();
+        (self.recipients, self.recipient_paths) = Self::to_arr(recipients_map).into_iter().unzip();
+        (self.attachments, self.attachment_paths) =
+            Self::to_arr(attachments_map).into_iter().unzip();
+        self.report.metrics.stream_decode += start.elapsed();
+    }
+
+    // Reports the root properties whose ANSI value is not
+    // a conversion of their Unicode value.
+    fn check_ansi_values(&mut self) {
+        let mut names: Vec<&String> = self.root_ansi.keys().collect();
+        names.sort();
+        for name in names {
+            let (unicode, ansi) = match (self.root.get(name), self.root_ansi.get(name)) {
+                (Some(DataType::PtypString(unicode)), Some(DataType::PtypString(ansi))) => {
+                    (unicode, ansi)
+                }
+                _ => continue,
+            };
+            if !is_ansi_variant(unicode.trim_end_matches('\0'), ansi) {
+                self.report.push(ParseWarning::StringVariantMismatch {
+                    name: name.clone(),
+                    unicode: unicode.clone(),
+                    ansi: ansi.clone(),
+                });
+            }
+        }
+    }
+
+    // Whether the message holds attachment storages,
+    // even if their streams were not processed.
+    pub fn has_attachments(&self) -> bool {
+        self.storage_map
+            .map
+            .values()
+            .any(|storage| matches!(storage, StorageType::Attachment(_)))
+    }
+
+    // Number of properties decoded for the message,
+    // its recipients and attachments.
+    pub fn property_count(&self) -> usize {
+        self.root.len()
+            + self.recipients.iter().map(|props| props.len()).sum::<usize>()
+            + self.attachments.iter().map(|props| props.len()).sum::<usize>()
+    }
+
+    // Tag of a property by canonical name, see PropIdNameMap::get_tag.
+    pub(crate) fn property_tag(&self, name: &str) -> Option<u32> {
+        self.prop_map.get_tag(name)
+    }
+
+    // Number of storages making up the message,
+    // embedded messages excluded.
+    pub fn storage_count(&self) -> usize {
+        self.storage_map.map.len()
     }
 
     pub fn new(parser: &Reader) -> Self {
+        let mut storages = Self::create(parser, EntryStorageMap::new(parser));
+        // Reported once for the file, rather than for each message in it
+        if parser.is_directory_truncated() {
+            storages.report.push(ParseWarning::TruncatedDirectory {
+                entries: parser
+                    .iterate()
+                    .filter(|entry| entry._type() != EntryType::Empty)
+                    .count(),
+            });
+        }
+        storages
+    }
+
+    // Storages of the message with all its streams processed,
+    // under the default options.
+    pub(crate) fn processed(parser: &Reader) -> Self {
+        let mut storages = Self::new(parser);
+        storages.process_streams(parser);
+        storages
+    }
+
+    // Truncates the values larger than max_size bytes.
+    pub fn max_property_size(mut self, max_size: Option<usize>) -> Self {
+        self.max_property_size = max_size;
+        self
+    }
+
+    // Decodes only the attachment payloads the filter allows.
+    pub(crate) fn attachment_filter(mut self, filter: AttachmentFilter) -> Self {
+        self.attachment_filter = filter;
+        self
+    }
+
+    // Decodes the string properties with decoder first.
+    pub fn string_decoder(mut self, decoder: Option<Arc<dyn StringDecoder>>) -> Self {
+        self.string_decoder = decoder;
+        self
+    }
+
+    // Scans the attachment payloads with scanner, filtered ones included.
+    pub fn content_scanner(mut self, scanner: Option<Arc<dyn ContentScanner>>) -> Self {
+        self.content_scanner = scanner;
+        self
+    }
+
+    // Keeps the NUL characters of string properties.
+    pub fn preserve_nuls(mut self, value: bool) -> Self {
+        self.preserve_nuls = value;
+        self
+    }
+
+    // Applies the limits, filter, decoder and scanner of options.
+    pub fn with_options(self, options: &ParseOptions) -> Self {
+        self.max_property_size(options.max_property_size)
+            .attachment_filter(options.attachment_filter.clone())
+            .string_decoder(options.string_decoder.clone())
+            .content_scanner(options.content_scanner.clone())
+            .preserve_nuls(options.preserve_nuls)
+    }
+
+    // Storages of the message held by the storage `root_id`,
+    // e.g. an embedded message.
+    pub fn with_root(parser: &Reader, root_id: u32) -> Self {
+        Self::create(parser, EntryStorageMap::with_root(parser, root_id))
+    }
+
+    fn create(parser: &Reader, storage_map: EntryStorageMap) -> Self {
         let root: Properties = HashMap::new();
         let recipients: Recipients = vec![];
         let attachments: Attachments = vec![];
-        let storage_map = EntryStorageMap::new(parser);
-        let prop_map = PropIdNameMap::init();
+        let prop_map = PropIdNameMap::init().with_named(NamedPropertyMap::from_reader(parser));
         Self {
             storage_map,
             prop_map,
             root,
+            root_ansi: HashMap::new(),
+            root_paths: HashMap::new(),
             recipients,
+            recipient_paths: vec![],
+            recipient_ids: vec![],
+            attachment_ids: vec![],
             attachments,
+            attachment_paths: vec![],
+            report: ParseReport::default(),
+            embedded_messages: HashMap::new(),
+            filtered_attachments: HashSet::new(),
+            classifications: HashMap::new(),
+            property_header: None,
+            stream_size: 0,
+            max_property_size: None,
+            attachment_filter: AttachmentFilter::default(),
+            string_decoder: None,
+            content_scanner: None,
+            preserve_nuls: false,
+            code_page: None,
         }
     }
 
+    // Looks up key regardless of case, then its aliases.
     pub fn get_val_from_root_or_default(&self, key: &str) -> String {
-        self.root.get(key).map_or(String::new(), |x| x.into())
+        let key = self.prop_map.get_name(key).unwrap_or(key);
+        get_aliased(&self.root, key).map_or(String::new(), |x| x.into())
+    }
+
+    // Value of the first of keys set in root, by order of preference.
+    pub fn get_first_from_root_or_default(&self, keys: &[&str]) -> String {
+        get_first(&self.root, keys).map_or(String::new(), |x| x.into())
+    }
+
+    pub fn get_binary_from_attachment(&self, idx: usize, key: &str) -> Option<&[u8]> {
+        match self.attachments.get(idx)?.get(key)? {
+            DataType::PtypBinary(bytes) => Some(bytes),
+            _ => None,
+        }
     }
 
     pub fn get_val_from_attachment_or_default(&self, idx: usize, key: &str) -> String {
+        let key = self.prop_map.get_name(key).unwrap_or(key);
         self.attachments
-            .iter()
-            .nth(idx)
-            .map(|attach| attach.get(key).map_or(String::from(""), |x| x.into()))
-            .unwrap_or(String::new())
+            .get(idx)
+            .map(|attach| get_aliased(attach, key).map_or(String::from(""), |x| x.into()))
+            .unwrap_or_default()
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::legacy_numeric_constants)]
 mod tests {
     use super::super::decode::DataType;
-    use super::{EntryStorageMap, Properties, StorageType, Storages};
+    use super::{
+        get_aliased, get_aliased_key, get_first, EntryStorageMap, Properties, StorageType,
+        Storages,
+    };
     use crate::ole::Reader;
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
 
     #[test]
     fn test_storage_type_convert() {
@@ -232,6 +1040,16 @@ mod tests {
 
         let unknown_storage = StorageType::create("");
         assert_eq!(unknown_storage, None);
+
+        let no_index = StorageType::create("__recip_version1.0_");
+        assert_eq!(no_index, None);
+
+        // Case and padding written by non-Outlook generators
+        let recipient = StorageType::create("__RECIP_VERSION1.0_#0000000a  ");
+        assert_eq!(recipient, Some(StorageType::Recipient(10)));
+
+        let attachment = StorageType::create(" __Attach_Version1.0_#00000001\0");
+        assert_eq!(attachment, Some(StorageType::Attachment(1)));
     }
 
     #[test]
@@ -248,7 +1066,6 @@ mod tests {
         expected_map.insert(120, StorageType::Recipient(4));
         expected_map.insert(132, StorageType::Recipient(5));
         expected_map.insert(143, StorageType::Attachment(0));
-        expected_map.insert(260, StorageType::Recipient(0));
         expected_map.insert(310, StorageType::Attachment(1));
         expected_map.insert(323, StorageType::Attachment(2));
         assert_eq!(storage_map.map, expected_map);
@@ -261,7 +1078,7 @@ mod tests {
         let mut map_bagel: Properties = HashMap::new();
         map_bagel.insert("B".to_string(), DataType::PtypString("Bagel".to_string()));
 
-        let mut basket: HashMap<u32, Properties> = HashMap::new();
+        let mut basket: BTreeMap<u32, Properties> = BTreeMap::new();
         basket.insert(1, map_apple);
         basket.insert(0, map_bagel);
 
@@ -276,6 +1093,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_aliased() {
+        let string = |value: &str| DataType::PtypString(value.to_string());
+        let mut props: Properties = HashMap::new();
+        props.insert("SenderEmailAddress".to_string(), string(""));
+        props.insert("SentRepresentingSmtpAddress".to_string(), string("a@x.com"));
+        props.insert("SentRepresentingEmailAddress".to_string(), string("b@x.com"));
+
+        // Empty values are skipped, aliased names matched regardless of case
+        assert_eq!(get_aliased(&props, "SenderSmtpAddress"), Some(&string("a@x.com")));
+        assert_eq!(get_aliased(&props, "senderSmtpAddress"), Some(&string("a@x.com")));
+        assert_eq!(get_aliased(&props, "SenderEmailAddress"), None);
+        assert_eq!(
+            get_aliased_key(&props, "SenderSmtpAddress"),
+            Some(("SentRepresentingSmtpAddress", &string("a@x.com")))
+        );
+        assert_eq!(
+            get_first(&props, &["SentRepresentingEmailAddress", "SenderSmtpAddress"]),
+            Some(&string("b@x.com"))
+        );
+        assert_eq!(get_first(&props, &["Subject"]), None);
+    }
+
     #[test]
     fn test_create_storage_test_email() {
         let parser = Reader::from_path("data/test_email.msg").unwrap();
@@ -285,6 +1125,10 @@ mod tests {
         let sender = storages.root.get("SenderEmailAddress");
         assert!(sender.is_none());
 
+        // Empty streams are still recorded.
+        let subject_prefix = storages.root.get("SubjectPrefix");
+        assert_eq!(subject_prefix, Some(&DataType::PtypString("".to_string())));
+
         // Check attachments
         assert_eq!(storages.attachments.len(), 3);
 
@@ -295,7 +1139,44 @@ mod tests {
         let display_name = storages.recipients[0].get("DisplayName").unwrap();
         assert_eq!(
             display_name,
-            &DataType::PtypString("marirs@outlook.com".to_string())
+            &DataType::PtypString("Sriram Govindan".to_string())
+        );
+
+        // Fixed length properties
+        assert_eq!(
+            storages.root.get("MessageFlags"),
+            Some(&DataType::PtypInteger32(0x19))
+        );
+
+        // Check stream paths
+        assert_eq!(
+            storages.root_paths.get("Subject").map(String::as_str),
+            Some("__substg1.0_0037001F")
+        );
+        assert_eq!(storages.recipient_paths.len(), 6);
+        assert_eq!(
+            storages.recipient_paths[0].get("DisplayName").map(String::as_str),
+            Some("__recip_version1.0_#00000000/__substg1.0_3001001F")
+        );
+
+        // Keys are looked up regardless of case
+        assert_eq!(storages.get_val_from_root_or_default("SUBJECT"), "Test Email");
+
+        // Attachment 0 is an embedded message
+        assert_eq!(storages.embedded_messages.get(&0), Some(&146));
+        let mut embedded = Storages::with_root(&parser, 146);
+        embedded.process_streams(&parser);
+        assert_eq!(embedded.recipients.len(), 1);
+        assert_eq!(
+            embedded.recipients[0].get("DisplayName"),
+            Some(&DataType::PtypString("marirs@outlook.com".to_string()))
+        );
+        assert_eq!(
+            embedded.recipient_paths[0].get("DisplayName").map(String::as_str),
+            Some(
+                "__attach_version1.0_#00000000/__substg1.0_3701000D/\
+                __recip_version1.0_#00000000/__substg1.0_3001001F"
+            )
         );
     }
 
@@ -332,4 +1213,219 @@ mod tests {
         let display_name = storages.recipients[1].get("DisplayName").unwrap();
         assert_eq!(display_name, &DataType::PtypString("Sriram Govindan".to_string()));
     }
+
+    #[test]
+    fn test_multiple_valued_properties() {
+        use crate::testing::{MessageBuilder, PropertyValue};
+
+        let names = vec!["Ann".to_string(), "Bob".to_string()];
+        let bytes = MessageBuilder::new()
+            .property("ChildrensNames", PropertyValue::MultipleString(names.clone()))
+            .property("Hobbies", PropertyValue::MultipleString(vec![]))
+            .property("Keyword", PropertyValue::MultipleBinary(vec![vec![1, 2], vec![3]]))
+            .property("Gender", PropertyValue::MultipleInteger32(vec![1, 2, 3]))
+            .build();
+        let parser = Reader::new(&bytes[..]).unwrap();
+        let mut storages = Storages::new(&parser);
+        storages.process_streams(&parser);
+
+        let get = |key: &str| storages.root.get(key).cloned();
+        assert_eq!(get("ChildrensNames"), Some(DataType::PtypMultipleString(names)));
+        assert_eq!(get("Hobbies"), Some(DataType::PtypMultipleString(vec![])));
+        assert_eq!(
+            get("Keyword"),
+            Some(DataType::PtypMultipleBinary(vec![vec![1, 2], vec![3]]))
+        );
+        assert_eq!(get("Gender"), Some(DataType::PtypMultipleInteger32(vec![1, 2, 3])));
+        assert!(storages.report.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_unreadable_element() {
+        use super::super::report::ParseWarning;
+        use crate::testing::{MessageBuilder, PropertyValue};
+        use std::io::{Cursor, Error, Read, Seek, SeekFrom};
+
+        // Source failing to read the byte at an offset
+        struct Failing(Cursor<Vec<u8>>, u64);
+        impl Read for Failing {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let position = self.0.position();
+                if (position..position + buf.len() as u64).contains(&self.1) {
+                    return Err(Error::other("unreadable"));
+                }
+                self.0.read(buf)
+            }
+        }
+        impl Seek for Failing {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.0.seek(pos)
+            }
+        }
+
+        let names = ["Ann", "Bob", "Cy"].map(String::from).to_vec();
+        let bytes = MessageBuilder::new()
+            .property("ChildrensNames", PropertyValue::MultipleString(names))
+            .build();
+        let offset = bytes.windows(6).position(|w| w == b"B\0o\0b\0").unwrap();
+        let parser = Reader::from_seekable(Failing(Cursor::new(bytes), offset as u64)).unwrap();
+        let mut storages = Storages::new(&parser);
+        storages.process_streams(&parser);
+
+        // The value of the stream which could not be read is left empty
+        let names = ["Ann", "", "Cy"].map(String::from).to_vec();
+        assert_eq!(
+            storages.root.get("ChildrensNames"),
+            Some(&DataType::PtypMultipleString(names))
+        );
+        assert_eq!(
+            storages.report.warnings,
+            vec![ParseWarning::UnreadableElement {
+                path: "__substg1.0_3A58101F-00000001".to_string(),
+                index: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_element_only_recipient() {
+        use crate::testing::Writer;
+
+        // The first recipient has only the values of a multiple
+        // valued property, the second a single stream
+        let mut writer = Writer::new();
+        writer.add_stream("__properties_version1.0", &[0u8; 32]);
+        writer.add_stream("__recip_version1.0_#00000000/__substg1.0_3A58101F-00000000", b"A\0");
+        writer.add_stream("__recip_version1.0_#00000001/__substg1.0_3001001F", b"B\0o\0b\0");
+        let bytes = writer.to_bytes();
+        let storages = Storages::processed(&Reader::new(&bytes[..]).unwrap());
+
+        assert_eq!(storages.recipients.len(), 2);
+        assert_eq!(storages.recipient_paths.len(), 2);
+        assert!(storages.recipient_paths[0].is_empty());
+        assert_eq!(
+            storages.recipient_paths[1].get("DisplayName").map(String::as_str),
+            Some("__recip_version1.0_#00000001/__substg1.0_3001001F")
+        );
+    }
+
+    #[test]
+    fn test_truncated_directory() {
+        use super::super::report::ParseWarning;
+        use crate::testing::Writer;
+
+        // Empty streams, so that the last directory sector ends the file
+        let mut writer = Writer::new();
+        for name in ["__properties_version1.0", "__substg1.0_0037001F", "a", "b", "c", "d"] {
+            writer.add_stream(name, &[]);
+        }
+        let bytes = writer.to_bytes();
+        let storages = Storages::new(&Reader::new(&bytes[..]).unwrap());
+        assert!(storages.report.warnings.is_empty());
+
+        let parser = Reader::new(&bytes[..bytes.len() - 512]).unwrap();
+        let storages = Storages::new(&parser);
+        assert_eq!(
+            storages.report.warnings,
+            [ParseWarning::TruncatedDirectory { entries: 4 }]
+        );
+    }
+
+    #[test]
+    fn test_public_api() {
+        use crate::{DataType, ParseOptions, Properties, StorageType, Storages};
+
+        let parser = Reader::from_path("data/unicode.msg").unwrap();
+        let options = ParseOptions::new().max_property_size(64);
+        let mut storages = Storages::new(&parser).with_options(&options);
+        storages.process_streams(&parser);
+
+        let root: &Properties = &storages.root;
+        match root.get("Body") {
+            Some(DataType::PtypString(body)) => assert_eq!(body.encode_utf16().count(), 32),
+            other => panic!("unexpected body {:?}", other),
+        }
+        assert!(!storages.report.warnings.is_empty());
+        assert_eq!(
+            StorageType::create("__attach_version1.0_#00000001"),
+            Some(StorageType::Attachment(1))
+        );
+    }
+
+    #[test]
+    fn test_duplicate_stream() {
+        use super::super::report::ParseWarning;
+        use crate::testing::Writer;
+
+        let utf16 = |text: &str| -> Vec<u8> {
+            text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+        };
+        let mut writer = Writer::new();
+        writer.add_stream("__properties_version1.0", &[0u8; 32]);
+        writer.add_stream("__substg1.0_0037001F", &[]);
+        writer.add_stream("__substg1.0_0037001X", &utf16("Budget"));
+        writer.add_stream("__substg1.0_1000001F", &utf16("Hello"));
+        writer.add_stream("__substg1.0_1000001X", &utf16("Hi"));
+        // Names are compared as normalized
+        writer.add_stream("__substg1.0_0E1D001F", &utf16("Budget"));
+        writer.add_stream("__substg1.0_0e1d001f \0", &utf16("Plan"));
+        let mut bytes = writer.to_bytes();
+        // Renamed once written, as the writer replaces streams of the same name
+        let (from, to) = (utf16("001X"), utf16("001F"));
+        while let Some(pos) = bytes.windows(from.len()).position(|window| window == from) {
+            bytes[pos..pos + from.len()].copy_from_slice(&to);
+        }
+
+        let parser = Reader::new(&bytes[..]).unwrap();
+        let mut storages = Storages::new(&parser);
+        storages.process_streams(&parser);
+        assert_eq!(storages.get_val_from_root_or_default("Subject"), "Budget");
+        assert_eq!(storages.get_val_from_root_or_default("Body"), "Hello");
+        assert_eq!(storages.get_val_from_root_or_default("NormalizedSubject"), "Budget");
+        assert_eq!(
+            storages.report.warnings,
+            [
+                ParseWarning::DuplicateStream {
+                    path: "__substg1.0_0037001F".to_string(),
+                    sizes: vec![0, 12],
+                },
+                ParseWarning::DuplicateStream {
+                    path: "__substg1.0_0E1D001F".to_string(),
+                    sizes: vec![12, 8],
+                },
+                ParseWarning::DuplicateStream {
+                    path: "__substg1.0_1000001F".to_string(),
+                    sizes: vec![10, 4],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_stream_name() {
+        use super::super::report::ParseWarning;
+        use crate::{testing::Writer, Outlook};
+
+        let mut writer = Writer::new();
+        writer.add_stream("__properties_version1.0", &[0u8; 32]);
+        writer.add_stream("__substg1.0_0037_01F", b"B\0u\0d\0");
+        writer.add_stream("__substg1.0_003700\u{E9}1", b"B\0u\0d\0");
+        writer.add_stream("__substg1.0_1000001F", b"H\0i\0");
+        let bytes = writer.to_bytes();
+
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert_eq!(outlook.subject, "");
+        assert_eq!(outlook.body, "Hi");
+        assert_eq!(
+            outlook.report.warnings,
+            [
+                ParseWarning::InvalidStreamName {
+                    path: "__substg1.0_0037_01F".to_string(),
+                },
+                ParseWarning::InvalidStreamName {
+                    path: "__substg1.0_003700\u{E9}1".to_string(),
+                },
+            ]
+        );
+    }
 }
@@ -1,9 +1,11 @@
+use std::io::Read;
+
 use crate::ole::EntrySlice;
 
 use super::{
     constants::PropIdNameMap,
-    decode::{DataType, PtypDecoder},
-    storage::StorageType,
+    decode::{element_type_code, is_known_type, DataType, PtypDecoder, StringDecoder},
+    storage::{normalize_name, StorageType},
 };
 
 // Stream refer to an element in Message object.
@@ -16,47 +18,138 @@ pub struct Stream {
 }
 
 impl Stream {
-    // __substg1.0__AAAABBBB where AAAA is property id and BBBB is property datatype
-    fn extract_id_and_datatype(name: &str) -> (String, String) {
-        let tag = name
-            .split("_")
-            .filter(|&x| x.len() > 0)
-            .collect::<Vec<&str>>()[1];
-        let prop_id = String::from("0x") + &tag[..4];
-        let prop_datatype = String::from("0x") + &tag[tag.len() - 4..];
-        return (prop_id, prop_datatype);
+    // __substg1.0__AAAABBBB where AAAA is property id and BBBB is property datatype.
+    // Values of multiple valued properties are in streams suffixed
+    // by their index: __substg1.0__AAAABBBB-00000000
+    // None when the name does not hold a tag of 8 hex digits.
+    pub(crate) fn extract_id_and_datatype(name: &str) -> Option<(String, String)> {
+        let name = normalize_name(name);
+        let tag = name.split('_').filter(|&x| !x.is_empty()).nth(1)?;
+        let tag = tag.split_once('-').map_or(tag, |(tag, _)| tag);
+        if tag.len() != 8 || !tag.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return None;
+        }
+        let prop_id = String::from("0x") + tag.get(..4)?;
+        let prop_datatype = String::from("0x") + tag.get(4..)?;
+        Some((prop_id, prop_datatype))
+    }
+
+    // Index of the value held by a stream of a multiple valued
+    // property, none for other streams.
+    pub(crate) fn element_index(name: &str) -> Option<u32> {
+        let name = normalize_name(name);
+        let (_, index) = name.split_once('-')?;
+        u32::from_str_radix(index, 16).ok()
+    }
+
+    // __properties_version1.0 holds the fixed length properties of its storage
+    pub(crate) fn is_properties(name: &str) -> bool {
+        normalize_name(name) == "__PROPERTIES_VERSION1.0"
     }
 
-    fn is_stream(name: &str) -> bool {
-        return name.starts_with("__substg1.0");
+    pub(crate) fn is_stream(name: &str) -> bool {
+        // Prefix followed by the 8 digits property tag
+        let name = normalize_name(name);
+        name.starts_with("__SUBSTG1.0_") && name.len() >= 20
     }
 
+    // Values are read up to max_size bytes. The parser reads values
+    // apart from decoding them, see Stream::from_value.
+    #[cfg(test)]
     pub fn create(
         name: &str,
         entry_slice: &mut EntrySlice,
         prop_map: &PropIdNameMap,
         parent: &StorageType,
+        max_size: usize,
+        string_decoder: Option<&dyn StringDecoder>,
+        code_page: Option<i64>,
     ) -> Option<Self> {
+        let code = Self::value_code(name)?;
+        let buff = PtypDecoder::read_value(entry_slice, &code, max_size).ok()?;
+        Self::from_value(name, &buff, prop_map, parent, string_decoder, code_page)
+    }
+
+    // Type code the value of the stream is read as, that of the
+    // values for the streams of multiple valued properties.
+    pub(crate) fn value_code(name: &str) -> Option<String> {
         if !Self::is_stream(name) {
             return None;
         }
         // Split name up into property id and datatype
-        let (prop_id, prop_datatype) = Self::extract_id_and_datatype(name);
-        let key = prop_map.get_canonical_name(&prop_id)?;
-        let value_res = PtypDecoder::decode(entry_slice, &prop_datatype);
-        if value_res.is_err() {
-            return None;
+        let (_, prop_datatype) = Self::extract_id_and_datatype(name)?;
+        // Streams of the values of multiple valued properties
+        if Self::element_index(name).is_some() {
+            return element_type_code(&prop_datatype).map(String::from);
         }
-        let value = value_res.unwrap();
+        Some(prop_datatype)
+    }
+
+    // Same as create, from the value read from the stream.
+    pub(crate) fn from_value(
+        name: &str,
+        buff: &[u8],
+        prop_map: &PropIdNameMap,
+        parent: &StorageType,
+        string_decoder: Option<&dyn StringDecoder>,
+        code_page: Option<i64>,
+    ) -> Option<Self> {
+        let code = Self::value_code(name)?;
+        let (prop_id, _) = Self::extract_id_and_datatype(name)?;
+        let key = prop_map.get_canonical_name(&prop_id)?;
+        let value = PtypDecoder::decode_value(buff, &code, string_decoder, code_page).ok()?;
         Some(Self {
             parent: parent.clone(),
             key,
             value,
         })
     }
+
+    // The property stream starts with a header of header_size bytes,
+    // followed by 16 bytes entries: the property tag, flags and value.
+    // Refer to MS-OXMSG 2.4.2
+    // Variable length properties have their own stream, so only
+    // fixed length properties are decoded from their entry.
+    // Type codes of entries which are not defined in MS-OXCDATA
+    // are returned alongside the streams.
+    pub fn create_from_properties(
+        entry_slice: &mut EntrySlice,
+        header_size: usize,
+        prop_map: &PropIdNameMap,
+        parent: &StorageType,
+    ) -> (Vec<Self>, Vec<String>) {
+        let mut buff = vec![0u8; entry_slice.len()];
+        if entry_slice.read_exact(&mut buff).is_err() || buff.len() < header_size {
+            return (vec![], vec![]);
+        }
+        let mut unknown_types = vec![];
+        let streams = buff[header_size..]
+            .chunks_exact(16)
+            .filter_map(|chunk| {
+                let tag = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                let prop_id = format!("0x{:04X}", tag >> 16);
+                let prop_datatype = format!("0x{:04X}", tag & 0xFFFF);
+                if !is_known_type(&prop_datatype) {
+                    unknown_types.push(prop_datatype);
+                    return None;
+                }
+                let key = prop_map.get_canonical_name(&prop_id)?;
+                let mut value = [0u8; 8];
+                value.copy_from_slice(&chunk[8..]);
+                let value = PtypDecoder::decode_fixed(&value, &prop_datatype).ok()?;
+                Some(Self {
+                    parent: parent.clone(),
+                    key,
+                    value,
+                })
+            })
+            .collect();
+        (streams, unknown_types)
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::iter_nth_zero)]
 mod tests {
     use super::{
         super::constants::PropIdNameMap, super::decode::DataType, super::storage::StorageType,
@@ -66,19 +159,40 @@ mod tests {
 
     #[test]
     fn test_extract_id_and_datatype() {
-        let (prop_id, prop_datatype) = Stream::extract_id_and_datatype("__substg1.0_3701000D");
+        let (prop_id, prop_datatype) =
+            Stream::extract_id_and_datatype("__substg1.0_3701000D").unwrap();
         assert_eq!(prop_id, "0x3701");
         assert_eq!(prop_datatype, "0x000D");
 
-        let (prop_id, prop_datatype) = Stream::extract_id_and_datatype("__substg1.0_1016102F");
+        let (prop_id, prop_datatype) =
+            Stream::extract_id_and_datatype("__substg1.0_1016102F").unwrap();
         assert_eq!(prop_id, "0x1016");
         assert_eq!(prop_datatype, "0x102F");
+
+        let (prop_id, prop_datatype) =
+            Stream::extract_id_and_datatype("__SUBSTG1.0_0c1f001f ").unwrap();
+        assert_eq!(prop_id, "0x0C1F");
+        assert_eq!(prop_datatype, "0x001F");
+
+        let (prop_id, prop_datatype) =
+            Stream::extract_id_and_datatype("__substg1.0_8007101F-00000008").unwrap();
+        assert_eq!(prop_id, "0x8007");
+        assert_eq!(prop_datatype, "0x101F");
+
+        assert_eq!(Stream::extract_id_and_datatype("__substg1.0_0037_01F"), None);
+        assert_eq!(Stream::extract_id_and_datatype("__substg1.0_003700\u{E9}1"), None);
+        assert_eq!(Stream::extract_id_and_datatype("__substg1.0_0037001G"), None);
+        assert_eq!(Stream::extract_id_and_datatype("__substg1.0_0037001F0"), None);
+        assert_eq!(Stream::extract_id_and_datatype("__substg1.0"), None);
     }
 
     #[test]
     fn test_is_stream() {
         assert_eq!(Stream::is_stream("__recip_version1.0_#00000000"), false);
+        assert!(!Stream::is_stream("__substg1.0_370"));
         assert_eq!(Stream::is_stream("__substg1.0_3701000D"), true);
+        assert!(Stream::is_stream("__SUBSTG1.0_3701000D"));
+        assert!(Stream::is_stream("__Substg1.0_3701000d \0"));
     }
 
     #[test]
@@ -99,6 +213,9 @@ mod tests {
             &mut slice,
             &prop_map,
             &StorageType::RootEntry,
+            usize::MAX,
+            None,
+            None,
         );
         assert_eq!(
             stream,
@@ -121,6 +238,9 @@ mod tests {
             &mut slice,
             &prop_map,
             &StorageType::Recipient(1),
+            usize::MAX,
+            None,
+            None,
         );
         assert_eq!(
             stream,
@@ -139,8 +259,8 @@ mod tests {
 
         // Attachment object.
         let mut attachment = parser
-            .iterate()
-            .find(|x| x.name() == "__substg1.0_3703001F" && x.parent_node() == Some(7u32))
+            .streams_of(7)
+            .find(|x| x.name() == "__substg1.0_3703001F")
             .and_then(|entry| parser.get_entry_slice(entry).ok())
             .unwrap();
         let stream = Stream::create(
@@ -148,6 +268,9 @@ mod tests {
             &mut attachment,
             &prop_map,
             &StorageType::Attachment(0),
+            usize::MAX,
+            None,
+            None,
         );
         assert_eq!(
             stream,
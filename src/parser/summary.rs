@@ -0,0 +1,101 @@
+use std::{fs::File, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ole;
+
+use super::{
+    error::Error,
+    outlook::{Outlook, Person},
+    storage::Storages,
+};
+
+// MessageSummary holds the few fields needed to index a message.
+// It is built without decoding attachments and bodies,
+// which keeps it cheap over large collections of files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageSummary {
+    pub message_id: String,    // "Message-ID" header or "InternetMessageId"
+    pub date: String,          // "Date" header
+    pub from: Person,          // See Outlook.sender
+    pub to: Vec<Person>,       // See Outlook.to
+    pub subject: String,       // "Subject"
+    pub has_attachments: bool, // Attachment storages found
+    pub size: usize,           // Size of the .msg file in bytes
+}
+
+impl MessageSummary {
+    fn create(parser: &ole::Reader, size: usize) -> Self {
+        let mut storages = Storages::new(parser);
+        storages.process_header_streams(parser);
+        let outlook = Outlook::populate(&storages);
+
+        let message_id = if outlook.headers.message_id.is_empty() {
+            storages.get_val_from_root_or_default("InternetMessageId")
+        } else {
+            outlook.headers.message_id
+        };
+        Self {
+            message_id,
+            date: outlook.headers.date,
+            from: outlook.sender,
+            to: outlook.to,
+            subject: outlook.subject,
+            has_attachments: storages.has_attachments(),
+            size,
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len() as usize;
+        let parser = ole::Reader::new(file)?;
+        Ok(Self::create(&parser, size))
+    }
+
+    pub fn from_slice(slice: &[u8]) -> Result<Self, Error> {
+        let parser = ole::Reader::new(slice)?;
+        Ok(Self::create(&parser, slice.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MessageSummary, Person};
+
+    #[test]
+    fn test_summary_unicode() {
+        let summary = MessageSummary::from_path("data/unicode.msg").unwrap();
+        assert_eq!(
+            summary.message_id,
+            "<CADtJ4eNjQSkGcBtVteCiTF+YFG89+AcHxK3QZ=-Mt48xygkvdQ@mail.gmail.com>"
+        );
+        assert_eq!(summary.date, "Mon, 18 Nov 2013 10:26:24 +0200");
+        assert_eq!(
+            summary.from,
+            Person {
+                name: "Brian Zhou".to_string(),
+                email: "brizhou@gmail.com".to_string(),
+                storage_index: None,
+                valid: true,
+            }
+        );
+        assert_eq!(summary.to.len(), 1);
+        assert_eq!(summary.subject, "Test for TIF files");
+        assert!(summary.has_attachments);
+        assert_eq!(
+            summary.size,
+            std::fs::metadata("data/unicode.msg").unwrap().len() as usize
+        );
+    }
+
+    #[test]
+    fn test_summary_from_slice() {
+        let bytes = std::fs::read("data/test_email.msg").unwrap();
+        let summary = MessageSummary::from_slice(&bytes).unwrap();
+        assert_eq!(summary.subject, "Test Email");
+        assert!(summary.has_attachments);
+        assert_eq!(summary.size, bytes.len());
+        assert!(serde_json::to_string(&summary).is_ok());
+    }
+}
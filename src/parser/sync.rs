@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+use super::{decode::DataType, named::format_guid, storage::Storages};
+
+// Size of the namespace GUID starting an XID
+const GUID_SIZE: usize = 16;
+// Counters of XIDs take 1 to 8 bytes
+const MAX_LOCAL_ID_SIZE: usize = 8;
+
+// Xid identifies a change, or an object, by the GUID of the
+// replica which made it and a counter within that replica.
+// Refer to MS-OXCFXICS 2.2.2.2
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Xid {
+    pub namespace_guid: String, // Formatted as in the "__nameid_version1.0" storage
+    pub local_id: u64,          // Big-endian counter
+}
+
+impl Xid {
+    // An XID taking the whole of bytes, none when its
+    // counter is missing or too large.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let local_id = bytes.get(GUID_SIZE..)?;
+        if local_id.is_empty() || local_id.len() > MAX_LOCAL_ID_SIZE {
+            return None;
+        }
+        Some(Self {
+            namespace_guid: format_guid(&bytes[..GUID_SIZE]),
+            local_id: local_id.iter().fold(0, |id, byte| id << 8 | *byte as u64),
+        })
+    }
+
+    // The XIDs of a predecessor change list, each prefixed by its
+    // size, none when one of them is truncated or invalid.
+    // Refer to MS-OXCFXICS 2.2.2.4
+    pub fn parse_list(bytes: &[u8]) -> Option<Vec<Self>> {
+        let mut xids = vec![];
+        let mut rest = bytes;
+        while let Some((size, tail)) = rest.split_first() {
+            let size = *size as usize;
+            xids.push(Self::parse(tail.get(..size)?)?);
+            rest = &tail[size..];
+        }
+        Some(xids)
+    }
+}
+
+// SyncKeys holds the properties identifying a message and its
+// versions across stores, as used to reconcile exports with the
+// change history of a mailbox.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncKeys {
+    pub search_key: Option<String>,                // "SearchKey", hex encoded as it is opaque
+    pub change_key: Option<Xid>,                   // "ChangeKey", the last change
+    pub predecessor_change_list: Option<Vec<Xid>>, // "PredecessorChangeList", the changes seen
+}
+
+impl SyncKeys {
+    // Keys of the message, none when it has none of them.
+    // Malformed values are left out.
+    pub(crate) fn create(storages: &Storages) -> Option<Self> {
+        let binary = |key: &str| match storages.root.get(key) {
+            Some(DataType::PtypBinary(bytes)) => Some(bytes.as_slice()),
+            _ => None,
+        };
+        let keys = Self {
+            search_key: binary("SearchKey").map(hex::encode_upper),
+            change_key: binary("ChangeKey").and_then(Xid::parse),
+            predecessor_change_list: binary("PredecessorChangeList").and_then(Xid::parse_list),
+        };
+        (keys != Self::default()).then_some(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Xid;
+
+    const GUID: [u8; 16] = [
+        0x33, 0x22, 0x11, 0x00, 0x55, 0x44, 0x77, 0x66, 0x88, 0x99, 0xAA, 0xBB, 0xCC, 0xDD, 0xEE,
+        0xFF,
+    ];
+
+    fn xid(local_id: &[u8]) -> Vec<u8> {
+        GUID.iter().chain(local_id).copied().collect()
+    }
+
+    #[test]
+    fn test_xid() {
+        let parsed = Xid::parse(&xid(&[0x00, 0x00, 0x00, 0x01, 0x02, 0x03])).unwrap();
+        assert_eq!(parsed.namespace_guid, "00112233-4455-6677-8899-AABBCCDDEEFF");
+        assert_eq!(parsed.local_id, 0x010203);
+        assert_eq!(Xid::parse(&xid(&[0x2A])).unwrap().local_id, 42);
+        assert!(Xid::parse(&xid(&[])).is_none());
+        assert!(Xid::parse(&xid(&[0; 9])).is_none());
+        assert!(Xid::parse(&GUID[..8]).is_none());
+    }
+
+    #[test]
+    fn test_predecessor_change_list() {
+        let mut list = vec![22];
+        list.extend(xid(&[0, 0, 0, 0, 0, 7]));
+        list.push(20);
+        list.extend(xid(&[0, 0, 0, 9]));
+        let xids = Xid::parse_list(&list).unwrap();
+        assert_eq!(xids.iter().map(|xid| xid.local_id).collect::<Vec<_>>(), [7, 9]);
+        assert_eq!(Xid::parse_list(&[]), Some(vec![]));
+        // Truncated
+        assert!(Xid::parse_list(&list[..list.len() - 1]).is_none());
+    }
+}
@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+
+use super::{decode::DataType, outlook::Person, storage::Storages};
+
+// Message classes of reports, read as REPORT.<original class>.<suffix>.
+// Refer to MS-OXOMSG 2.2.1.1
+const REPORT_CLASS_PREFIX: &str = "REPORT.";
+const REPORT_SUFFIXES: [(&str, SystemMessageKind); 5] = [
+    (".DR", SystemMessageKind::DeliveryReport),
+    (".NDR", SystemMessageKind::NonDeliveryReport),
+    (".IPNRN", SystemMessageKind::ReadReceipt),
+    (".IPNNRN", SystemMessageKind::NonReadReceipt),
+    (".DELAYED", SystemMessageKind::DelayedReport),
+];
+
+// Message classes written by Outlook for its rules and
+// out of office replies.
+const OOF_CLASSES: [&str; 2] = [
+    "IPM.Note.Rules.OofTemplate.Microsoft",
+    "IPM.Note.Rules.ExternalOofTemplate.Microsoft",
+];
+const RULE_CLASSES: [&str; 3] = [
+    "IPM.Rule.Message",
+    "IPM.ExtendedRule.Message",
+    "IPM.Note.Rules.ReplyTemplate.Microsoft",
+];
+
+// SystemMessageKind tells which system generated message
+// a SystemMessage is.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SystemMessageKind {
+    DeliveryReport,
+    NonDeliveryReport,
+    ReadReceipt,
+    NonReadReceipt,
+    DelayedReport,
+    OutOfOffice,
+    Rule,
+}
+
+impl SystemMessageKind {
+    // Kind of a message class, along with the class of the
+    // message reported on for reports.
+    fn from_class(class: &str) -> Option<(Self, String)> {
+        let upper = class.to_ascii_uppercase();
+        if upper.starts_with(REPORT_CLASS_PREFIX) {
+            return REPORT_SUFFIXES.iter().find_map(|(suffix, kind)| {
+                upper.ends_with(suffix).then(|| {
+                    let original = &class[REPORT_CLASS_PREFIX.len()..class.len() - suffix.len()];
+                    (*kind, original.to_string())
+                })
+            });
+        }
+        let matches = |classes: &[&str]| classes.iter().any(|c| c.eq_ignore_ascii_case(class));
+        if matches(&OOF_CLASSES) {
+            Some((SystemMessageKind::OutOfOffice, String::new()))
+        } else if matches(&RULE_CLASSES) {
+            Some((SystemMessageKind::Rule, String::new()))
+        } else {
+            None
+        }
+    }
+}
+
+// ReportRecipient is the delivery status reported for a recipient.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportRecipient {
+    pub recipient: Person,            // See Outlook.to
+    pub reason_code: Option<i32>,     // "NonDeliveryReportReasonCode"
+    pub diagnostic_code: Option<i32>, // "NonDeliveryReportDiagCode"
+    pub status_code: Option<i32>,     // "NonDeliveryReportStatusCode"
+    pub supplementary_info: String,   // "SupplementaryInfo", e.g. the SMTP response
+}
+
+// SystemMessage holds what identifies reports, out of office
+// replies and rule messages, so they can be told apart without
+// looking for their properties.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemMessage {
+    pub kind: SystemMessageKind,
+    pub original_class: String,           // Class of the reported message, reports only
+    pub text: String,                     // "ReportText", or "Body"
+    pub recipients: Vec<ReportRecipient>, // Recipients with a delivery status
+}
+
+impl SystemMessage {
+    pub(crate) fn create(storages: &Storages) -> Option<Self> {
+        let class = storages.get_val_from_root_or_default("MessageClass");
+        let (kind, original_class) = SystemMessageKind::from_class(&class)?;
+
+        Some(Self {
+            kind,
+            original_class,
+            text: storages.get_first_from_root_or_default(&["ReportText", "Body"]),
+            recipients: Self::report_recipients(storages),
+        })
+    }
+
+    fn report_recipients(storages: &Storages) -> Vec<ReportRecipient> {
+        storages
+            .recipients
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, recipient)| {
+                let int = |key: &str| {
+                    recipient
+                        .get(key)
+                        .and_then(DataType::as_i64)
+                        .map(|value| value as i32)
+                };
+                let supplementary_info: String = recipient
+                    .get("SupplementaryInfo")
+                    .map_or(String::new(), |info| info.into());
+                let reported = ReportRecipient {
+                    recipient: Person::create_from_recipient(storages, idx),
+                    reason_code: int("NonDeliveryReportReasonCode"),
+                    diagnostic_code: int("NonDeliveryReportDiagCode"),
+                    status_code: int("NonDeliveryReportStatusCode"),
+                    supplementary_info,
+                };
+                let has_status = reported.reason_code.is_some()
+                    || reported.diagnostic_code.is_some()
+                    || reported.status_code.is_some()
+                    || !reported.supplementary_info.is_empty();
+                has_status.then_some(reported)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SystemMessage, SystemMessageKind};
+    use crate::parser::{decode::DataType, fixtures, storage::Storages};
+
+    fn storages(class: &str) -> Storages {
+        fixtures::storages(vec![(
+            "MessageClass",
+            DataType::PtypString(class.to_string()),
+        )])
+    }
+
+    #[test]
+    fn test_from_class() {
+        assert_eq!(
+            SystemMessageKind::from_class("REPORT.IPM.Note.NDR"),
+            Some((SystemMessageKind::NonDeliveryReport, "IPM.Note".to_string()))
+        );
+        assert_eq!(
+            SystemMessageKind::from_class("Report.IPM.Schedule.Meeting.Request.IPNRN"),
+            Some((
+                SystemMessageKind::ReadReceipt,
+                "IPM.Schedule.Meeting.Request".to_string()
+            ))
+        );
+        assert_eq!(
+            SystemMessageKind::from_class("IPM.Note.Rules.OofTemplate.Microsoft").unwrap().0,
+            SystemMessageKind::OutOfOffice
+        );
+        assert!(SystemMessageKind::from_class("IPM.Note").is_none());
+        assert!(SystemMessageKind::from_class("REPORT.IPM.Note.Unknown").is_none());
+    }
+
+    #[test]
+    fn test_non_delivery_report() {
+        let mut storages = storages("REPORT.IPM.Note.NDR");
+        assert!(SystemMessage::create(&storages).unwrap().recipients.is_empty());
+
+        storages.root.insert(
+            "ReportText".to_string(),
+            DataType::PtypString("Delivery has failed to these recipients".to_string()),
+        );
+        storages.recipients[1].insert(
+            "NonDeliveryReportReasonCode".to_string(),
+            DataType::PtypInteger32(0),
+        );
+        storages.recipients[1].insert(
+            "NonDeliveryReportDiagCode".to_string(),
+            DataType::PtypInteger32(0x30),
+        );
+        storages.recipients[1].insert(
+            "SupplementaryInfo".to_string(),
+            DataType::PtypString("550 5.1.1 User unknown".to_string()),
+        );
+        let report = SystemMessage::create(&storages).unwrap();
+        assert_eq!(report.kind, SystemMessageKind::NonDeliveryReport);
+        assert_eq!(report.original_class, "IPM.Note");
+        assert_eq!(report.text, "Delivery has failed to these recipients");
+        assert_eq!(report.recipients.len(), 1);
+        assert_eq!(report.recipients[0].reason_code, Some(0));
+        assert_eq!(report.recipients[0].diagnostic_code, Some(0x30));
+        assert_eq!(report.recipients[0].status_code, None);
+        assert_eq!(report.recipients[0].supplementary_info, "550 5.1.1 User unknown");
+    }
+
+    #[test]
+    fn test_out_of_office() {
+        assert!(SystemMessage::create(&storages("IPM.Note")).is_none());
+
+        let storages = storages("IPM.Note.Rules.OofTemplate.Microsoft");
+        let oof = SystemMessage::create(&storages).unwrap();
+        assert_eq!(oof.kind, SystemMessageKind::OutOfOffice);
+        assert_eq!(oof.text, storages.get_val_from_root_or_default("Body"));
+        assert!(!oof.text.is_empty());
+    }
+}
@@ -0,0 +1,30 @@
+use super::outlook::Attachment;
+
+// AttachmentTextExtractor extracts the text of attachment payloads,
+// e.g. of PDF or Office documents, for indexing and searching without
+// this crate depending on their parsers. Set with
+// ParseOptions::attachment_text_extractor or SearchScope::attachment_text.
+pub trait AttachmentTextExtractor: Send + Sync {
+    // Text of the attachment, None when it has none
+    fn extract(&self, attachment: &Attachment, payload: &[u8]) -> Option<String>;
+}
+
+impl std::fmt::Debug for dyn AttachmentTextExtractor {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("AttachmentTextExtractor")
+    }
+}
+
+// Text of an attachment, none for embedded messages, which have
+// a text of their own, and for payloads left out by the attachment
+// filter.
+pub(crate) fn extract_text(
+    extractor: &dyn AttachmentTextExtractor,
+    attachment: &Attachment,
+) -> Option<String> {
+    if attachment.embedded_message.is_some() || attachment.payload_filtered {
+        return None;
+    }
+    let payload = hex::decode(&attachment.payload).ok()?;
+    extractor.extract(attachment, &payload)
+}
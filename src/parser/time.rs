@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, FixedOffset, NaiveDateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{decode::DataType, storage::Storages};
+
+// FILETIME counts 100-nanosecond intervals from January 1, 1601,
+// this many seconds before the Unix epoch.
+const FILETIME_UNIX_OFFSET: i64 = 11_644_473_600;
+const FILETIME_TICKS_PER_SECOND: u64 = 10_000_000;
+
+// TimePolicy tells how timestamps are converted.
+// FILETIME values are stored in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum TimePolicy {
+    // Wall clock time in UTC, without any offset
+    Naive,
+    // UTC, with a +00:00 offset
+    #[default]
+    Utc,
+    // Shifted to the offset of the "Date" header, the sender's.
+    // UTC when the header has no offset.
+    Original,
+}
+
+// Timestamp holds a FILETIME property both as stored
+// and converted following the TimePolicy.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Timestamp {
+    pub filetime: u64,       // Raw FILETIME value
+    pub datetime: String,    // RFC 3339, without offset when naive
+    pub offset: Option<i32>, // Seconds east of UTC, none when naive
+}
+
+impl Timestamp {
+    pub fn new(filetime: u64, policy: TimePolicy, original_offset: Option<i32>) -> Self {
+        let offset = match policy {
+            TimePolicy::Naive => None,
+            TimePolicy::Utc => Some(0),
+            TimePolicy::Original => Some(original_offset.unwrap_or(0)),
+        };
+        let mut timestamp = Self {
+            filetime,
+            datetime: String::new(),
+            offset,
+        };
+        timestamp.datetime = match (timestamp.to_datetime(), timestamp.to_naive()) {
+            (Some(datetime), _) => datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            (None, Some(naive)) => naive.format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
+            (None, None) => String::new(),
+        };
+        timestamp
+    }
+
+    // The FILETIME as a UTC date, if within the supported range.
+    pub fn to_utc(&self) -> Option<DateTime<Utc>> {
+        filetime_to_utc(self.filetime)
+    }
+
+    // The date at its offset, none when naive.
+    pub fn to_datetime(&self) -> Option<DateTime<FixedOffset>> {
+        let offset = FixedOffset::east_opt(self.offset?)?;
+        Some(self.to_utc()?.with_timezone(&offset))
+    }
+
+    // The wall clock time at the offset, UTC when naive.
+    pub fn to_naive(&self) -> Option<NaiveDateTime> {
+        match self.to_datetime() {
+            Some(datetime) => Some(datetime.naive_local()),
+            None => Some(self.to_utc()?.naive_utc()),
+        }
+    }
+}
+
+// A FILETIME as a UTC date, if within the range of DateTime.
+pub(crate) fn filetime_to_utc(filetime: u64) -> Option<DateTime<Utc>> {
+    let secs = (filetime / FILETIME_TICKS_PER_SECOND) as i64 - FILETIME_UNIX_OFFSET;
+    let nanos = (filetime % FILETIME_TICKS_PER_SECOND) as u32 * 100;
+    DateTime::from_timestamp(secs, nanos)
+}
+
+// Offset of a RFC 5322 date such as "Mon, 18 Nov 2013 10:26:24 +0200",
+// in seconds east of UTC. Refer to RFC 5322 3.3
+pub fn date_offset(date: &str) -> Option<i32> {
+    let zone = date.split_whitespace().last()?;
+    if ["GMT", "UT", "Z"].iter().any(|utc| zone.eq_ignore_ascii_case(utc)) {
+        return Some(0);
+    }
+    let sign = match zone.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits = &zone[1..];
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+// Timestamps of the root properties of type PtypTime, by name.
+pub(crate) fn root_timestamps(
+    storages: &Storages,
+    policy: TimePolicy,
+    date: &str,
+) -> BTreeMap<String, Timestamp> {
+    let original_offset = date_offset(date);
+    storages
+        .root
+        .iter()
+        .filter_map(|(key, value)| match value {
+            DataType::PtypTime(filetime) => Some((
+                key.clone(),
+                Timestamp::new(*filetime, policy, original_offset),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{date_offset, TimePolicy, Timestamp};
+
+    // 2013-11-18T08:26:24Z
+    const FILETIME: u64 = 130292367840000000;
+
+    #[test]
+    fn test_date_offset() {
+        assert_eq!(date_offset("Mon, 18 Nov 2013 10:26:24 +0200"), Some(7200));
+        assert_eq!(date_offset("Mon, 18 Nov 2013 10:26:24 -0530"), Some(-19800));
+        assert_eq!(date_offset("18 Nov 2013 10:26:24 GMT"), Some(0));
+        assert_eq!(date_offset("18 Nov 2013 10:26:24"), None);
+        assert_eq!(date_offset(""), None);
+    }
+
+    #[test]
+    fn test_time_policy() {
+        let utc = Timestamp::new(FILETIME, TimePolicy::Utc, Some(7200));
+        assert_eq!(utc.filetime, FILETIME);
+        assert_eq!(utc.datetime, "2013-11-18T08:26:24Z");
+        assert_eq!(utc.to_utc().unwrap().timestamp(), 1384763184);
+
+        let original = Timestamp::new(FILETIME, TimePolicy::Original, Some(7200));
+        assert_eq!(original.datetime, "2013-11-18T10:26:24+02:00");
+        assert_eq!(original.to_utc(), utc.to_utc());
+        assert_eq!(
+            Timestamp::new(FILETIME, TimePolicy::Original, None).datetime,
+            utc.datetime
+        );
+
+        let naive = Timestamp::new(FILETIME, TimePolicy::Naive, Some(7200));
+        assert_eq!(naive.datetime, "2013-11-18T08:26:24");
+        assert_eq!(naive.offset, None);
+        assert!(naive.to_datetime().is_none());
+    }
+}
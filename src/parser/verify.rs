@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+
+use super::storage::Storages;
+
+// PropertyHeader holds the fields of the header of the message
+// property stream, "__properties_version1.0".
+// Refer to MS-OXMSG 2.4.1.1
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PropertyHeader {
+    pub next_recipient_id: u32,  // Number of the next recipient storage to be added
+    pub next_attachment_id: u32, // Number of the next attachment storage to be added
+    pub recipient_count: u32,
+    pub attachment_count: u32,
+}
+
+// Discrepancy is a mismatch between what a message declares
+// about itself and what was found in the file, as left by
+// a lossy migration or a partial copy.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Discrepancy {
+    // "MessageSizeExtended" is smaller than the streams of the
+    // message. The declared size counts the overhead of the store
+    // it was computed by, so only a smaller size is reported.
+    MessageSize { declared: u64, actual: u64 },
+    // Attachment count of the message header
+    AttachmentCount { declared: u32, found: usize },
+    // Recipient count of the message header
+    RecipientCount { declared: u32, found: usize },
+    // Attachment storage numbered from the next attachment id
+    // of the message header on
+    AttachmentId { next_id: u32, found: u32 },
+    // Recipient storage numbered from the next recipient id
+    // of the message header on
+    RecipientId { next_id: u32, found: u32 },
+}
+
+impl std::fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Discrepancy::MessageSize { declared, actual } => write!(
+                f,
+                "Message size of {} bytes is smaller than its {} bytes of streams",
+                declared, actual
+            ),
+            Discrepancy::AttachmentCount { declared, found } => write!(
+                f,
+                "{} attachments declared, {} found",
+                declared, found
+            ),
+            Discrepancy::RecipientCount { declared, found } => write!(
+                f,
+                "{} recipients declared, {} found",
+                declared, found
+            ),
+            Discrepancy::AttachmentId { next_id, found } => write!(
+                f,
+                "Attachment storage #{:08X} found, next id is #{:08X}",
+                found, next_id
+            ),
+            Discrepancy::RecipientId { next_id, found } => write!(
+                f,
+                "Recipient storage #{:08X} found, next id is #{:08X}",
+                found, next_id
+            ),
+        }
+    }
+}
+
+// What the message declares about itself, kept for Outlook::verify.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Declared {
+    header: Option<PropertyHeader>,
+    // Not declared, found along with the header
+    stream_size: u64,
+    max_recipient_id: Option<u32>,
+    max_attachment_id: Option<u32>,
+}
+
+impl Declared {
+    pub(crate) fn create(storages: &Storages) -> Self {
+        Self {
+            header: storages.property_header,
+            stream_size: storages.stream_size,
+            max_recipient_id: storages.recipient_ids.iter().copied().max(),
+            max_attachment_id: storages.attachment_ids.iter().copied().max(),
+        }
+    }
+
+    pub(crate) fn verify(
+        &self,
+        message_size: Option<u64>,
+        recipients: usize,
+        attachments: usize,
+    ) -> Vec<Discrepancy> {
+        let mut discrepancies = vec![];
+        if let Some(declared) = message_size.filter(|size| *size < self.stream_size) {
+            discrepancies.push(Discrepancy::MessageSize {
+                declared,
+                actual: self.stream_size,
+            });
+        }
+        let header = match self.header {
+            Some(header) => header,
+            None => return discrepancies,
+        };
+        if header.attachment_count as usize != attachments {
+            discrepancies.push(Discrepancy::AttachmentCount {
+                declared: header.attachment_count,
+                found: attachments,
+            });
+        }
+        if header.recipient_count as usize != recipients {
+            discrepancies.push(Discrepancy::RecipientCount {
+                declared: header.recipient_count,
+                found: recipients,
+            });
+        }
+        if let Some(found) = self
+            .max_attachment_id
+            .filter(|id| *id >= header.next_attachment_id)
+        {
+            discrepancies.push(Discrepancy::AttachmentId {
+                next_id: header.next_attachment_id,
+                found,
+            });
+        }
+        if let Some(found) = self
+            .max_recipient_id
+            .filter(|id| *id >= header.next_recipient_id)
+        {
+            discrepancies.push(Discrepancy::RecipientId {
+                next_id: header.next_recipient_id,
+                found,
+            });
+        }
+        discrepancies
+    }
+}
@@ -0,0 +1,331 @@
+// Builders of synthetic .msg files, so that tests can do without
+// binary fixtures. Enabled by the "testing" feature:
+//
+// let bytes = MessageBuilder::new()
+//     .subject("Hello")
+//     .recipient("Jane Doe", "jane@example.com")
+//     .attachment("notes.txt", "text/plain", b"notes")
+//     .build();
+// let outlook = Outlook::from_slice(&bytes).unwrap();
+//
+// Writer builds any compound file, e.g. to craft malformed messages.
+pub use crate::ole::Writer;
+
+use crate::PropIdNameMap;
+
+// Property types written. Refer to MS-OXCDATA 2.11.1
+const PTYP_INTEGER32: u16 = 0x0003;
+const PTYP_BOOLEAN: u16 = 0x000B;
+const PTYP_TIME: u16 = 0x0040;
+const PTYP_STRING8: u16 = 0x001E;
+const PTYP_STRING: u16 = 0x001F;
+const PTYP_BINARY: u16 = 0x0102;
+const PTYP_MULTIPLE_INTEGER32: u16 = 0x1003;
+const PTYP_MULTIPLE_STRING: u16 = 0x101F;
+const PTYP_MULTIPLE_BINARY: u16 = 0x1102;
+
+// Properties are readable and writable.
+const PROPATTR_READABLE_WRITABLE: u32 = 0x06;
+
+// Size of the property stream header of a top level message.
+// Refer to MS-OXMSG 2.4.1.1
+const ROOT_HEADER_SIZE: usize = 32;
+const OTHER_HEADER_SIZE: usize = 8;
+
+// Value of a property, by type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    Integer32(i32),
+    Boolean(bool),
+    Time(u64), // FILETIME
+    String(String),
+    String8(Vec<u8>), // ANSI, as encoded
+    Binary(Vec<u8>),
+    MultipleInteger32(Vec<i32>),
+    MultipleString(Vec<String>),
+    MultipleBinary(Vec<Vec<u8>>),
+}
+
+impl PropertyValue {
+    fn type_code(&self) -> u16 {
+        match self {
+            PropertyValue::Integer32(_) => PTYP_INTEGER32,
+            PropertyValue::Boolean(_) => PTYP_BOOLEAN,
+            PropertyValue::Time(_) => PTYP_TIME,
+            PropertyValue::String(_) => PTYP_STRING,
+            PropertyValue::String8(_) => PTYP_STRING8,
+            PropertyValue::Binary(_) => PTYP_BINARY,
+            PropertyValue::MultipleInteger32(_) => PTYP_MULTIPLE_INTEGER32,
+            PropertyValue::MultipleString(_) => PTYP_MULTIPLE_STRING,
+            PropertyValue::MultipleBinary(_) => PTYP_MULTIPLE_BINARY,
+        }
+    }
+
+    fn utf16(value: &str) -> Vec<u8> {
+        value.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    // Variable length values have a stream of their own,
+    // fixed length ones live in the property stream.
+    fn stream_bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            PropertyValue::String(value) => Some(Self::utf16(value)),
+            PropertyValue::String8(value) | PropertyValue::Binary(value) => Some(value.clone()),
+            PropertyValue::MultipleInteger32(values) => {
+                Some(values.iter().flat_map(|value| value.to_le_bytes()).collect())
+            }
+            // Lengths of the values, strings counting their terminator
+            PropertyValue::MultipleString(values) => Some(
+                values
+                    .iter()
+                    .flat_map(|value| ((Self::utf16(value).len() + 2) as u32).to_le_bytes())
+                    .collect(),
+            ),
+            PropertyValue::MultipleBinary(values) => Some(
+                values
+                    .iter()
+                    .flat_map(|value| (value.len() as u64).to_le_bytes())
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    // Values of multiple valued properties which have a stream each.
+    // Refer to MS-OXMSG 2.1.4.1.3
+    fn element_bytes(&self) -> Vec<Vec<u8>> {
+        match self {
+            PropertyValue::MultipleString(values) => {
+                values.iter().map(|value| Self::utf16(value)).collect()
+            }
+            PropertyValue::MultipleBinary(values) => values.clone(),
+            _ => vec![],
+        }
+    }
+
+    fn fixed_bytes(&self) -> [u8; 8] {
+        let mut bytes = [0u8; 8];
+        match self {
+            PropertyValue::Integer32(value) => bytes[..4].copy_from_slice(&value.to_le_bytes()),
+            PropertyValue::Boolean(value) => bytes[0] = *value as u8,
+            PropertyValue::Time(value) => bytes.copy_from_slice(&value.to_le_bytes()),
+            // Size of the stream, a string counting its terminator
+            PropertyValue::String(value) => bytes[..4]
+                .copy_from_slice(&((value.encode_utf16().count() * 2 + 2) as u32).to_le_bytes()),
+            PropertyValue::String8(value) => {
+                bytes[..4].copy_from_slice(&(value.len() as u32 + 1).to_le_bytes())
+            }
+            PropertyValue::Binary(value) => {
+                bytes[..4].copy_from_slice(&(value.len() as u32).to_le_bytes())
+            }
+            PropertyValue::MultipleInteger32(_)
+            | PropertyValue::MultipleString(_)
+            | PropertyValue::MultipleBinary(_) => {
+                let size = self.stream_bytes().unwrap_or_default().len();
+                bytes[..4].copy_from_slice(&(size as u32).to_le_bytes())
+            }
+        }
+        bytes
+    }
+}
+
+// Properties of a storage, by id.
+type PropertyList = Vec<(u16, PropertyValue)>;
+
+// MessageBuilder builds a .msg file from its properties,
+// set by canonical name as in PropIdNameMap.
+//
+// Properties are set with chained calls, and build returns
+// the bytes of the file.
+pub struct MessageBuilder {
+    prop_map: PropIdNameMap,
+    root: PropertyList,
+    recipients: Vec<PropertyList>,
+    attachments: Vec<PropertyList>,
+}
+
+impl Default for MessageBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageBuilder {
+    // A message of class IPM.Note, without any other property.
+    pub fn new() -> Self {
+        Self {
+            prop_map: PropIdNameMap::init(),
+            root: vec![],
+            recipients: vec![],
+            attachments: vec![],
+        }
+        .property("MessageClass", PropertyValue::String("IPM.Note".to_string()))
+    }
+
+    // Id of a property from its canonical name.
+    // Panics on unknown names, which are a mistake of the test.
+    fn property_id(&self, name: &str) -> u16 {
+        match self.prop_map.get_tag(name) {
+            Some(tag) => (tag >> 16) as u16,
+            None => panic!("unknown property {}", name),
+        }
+    }
+
+    // A property can have both a Unicode and an ANSI value.
+    fn set(&self, props: &mut PropertyList, name: &str, value: PropertyValue) {
+        let id = self.property_id(name);
+        props.retain(|(prop_id, prop)| {
+            *prop_id != id || prop.type_code() != value.type_code()
+        });
+        props.push((id, value));
+    }
+
+    fn string(value: &str) -> PropertyValue {
+        PropertyValue::String(value.to_string())
+    }
+
+    // Sets a property of the message, e.g. "InternetMessageId".
+    pub fn property(mut self, name: &str, value: PropertyValue) -> Self {
+        let mut root = std::mem::take(&mut self.root);
+        self.set(&mut root, name, value);
+        self.root = root;
+        self
+    }
+
+    pub fn message_class(self, value: &str) -> Self {
+        self.property("MessageClass", Self::string(value))
+    }
+
+    pub fn subject(self, value: &str) -> Self {
+        self.property("Subject", Self::string(value))
+    }
+
+    pub fn body(self, value: &str) -> Self {
+        self.property("Body", Self::string(value))
+    }
+
+    // Raw transport headers, as received.
+    pub fn headers(self, value: &str) -> Self {
+        self.property("TransportMessageHeaders", Self::string(value))
+    }
+
+    pub fn sender(self, name: &str, email: &str) -> Self {
+        self.property("SenderName", Self::string(name))
+            .property("SenderSmtpAddress", Self::string(email))
+    }
+
+    // Adds a To recipient.
+    pub fn recipient(mut self, name: &str, email: &str) -> Self {
+        let mut props = PropertyList::new();
+        self.set(&mut props, "DisplayName", Self::string(name));
+        self.set(&mut props, "SmtpAddress", Self::string(email));
+        self.set(&mut props, "RecipientType", PropertyValue::Integer32(1));
+        self.recipients.push(props);
+        self
+    }
+
+    // Adds an attachment by value.
+    pub fn attachment(mut self, file_name: &str, mime: &str, data: &[u8]) -> Self {
+        let extension = file_name
+            .rsplit_once('.')
+            .map_or(String::new(), |(_, ext)| format!(".{}", ext));
+        let mut props = PropertyList::new();
+        self.set(&mut props, "DisplayName", Self::string(file_name));
+        self.set(&mut props, "AttachLongFilename", Self::string(file_name));
+        self.set(&mut props, "AttachExtension", PropertyValue::String(extension));
+        self.set(&mut props, "AttachMimeTag", Self::string(mime));
+        self.set(&mut props, "AttachMethod", PropertyValue::Integer32(1));
+        self.set(&mut props, "AttachDataObject", PropertyValue::Binary(data.to_vec()));
+        self.attachments.push(props);
+        self
+    }
+
+    // Writes the property stream and the streams of the
+    // variable length properties of a storage.
+    fn write_storage(writer: &mut Writer, storage: &str, header: Vec<u8>, props: &PropertyList) {
+        let prefix = if storage.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", storage)
+        };
+        let mut stream = header;
+        for (id, value) in props {
+            let tag = (*id as u32) << 16 | value.type_code() as u32;
+            stream.extend_from_slice(&tag.to_le_bytes());
+            stream.extend_from_slice(&PROPATTR_READABLE_WRITABLE.to_le_bytes());
+            stream.extend_from_slice(&value.fixed_bytes());
+            if let Some(bytes) = value.stream_bytes() {
+                writer.add_stream(&format!("{}__substg1.0_{:08X}", prefix, tag), &bytes);
+            }
+            for (idx, bytes) in value.element_bytes().iter().enumerate() {
+                let name = format!("{}__substg1.0_{:08X}-{:08X}", prefix, tag, idx);
+                writer.add_stream(&name, bytes);
+            }
+        }
+        writer.add_stream(&format!("{}__properties_version1.0", prefix), &stream);
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        // The named property mapping, left empty
+        for stream in ["00020102", "00030102", "00040102"] {
+            writer.add_stream(&format!("__nameid_version1.0/__substg1.0_{}", stream), &[]);
+        }
+
+        let mut header = vec![0u8; ROOT_HEADER_SIZE];
+        for (offset, count) in [
+            (8, self.recipients.len()),
+            (12, self.attachments.len()),
+            (16, self.recipients.len()),
+            (20, self.attachments.len()),
+        ] {
+            header[offset..offset + 4].copy_from_slice(&(count as u32).to_le_bytes());
+        }
+        Self::write_storage(&mut writer, "", header, &self.root);
+        for (idx, props) in self.recipients.iter().enumerate() {
+            let storage = format!("__recip_version1.0_#{:08X}", idx);
+            Self::write_storage(&mut writer, &storage, vec![0u8; OTHER_HEADER_SIZE], props);
+        }
+        for (idx, props) in self.attachments.iter().enumerate() {
+            let storage = format!("__attach_version1.0_#{:08X}", idx);
+            Self::write_storage(&mut writer, &storage, vec![0u8; OTHER_HEADER_SIZE], props);
+        }
+        writer.to_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MessageBuilder, PropertyValue};
+    use crate::Outlook;
+
+    #[test]
+    fn test_message_builder() {
+        // Larger than the short stream cut-off
+        let payload: Vec<u8> = (0..5000u32).map(|i| i as u8).collect();
+        let bytes = MessageBuilder::new()
+            .subject("Synthetic")
+            .body("Hello\r\nWorld")
+            .sender("John Doe", "john@example.com")
+            .recipient("Jane Doe", "jane@example.com")
+            .recipient("Bob", "bob@example.com")
+            .attachment("notes.txt", "text/plain", b"some notes")
+            .attachment("data.bin", "application/octet-stream", &payload)
+            .property("MessageFlags", PropertyValue::Integer32(1))
+            .build();
+
+        let outlook = Outlook::from_slice(&bytes).unwrap();
+        assert!(outlook.report.warnings.is_empty());
+        assert_eq!(outlook.subject, "Synthetic");
+        assert_eq!(outlook.body, "Hello\r\nWorld");
+        assert_eq!(outlook.sender.email, "john@example.com");
+        assert_eq!(outlook.to.len(), 2);
+        assert_eq!(outlook.to[1].name, "Bob");
+        assert_eq!(outlook.attachments.len(), 2);
+        assert_eq!(outlook.attachments[0].name, "notes.txt");
+        assert_eq!(outlook.attachments[0].extension, ".txt");
+        assert_eq!(outlook.attachments[0].payload, hex::encode(b"some notes"));
+        assert_eq!(outlook.attachments[1].mime_tag, "application/octet-stream");
+        assert_eq!(outlook.attachments[1].payload, hex::encode(&payload));
+    }
+}